@@ -0,0 +1,12 @@
+-- Link follow-up transactions (void, refund, completion, reversal) to their original
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS orig_tr_dt VARCHAR(8);
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS orig_tr_tm VARCHAR(6);
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS orig_tr_uniq_no VARCHAR(64);
+
+-- Cumulative refunded amount (minor units), maintained on the original sale
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS refund_amt BIGINT NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_iso8583_payment_original
+    ON iso8583_payment (orig_tr_dt, orig_tr_tm, orig_tr_uniq_no);
+CREATE INDEX IF NOT EXISTS idx_iso8583_payment_trm_rrn
+    ON iso8583_payment (trm_id, field_037);
@@ -0,0 +1,17 @@
+-- Open pre-authorization holds, keyed by the original pre-auth transaction
+CREATE TABLE IF NOT EXISTS iso8583_preauth_hold (
+    tr_dt          VARCHAR(8)  NOT NULL,
+    tr_tm          VARCHAR(6)  NOT NULL,
+    tr_uniq_no     VARCHAR(64) NOT NULL,
+    trm_id         VARCHAR(16),
+    authorized_amt BIGINT      NOT NULL,
+    completed_amt  BIGINT,
+    status         VARCHAR(16) NOT NULL DEFAULT 'OPEN',
+    expire_dtm     VARCHAR(14) NOT NULL,
+    inst_dtm       VARCHAR(14),
+    updt_dtm       VARCHAR(14),
+    PRIMARY KEY (tr_dt, tr_tm, tr_uniq_no)
+);
+
+CREATE INDEX IF NOT EXISTS idx_iso8583_preauth_hold_open
+    ON iso8583_preauth_hold (status, expire_dtm);
@@ -0,0 +1,21 @@
+-- Store-and-forward queue: reversals, completion advices, offline uploads, void advices
+CREATE TABLE IF NOT EXISTS iso8583_saf_queue (
+    saf_id           BIGSERIAL PRIMARY KEY,
+    saf_type         VARCHAR(32) NOT NULL,
+    tr_dt            VARCHAR(8)  NOT NULL,
+    tr_tm            VARCHAR(6)  NOT NULL,
+    tr_uniq_no       VARCHAR(64) NOT NULL,
+    trm_id           VARCHAR(16),
+    message          TEXT        NOT NULL,
+    attempts         INTEGER     NOT NULL DEFAULT 0,
+    status           VARCHAR(16) NOT NULL DEFAULT 'PENDING',
+    next_attempt_dtm VARCHAR(14) NOT NULL,
+    last_error       TEXT,
+    inst_dtm         VARCHAR(14),
+    updt_dtm         VARCHAR(14),
+    UNIQUE (saf_type, tr_dt, tr_tm, tr_uniq_no)
+);
+
+CREATE INDEX IF NOT EXISTS idx_iso8583_saf_queue_pending
+    ON iso8583_saf_queue (status, trm_id, saf_id);
+
@@ -0,0 +1,16 @@
+-- Every state transition of iso8583_payment, in order
+CREATE TABLE IF NOT EXISTS iso8583_payment_state_history (
+    hist_id    BIGSERIAL PRIMARY KEY,
+    tr_dt      VARCHAR(8)  NOT NULL,
+    tr_tm      VARCHAR(6)  NOT NULL,
+    tr_uniq_no VARCHAR(64) NOT NULL,
+    from_state VARCHAR(16),
+    to_state   VARCHAR(16) NOT NULL,
+    event      VARCHAR(16),
+    actor      VARCHAR(64) NOT NULL,
+    reason     TEXT,
+    chg_dtm    VARCHAR(17) NOT NULL -- YYYYMMDDhhmmssSSS
+);
+
+CREATE INDEX IF NOT EXISTS idx_iso8583_payment_state_history_tx
+    ON iso8583_payment_state_history (tr_dt, tr_tm, tr_uniq_no, hist_id);
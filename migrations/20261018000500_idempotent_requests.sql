@@ -0,0 +1,8 @@
+-- Final terminal response, replayed when the terminal retransmits the same request
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS resp_json TEXT;
+
+-- One transaction per terminal and terminal transactionId
+-- (QR payments carry no terminal and are excluded)
+CREATE UNIQUE INDEX IF NOT EXISTS uq_iso8583_payment_trm_tx
+    ON iso8583_payment (trm_id, tr_uniq_no)
+    WHERE trm_id IS NOT NULL;
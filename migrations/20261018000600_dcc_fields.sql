@@ -0,0 +1,4 @@
+-- Dynamic currency conversion: cardholder billing amount, conversion rate and currency
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS field_006 VARCHAR(12);
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS field_010 VARCHAR(8);
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS field_051 VARCHAR(3);
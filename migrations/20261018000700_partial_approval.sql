@@ -0,0 +1,2 @@
+-- Amount approved by the host (minor units); below DE4 on a partial approval (code 10)
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS apprv_amt BIGINT;
@@ -0,0 +1,4 @@
+-- Tip on a sale (set by tip adjustment advices) and cashback handed out on a purchase,
+-- both in minor units and counted in settlement totals
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS tip_amt BIGINT;
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS cashback_amt BIGINT;
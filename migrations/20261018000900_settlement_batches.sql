@@ -0,0 +1,37 @@
+-- Terminal settlement batches and the reconciliation totals of their last 0500
+CREATE TABLE IF NOT EXISTS settlement_batch (
+    batch_id       BIGSERIAL PRIMARY KEY,
+    trm_id         VARCHAR(16) NOT NULL,
+    batch_no       INTEGER     NOT NULL,
+    status         VARCHAR(16) NOT NULL DEFAULT 'OPEN',
+    currency       VARCHAR(3)  NOT NULL,
+    debit_cnt      BIGINT      NOT NULL DEFAULT 0,
+    debit_amt      BIGINT      NOT NULL DEFAULT 0,
+    debit_rev_cnt  BIGINT      NOT NULL DEFAULT 0,
+    debit_rev_amt  BIGINT      NOT NULL DEFAULT 0,
+    credit_cnt     BIGINT      NOT NULL DEFAULT 0,
+    credit_amt     BIGINT      NOT NULL DEFAULT 0,
+    credit_rev_cnt BIGINT      NOT NULL DEFAULT 0,
+    credit_rev_amt BIGINT      NOT NULL DEFAULT 0,
+    inquiry_cnt    BIGINT      NOT NULL DEFAULT 0,
+    auth_cnt       BIGINT      NOT NULL DEFAULT 0,
+    upload_cnt     INTEGER     NOT NULL DEFAULT 0,
+    attempts       INTEGER     NOT NULL DEFAULT 0,
+    resp_cd        VARCHAR(2),
+    open_dtm       VARCHAR(14) NOT NULL,
+    close_dtm      VARCHAR(14),
+    updt_dtm       VARCHAR(14)
+);
+
+-- One open batch per terminal
+CREATE UNIQUE INDEX IF NOT EXISTS uq_settlement_batch_open
+    ON settlement_batch (trm_id) WHERE status = 'OPEN';
+
+CREATE INDEX IF NOT EXISTS idx_settlement_batch_trm
+    ON settlement_batch (trm_id, batch_id);
+
+-- Batch a transaction was settled in; NULL while it waits for the next settlement
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS batch_id BIGINT;
+
+CREATE INDEX IF NOT EXISTS idx_iso8583_payment_batch
+    ON iso8583_payment (batch_id);
@@ -0,0 +1,6 @@
+-- Review flags of sales approved offline by the card (floor limit, TVR),
+-- comma separated; NULL for online transactions and clean offline approvals
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS offline_flags VARCHAR(200);
+
+CREATE INDEX IF NOT EXISTS idx_iso8583_payment_offline_flags
+    ON iso8583_payment (tr_dt) WHERE offline_flags IS NOT NULL;
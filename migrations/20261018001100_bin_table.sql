@@ -0,0 +1,22 @@
+-- BIN table: PAN ranges by leading digits, routed to a host group
+-- Loaded when BIN_TABLE_SOURCE=db and reloaded through POST /bins/reload
+CREATE TABLE IF NOT EXISTS bin_range (
+    bin_id      BIGSERIAL PRIMARY KEY,
+    low_bin     VARCHAR(11) NOT NULL,
+    high_bin    VARCHAR(11) NOT NULL,
+    scheme      VARCHAR(20),
+    issuer      VARCHAR(100),
+    country     CHAR(2),
+    card_type   VARCHAR(10),
+    host_group  VARCHAR(30) NOT NULL,
+    active      BOOLEAN NOT NULL DEFAULT TRUE,
+    inst_dtm    VARCHAR(14),
+    updt_dtm    VARCHAR(14),
+    CONSTRAINT ck_bin_range_bounds CHECK (LENGTH(low_bin) = LENGTH(high_bin) AND low_bin <= high_bin)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS uq_bin_range ON bin_range (low_bin, high_bin);
+
+-- Scheme and host group the transaction was routed to; follow-ups go to the same host
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS card_scheme VARCHAR(20);
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS host_group VARCHAR(30);
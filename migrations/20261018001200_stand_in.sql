@@ -0,0 +1,18 @@
+-- Transactions approved in stand-in (STIP) while the issuer host was unavailable;
+-- the host is advised through the SAF queue (STAND_IN_ADVICE)
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS stand_in BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_iso8583_payment_stand_in
+    ON iso8583_payment (tr_dt) WHERE stand_in;
+
+-- Stand-in exposure by business day, host group and currency, for finance
+CREATE OR REPLACE VIEW iso8583_stand_in_exposure AS
+SELECT tr_dt,
+       host_group,
+       field_049 AS currency,
+       COUNT(*) AS transactions,
+       SUM(COALESCE(apprv_amt, CAST(field_004 AS BIGINT))) AS exposure
+FROM iso8583_payment
+WHERE stand_in
+  AND tr_type = 'APPROVED'
+GROUP BY tr_dt, host_group, field_049;
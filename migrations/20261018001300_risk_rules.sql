@@ -0,0 +1,28 @@
+-- Pre-authorization risk rules, loaded when RISK_RULES_SOURCE=db and reloaded
+-- through POST /risk/rules/reload; definition is the rule JSON without its id
+CREATE TABLE IF NOT EXISTS risk_rule (
+    rule_id     VARCHAR(50) PRIMARY KEY,
+    priority    INTEGER     NOT NULL DEFAULT 100,
+    definition  TEXT        NOT NULL,
+    active      BOOLEAN     NOT NULL DEFAULT TRUE,
+    inst_dtm    VARCHAR(14),
+    updt_dtm    VARCHAR(14)
+);
+
+-- Velocity and decline counters shared by all instances (RISK_COUNTER_STORE=postgres);
+-- cards are keyed by PAN hash, events older than the rule window are removed
+CREATE TABLE IF NOT EXISTS risk_counter_event (
+    event_id    BIGSERIAL PRIMARY KEY,
+    counter_key VARCHAR(200) NOT NULL,
+    amount      BIGINT       NOT NULL DEFAULT 0,
+    event_dtm   VARCHAR(14)  NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_risk_counter_event_key
+    ON risk_counter_event (counter_key, event_dtm);
+
+-- Risk rules that flagged the transaction for review, comma separated
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS risk_flags VARCHAR(200);
+
+CREATE INDEX IF NOT EXISTS idx_iso8583_payment_risk_flags
+    ON iso8583_payment (tr_dt) WHERE risk_flags IS NOT NULL;
@@ -0,0 +1,30 @@
+-- Merchant master data: acceptance details sent in DE18/DE42/DE43 and acquirer routing (DE32)
+-- Status is ACTIVE, SUSPENDED or CLOSED; only active merchants can transact
+CREATE TABLE IF NOT EXISTS merchant (
+    merchant_id      VARCHAR(15) PRIMARY KEY,
+    name             VARCHAR(25) NOT NULL,
+    city             VARCHAR(13),
+    country          CHAR(2),
+    mcc              CHAR(4) NOT NULL,
+    status           VARCHAR(10) NOT NULL DEFAULT 'ACTIVE',
+    currency         CHAR(3),
+    acquirer_id      VARCHAR(11),
+    -- Comma separated transaction types (PURCHASE,REFUND,...), NULL allows every type
+    allowed_tx_types VARCHAR(500),
+    inst_dtm         VARCHAR(14),
+    updt_dtm         VARCHAR(14),
+    CONSTRAINT ck_merchant_status CHECK (status IN ('ACTIVE', 'SUSPENDED', 'CLOSED'))
+);
+
+-- Terminals installed at a merchant; allowed_tx_types overrides the merchant list
+CREATE TABLE IF NOT EXISTS terminal (
+    trm_id           VARCHAR(8) PRIMARY KEY,
+    merchant_id      VARCHAR(15) NOT NULL REFERENCES merchant (merchant_id),
+    status           VARCHAR(10) NOT NULL DEFAULT 'ACTIVE',
+    allowed_tx_types VARCHAR(500),
+    inst_dtm         VARCHAR(14),
+    updt_dtm         VARCHAR(14),
+    CONSTRAINT ck_terminal_status CHECK (status IN ('ACTIVE', 'SUSPENDED', 'CLOSED'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_terminal_merchant ON terminal (merchant_id);
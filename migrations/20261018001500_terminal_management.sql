@@ -0,0 +1,32 @@
+-- Terminal logon: each terminal answers a challenge with an HMAC-SHA256 under its logon key
+ALTER TABLE terminal ADD COLUMN IF NOT EXISTS logon_key VARCHAR(64);
+ALTER TABLE terminal ADD COLUMN IF NOT EXISTS last_logon_dtm VARCHAR(14);
+
+-- Parameter download: the set a terminal uses, the version it last downloaded and
+-- the version it confirmed it applied
+ALTER TABLE terminal ADD COLUMN IF NOT EXISTS param_set_id VARCHAR(20) NOT NULL DEFAULT 'DEFAULT';
+ALTER TABLE terminal ADD COLUMN IF NOT EXISTS param_download_version INTEGER;
+ALTER TABLE terminal ADD COLUMN IF NOT EXISTS param_version INTEGER;
+ALTER TABLE terminal ADD COLUMN IF NOT EXISTS param_updt_dtm VARCHAR(14);
+
+-- Published parameter sets (EMV AIDs/CAPKs, floor limits, hosts, receipt header)
+-- Versions are immutable; publishing adds the next version
+CREATE TABLE IF NOT EXISTS terminal_param_set (
+    param_set_id VARCHAR(20) NOT NULL,
+    version      INTEGER NOT NULL,
+    parameters   TEXT NOT NULL,
+    inst_dtm     VARCHAR(14),
+    PRIMARY KEY (param_set_id, version)
+);
+
+-- Terminals that have not applied the latest version of their set
+CREATE OR REPLACE VIEW terminal_param_outdated AS
+SELECT t.trm_id, t.param_set_id, t.param_version, t.param_download_version,
+       latest.version AS latest_version, t.param_updt_dtm
+FROM terminal t
+JOIN (
+    SELECT param_set_id, MAX(version) AS version
+    FROM terminal_param_set
+    GROUP BY param_set_id
+) latest ON latest.param_set_id = t.param_set_id
+WHERE t.param_version IS NULL OR t.param_version < latest.version;
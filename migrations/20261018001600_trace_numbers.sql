@@ -0,0 +1,8 @@
+-- STAN and RRN sequences, leased in blocks by every instance of the switch
+-- next_value is the first value not leased yet; spaces are STAN, STAN:TERMINAL:<trm_id>,
+-- STAN:HOST:<host_group> and RRN
+CREATE TABLE IF NOT EXISTS trace_number_space (
+    space      VARCHAR(60) PRIMARY KEY,
+    next_value BIGINT NOT NULL,
+    updt_dtm   VARCHAR(14)
+);
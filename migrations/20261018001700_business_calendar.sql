@@ -0,0 +1,14 @@
+-- Business days opened by a cutover, the latest one is the current business date
+-- source is SCHEDULED (cutover time reached) or HOST (0800 cutover from the host)
+CREATE TABLE IF NOT EXISTS business_day (
+    busi_dt  VARCHAR(8) PRIMARY KEY,
+    source   VARCHAR(10) NOT NULL,
+    open_dtm VARCHAR(14) NOT NULL
+);
+
+-- Business date (YYYYMMDD) a transaction or batch belongs to; DE15 carries its MMDD
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS field_015 VARCHAR(4);
+ALTER TABLE iso8583_payment ADD COLUMN IF NOT EXISTS busi_dt VARCHAR(8);
+ALTER TABLE settlement_batch ADD COLUMN IF NOT EXISTS busi_dt VARCHAR(8);
+
+CREATE INDEX IF NOT EXISTS idx_iso8583_payment_busi_dt ON iso8583_payment (busi_dt);
@@ -0,0 +1,3 @@
+-- DE39 the host answered a delivered SAF entry with; a delivered entry is not
+-- necessarily approved (e.g. 25 on a reversal of a request the host never saw)
+ALTER TABLE iso8583_saf_queue ADD COLUMN IF NOT EXISTS response_code VARCHAR(2);
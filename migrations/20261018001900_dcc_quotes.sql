@@ -0,0 +1,17 @@
+-- Open DCC offers, shared by every instance so the cardholder's answer can reach any
+-- of them; keyed by terminal and the terminal's transaction number (tr_uniq_no)
+CREATE TABLE IF NOT EXISTS dcc_quote (
+    trm_id      VARCHAR(16) NOT NULL,
+    tr_uniq_no  VARCHAR(64) NOT NULL,
+    local_amt   BIGINT      NOT NULL,
+    local_ccy   VARCHAR(3)  NOT NULL,
+    billing_amt BIGINT      NOT NULL,
+    billing_ccy VARCHAR(3)  NOT NULL,
+    conv_rate   VARCHAR(8)  NOT NULL,
+    markup_bps  INTEGER     NOT NULL,
+    expr_dtm    VARCHAR(14) NOT NULL,
+    inst_dtm    VARCHAR(14),
+    PRIMARY KEY (trm_id, tr_uniq_no)
+);
+
+CREATE INDEX IF NOT EXISTS idx_dcc_quote_expiry ON dcc_quote (expr_dtm);
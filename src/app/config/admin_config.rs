@@ -0,0 +1,22 @@
+use std::env;
+
+/// Admin API settings
+#[derive(Debug, Clone, Default)]
+pub struct AdminConfig {
+    /// Shared secret expected in the X-Admin-Token header; without one every
+    /// admin request is refused
+    pub api_token: Option<String>,
+}
+
+impl AdminConfig {
+    /// Load from environment (ADMIN_API_TOKEN)
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        Self {
+            api_token: env::var("ADMIN_API_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+        }
+    }
+}
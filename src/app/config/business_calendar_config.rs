@@ -0,0 +1,59 @@
+use chrono::NaiveTime;
+use std::env;
+
+/// What moves the business date to the next day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CutoverMode {
+    /// Local time reaching the cutover time (or an earlier host cutover)
+    Scheduled,
+    /// Only a host cutover (0800, DE70 201)
+    Host,
+}
+
+/// Business day settings
+#[derive(Debug, Clone)]
+pub struct BusinessCalendarConfig {
+    pub mode: CutoverMode,
+    /// Local time at which the next business day starts; midnight keeps the
+    /// business date on the calendar date
+    pub cutover_time: NaiveTime,
+}
+
+impl Default for BusinessCalendarConfig {
+    fn default() -> Self {
+        Self {
+            mode: CutoverMode::Scheduled,
+            cutover_time: NaiveTime::MIN,
+        }
+    }
+}
+
+impl BusinessCalendarConfig {
+    /// Load from environment
+    /// BUSINESS_DAY_CUTOVER_MODE is scheduled (default) or host, BUSINESS_DAY_CUTOVER_TIME
+    /// is HH:MM or HH:MM:SS local time (default 00:00)
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let defaults = Self::default();
+        Self {
+            mode: match env::var("BUSINESS_DAY_CUTOVER_MODE")
+                .map(|v| v.to_lowercase())
+                .as_deref()
+            {
+                Ok("host") => CutoverMode::Host,
+                _ => CutoverMode::Scheduled,
+            },
+            cutover_time: env::var("BUSINESS_DAY_CUTOVER_TIME")
+                .ok()
+                .and_then(|v| parse_time(&v))
+                .unwrap_or(defaults.cutover_time),
+        }
+    }
+}
+
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(value, "%H:%M"))
+        .ok()
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::env;
+
+use crate::app::config::parse_env;
+
+/// Where open DCC offers are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStoreKind {
+    /// `dcc_quote` table, shared by every instance
+    Postgres,
+    /// This instance only (tests, single-node development)
+    Memory,
+}
+
+/// Dynamic currency conversion settings
+#[derive(Debug, Clone)]
+pub struct DccConfig {
+    /// Master switch, DCC is only offered when enabled
+    pub enabled: bool,
+    /// ISO 3166 alpha-2 country of the acquirer; cards issued elsewhere are foreign
+    pub local_country: String,
+    /// Markup applied on top of the provider rate (basis points, 300 = 3%)
+    pub markup_bps: u32,
+    /// Opted-in merchants (DE42), with an optional merchant-specific markup
+    pub merchants: HashMap<String, Option<u32>>,
+    /// JSON file with conversion rates for the file-based rate provider
+    pub rate_file: Option<String>,
+    /// JSON file mapping BIN prefixes to the issuer country
+    pub bin_file: Option<String>,
+    /// How long an offered conversion can be accepted (seconds)
+    pub quote_ttl_secs: u64,
+    /// Where open offers are kept
+    pub quote_store: QuoteStoreKind,
+}
+
+impl Default for DccConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            local_country: "VN".to_string(),
+            markup_bps: 300,
+            merchants: HashMap::new(),
+            rate_file: None,
+            bin_file: None,
+            quote_ttl_secs: 120,
+            quote_store: QuoteStoreKind::Postgres,
+        }
+    }
+}
+
+impl DccConfig {
+    /// Load from environment, falling back to defaults for missing/invalid values
+    /// DCC_MERCHANTS lists opted-in merchants as `MID[:markup_bps]`, comma separated;
+    /// DCC_QUOTE_STORE is postgres (default) or memory
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let defaults = Self::default();
+        Self {
+            enabled: parse_env("DCC_ENABLED").unwrap_or(defaults.enabled),
+            local_country: env::var("DCC_LOCAL_COUNTRY").unwrap_or(defaults.local_country),
+            markup_bps: parse_env("DCC_MARKUP_BPS").unwrap_or(defaults.markup_bps),
+            merchants: env::var("DCC_MERCHANTS")
+                .map(|v| parse_merchants(&v))
+                .unwrap_or(defaults.merchants),
+            rate_file: env::var("DCC_RATE_FILE").ok().filter(|v| !v.is_empty()),
+            bin_file: env::var("DCC_BIN_FILE").ok().filter(|v| !v.is_empty()),
+            quote_ttl_secs: parse_env("DCC_QUOTE_TTL_SECS").unwrap_or(defaults.quote_ttl_secs),
+            quote_store: match env::var("DCC_QUOTE_STORE").as_deref() {
+                Ok("memory") | Ok("MEMORY") => QuoteStoreKind::Memory,
+                Ok("postgres") | Ok("POSTGRES") => QuoteStoreKind::Postgres,
+                _ => defaults.quote_store,
+            },
+        }
+    }
+
+    /// Markup for a merchant, None when the merchant has not opted in
+    pub fn markup_for(&self, merchant_id: &str) -> Option<u32> {
+        self.merchants
+            .get(merchant_id.trim())
+            .map(|markup| markup.unwrap_or(self.markup_bps))
+    }
+}
+
+fn parse_merchants(value: &str) -> HashMap<String, Option<u32>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once(':') {
+            Some((mid, markup)) => (mid.trim().to_string(), markup.trim().parse().ok()),
+            None => (entry.to_string(), None),
+        })
+        .collect()
+}
@@ -1,3 +1,20 @@
+pub mod admin_config;
+pub mod business_calendar_config;
 pub mod database_config;
+pub mod dcc_config;
 pub mod kafka_config;
-pub mod connection_config;
\ No newline at end of file
+pub mod connection_config;
+pub mod network_config;
+pub mod offline_config;
+pub mod preauth_config;
+pub mod reversal_config;
+pub mod saf_config;
+pub mod settlement_config;
+pub mod stip_config;
+pub mod terminal_config;
+pub mod trace_config;
+
+/// Environment variable parsed as `T`; None when unset or invalid
+pub fn parse_env<T: std::str::FromStr>(key: &str) -> Option<T> {
+    std::env::var(key).ok().and_then(|v| v.parse().ok())
+}
@@ -0,0 +1,34 @@
+use crate::app::config::parse_env;
+
+/// Host network management (0800) settings
+#[derive(Debug, Clone)]
+pub struct NetworkConfig {
+    /// Interval between echo tests (seconds)
+    pub echo_interval_secs: u64,
+    /// How long to wait for the 0810 (milliseconds)
+    pub response_timeout_ms: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            echo_interval_secs: 60,
+            response_timeout_ms: 10_000,
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Load from environment, falling back to defaults for missing/invalid values
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let defaults = Self::default();
+        Self {
+            echo_interval_secs: parse_env("NETWORK_ECHO_INTERVAL_SECS")
+                .unwrap_or(defaults.echo_interval_secs),
+            response_timeout_ms: parse_env("NETWORK_RESPONSE_TIMEOUT_MS")
+                .unwrap_or(defaults.response_timeout_ms),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+use std::env;
+
+use crate::models::amount::{Amount, Currency};
+
+/// Offline EMV approval settings
+#[derive(Debug, Clone, Default)]
+pub struct OfflineConfig {
+    /// Terminal floor limits by ISO 4217 alpha code, in major units
+    /// Offline approvals above the limit, or in a currency without one, are flagged
+    pub floor_limits: HashMap<String, String>,
+}
+
+impl OfflineConfig {
+    /// Load from environment
+    /// OFFLINE_FLOOR_LIMITS lists limits as `CUR:amount`, comma separated
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        Self {
+            floor_limits: env::var("OFFLINE_FLOOR_LIMITS")
+                .map(|v| parse_floor_limits(&v))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Floor limit in the given currency, zero when none is configured
+    pub fn floor_limit(&self, currency: &'static Currency) -> Amount {
+        self.floor_limits
+            .get(currency.alpha)
+            .and_then(|limit| Amount::from_major_str(limit, currency).ok())
+            .unwrap_or(Amount::from_minor(0, currency))
+    }
+}
+
+fn parse_floor_limits(value: &str) -> HashMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|entry| entry.split_once(':'))
+        .map(|(currency, limit)| (currency.trim().to_uppercase(), limit.trim().to_string()))
+        .collect()
+}
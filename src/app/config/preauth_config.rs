@@ -0,0 +1,38 @@
+use crate::app::config::parse_env;
+
+/// Pre-authorization hold settings
+#[derive(Debug, Clone)]
+pub struct PreAuthConfig {
+    /// Days an open hold stays valid before it expires
+    pub expiry_days: i64,
+    /// Completion may exceed the held amount by this percentage (hotel/car rental)
+    pub completion_tolerance_pct: u32,
+    /// How often expired holds are swept (seconds)
+    pub sweep_interval_secs: u64,
+}
+
+impl Default for PreAuthConfig {
+    fn default() -> Self {
+        Self {
+            expiry_days: 7,
+            completion_tolerance_pct: 15,
+            sweep_interval_secs: 3600,
+        }
+    }
+}
+
+impl PreAuthConfig {
+    /// Load from environment, falling back to defaults for missing/invalid values
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let defaults = Self::default();
+        Self {
+            expiry_days: parse_env("PREAUTH_EXPIRY_DAYS").unwrap_or(defaults.expiry_days),
+            completion_tolerance_pct: parse_env("PREAUTH_COMPLETION_TOLERANCE_PCT")
+                .unwrap_or(defaults.completion_tolerance_pct),
+            sweep_interval_secs: parse_env("PREAUTH_SWEEP_INTERVAL_SECS")
+                .unwrap_or(defaults.sweep_interval_secs),
+        }
+    }
+}
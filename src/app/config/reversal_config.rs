@@ -0,0 +1,21 @@
+use std::env;
+
+/// Reversal message settings
+#[derive(Debug, Clone, Default)]
+pub struct ReversalConfig {
+    /// Forwarding institution ID placed in DE90
+    pub forwarding_id: Option<String>,
+}
+
+impl ReversalConfig {
+    /// Load from environment
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        Self {
+            forwarding_id: env::var("FORWARDING_INSTITUTION_ID")
+                .ok()
+                .filter(|id| !id.is_empty()),
+        }
+    }
+}
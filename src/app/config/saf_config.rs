@@ -0,0 +1,43 @@
+use crate::app::config::parse_env;
+
+/// Store-and-forward delivery settings
+#[derive(Debug, Clone)]
+pub struct SafConfig {
+    /// How long to wait for the host acknowledgement (milliseconds)
+    pub response_timeout_ms: u64,
+    /// Delay after the first failed attempt, doubled on every further attempt (seconds)
+    pub base_backoff_secs: i64,
+    /// Upper bound of the retry delay (seconds)
+    pub max_backoff_secs: i64,
+    /// Attempts before an entry is marked dead
+    pub max_attempts: i32,
+}
+
+impl Default for SafConfig {
+    fn default() -> Self {
+        Self {
+            response_timeout_ms: 30_000,
+            base_backoff_secs: 30,
+            max_backoff_secs: 3600,
+            max_attempts: 10,
+        }
+    }
+}
+
+impl SafConfig {
+    /// Load from environment, falling back to defaults for missing/invalid values
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let defaults = Self::default();
+        Self {
+            response_timeout_ms: parse_env("SAF_RESPONSE_TIMEOUT_MS")
+                .unwrap_or(defaults.response_timeout_ms),
+            base_backoff_secs: parse_env("SAF_BASE_BACKOFF_SECS")
+                .unwrap_or(defaults.base_backoff_secs),
+            max_backoff_secs: parse_env("SAF_MAX_BACKOFF_SECS")
+                .unwrap_or(defaults.max_backoff_secs),
+            max_attempts: parse_env("SAF_MAX_ATTEMPTS").unwrap_or(defaults.max_attempts),
+        }
+    }
+}
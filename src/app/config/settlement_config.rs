@@ -0,0 +1,34 @@
+use crate::app::config::parse_env;
+
+/// Terminal batch settlement settings
+#[derive(Debug, Clone)]
+pub struct SettlementConfig {
+    /// How long to wait for the 0510/0330 of each request (milliseconds)
+    pub response_timeout_ms: u64,
+    /// Batch uploads (0320) followed by a settlement retry when the host reports
+    /// an out-of-balance batch (response code 95)
+    pub max_uploads: u32,
+}
+
+impl Default for SettlementConfig {
+    fn default() -> Self {
+        Self {
+            response_timeout_ms: 30_000,
+            max_uploads: 1,
+        }
+    }
+}
+
+impl SettlementConfig {
+    /// Load from environment, falling back to defaults for missing/invalid values
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let defaults = Self::default();
+        Self {
+            response_timeout_ms: parse_env("SETTLEMENT_RESPONSE_TIMEOUT_MS")
+                .unwrap_or(defaults.response_timeout_ms),
+            max_uploads: parse_env("SETTLEMENT_MAX_UPLOADS").unwrap_or(defaults.max_uploads),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::env;
+
+use crate::app::config::parse_env;
+use crate::models::amount::{Amount, Currency};
+
+/// Stand-in processing (STIP) settings
+/// Limits are in major units of the transaction currency
+#[derive(Debug, Clone, Default)]
+pub struct StipConfig {
+    /// Approve locally when the host does not answer
+    pub enabled: bool,
+    /// Limit of cards and merchants without a specific limit; None disables it
+    pub default_limit: Option<String>,
+    /// Limits by BIN prefix, the longest matching prefix applies
+    pub bin_limits: HashMap<String, String>,
+    /// Limits by merchant ID (DE42)
+    pub merchant_limits: HashMap<String, String>,
+    /// Require a valid ARQC on chip transactions
+    pub verify_arqc: bool,
+    /// Issuer master key of the software HSM (hex), the mock key when unset
+    pub issuer_master_key: Option<String>,
+}
+
+impl StipConfig {
+    /// Load from environment
+    /// STIP_BIN_LIMITS and STIP_MERCHANT_LIMITS list limits as `key:amount`, comma separated
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        Self {
+            enabled: parse_env("STIP_ENABLED").unwrap_or(false),
+            default_limit: env::var("STIP_DEFAULT_LIMIT")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            bin_limits: env::var("STIP_BIN_LIMITS")
+                .map(|v| parse_limits(&v))
+                .unwrap_or_default(),
+            merchant_limits: env::var("STIP_MERCHANT_LIMITS")
+                .map(|v| parse_limits(&v))
+                .unwrap_or_default(),
+            verify_arqc: parse_env("STIP_VERIFY_ARQC").unwrap_or(true),
+            issuer_master_key: env::var("STIP_ISSUER_MASTER_KEY")
+                .ok()
+                .filter(|v| !v.is_empty()),
+        }
+    }
+
+    /// Stand-in limit of a card at a merchant
+    /// The lower of the BIN and merchant limits, the default limit when neither is set
+    pub fn limit(
+        &self,
+        pan: Option<&str>,
+        merchant_id: Option<&str>,
+        currency: &'static Currency,
+    ) -> Option<Amount> {
+        let bin_limit = pan.and_then(|pan| {
+            self.bin_limits
+                .iter()
+                .filter(|(prefix, _)| pan.starts_with(prefix.as_str()))
+                .max_by_key(|(prefix, _)| prefix.len())
+                .map(|(_, limit)| limit)
+        });
+        let merchant_limit = merchant_id.and_then(|mid| self.merchant_limits.get(mid.trim()));
+
+        let parse = |limit: &String| Amount::from_major_str(limit, currency).ok();
+        match (bin_limit.and_then(parse), merchant_limit.and_then(parse)) {
+            (Some(bin), Some(merchant)) => Some(if bin.minor() <= merchant.minor() {
+                bin
+            } else {
+                merchant
+            }),
+            (Some(limit), None) | (None, Some(limit)) => Some(limit),
+            (None, None) => self.default_limit.as_ref().and_then(parse),
+        }
+    }
+}
+
+fn parse_limits(value: &str) -> HashMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|entry| entry.split_once(':'))
+        .map(|(key, limit)| (key.trim().to_string(), limit.trim().to_string()))
+        .collect()
+}
@@ -0,0 +1,39 @@
+use crate::app::config::parse_env;
+
+/// Terminal logon and parameter download settings
+#[derive(Debug, Clone)]
+pub struct TerminalConfig {
+    /// Refuse transactions on connections that have not logged on
+    pub logon_required: bool,
+    /// How long a logon challenge can be answered (seconds)
+    pub challenge_ttl_secs: u64,
+    /// Characters of parameter content per download block
+    pub param_block_size: usize,
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        Self {
+            logon_required: true,
+            challenge_ttl_secs: 60,
+            param_block_size: 2048,
+        }
+    }
+}
+
+impl TerminalConfig {
+    /// Load from environment, falling back to defaults for missing/invalid values
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let defaults = Self::default();
+        Self {
+            logon_required: parse_env("TERMINAL_LOGON_REQUIRED").unwrap_or(defaults.logon_required),
+            challenge_ttl_secs: parse_env("TERMINAL_LOGON_CHALLENGE_TTL_SECS")
+                .unwrap_or(defaults.challenge_ttl_secs),
+            param_block_size: parse_env("TERMINAL_PARAM_BLOCK_SIZE")
+                .filter(|size| *size > 0)
+                .unwrap_or(defaults.param_block_size),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::env;
+
+use crate::app::config::parse_env;
+
+/// Where trace numbers are leased from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceStoreKind {
+    /// `trace_number_space` table, shared by every instance
+    Postgres,
+    /// This instance only, restarting from 1 (tests, single-node development)
+    Memory,
+}
+
+/// STAN spaces: one for the whole switch, one per terminal or one per host group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StanScope {
+    Global,
+    Terminal,
+    Host,
+}
+
+/// STAN and RRN allocation settings
+#[derive(Debug, Clone)]
+pub struct TraceConfig {
+    pub store: TraceStoreKind,
+    pub stan_scope: StanScope,
+    /// Values leased per database round trip; unused values are skipped on restart
+    pub block_size: u32,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            store: TraceStoreKind::Postgres,
+            stan_scope: StanScope::Global,
+            block_size: 100,
+        }
+    }
+}
+
+impl TraceConfig {
+    /// Load from environment
+    /// TRACE_NUMBER_STORE is postgres (default) or memory, STAN_SCOPE is global
+    /// (default), terminal or host
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let defaults = Self::default();
+        Self {
+            store: match env::var("TRACE_NUMBER_STORE").as_deref() {
+                Ok("memory") | Ok("MEMORY") => TraceStoreKind::Memory,
+                Ok("postgres") | Ok("POSTGRES") => TraceStoreKind::Postgres,
+                _ => defaults.store,
+            },
+            stan_scope: match env::var("STAN_SCOPE").map(|v| v.to_lowercase()).as_deref() {
+                Ok("terminal") => StanScope::Terminal,
+                Ok("host") => StanScope::Host,
+                _ => StanScope::Global,
+            },
+            block_size: parse_env("TRACE_BLOCK_SIZE")
+                .filter(|size| *size > 0)
+                .unwrap_or(defaults.block_size),
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use crate::app::error::AppError;
+use crate::app::service::bin_table::BIN_REGISTRY;
+use crate::repository::bin_repository::BinRepository;
+use actix_web::{HttpResponse, Responder, post, web};
+use tracing::info;
+
+/// Reload the BIN table from its configured source (CSV file or database)
+#[post("/bins/reload")]
+pub async fn reload_bins(repo: web::Data<BinRepository>) -> Result<impl Responder, AppError> {
+    info!("Reloading BIN table");
+
+    let ranges = BIN_REGISTRY
+        .reload(&repo)
+        .await
+        .map_err(|e| AppError::Config(e.to_string()))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "reloaded",
+        "ranges": ranges,
+    })))
+}
@@ -3,11 +3,42 @@ use std::io;
 use std::sync::Arc;
 use tracing::{error, info};
 
+use crate::app::config::dcc_config::DccConfig;
+use crate::app::config::network_config::NetworkConfig;
+use crate::app::config::preauth_config::PreAuthConfig;
+use crate::app::config::reversal_config::ReversalConfig;
+use crate::app::config::saf_config::SafConfig;
+use crate::app::config::settlement_config::SettlementConfig;
+use crate::app::config::terminal_config::TerminalConfig;
+use crate::app::config::trace_config::TraceConfig;
+use crate::app::service::bin_table::BIN_REGISTRY;
+use crate::app::service::business_calendar::BUSINESS_CALENDAR;
+use crate::app::service::dcc_service::DccService;
 use crate::app::service::iso8583_transaction_service::Iso8583TransactionService;
+use crate::app::service::network_management_service::NetworkManagementService;
+use crate::app::service::preauth_service::PreAuthService;
+use crate::app::service::reversal_service::ReversalService;
+use crate::app::service::risk_engine::RISK_RULES;
+use crate::app::service::saf_service::SafService;
+use crate::app::service::settlement_service::{self, SettlementService};
 use crate::app::service::stan_generator::StanGenerator;
+use crate::app::service::terminal_management_service::{
+    TerminalManagementService, TerminalRequest,
+};
 use crate::app::service::tlv_parser::ParsedEmvData;
 use crate::models::card_request::CardRequest;
+use crate::models::iso8583_message::Iso8583Message;
+use crate::models::terminal_session::TerminalSession;
+use crate::repository::bin_repository::BinRepository;
+use crate::repository::business_day_repository::BusinessDayRepository;
 use crate::repository::card_transaction_repository::CardTransactionRepository;
+use crate::repository::dcc_repository::DccRepository;
+use crate::repository::preauth_repository::PreAuthRepository;
+use crate::repository::risk_repository::RiskRepository;
+use crate::repository::saf_repository::SafRepository;
+use crate::repository::settlement_repository::SettlementRepository;
+use crate::repository::terminal_repository::TerminalRepository;
+use crate::repository::trace_repository::TraceRepository;
 use sqlx::PgPool;
 use crate::models::app_context::AppContext;
 
@@ -15,25 +46,118 @@ use crate::models::app_context::AppContext;
 lazy_static::lazy_static! {
     static ref TRANSACTION_SERVICE: tokio::sync::OnceCell<Arc<Iso8583TransactionService>> =
         tokio::sync::OnceCell::new();
+    static ref SETTLEMENT_SERVICE: tokio::sync::OnceCell<Arc<SettlementService>> =
+        tokio::sync::OnceCell::new();
+    static ref TERMINAL_SERVICE: tokio::sync::OnceCell<Arc<TerminalManagementService>> =
+        tokio::sync::OnceCell::new();
+    static ref NETWORK_SERVICE: tokio::sync::OnceCell<Arc<NetworkManagementService>> =
+        tokio::sync::OnceCell::new();
 }
 
 /// Initialize the transaction service (call this from builder)
 pub async fn init_service(db_pool: Arc<PgPool>, ctx: Arc<AppContext>) {
-    let stan_generator = Arc::new(StanGenerator::new());
+    // BIN table routes card transactions; keep the bundled table if the source fails
+    if let Err(e) = BIN_REGISTRY
+        .reload(&BinRepository::new((*db_pool).clone()))
+        .await
+    {
+        error!("Failed to load BIN table, using bundled table: {}", e);
+    }
+    if let Err(e) = RISK_RULES
+        .reload(&RiskRepository::new((*db_pool).clone()))
+        .await
+    {
+        error!("Failed to load risk rules, using bundled rules: {}", e);
+    }
+    // Business date survives restarts; without a recorded day it follows the cutover time
+    if let Err(e) = BUSINESS_CALENDAR
+        .restore(&BusinessDayRepository::new((*db_pool).clone()))
+        .await
+    {
+        error!("Failed to restore the business date: {}", e);
+    }
+
+    // STAN/RRN blocks are leased from the database so restarts and replicas never reuse them
+    let stan_generator = Arc::new(StanGenerator::from_config(
+        TraceRepository::new((*db_pool).clone()),
+        TraceConfig::from_env(),
+    ));
     let transaction_repo = Arc::new(CardTransactionRepository::new((*db_pool).clone()));
+    let preauth_service = Arc::new(PreAuthService::new(
+        Arc::new(PreAuthRepository::new((*db_pool).clone())),
+        PreAuthConfig::from_env(),
+    ));
+    preauth_service.clone().spawn_expiry_sweeper();
+
+    // Reversals and advices are stored in the SAF queue and survive restarts;
+    // the queue is drained whenever sign-on or echo succeeds
+    let saf_repo = Arc::new(SafRepository::new((*db_pool).clone()));
+    let reversal_service = Arc::new(ReversalService::new(
+        stan_generator.clone(),
+        transaction_repo.clone(),
+        saf_repo.clone(),
+        ReversalConfig::from_env(),
+    ));
+    let saf_service = Arc::new(SafService::new(
+        saf_repo,
+        transaction_repo.clone(),
+        reversal_service.clone(),
+        SafConfig::from_env(),
+    ));
+    let network_service = Arc::new(NetworkManagementService::new(
+        stan_generator.clone(),
+        saf_service.clone(),
+        BusinessDayRepository::new((*db_pool).clone()),
+        NetworkConfig::from_env(),
+    ));
+    network_service.clone().spawn();
+    let _ = NETWORK_SERVICE.set(network_service.clone());
+
+    let settlement_service = Arc::new(SettlementService::new(
+        stan_generator.clone(),
+        transaction_repo.clone(),
+        Arc::new(SettlementRepository::new((*db_pool).clone())),
+        SettlementConfig::from_env(),
+    ));
+    let _ = SETTLEMENT_SERVICE.set(settlement_service);
+
+    let terminal_service = Arc::new(TerminalManagementService::new(
+        Arc::new(TerminalRepository::new((*db_pool).clone())),
+        TerminalConfig::from_env(),
+    ));
+    let _ = TERMINAL_SERVICE.set(terminal_service);
+
     let service = Arc::new(Iso8583TransactionService::new(
         stan_generator,
         transaction_repo,
+        preauth_service,
+        reversal_service,
+        saf_service,
+        Arc::new(DccService::from_config(
+            DccConfig::from_env(),
+            DccRepository::new((*db_pool).clone()),
+        )),
         ctx,
-    ));
+    )
+    .with_network_service(network_service));
 
     let _ = TRANSACTION_SERVICE.set(service);
     info!("ISO8583 Transaction Service initialized");
 }
 
+/// Handle a network management request (0800) sent by the host
+pub async fn handle_host_message(request: &Iso8583Message) -> io::Result<Iso8583Message> {
+    let service = NETWORK_SERVICE.get().ok_or_else(|| {
+        error!("Network management service not initialized");
+        io::Error::other("Service not initialized")
+    })?;
+    Ok(service.handle_host_request(request).await)
+}
+
 /// Handle incoming TCP message from terminal
 /// Parse JSON, process as ISO8583 transaction, return response
-pub async fn handle_message(raw_msg: &str) -> io::Result<Vec<u8>> {
+/// `session` is the logon state of the connection the message arrived on
+pub async fn handle_message(raw_msg: &str, session: &mut TerminalSession) -> io::Result<Vec<u8>> {
     info!("Received raw message: {:?}", raw_msg);
 
     // Step 1: Parse the main JSON message
@@ -50,6 +174,44 @@ pub async fn handle_message(raw_msg: &str) -> io::Result<Vec<u8>> {
         card_request.amount
     );
 
+    // Logon and parameter download; every other request needs a logged on connection
+    let terminal_service = TERMINAL_SERVICE.get().ok_or_else(|| {
+        error!("Terminal management service not initialized");
+        io::Error::other("Service not initialized")
+    })?;
+    let response_json = match TerminalRequest::from_str(&card_request.transaction_type) {
+        Some(request) => Some(
+            terminal_service
+                .process_request(request, &card_request, session)
+                .await
+                .map_err(|e| {
+                    error!("Terminal management request failed: {}", e);
+                    io::Error::other(e.to_string())
+                })?,
+        ),
+        None => terminal_service.authorize(session, &card_request),
+    };
+    if let Some(response_json) = response_json {
+        let response_str = serde_json::to_string(&response_json)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        return Ok(response_str.into_bytes());
+    }
+
+    // Settlement closes the terminal batch instead of going through the transaction flow
+    if settlement_service::is_settlement_request(&card_request.transaction_type) {
+        let service = SETTLEMENT_SERVICE.get().ok_or_else(|| {
+            error!("Settlement service not initialized");
+            io::Error::other("Service not initialized")
+        })?;
+        let response_json = service.process_request(&card_request).await.map_err(|e| {
+            error!("Settlement failed: {}", e);
+            io::Error::other(e.to_string())
+        })?;
+        let response_str = serde_json::to_string(&response_json)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        return Ok(response_str.into_bytes());
+    }
+
     // Step 2: Get transaction service
     let service = TRANSACTION_SERVICE.get().ok_or_else(|| {
         error!("Transaction service not initialized");
@@ -0,0 +1,17 @@
+use crate::app::error::AppError;
+use crate::app::service::master_data_cache::MASTER_DATA_CACHE;
+use actix_web::{HttpResponse, Responder, post};
+use tracing::info;
+
+/// Drop cached merchant/terminal master data so changes apply immediately
+#[post("/master-data/cache/clear")]
+pub async fn clear_master_data_cache() -> Result<impl Responder, AppError> {
+    info!("Clearing master data cache");
+
+    let entries = MASTER_DATA_CACHE.clear();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "cleared",
+        "entries": entries,
+    })))
+}
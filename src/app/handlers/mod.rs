@@ -1,7 +1,11 @@
+pub mod bin_admin_handler;
 pub mod handler_error;
 pub mod iso8583_msg_handler;
+pub mod master_data_admin_handler;
+pub mod network_admin_handler;
 pub mod pay_os_qr_handler;
 pub mod pay_os_resp_handler;
-
-
-
+pub mod profile_admin_handler;
+pub mod risk_admin_handler;
+pub mod saf_admin_handler;
+pub mod terminal_admin_handler;
\ No newline at end of file
@@ -0,0 +1,53 @@
+use crate::app::error::AppError;
+use crate::app::handlers::iso8583_msg_handler;
+use crate::app::service::business_calendar::{BUSINESS_CALENDAR, BUSINESS_DATE_FORMAT};
+use crate::models::iso8583_message::Iso8583Message;
+use actix_web::{HttpResponse, Responder, get, post, web};
+use serde::Deserialize;
+use std::collections::HashMap;
+use tracing::info;
+
+/// Network management message from the host: MTI and data elements
+#[derive(Debug, Deserialize)]
+pub struct HostMessage {
+    pub mti: String,
+    pub fields: HashMap<u8, String>,
+}
+
+/// Deliver a host network management request (0800) and return the 0810
+/// A cutover (DE70 201) moves the business date to DE15
+#[post("/network/host-message")]
+pub async fn receive_host_message(
+    message: web::Json<HostMessage>,
+) -> Result<impl Responder, AppError> {
+    let message = message.into_inner();
+    info!(
+        "Host network management message: MTI={}, DE70={:?}",
+        message.mti,
+        message.fields.get(&70)
+    );
+
+    let mut request = Iso8583Message::new(&message.mti);
+    for (de, value) in message.fields {
+        request.set_field(de, value);
+    }
+    let response = iso8583_msg_handler::handle_host_message(&request)
+        .await
+        .map_err(|e| AppError::ExternalService(e.to_string()))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "mti": response.mti,
+        "fields": response.fields,
+    })))
+}
+
+/// Current business date and the DE15 settlement date sent with it
+#[get("/business-date")]
+pub async fn current_business_date() -> Result<impl Responder, AppError> {
+    let business_date = BUSINESS_CALENDAR.business_date();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "businessDate": business_date.format(BUSINESS_DATE_FORMAT).to_string(),
+        "settlementDate": business_date.format("%m%d").to_string(),
+    })))
+}
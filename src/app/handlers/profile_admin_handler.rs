@@ -0,0 +1,37 @@
+use crate::app::error::AppError;
+use crate::app::service::transaction_profile::PROFILE_REGISTRY;
+use actix_web::{HttpResponse, Responder, post, web};
+use tracing::info;
+
+/// Reload base transaction profiles and all acquirer overrides
+#[post("/profiles/reload")]
+pub async fn reload_profiles() -> Result<impl Responder, AppError> {
+    info!("Reloading all transaction profiles");
+
+    PROFILE_REGISTRY
+        .reload()
+        .map_err(|e| AppError::Config(e.to_string()))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "reloaded",
+    })))
+}
+
+/// Reload the profile overrides of a single acquirer
+#[post("/profiles/reload/{acquirer_id}")]
+pub async fn reload_acquirer_profiles(path: web::Path<String>) -> Result<impl Responder, AppError> {
+    let acquirer_id = path.into_inner();
+    info!(
+        "Reloading transaction profiles for acquirer {}",
+        acquirer_id
+    );
+
+    PROFILE_REGISTRY
+        .reload_acquirer(&acquirer_id)
+        .map_err(|e| AppError::Config(e.to_string()))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "reloaded",
+        "acquirerId": acquirer_id,
+    })))
+}
@@ -0,0 +1,23 @@
+use crate::app::error::AppError;
+use crate::app::service::risk_engine::RISK_RULES;
+use crate::repository::risk_repository::RiskRepository;
+use actix_web::{HttpResponse, Responder, post, web};
+use tracing::info;
+
+/// Reload the risk rules from their configured source (JSON file or database)
+#[post("/risk/rules/reload")]
+pub async fn reload_risk_rules(
+    repo: web::Data<RiskRepository>,
+) -> Result<impl Responder, AppError> {
+    info!("Reloading risk rules");
+
+    let rules = RISK_RULES
+        .reload(&repo)
+        .await
+        .map_err(|e| AppError::Config(e.to_string()))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "reloaded",
+        "rules": rules,
+    })))
+}
@@ -0,0 +1,37 @@
+use crate::app::error::AppError;
+use crate::repository::saf_repository::SafRepository;
+use actix_web::{HttpResponse, Responder, get, post, web};
+use tracing::info;
+
+/// List dead SAF entries (max attempts reached) for operations
+#[get("/saf/dead")]
+pub async fn list_dead_saf(repo: web::Data<SafRepository>) -> Result<impl Responder, AppError> {
+    let entries = repo.find_dead().await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "count": entries.len(),
+        "entries": entries,
+    })))
+}
+
+/// Put a dead SAF entry back in the queue; it is sent on the next drain
+#[post("/saf/{saf_id}/requeue")]
+pub async fn requeue_saf(
+    repo: web::Data<SafRepository>,
+    path: web::Path<i64>,
+) -> Result<impl Responder, AppError> {
+    let saf_id = path.into_inner();
+    info!("Requeueing dead SAF entry {}", saf_id);
+
+    if !repo.requeue_dead(saf_id).await? {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "status": "not_found",
+            "safId": saf_id,
+        })));
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "requeued",
+        "safId": saf_id,
+    })))
+}
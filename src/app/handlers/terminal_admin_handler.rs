@@ -0,0 +1,38 @@
+use crate::app::error::AppError;
+use crate::models::terminal_params::TerminalParameters;
+use crate::repository::terminal_repository::TerminalRepository;
+use actix_web::{HttpResponse, Responder, post, web};
+use tracing::info;
+
+/// Publish the next version of a terminal parameter set
+/// Terminals using the set download it at their next logon
+#[post("/terminals/params/{param_set_id}")]
+pub async fn publish_terminal_params(
+    repo: web::Data<TerminalRepository>,
+    path: web::Path<String>,
+    parameters: web::Json<TerminalParameters>,
+) -> Result<impl Responder, AppError> {
+    let param_set_id = path.into_inner();
+
+    if let Err(reason) = parameters.validate() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "status": "invalid",
+            "paramSetId": param_set_id,
+            "reason": reason,
+        })));
+    }
+
+    let content = serde_json::to_string(&parameters.into_inner())
+        .map_err(|e| AppError::Config(e.to_string()))?;
+    let version = repo.publish_param_set(&param_set_id, &content).await?;
+    info!(
+        "Published terminal parameter set {} version {}",
+        param_set_id, version
+    );
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "published",
+        "paramSetId": param_set_id,
+        "version": version,
+    })))
+}
@@ -0,0 +1,92 @@
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpResponse, web};
+use ring::hmac;
+use ring::rand::SystemRandom;
+use tracing::warn;
+
+use crate::app::config::admin_config::AdminConfig;
+
+/// Header carrying the admin shared secret
+pub const ADMIN_TOKEN_HEADER: &str = "X-Admin-Token";
+
+/// Shared-secret check of admin requests
+/// Tokens are compared as HMACs under a per-process key, so the comparison takes
+/// the same time whatever the presented token
+pub struct AdminAuth {
+    key: hmac::Key,
+    expected: Option<hmac::Tag>,
+}
+
+impl AdminAuth {
+    pub fn new(config: &AdminConfig) -> Self {
+        let key = hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+            .expect("Failed to generate admin token key");
+        let expected = config
+            .api_token
+            .as_ref()
+            .map(|token| hmac::sign(&key, token.as_bytes()));
+        if expected.is_none() {
+            warn!("ADMIN_API_TOKEN not set, admin API disabled");
+        }
+        Self { key, expected }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(&AdminConfig::from_env())
+    }
+
+    /// Whether a presented token is the configured one
+    pub fn authorizes(&self, presented: Option<&str>) -> bool {
+        match (&self.expected, presented) {
+            (Some(expected), Some(presented)) => {
+                hmac::verify(&self.key, presented.as_bytes(), expected.as_ref()).is_ok()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Middleware of the /admin scope: refuse requests without the admin token (401)
+pub async fn require_admin_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let authorized = req.app_data::<web::Data<AdminAuth>>().is_some_and(|auth| {
+        auth.authorizes(
+            req.headers()
+                .get(ADMIN_TOKEN_HEADER)
+                .and_then(|value| value.to_str().ok()),
+        )
+    });
+    if !authorized {
+        warn!("Admin request refused: {} {}", req.method(), req.path());
+        return Ok(req
+            .into_response(HttpResponse::Unauthorized().json(serde_json::json!({
+                "status": "unauthorized",
+            })))
+            .map_into_right_body());
+    }
+
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admin_token_check() {
+        let auth = AdminAuth::new(&AdminConfig {
+            api_token: Some("s3cret".to_string()),
+        });
+        assert!(auth.authorizes(Some("s3cret")));
+        assert!(!auth.authorizes(Some("s3cre")));
+        assert!(!auth.authorizes(None));
+
+        let disabled = AdminAuth::new(&AdminConfig::default());
+        assert!(!disabled.authorizes(Some("")));
+        assert!(!disabled.authorizes(None));
+    }
+}
@@ -1 +1,3 @@
+pub mod admin_auth;
 pub mod mac_calculator;
+pub mod soft_hsm;
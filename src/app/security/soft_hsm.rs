@@ -0,0 +1,79 @@
+use hex;
+use ring::hmac;
+
+/// Software HSM for stand-in ARQC verification
+/// Derives card and session keys from the issuer master key the way EMV key
+/// derivation does, using HMAC-SHA256 like MacCalculator (production would use
+/// a hardware HSM with the scheme's 3DES/AES derivation)
+pub struct SoftHsm {
+    /// Issuer master key for application cryptograms
+    imk: Vec<u8>,
+}
+
+impl SoftHsm {
+    /// Create a software HSM with a mock issuer master key
+    pub fn new_mock() -> Self {
+        let imk =
+            hex::decode("0123456789ABCDEFFEDCBA9876543210").expect("Failed to decode mock IMK");
+
+        Self { imk }
+    }
+
+    /// Create with custom issuer master key
+    pub fn with_key(key: Vec<u8>) -> Self {
+        Self { imk: key }
+    }
+
+    /// Card master key from the PAN and PAN sequence number (5F34)
+    fn card_key(&self, pan: &str, psn: &str) -> hmac::Tag {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, &self.imk);
+        hmac::sign(&key, format!("{}{}", pan, psn).as_bytes())
+    }
+
+    /// Session key for one application transaction counter (9F36)
+    fn session_key(&self, pan: &str, psn: &str, atc: &[u8]) -> hmac::Key {
+        let card_key = hmac::Key::new(hmac::HMAC_SHA256, self.card_key(pan, psn).as_ref());
+        hmac::Key::new(hmac::HMAC_SHA256, hmac::sign(&card_key, atc).as_ref())
+    }
+
+    /// Generate the ARQC over the transaction data
+    /// Returns 8-byte cryptogram as hex string
+    pub fn generate_arqc(&self, pan: &str, psn: &str, atc: &[u8], data: &[u8]) -> String {
+        let tag = hmac::sign(&self.session_key(pan, psn, atc), data);
+        hex::encode_upper(&tag.as_ref()[..8])
+    }
+
+    /// Verify the ARQC (9F26) the card generated over the transaction data
+    pub fn verify_arqc(&self, pan: &str, psn: &str, atc: &[u8], data: &[u8], arqc: &[u8]) -> bool {
+        self.generate_arqc(pan, psn, atc, data) == hex::encode_upper(arqc)
+    }
+}
+
+impl Default for SoftHsm {
+    fn default() -> Self {
+        Self::new_mock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arqc_verification() {
+        let hsm = SoftHsm::new_mock();
+        let pan = "4111111111111111";
+        let atc = [0x00, 0x12];
+        let data = b"000000050000";
+
+        let arqc = hex::decode(hsm.generate_arqc(pan, "01", &atc, data)).unwrap();
+        assert_eq!(arqc.len(), 8);
+        assert!(hsm.verify_arqc(pan, "01", &atc, data, &arqc));
+
+        // Other card, counter, data or key
+        assert!(!hsm.verify_arqc("4111111111111129", "01", &atc, data, &arqc));
+        assert!(!hsm.verify_arqc(pan, "01", &[0x00, 0x13], data, &arqc));
+        assert!(!hsm.verify_arqc(pan, "01", &atc, b"000000060000", &arqc));
+        assert!(!SoftHsm::with_key(vec![0; 16]).verify_arqc(pan, "01", &atc, data, &arqc));
+    }
+}
@@ -0,0 +1,254 @@
+use once_cell::sync::Lazy;
+use std::cmp::Reverse;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+use tracing::{error, info, warn};
+
+use crate::models::bin_range::BinRange;
+use crate::repository::bin_repository::BinRepository;
+
+/// Default BIN table shipped with the application
+const DEFAULT_BIN_TABLE_CSV: &str = include_str!("../../../config/bin_table.csv");
+
+/// BIN table errors
+#[derive(Debug, Error)]
+pub enum BinTableError {
+    #[error("Failed to read BIN table {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid BIN table line {line}: {reason}")]
+    Parse { line: usize, reason: String },
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Where the BIN table is loaded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BinSource {
+    /// CSV file, or the bundled table when no path is configured
+    Csv(Option<PathBuf>),
+    /// `bin_range` table
+    Database,
+}
+
+/// BIN ranges, looked up by longest matching range
+#[derive(Debug, Default)]
+pub struct BinTable {
+    ranges: Vec<BinRange>,
+}
+
+impl BinTable {
+    pub fn new(mut ranges: Vec<BinRange>) -> Self {
+        // Longest ranges first so the first match is the most specific
+        ranges.sort_by_key(|range| Reverse(range.low_bin.len()));
+        Self { ranges }
+    }
+
+    /// Parse a CSV of `low_bin,high_bin,scheme,issuer,country,card_type,host_group`
+    /// with a header line; empty optional columns are allowed
+    pub fn from_csv(csv: &str) -> Result<Self, BinTableError> {
+        let mut ranges = Vec::new();
+        for (index, line) in csv.lines().enumerate().skip(1) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            ranges.push(parse_line(line).map_err(|reason| BinTableError::Parse {
+                line: index + 1,
+                reason,
+            })?);
+        }
+        Ok(Self::new(ranges))
+    }
+
+    /// Most specific range containing the PAN
+    pub fn lookup(&self, pan: &str) -> Option<&BinRange> {
+        self.ranges.iter().find(|range| range.matches(pan))
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+fn parse_line(line: &str) -> Result<BinRange, String> {
+    let columns: Vec<&str> = line.split(',').map(str::trim).collect();
+    let [
+        low_bin,
+        high_bin,
+        scheme,
+        issuer,
+        country,
+        card_type,
+        host_group,
+    ] = columns[..]
+    else {
+        return Err(format!("expected 7 columns, found {}", columns.len()));
+    };
+    if low_bin.is_empty()
+        || low_bin.len() != high_bin.len()
+        || !low_bin
+            .bytes()
+            .chain(high_bin.bytes())
+            .all(|b| b.is_ascii_digit())
+        || low_bin > high_bin
+    {
+        return Err(format!("invalid range {}-{}", low_bin, high_bin));
+    }
+    if host_group.is_empty() {
+        return Err("missing host group".to_string());
+    }
+
+    let optional = |value: &str| (!value.is_empty()).then(|| value.to_string());
+    Ok(BinRange {
+        low_bin: low_bin.to_string(),
+        high_bin: high_bin.to_string(),
+        scheme: optional(scheme),
+        issuer: optional(issuer),
+        country: optional(country),
+        card_type: optional(card_type),
+        host_group: host_group.to_string(),
+    })
+}
+
+/// Hot-reloadable BIN table
+/// Loaded from `BIN_TABLE_PATH` (or the bundled CSV), or from the database when
+/// `BIN_TABLE_SOURCE=db`; lookups keep the old table until a reload succeeds
+pub struct BinRegistry {
+    source: BinSource,
+    table: RwLock<Arc<BinTable>>,
+}
+
+impl BinRegistry {
+    /// Build the registry from environment configuration
+    /// Starts with the bundled table; the database source is loaded at service start
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let source = match env::var("BIN_TABLE_SOURCE").as_deref() {
+            Ok("db") | Ok("DB") => BinSource::Database,
+            _ => BinSource::Csv(env::var("BIN_TABLE_PATH").ok().map(PathBuf::from)),
+        };
+        let registry = Self {
+            source,
+            table: RwLock::new(Arc::new(
+                BinTable::from_csv(DEFAULT_BIN_TABLE_CSV).expect("Bundled BIN table must be valid"),
+            )),
+        };
+        if let BinSource::Csv(Some(_)) = registry.source
+            && let Err(e) = registry.reload_csv()
+        {
+            error!("Failed to load BIN table, using bundled table: {}", e);
+        }
+        registry
+    }
+
+    /// Reload from the configured source; returns the number of ranges
+    pub async fn reload(&self, repo: &BinRepository) -> Result<usize, BinTableError> {
+        match self.source {
+            BinSource::Database => {
+                let table = BinTable::new(repo.find_active().await?);
+                Ok(self.replace(table))
+            }
+            BinSource::Csv(_) => self.reload_csv(),
+        }
+    }
+
+    fn reload_csv(&self) -> Result<usize, BinTableError> {
+        let table = match &self.source {
+            BinSource::Csv(Some(path)) => {
+                let csv = fs::read_to_string(path).map_err(|source| BinTableError::Io {
+                    path: path.display().to_string(),
+                    source,
+                })?;
+                BinTable::from_csv(&csv)?
+            }
+            _ => BinTable::from_csv(DEFAULT_BIN_TABLE_CSV)?,
+        };
+        Ok(self.replace(table))
+    }
+
+    fn replace(&self, table: BinTable) -> usize {
+        let len = table.len();
+        if table.is_empty() {
+            warn!("BIN table is empty, every card will be declined");
+        }
+        info!("Loaded BIN table with {} ranges", len);
+        *self.table.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(table);
+        len
+    }
+
+    /// Current table
+    pub fn table(&self) -> Arc<BinTable> {
+        self.table.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Most specific range containing the PAN
+    pub fn lookup(&self, pan: &str) -> Option<BinRange> {
+        self.table().lookup(pan).cloned()
+    }
+}
+
+/// Global BIN table
+pub static BIN_REGISTRY: Lazy<BinRegistry> = Lazy::new(BinRegistry::from_env);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::bin_range::CardScheme;
+
+    #[test]
+    fn test_longest_range_wins() {
+        let table = BinTable::from_csv(
+            "low_bin,high_bin,scheme,issuer,country,card_type,host_group\n\
+             4,4,VISA,,,,VISA_HOST\n\
+             411111,411111,,Test Bank,US,CREDIT,US_HOST\n\
+             970400,970499,NAPAS,,VN,DEBIT,NAPAS_HOST\n",
+        )
+        .unwrap();
+
+        let range = table.lookup("4111111111111111").unwrap();
+        assert_eq!(range.host_group, "US_HOST");
+        assert_eq!(range.country.as_deref(), Some("US"));
+        assert_eq!(range.scheme("4111111111111111"), Some(CardScheme::Visa));
+
+        assert_eq!(
+            table.lookup("4000056655665556").unwrap().host_group,
+            "VISA_HOST"
+        );
+        assert_eq!(
+            table.lookup("9704000000000018").unwrap().host_group,
+            "NAPAS_HOST"
+        );
+        assert!(table.lookup("5555555555554444").is_none());
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        let header = "low_bin,high_bin,scheme,issuer,country,card_type,host_group\n";
+        for line in [
+            "4,41,VISA,,,,HOST",
+            "5,4,VISA,,,,HOST",
+            "4,4,VISA,,,,",
+            "4,4,VISA",
+        ] {
+            assert!(BinTable::from_csv(&format!("{}{}", header, line)).is_err());
+        }
+        assert!(
+            !BinTable::from_csv(DEFAULT_BIN_TABLE_CSV)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
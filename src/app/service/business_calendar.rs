@@ -0,0 +1,302 @@
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+use thiserror::Error;
+use tracing::info;
+
+use crate::app::config::business_calendar_config::{BusinessCalendarConfig, CutoverMode};
+use crate::repository::business_day_repository::BusinessDayRepository;
+
+/// Business date format of the `busi_dt` columns
+pub const BUSINESS_DATE_FORMAT: &str = "%Y%m%d";
+
+/// What opened a business day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CutoverSource {
+    Scheduled,
+    Host,
+}
+
+impl CutoverSource {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CutoverSource::Scheduled => "SCHEDULED",
+            CutoverSource::Host => "HOST",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CutoverError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Invalid settlement date: {0}")]
+    InvalidDate(String),
+
+    #[error("Business date {current} cannot go back to {requested}")]
+    Backwards {
+        current: NaiveDate,
+        requested: NaiveDate,
+    },
+}
+
+/// DE7 transmission date and time (MMDDhhmmss), always GMT
+pub fn transmission_date_time() -> String {
+    Utc::now().format("%m%d%H%M%S").to_string()
+}
+
+/// Business calendar
+/// Transactions and batches belong to the business date, which moves to the next
+/// day at the configured cutover time or when the host cuts over (0800, DE70 201),
+/// not at midnight. Cutovers are recorded in `business_day` so the date survives
+/// restarts; the date never goes back
+pub struct BusinessCalendar {
+    config: BusinessCalendarConfig,
+    /// Latest business day opened by a cutover
+    opened: RwLock<Option<NaiveDate>>,
+}
+
+impl BusinessCalendar {
+    pub fn new(config: BusinessCalendarConfig) -> Self {
+        Self {
+            config,
+            opened: RwLock::new(None),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(BusinessCalendarConfig::from_env())
+    }
+
+    /// Current business date
+    pub fn business_date(&self) -> NaiveDate {
+        self.business_date_at(Local::now().naive_local())
+    }
+
+    /// DE15 settlement date (MMDD) of the current business date
+    pub fn settlement_date(&self) -> String {
+        self.business_date().format("%m%d").to_string()
+    }
+
+    /// Business date at a local time
+    /// In host mode the date only moves on host cutovers, the schedule applies
+    /// until the first one
+    pub fn business_date_at(&self, now: NaiveDateTime) -> NaiveDate {
+        let opened = *self.opened.read().unwrap_or_else(|e| e.into_inner());
+        match (self.config.mode, opened) {
+            (CutoverMode::Host, Some(opened)) => opened,
+            (_, opened) => opened.map_or(self.scheduled_date(now), |opened| {
+                opened.max(self.scheduled_date(now))
+            }),
+        }
+    }
+
+    /// Business date by the cutover time alone
+    fn scheduled_date(&self, now: NaiveDateTime) -> NaiveDate {
+        if self.config.cutover_time > NaiveTime::MIN && now.time() >= self.config.cutover_time {
+            now.date().succ_opt().unwrap_or(now.date())
+        } else {
+            now.date()
+        }
+    }
+
+    /// Load the latest business day opened; returns it
+    pub async fn restore(
+        &self,
+        repo: &BusinessDayRepository,
+    ) -> Result<Option<NaiveDate>, sqlx::Error> {
+        let restored = Self::recorded(repo).await?;
+        if let Some(date) = restored {
+            self.open(date);
+            info!("Business date restored: {}", date);
+        }
+        Ok(restored)
+    }
+
+    /// Pick up business days recorded since the last look, e.g. a host cutover
+    /// received by another instance
+    pub async fn refresh(&self, repo: &BusinessDayRepository) -> Result<(), sqlx::Error> {
+        if let Some(date) = Self::recorded(repo).await?
+            && self.open(date)
+        {
+            info!(
+                "Business date {} picked up from the business day record",
+                date
+            );
+        }
+        Ok(())
+    }
+
+    /// Record a business day opened by the schedule since the last cutover
+    pub async fn record_scheduled(&self, repo: &BusinessDayRepository) -> Result<(), sqlx::Error> {
+        let current = self.business_date();
+        let opened = *self.opened.read().unwrap_or_else(|e| e.into_inner());
+        if opened.is_some_and(|opened| opened >= current) {
+            return Ok(());
+        }
+        repo.open(
+            &current.format(BUSINESS_DATE_FORMAT).to_string(),
+            CutoverSource::Scheduled.as_str(),
+        )
+        .await?;
+        self.open(current);
+        info!("Business day {} opened at the scheduled cutover", current);
+        Ok(())
+    }
+
+    /// Cut over to `to`, or to the day after the current business date
+    /// Cutting over to the current date again (a repeated host cutover) is accepted
+    pub async fn cutover(
+        &self,
+        repo: &BusinessDayRepository,
+        to: Option<NaiveDate>,
+        source: CutoverSource,
+    ) -> Result<NaiveDate, CutoverError> {
+        let current = self.business_date();
+        let next = match to {
+            Some(date) => date,
+            None => current
+                .succ_opt()
+                .ok_or_else(|| CutoverError::InvalidDate(current.to_string()))?,
+        };
+        if next < current {
+            return Err(CutoverError::Backwards {
+                current,
+                requested: next,
+            });
+        }
+
+        repo.open(
+            &next.format(BUSINESS_DATE_FORMAT).to_string(),
+            source.as_str(),
+        )
+        .await?;
+        self.open(next);
+        info!(
+            "Business day {} opened by {} cutover (was {})",
+            next,
+            source.as_str(),
+            current
+        );
+        Ok(next)
+    }
+
+    /// Date of a DE15 (MMDD) settlement date: the one nearest the current business
+    /// date, so a cutover around new year lands in the right year
+    pub fn resolve_settlement_date(&self, de15: &str) -> Result<NaiveDate, CutoverError> {
+        let current = self.business_date();
+        let invalid = || CutoverError::InvalidDate(de15.to_string());
+        if de15.len() != 4 || !de15.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let month: u32 = de15[..2].parse().map_err(|_| invalid())?;
+        let day: u32 = de15[2..].parse().map_err(|_| invalid())?;
+
+        [current.year() - 1, current.year(), current.year() + 1]
+            .into_iter()
+            .filter_map(|year| NaiveDate::from_ymd_opt(year, month, day))
+            .min_by_key(|date| (*date - current).num_days().abs())
+            .ok_or_else(invalid)
+    }
+
+    /// Latest business day recorded
+    async fn recorded(repo: &BusinessDayRepository) -> Result<Option<NaiveDate>, sqlx::Error> {
+        Ok(repo
+            .current()
+            .await?
+            .and_then(|busi_dt| NaiveDate::parse_from_str(&busi_dt, BUSINESS_DATE_FORMAT).ok()))
+    }
+
+    /// Open `date` unless a later day is open; returns whether the date moved
+    fn open(&self, date: NaiveDate) -> bool {
+        let mut opened = self.opened.write().unwrap_or_else(|e| e.into_inner());
+        let moved = opened.is_none_or(|opened| opened < date);
+        if moved {
+            *opened = Some(date);
+        }
+        moved
+    }
+}
+
+/// Business calendar of the switch
+pub static BUSINESS_CALENDAR: Lazy<BusinessCalendar> = Lazy::new(BusinessCalendar::from_env);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{date} {time}"), "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_business_date_follows_cutover_not_midnight() {
+        let calendar = BusinessCalendar::new(BusinessCalendarConfig {
+            mode: CutoverMode::Scheduled,
+            cutover_time: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+        });
+        assert_eq!(
+            calendar.business_date_at(at("2026-10-18", "21:59:59")),
+            date("2026-10-18")
+        );
+        assert_eq!(
+            calendar.business_date_at(at("2026-10-18", "22:00:00")),
+            date("2026-10-19")
+        );
+        assert_eq!(
+            calendar.business_date_at(at("2026-10-19", "00:30:00")),
+            date("2026-10-19")
+        );
+
+        // An early host cutover holds until the schedule catches up
+        calendar.open(date("2026-10-20"));
+        assert_eq!(
+            calendar.business_date_at(at("2026-10-19", "10:00:00")),
+            date("2026-10-20")
+        );
+
+        let host = BusinessCalendar::new(BusinessCalendarConfig {
+            mode: CutoverMode::Host,
+            cutover_time: NaiveTime::MIN,
+        });
+        assert_eq!(
+            host.business_date_at(at("2026-10-18", "12:00:00")),
+            date("2026-10-18")
+        );
+        assert!(host.open(date("2026-10-18")));
+        assert_eq!(
+            host.business_date_at(at("2026-10-19", "03:00:00")),
+            date("2026-10-18")
+        );
+        // The date never goes back
+        assert!(!host.open(date("2026-10-17")));
+        assert_eq!(
+            host.business_date_at(at("2026-10-19", "03:00:00")),
+            date("2026-10-18")
+        );
+    }
+
+    #[test]
+    fn test_resolve_settlement_date_across_year_end() {
+        let calendar = BusinessCalendar::new(BusinessCalendarConfig {
+            mode: CutoverMode::Host,
+            cutover_time: NaiveTime::MIN,
+        });
+        calendar.open(date("2026-12-31"));
+        assert_eq!(
+            calendar.resolve_settlement_date("0101").unwrap(),
+            date("2027-01-01")
+        );
+        assert_eq!(
+            calendar.resolve_settlement_date("1231").unwrap(),
+            date("2026-12-31")
+        );
+        assert!(calendar.resolve_settlement_date("1332").is_err());
+        assert!(calendar.resolve_settlement_date("12a1").is_err());
+    }
+}
@@ -0,0 +1,608 @@
+use async_trait::async_trait;
+use chrono::{Duration, Local, NaiveDateTime};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::app::config::dcc_config::{DccConfig, QuoteStoreKind};
+use crate::app::service::bin_table::BIN_REGISTRY;
+use crate::app::service::transaction_profile::TransactionType;
+use crate::app::utils::metrics;
+use crate::models::amount::{Amount, AmountError, ConversionRate, Currency};
+use crate::models::card_request::CardRequest;
+use crate::models::iso8583_message::Iso8583Message;
+use crate::repository::dcc_repository::{DccQuoteRow, DccRepository};
+
+#[derive(Debug, Error)]
+pub enum DccError {
+    #[error("Failed to read {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid rate: {0}")]
+    Rate(#[from] AmountError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Source of conversion rates
+#[async_trait]
+pub trait RateProvider: Send + Sync {
+    /// Rate converting one major unit of `from` into `to`, None when not quoted
+    async fn rate(
+        &self,
+        from: &'static Currency,
+        to: &'static Currency,
+    ) -> Result<Option<ConversionRate>, DccError>;
+}
+
+/// Rate provider reading a JSON file of `{"VND": {"USD": "0.0000393", ...}}`
+pub struct FileRateProvider {
+    rates: HashMap<(String, String), ConversionRate>,
+}
+
+impl FileRateProvider {
+    pub fn load(path: &str) -> Result<Self, DccError> {
+        let json = fs::read_to_string(path).map_err(|source| DccError::Io {
+            path: path.to_string(),
+            source,
+        })?;
+        Self::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, DccError> {
+        let table: HashMap<String, HashMap<String, String>> = serde_json::from_str(json)?;
+
+        let mut rates = HashMap::new();
+        for (from, quotes) in table {
+            for (to, rate) in quotes {
+                let rate = ConversionRate::from_decimal_str(&rate)?;
+                rates.insert((from.to_uppercase(), to.to_uppercase()), rate);
+            }
+        }
+        Ok(Self { rates })
+    }
+}
+
+#[async_trait]
+impl RateProvider for FileRateProvider {
+    async fn rate(
+        &self,
+        from: &'static Currency,
+        to: &'static Currency,
+    ) -> Result<Option<ConversionRate>, DccError> {
+        Ok(self
+            .rates
+            .get(&(from.alpha.to_string(), to.alpha.to_string()))
+            .copied())
+    }
+}
+
+/// Issuer country by BIN prefix (longest prefix wins)
+#[derive(Debug, Default)]
+pub struct BinCountries {
+    prefixes: HashMap<String, String>,
+}
+
+impl BinCountries {
+    /// Load a JSON file of `{"411111": "US", "3528": "JP"}`
+    pub fn load(path: &str) -> Result<Self, DccError> {
+        let json = fs::read_to_string(path).map_err(|source| DccError::Io {
+            path: path.to_string(),
+            source,
+        })?;
+        Ok(Self {
+            prefixes: serde_json::from_str(&json)?,
+        })
+    }
+
+    pub fn country(&self, pan: &str) -> Option<&str> {
+        if !pan.is_ascii() {
+            return None;
+        }
+        (1..=pan.len().min(11))
+            .rev()
+            .find_map(|len| self.prefixes.get(&pan[..len]))
+            .map(String::as_str)
+    }
+}
+
+/// Conversion offered to the cardholder
+#[derive(Debug, Clone)]
+pub struct DccQuote {
+    /// Transaction amount in the merchant currency (DE4)
+    pub local: Amount,
+    /// Cardholder billing amount (DE6/DE51)
+    pub billing: Amount,
+    /// Rate including markup (DE10)
+    pub rate: ConversionRate,
+    pub markup_bps: u32,
+    /// Local time after which the offer can no longer be accepted
+    expires_at: NaiveDateTime,
+}
+
+impl DccQuote {
+    /// Can this quote still be accepted for `amount`?
+    pub fn is_valid_for(&self, amount: &Amount) -> bool {
+        self.local == *amount && Local::now().naive_local() < self.expires_at
+    }
+
+    /// Populate DE6, DE10 and DE51 of an accepted conversion
+    pub fn apply(&self, msg: &mut Iso8583Message) {
+        msg.set_field(6, self.billing.to_iso());
+        msg.set_field(10, self.rate.to_de10());
+        msg.set_field(51, self.billing.currency().numeric.to_string());
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "cardholderAmount": self.billing.to_json(),
+            "cardholderCurrency": self.billing.currency().alpha,
+            "conversionRate": self.rate.to_decimal_string(),
+            "markupBps": self.markup_bps,
+            "expiresIn": (self.expires_at - Local::now().naive_local()).num_seconds().max(0),
+        })
+    }
+}
+
+/// Open offers, by terminal and the terminal's transaction number
+#[async_trait]
+pub trait QuoteStore: Send + Sync {
+    /// Keep the offer until the cardholder answers, replacing an earlier one
+    async fn save(&self, trm_id: &str, tr_uniq_no: &str, quote: &DccQuote) -> Result<(), DccError>;
+
+    /// Remove and return the offer; each offer is answered once
+    async fn take(&self, trm_id: &str, tr_uniq_no: &str) -> Result<Option<DccQuote>, DccError>;
+}
+
+/// Offers of this instance only (tests, single-node development)
+#[derive(Default)]
+pub struct InMemoryQuoteStore {
+    quotes: Mutex<HashMap<(String, String), DccQuote>>,
+}
+
+#[async_trait]
+impl QuoteStore for InMemoryQuoteStore {
+    async fn save(&self, trm_id: &str, tr_uniq_no: &str, quote: &DccQuote) -> Result<(), DccError> {
+        let mut quotes = self.quotes.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Local::now().naive_local();
+        quotes.retain(|_, q| q.expires_at > now);
+        quotes.insert((trm_id.to_string(), tr_uniq_no.to_string()), quote.clone());
+        Ok(())
+    }
+
+    async fn take(&self, trm_id: &str, tr_uniq_no: &str) -> Result<Option<DccQuote>, DccError> {
+        let mut quotes = self.quotes.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(quotes.remove(&(trm_id.to_string(), tr_uniq_no.to_string())))
+    }
+}
+
+/// Offers shared by every instance, so the answer may reach another one
+#[async_trait]
+impl QuoteStore for DccRepository {
+    async fn save(&self, trm_id: &str, tr_uniq_no: &str, quote: &DccQuote) -> Result<(), DccError> {
+        let row = DccQuoteRow {
+            local_amt: quote.local.minor(),
+            local_ccy: quote.local.currency().numeric.to_string(),
+            billing_amt: quote.billing.minor(),
+            billing_ccy: quote.billing.currency().numeric.to_string(),
+            conv_rate: quote.rate.to_de10(),
+            markup_bps: quote.markup_bps as i32,
+            expr_dtm: quote.expires_at.format(QUOTE_EXPIRY_FORMAT).to_string(),
+        };
+        Ok(self.save_quote(trm_id, tr_uniq_no, &row).await?)
+    }
+
+    async fn take(&self, trm_id: &str, tr_uniq_no: &str) -> Result<Option<DccQuote>, DccError> {
+        let Some(row) = self.take_quote(trm_id, tr_uniq_no).await? else {
+            return Ok(None);
+        };
+        let currency = |code: &str| {
+            Currency::from_code(code).ok_or_else(|| AmountError::UnknownCurrency(code.to_string()))
+        };
+        Ok(Some(DccQuote {
+            local: Amount::from_minor(row.local_amt, currency(&row.local_ccy)?),
+            billing: Amount::from_minor(row.billing_amt, currency(&row.billing_ccy)?),
+            rate: ConversionRate::from_de10(&row.conv_rate)?,
+            markup_bps: u32::try_from(row.markup_bps).unwrap_or_default(),
+            expires_at: NaiveDateTime::parse_from_str(&row.expr_dtm, QUOTE_EXPIRY_FORMAT)
+                .map_err(|_| AmountError::Invalid(row.expr_dtm.clone()))?,
+        }))
+    }
+}
+
+/// Offer expiry as stored (local time)
+const QUOTE_EXPIRY_FORMAT: &str = "%Y%m%d%H%M%S";
+
+/// What to do with DCC for a request
+#[derive(Debug)]
+pub enum DccDecision {
+    /// Not a foreign card, not opted in, or declined by the cardholder
+    NotApplicable,
+    /// Return the offer to the terminal before contacting the host
+    Offer(DccQuote),
+    /// Cardholder accepted: send in the billing currency
+    Accepted(DccQuote),
+}
+
+/// Dynamic Currency Conversion Service
+/// Offers foreign cardholders the amount in their billing currency; the terminal
+/// repeats the request with `dccAccepted` once the cardholder has chosen
+pub struct DccService {
+    config: DccConfig,
+    provider: Arc<dyn RateProvider>,
+    bins: BinCountries,
+    /// Open offers by (terminal, transactionId)
+    quotes: Arc<dyn QuoteStore>,
+}
+
+impl DccService {
+    pub fn new(
+        config: DccConfig,
+        provider: Arc<dyn RateProvider>,
+        bins: BinCountries,
+        quotes: Arc<dyn QuoteStore>,
+    ) -> Self {
+        Self {
+            config,
+            provider,
+            bins,
+            quotes,
+        }
+    }
+
+    /// Build from configuration; DCC stays off when its files cannot be loaded
+    pub fn from_config(mut config: DccConfig, repo: DccRepository) -> Self {
+        let provider = match config.rate_file.as_deref().map(FileRateProvider::load) {
+            Some(Ok(provider)) => provider,
+            Some(Err(e)) => {
+                warn!("DCC disabled, failed to load rates: {}", e);
+                config.enabled = false;
+                FileRateProvider::from_json("{}").expect("empty rate table")
+            }
+            None => FileRateProvider::from_json("{}").expect("empty rate table"),
+        };
+        let bins = match config.bin_file.as_deref().map(BinCountries::load) {
+            Some(Ok(bins)) => bins,
+            Some(Err(e)) => {
+                warn!("DCC disabled, failed to load BIN countries: {}", e);
+                config.enabled = false;
+                BinCountries::default()
+            }
+            None => BinCountries::default(),
+        };
+        info!(
+            "DCC {} for {} merchants",
+            if config.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            },
+            config.merchants.len()
+        );
+        let quotes: Arc<dyn QuoteStore> = match config.quote_store {
+            QuoteStoreKind::Postgres => Arc::new(repo),
+            QuoteStoreKind::Memory => Arc::new(InMemoryQuoteStore::default()),
+        };
+        Self::new(config, Arc::new(provider), bins, quotes)
+    }
+
+    /// Decide whether to offer or apply a conversion for this request
+    pub async fn decide(
+        &self,
+        card_request: &CardRequest,
+        tx_type: TransactionType,
+        pan: Option<&str>,
+        amount: Amount,
+    ) -> DccDecision {
+        if !self.config.enabled
+            || !matches!(
+                tx_type,
+                TransactionType::Purchase | TransactionType::PreAuth
+            )
+        {
+            return DccDecision::NotApplicable;
+        }
+
+        let (trm_id, tr_uniq_no) = (&card_request.trm_id, &card_request.transaction_id);
+        let offered = match card_request.dcc_accepted {
+            Some(_) => self
+                .quotes
+                .take(trm_id, tr_uniq_no)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!(
+                        "Failed to load DCC offer for transaction {}: {}",
+                        tr_uniq_no, e
+                    );
+                    None
+                }),
+            None => None,
+        };
+        match card_request.dcc_accepted {
+            Some(false) => {
+                metrics::increment("dcc.declined", 1);
+                return DccDecision::NotApplicable;
+            }
+            Some(true) => {
+                if let Some(quote) = offered.filter(|q| q.is_valid_for(&amount)) {
+                    metrics::increment("dcc.accepted", 1);
+                    return DccDecision::Accepted(quote);
+                }
+                // Offer expired or amount changed: quote again
+                warn!(
+                    "No valid DCC offer for transaction {}, quoting again",
+                    card_request.transaction_id
+                );
+            }
+            None => {}
+        }
+
+        let (Some(merchant_id), Some(pan)) = (card_request.merchant_id.as_deref(), pan) else {
+            return DccDecision::NotApplicable;
+        };
+        match self.quote(merchant_id, pan, amount).await {
+            Some(quote) => {
+                // An offer that cannot be kept could never be accepted
+                if let Err(e) = self.quotes.save(trm_id, tr_uniq_no, &quote).await {
+                    warn!(
+                        "Failed to keep DCC offer for transaction {}: {}",
+                        tr_uniq_no, e
+                    );
+                    return DccDecision::NotApplicable;
+                }
+                metrics::increment("dcc.offered", 1);
+                DccDecision::Offer(quote)
+            }
+            None => DccDecision::NotApplicable,
+        }
+    }
+
+    /// Quote a conversion for a foreign card at an opted-in merchant
+    async fn quote(&self, merchant_id: &str, pan: &str, amount: Amount) -> Option<DccQuote> {
+        let markup_bps = self.config.markup_for(merchant_id)?;
+        // DCC's own BIN file first, then the issuer country of the BIN table
+        let country = match self.bins.country(pan) {
+            Some(country) => country.to_string(),
+            None => BIN_REGISTRY.lookup(pan)?.country?,
+        };
+        if country.eq_ignore_ascii_case(&self.config.local_country) {
+            return None;
+        }
+        let billing_currency = Currency::for_country(&country)?;
+        if billing_currency == amount.currency() {
+            return None;
+        }
+
+        let rate = match self
+            .provider
+            .rate(amount.currency(), billing_currency)
+            .await
+        {
+            Ok(Some(rate)) => rate.with_markup(markup_bps)?,
+            Ok(None) => return None,
+            Err(e) => {
+                warn!("DCC rate lookup failed: {}", e);
+                return None;
+            }
+        };
+        let billing = amount.convert(rate, billing_currency);
+        if billing.minor() == 0 {
+            return None;
+        }
+
+        Some(DccQuote {
+            local: amount,
+            billing,
+            rate,
+            markup_bps,
+            expires_at: Local::now().naive_local()
+                + Duration::seconds(self.config.quote_ttl_secs as i64),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> DccService {
+        service_with(Arc::new(InMemoryQuoteStore::default()))
+    }
+
+    fn service_with(quotes: Arc<dyn QuoteStore>) -> DccService {
+        let config = DccConfig {
+            enabled: true,
+            merchants: HashMap::from([
+                ("MERCHANT01".to_string(), None),
+                ("MERCHANT02".to_string(), Some(0)),
+            ]),
+            ..DccConfig::default()
+        };
+        let provider =
+            FileRateProvider::from_json(r#"{"VND": {"USD": "0.0000393", "JPY": "0.0058"}}"#)
+                .unwrap();
+        let bins = BinCountries {
+            prefixes: HashMap::from([
+                ("4111".to_string(), "US".to_string()),
+                ("411122".to_string(), "VN".to_string()),
+                ("3528".to_string(), "JP".to_string()),
+            ]),
+        };
+        DccService::new(config, Arc::new(provider), bins, quotes)
+    }
+
+    fn request(merchant_id: &str, accepted: Option<bool>) -> CardRequest {
+        serde_json::from_value(serde_json::json!({
+            "msgType": "SALE",
+            "trmId": "T0000001",
+            "transactionId": "TX1",
+            "amount": 1500000,
+            "transactionType": "PURCHASE",
+            "merchantId": merchant_id,
+            "dccAccepted": accepted,
+        }))
+        .unwrap()
+    }
+
+    fn vnd(minor: i64) -> Amount {
+        Amount::from_minor(minor, Currency::vnd())
+    }
+
+    #[test]
+    fn test_bin_country_longest_prefix() {
+        let dcc = service();
+        assert_eq!(dcc.bins.country("4111111111111111"), Some("US"));
+        assert_eq!(dcc.bins.country("4111221111111111"), Some("VN"));
+        assert_eq!(dcc.bins.country("5555555555554444"), None);
+    }
+
+    #[tokio::test]
+    async fn test_offer_then_accept() {
+        let dcc = service();
+        let pan = Some("4111111111111111");
+
+        let DccDecision::Offer(offer) = dcc
+            .decide(
+                &request("MERCHANT01", None),
+                TransactionType::Purchase,
+                pan,
+                vnd(1_500_000),
+            )
+            .await
+        else {
+            panic!("foreign card at opted-in merchant must get an offer");
+        };
+        // 0.0000393 + 3% markup
+        assert_eq!(offer.billing.minor(), 6072);
+        assert_eq!(offer.billing.currency().alpha, "USD");
+
+        let DccDecision::Accepted(accepted) = dcc
+            .decide(
+                &request("MERCHANT01", Some(true)),
+                TransactionType::Purchase,
+                pan,
+                vnd(1_500_000),
+            )
+            .await
+        else {
+            panic!("accepted offer must be applied");
+        };
+        let mut msg = Iso8583Message::new("0200");
+        accepted.apply(&mut msg);
+        assert_eq!(msg.get_field(6).map(String::as_str), Some("000000006072"));
+        assert_eq!(msg.get_field(10).map(String::as_str), Some("90040479"));
+        assert_eq!(msg.get_field(51).map(String::as_str), Some("840"));
+    }
+
+    #[tokio::test]
+    async fn test_offer_accepted_by_another_instance() {
+        let quotes: Arc<dyn QuoteStore> = Arc::new(InMemoryQuoteStore::default());
+        let (first, second) = (service_with(quotes.clone()), service_with(quotes));
+        let pan = Some("4111111111111111");
+
+        assert!(matches!(
+            first
+                .decide(
+                    &request("MERCHANT01", None),
+                    TransactionType::Purchase,
+                    pan,
+                    vnd(1_500_000),
+                )
+                .await,
+            DccDecision::Offer(_)
+        ));
+        let DccDecision::Accepted(accepted) = second
+            .decide(
+                &request("MERCHANT01", Some(true)),
+                TransactionType::Purchase,
+                pan,
+                vnd(1_500_000),
+            )
+            .await
+        else {
+            panic!("offer made by one instance must be accepted by another");
+        };
+        assert_eq!(accepted.billing.minor(), 6072);
+
+        // An offer is answered once
+        assert!(matches!(
+            first
+                .decide(
+                    &request("MERCHANT01", Some(true)),
+                    TransactionType::Purchase,
+                    pan,
+                    vnd(1_500_000),
+                )
+                .await,
+            DccDecision::Offer(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_not_applicable() {
+        let dcc = service();
+        let purchase = TransactionType::Purchase;
+
+        // Domestic card, merchant not opted in, refund, cardholder declined
+        for (req, tx_type, pan) in [
+            (request("MERCHANT01", None), purchase, "4111221111111111"),
+            (request("OTHER", None), purchase, "4111111111111111"),
+            (
+                request("MERCHANT01", None),
+                TransactionType::Refund,
+                "4111111111111111",
+            ),
+            (
+                request("MERCHANT01", Some(false)),
+                purchase,
+                "4111111111111111",
+            ),
+        ] {
+            assert!(matches!(
+                dcc.decide(&req, tx_type, Some(pan), vnd(1_500_000)).await,
+                DccDecision::NotApplicable
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_merchant_markup_and_changed_amount() {
+        let dcc = service();
+        let pan = Some("3528000000000001");
+
+        let DccDecision::Offer(offer) = dcc
+            .decide(
+                &request("MERCHANT02", None),
+                TransactionType::Purchase,
+                pan,
+                vnd(1_500_000),
+            )
+            .await
+        else {
+            panic!("expected offer");
+        };
+        assert_eq!(offer.billing.minor(), 8700);
+        assert_eq!(offer.markup_bps, 0);
+
+        // Accepting for another amount gets a fresh offer instead
+        assert!(matches!(
+            dcc.decide(
+                &request("MERCHANT02", Some(true)),
+                TransactionType::Purchase,
+                pan,
+                vnd(2_000_000)
+            )
+            .await,
+            DccDecision::Offer(_)
+        ));
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::watch;
+
+use crate::models::transaction::Iso8583Transaction;
+
+/// Payload of a request that must match for a retransmission to be a true duplicate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestFingerprint {
+    pub processing_code: String,
+    pub amount_minor: i64,
+    /// ISO 4217 numeric currency code
+    pub currency: String,
+}
+
+impl RequestFingerprint {
+    pub fn new(processing_code: &str, amount_minor: i64, currency: &str) -> Self {
+        Self {
+            processing_code: processing_code.to_string(),
+            amount_minor,
+            currency: currency.to_string(),
+        }
+    }
+
+    /// Fingerprint of a stored transaction (DE3 + DE4 + DE49)
+    /// Tip adjustments match on the tip, their DE4 is the adjusted total of the sale
+    pub fn from_transaction(tx: &Iso8583Transaction) -> Self {
+        let amount_minor = if tx.is_adjustment() {
+            tx.tip_amt
+        } else {
+            tx.amount_minor()
+        };
+        Self {
+            processing_code: tx.field_003.clone().unwrap_or_default(),
+            amount_minor: amount_minor.unwrap_or(0),
+            currency: tx.field_049.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Outcome of matching a retransmission against the stored transaction
+#[derive(Debug, Clone, PartialEq)]
+pub enum Duplicate {
+    /// Already answered: replay the stored response
+    Completed(serde_json::Value),
+    /// Still being processed (possibly by another instance)
+    Pending,
+    /// Same transactionId but a different payload
+    Mismatch,
+}
+
+/// Classify a stored transaction with the same (terminal, transactionId)
+pub fn classify(existing: &Iso8583Transaction, fingerprint: &RequestFingerprint) -> Duplicate {
+    if RequestFingerprint::from_transaction(existing) != *fingerprint {
+        return Duplicate::Mismatch;
+    }
+
+    match existing
+        .resp_json
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+    {
+        Some(response) => Duplicate::Completed(response),
+        None => Duplicate::Pending,
+    }
+}
+
+/// Admission of a request into the in-flight registry
+pub enum Admission<'a> {
+    /// First request for the key: process it and complete the guard
+    Leader(InFlightGuard<'a>),
+    /// Same request already in flight on this instance: wait for its response
+    Follower(watch::Receiver<Option<serde_json::Value>>),
+    /// Same key in flight with a different payload
+    Mismatch,
+}
+
+struct InFlight {
+    fingerprint: RequestFingerprint,
+    result: watch::Receiver<Option<serde_json::Value>>,
+}
+
+/// Requests currently being processed on this instance, by (terminal, transactionId)
+/// Lets a retransmission arriving on a new connection attach to the running request
+/// instead of creating a second one
+#[derive(Default)]
+pub struct InFlightRegistry {
+    entries: Mutex<HashMap<(String, String), InFlight>>,
+}
+
+impl InFlightRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a request, or attach to the one already in flight for the same key
+    pub fn admit(
+        &self,
+        trm_id: &str,
+        transaction_id: &str,
+        fingerprint: &RequestFingerprint,
+    ) -> Admission<'_> {
+        let key = (trm_id.to_string(), transaction_id.to_string());
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(in_flight) = entries.get(&key) {
+            if in_flight.fingerprint != *fingerprint {
+                return Admission::Mismatch;
+            }
+            return Admission::Follower(in_flight.result.clone());
+        }
+
+        let (sender, receiver) = watch::channel(None);
+        entries.insert(
+            key.clone(),
+            InFlight {
+                fingerprint: fingerprint.clone(),
+                result: receiver,
+            },
+        );
+        Admission::Leader(InFlightGuard {
+            registry: self,
+            key,
+            sender,
+        })
+    }
+
+    fn release(&self, key: &(String, String)) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove(key);
+    }
+}
+
+/// Held by the request being processed; removes the registry entry when dropped
+/// Followers see the sender close without a value if the leader fails
+pub struct InFlightGuard<'a> {
+    registry: &'a InFlightRegistry,
+    key: (String, String),
+    sender: watch::Sender<Option<serde_json::Value>>,
+}
+
+impl InFlightGuard<'_> {
+    /// Hand the final response to every attached follower
+    pub fn complete(self, response: &serde_json::Value) {
+        self.sender.send_replace(Some(response.clone()));
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.registry.release(&self.key);
+    }
+}
+
+/// Wait for the leader's response; None when it finished without one
+pub async fn wait_for(
+    mut result: watch::Receiver<Option<serde_json::Value>>,
+) -> Option<serde_json::Value> {
+    result
+        .wait_for(Option::is_some)
+        .await
+        .ok()
+        .and_then(|response| response.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(processing_code: &str, amount: &str, resp_json: Option<&str>) -> Iso8583Transaction {
+        let mut tx = Iso8583Transaction::new("000001", "0200");
+        tx.field_003 = Some(processing_code.to_string());
+        tx.field_004 = Some(amount.to_string());
+        tx.field_049 = Some("704".to_string());
+        tx.resp_json = resp_json.map(str::to_string);
+        tx
+    }
+
+    #[test]
+    fn test_classify_stored_duplicate() {
+        let fingerprint = RequestFingerprint::new("000000", 10000, "704");
+
+        let completed = stored("000000", "000000010000", Some(r#"{"responseCode":"00"}"#));
+        assert_eq!(
+            classify(&completed, &fingerprint),
+            Duplicate::Completed(serde_json::json!({"responseCode": "00"}))
+        );
+
+        let pending = stored("000000", "000000010000", None);
+        assert_eq!(classify(&pending, &fingerprint), Duplicate::Pending);
+
+        let other_amount = stored("000000", "000000020000", None);
+        assert_eq!(classify(&other_amount, &fingerprint), Duplicate::Mismatch);
+
+        let other_type = stored("200000", "000000010000", None);
+        assert_eq!(classify(&other_type, &fingerprint), Duplicate::Mismatch);
+
+        let other_currency = RequestFingerprint::new("000000", 10000, "840");
+        assert_eq!(classify(&pending, &other_currency), Duplicate::Mismatch);
+
+        let mut tip_adjustment = stored("020000", "000000012000", None);
+        tip_adjustment.tip_amt = Some(2000);
+        let tip = RequestFingerprint::new("020000", 2000, "704");
+        assert_eq!(classify(&tip_adjustment, &tip), Duplicate::Pending);
+    }
+
+    #[tokio::test]
+    async fn test_follower_attaches_to_in_flight_request() {
+        let registry = InFlightRegistry::new();
+        let fingerprint = RequestFingerprint::new("000000", 10000, "704");
+
+        let Admission::Leader(guard) = registry.admit("T0000001", "TX1", &fingerprint) else {
+            panic!("first request must lead");
+        };
+        let Admission::Follower(result) = registry.admit("T0000001", "TX1", &fingerprint) else {
+            panic!("retransmission must attach");
+        };
+        assert!(matches!(
+            registry.admit(
+                "T0000001",
+                "TX1",
+                &RequestFingerprint::new("000000", 1, "704")
+            ),
+            Admission::Mismatch
+        ));
+        // Same transactionId on another terminal is a different transaction
+        assert!(matches!(
+            registry.admit("T0000002", "TX1", &fingerprint),
+            Admission::Leader(_)
+        ));
+
+        guard.complete(&serde_json::json!({"responseCode": "00"}));
+        assert_eq!(
+            wait_for(result).await,
+            Some(serde_json::json!({"responseCode": "00"}))
+        );
+        assert!(matches!(
+            registry.admit("T0000001", "TX1", &fingerprint),
+            Admission::Leader(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_follower_released_when_leader_fails() {
+        let registry = InFlightRegistry::new();
+        let fingerprint = RequestFingerprint::new("000000", 10000, "704");
+
+        let guard = registry.admit("T0000001", "TX1", &fingerprint);
+        let Admission::Follower(result) = registry.admit("T0000001", "TX1", &fingerprint) else {
+            panic!("retransmission must attach");
+        };
+        drop(guard);
+        assert_eq!(wait_for(result).await, None);
+    }
+}
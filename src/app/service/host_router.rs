@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::app::service::bin_table::BIN_REGISTRY;
+use crate::app::service::response_handler::MockBankResponseHandler;
+use crate::models::iso8583_message::Iso8583Message;
+
+/// Host group of requests without a card number (QR, follow-ups of unrouted originals)
+const DEFAULT_HOST_GROUP: &str = "DEFAULT";
+
+#[derive(Debug, Error)]
+pub enum RoutingError {
+    #[error("Host group {0} is not configured")]
+    UnknownHostGroup(String),
+}
+
+/// Connection to one issuing/switching host
+pub struct HostConnector {
+    group: String,
+    handler: MockBankResponseHandler,
+}
+
+impl HostConnector {
+    pub fn new(group: &str, success_rate: f64) -> Self {
+        Self {
+            group: group.to_string(),
+            handler: MockBankResponseHandler::new(success_rate),
+        }
+    }
+
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    /// Send a request and wait for the host response
+    pub async fn exchange(&self, request: &Iso8583Message) -> Iso8583Message {
+        self.handler.simulate_delay().await;
+        self.handler.process_request(request).await
+    }
+}
+
+/// Routes requests to a host connector by the host group of their BIN range
+pub struct HostRouter {
+    connectors: HashMap<String, Arc<HostConnector>>,
+    default_group: String,
+}
+
+impl HostRouter {
+    /// Build connectors from environment configuration
+    /// HOST_GROUPS lists groups as `GROUP[:success_rate]`, comma separated;
+    /// HOST_DEFAULT_GROUP handles requests no BIN range routes
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let groups = env::var("HOST_GROUPS").unwrap_or_else(|_| "INTERNATIONAL,NAPAS".to_string());
+        let default_group =
+            env::var("HOST_DEFAULT_GROUP").unwrap_or_else(|_| DEFAULT_HOST_GROUP.to_string());
+        Self::new(&groups, &default_group)
+    }
+
+    pub fn new(groups: &str, default_group: &str) -> Self {
+        let mut connectors: HashMap<String, Arc<HostConnector>> = groups
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (group, success_rate) = match entry.split_once(':') {
+                    Some((group, rate)) => (group.trim(), rate.trim().parse().unwrap_or(0.9)),
+                    None => (entry, 0.9),
+                };
+                (
+                    group.to_string(),
+                    Arc::new(HostConnector::new(group, success_rate)),
+                )
+            })
+            .collect();
+        connectors
+            .entry(default_group.to_string())
+            .or_insert_with(|| Arc::new(HostConnector::new(default_group, 0.9)));
+
+        Self {
+            connectors,
+            default_group: default_group.to_string(),
+        }
+    }
+
+    /// Connector of a host group
+    /// A group without a connector is a configuration error, never sent to another host
+    pub fn connector(&self, group: &str) -> Result<Arc<HostConnector>, RoutingError> {
+        self.connectors
+            .get(group)
+            .cloned()
+            .ok_or_else(|| RoutingError::UnknownHostGroup(group.to_string()))
+    }
+
+    /// Every configured host connector
+    pub fn connectors(&self) -> impl Iterator<Item = &Arc<HostConnector>> {
+        self.connectors.values()
+    }
+
+    pub fn default_connector(&self) -> Arc<HostConnector> {
+        self.connectors[&self.default_group].clone()
+    }
+
+    /// Connector for a message, by the BIN range of its PAN (DE2)
+    pub fn route(&self, msg: &Iso8583Message) -> Result<Arc<HostConnector>, RoutingError> {
+        match msg.get_field(2).and_then(|pan| BIN_REGISTRY.lookup(pan)) {
+            Some(range) => self.connector(&range.host_group),
+            None => Ok(self.default_connector()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_group_is_a_routing_error() {
+        let router = HostRouter::new("INTERNATIONAL:1.0, NAPAS", "NAPAS");
+        assert_eq!(
+            router.connector("INTERNATIONAL").unwrap().group(),
+            "INTERNATIONAL"
+        );
+        assert!(matches!(
+            router.connector("AMEX"),
+            Err(RoutingError::UnknownHostGroup(group)) if group == "AMEX"
+        ));
+
+        let router = HostRouter::new("", "DEFAULT");
+        assert_eq!(router.default_connector().group(), "DEFAULT");
+    }
+}
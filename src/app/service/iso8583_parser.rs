@@ -61,15 +61,19 @@ impl Iso8583Parser {
         self.field_formats.insert(2, Llvar(19));        // PAN
         self.field_formats.insert(3, FixedNumeric(6));  // Processing Code
         self.field_formats.insert(4, FixedNumeric(12)); // Amount, Transaction
+        self.field_formats.insert(6, FixedNumeric(12)); // Amount, Cardholder Billing
         self.field_formats.insert(7, FixedNumeric(10)); // Transmission Date & Time
+        self.field_formats.insert(10, FixedNumeric(8)); // Conversion Rate, Cardholder Billing
         self.field_formats.insert(11, FixedNumeric(6)); // STAN
         self.field_formats.insert(12, FixedNumeric(6)); // Time, Local Transaction
         self.field_formats.insert(13, FixedNumeric(4)); // Date, Local Transaction
         self.field_formats.insert(14, FixedNumeric(4)); // Date, Expiration
+        self.field_formats.insert(15, FixedNumeric(4)); // Date, Settlement
         self.field_formats.insert(18, FixedNumeric(4)); // Merchant Type
         self.field_formats.insert(22, FixedNumeric(3)); // POS Entry Mode
         self.field_formats.insert(23, FixedNumeric(3)); // Card Sequence Number
         self.field_formats.insert(25, FixedNumeric(2)); // POS Condition Code
+        self.field_formats.insert(26, FixedNumeric(2)); // POS PIN Capture Code
         self.field_formats.insert(32, Llvar(11));       // Acquiring Institution ID
         self.field_formats.insert(35, Llvar(37));       // Track 2 Data
         self.field_formats.insert(37, FixedAlpha(12));  // RRN
@@ -79,6 +83,7 @@ impl Iso8583Parser {
         self.field_formats.insert(42, FixedAlpha(15));  // Merchant ID
         self.field_formats.insert(43, FixedAlpha(40));  // Merchant Name/Location
         self.field_formats.insert(49, FixedNumeric(3)); // Currency Code
+        self.field_formats.insert(51, FixedNumeric(3)); // Currency Code, Cardholder Billing
         self.field_formats.insert(52, Binary(8));       // PIN Data
         self.field_formats.insert(54, Lllvar(120));     // Additional Amounts
         self.field_formats.insert(55, Lllvar(999));     // EMV Data (DE55)
@@ -88,8 +93,21 @@ impl Iso8583Parser {
         self.field_formats.insert(63, Lllvar(999));     // Reserved Private
         self.field_formats.insert(64, Binary(8));       // MAC
         self.field_formats.insert(70, FixedNumeric(3)); // Network Management Code
+        self.field_formats.insert(74, FixedNumeric(10)); // Credits, Number
+        self.field_formats.insert(75, FixedNumeric(10)); // Credits, Reversal Number
+        self.field_formats.insert(76, FixedNumeric(10)); // Debits, Number
+        self.field_formats.insert(77, FixedNumeric(10)); // Debits, Reversal Number
+        self.field_formats.insert(78, FixedNumeric(10)); // Transfer, Number
+        self.field_formats.insert(79, FixedNumeric(10)); // Transfer, Reversal Number
+        self.field_formats.insert(80, FixedNumeric(10)); // Inquiries, Number
+        self.field_formats.insert(81, FixedNumeric(10)); // Authorizations, Number
+        self.field_formats.insert(86, FixedNumeric(16)); // Credits, Amount
+        self.field_formats.insert(87, FixedNumeric(16)); // Credits, Reversal Amount
+        self.field_formats.insert(88, FixedNumeric(16)); // Debits, Amount
+        self.field_formats.insert(89, FixedNumeric(16)); // Debits, Reversal Amount
         self.field_formats.insert(90, FixedNumeric(42)); // Original Data Elements
         self.field_formats.insert(95, FixedAlpha(42));  // Replacement Amounts
+        self.field_formats.insert(97, FixedAlpha(17));  // Amount, Net Settlement
         self.field_formats.insert(102, Llvar(28));      // Account ID 1
         self.field_formats.insert(103, Llvar(28));      // Account ID 2
         self.field_formats.insert(123, Lllvar(999));    // Reserved Private
@@ -285,6 +303,51 @@ impl Iso8583Parser {
         Ok(hex::encode_upper(result))
     }
 
+    /// Validate a field value against its format definition
+    pub fn validate_field(&self, de: u8, value: &str) -> Result<(), ParseError> {
+        let format = self.field_formats.get(&de)
+            .ok_or_else(|| ParseError::InvalidField {
+                de,
+                msg: "Unknown field format".to_string(),
+            })?;
+
+        let invalid = |msg: String| Err(ParseError::InvalidField { de, msg });
+
+        match *format {
+            FieldFormat::FixedNumeric(len) => {
+                if value.len() != len {
+                    return invalid(format!("expected {} digits, got {}", len, value.len()));
+                }
+                if !value.bytes().all(|b| b.is_ascii_digit()) {
+                    return invalid("non-numeric value".to_string());
+                }
+            }
+            FieldFormat::FixedAlpha(len) => {
+                if value.len() != len {
+                    return invalid(format!("expected {} characters, got {}", len, value.len()));
+                }
+            }
+            FieldFormat::Llvar(max) => {
+                if value.len() > max {
+                    return invalid(format!("length {} exceeds max {}", value.len(), max));
+                }
+            }
+            // Hex encoded: two characters per byte
+            FieldFormat::Lllvar(max) => {
+                if value.len() / 2 > max {
+                    return invalid(format!("length {} exceeds max {}", value.len() / 2, max));
+                }
+            }
+            FieldFormat::Binary(len) => {
+                if value.len() != len * 2 {
+                    return invalid(format!("expected {} bytes, got {}", len, value.len() / 2));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Build a single field
     fn build_field(&self, de: u8, value: &str) -> Result<Vec<u8>, ParseError> {
         let format = self.field_formats.get(&de)
@@ -351,4 +414,13 @@ mod tests {
         let result = parser.build(&mut msg);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_validate_original_data_elements() {
+        let parser = Iso8583Parser::new();
+
+        assert!(parser.validate_field(90, &"0".repeat(42)).is_ok());
+        assert!(parser.validate_field(90, &"0".repeat(41)).is_err());
+        assert!(parser.validate_field(90, &format!("Some(\"1\"){}", "0".repeat(33))).is_err());
+    }
 }
@@ -1,45 +1,147 @@
 use serde_json;
+use std::collections::HashSet;
 use std::io;
 use std::sync::Arc;
-use tracing::{error, info};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
 
+use crate::app::config::offline_config::OfflineConfig;
+use crate::app::config::stip_config::StipConfig;
 use crate::app::security::mac_calculator::MacCalculator;
-use crate::app::service::response_handler::{MockBankResponseHandler, ResponseHandler};
+use crate::app::service::bin_table::BIN_REGISTRY;
+use crate::app::service::business_calendar::{self, BUSINESS_CALENDAR, BUSINESS_DATE_FORMAT};
+use crate::app::service::dcc_service::{DccDecision, DccQuote, DccService};
+use crate::app::service::duplicate_guard::{
+    self, Admission, Duplicate, InFlightRegistry, RequestFingerprint,
+};
+use crate::app::service::host_router::{HostConnector, HostRouter, RoutingError};
+use crate::app::service::iso_builder_service::TcpTransactionType;
+use crate::app::service::master_data_cache::MASTER_DATA_CACHE;
+use crate::app::service::network_management_service::NetworkManagementService;
+use crate::app::service::offline_advice::OfflineApproval;
+use crate::app::service::preauth_service::{PreAuthError, PreAuthService};
+use crate::app::service::response_handler::{ResponseCode, ResponseHandler};
+use crate::app::service::reversal_service::{ReversalReason, ReversalService};
+use crate::app::service::risk_engine::{self, RISK_RULES, RiskContext, RiskEngine};
+use crate::app::service::saf_service::SafService;
 use crate::app::service::stan_generator::StanGenerator;
+use crate::app::service::stip_service::StipService;
 use crate::app::service::tlv_parser::ParsedEmvData;
+use crate::app::service::transaction_profile::{
+    PROFILE_REGISTRY, TransactionProfile, TransactionType, ValidationResult,
+};
 use crate::app::utils::kafka_message_sender::KafkaMessageSender;
+use crate::app::utils::metrics;
+use crate::models::amount::{
+    AdditionalAmount, Amount, AmountError, Balances, ConversionRate, Currency,
+};
 use crate::models::app_context::AppContext;
+use crate::models::bin_range::{self, BinRange};
 use crate::models::card_request::CardRequest;
 use crate::models::iso8583_message::Iso8583Message;
-use crate::models::transaction::{Iso8583Transaction, TransactionState};
+use crate::models::merchant::TerminalMaster;
+use crate::models::pos_entry::{self, EntryMode, PosEntry};
+use crate::models::saf_entry::SafType;
+use crate::models::transaction::{
+    Iso8583Transaction, StateTransition, TransactionEvent, TransactionState,
+};
 use crate::repository::card_transaction_repository::CardTransactionRepository;
+use crate::repository::merchant_repository::MerchantRepository;
+use crate::repository::risk_repository::RiskRepository;
 use chrono::Local;
 
+/// Actor recorded in the transaction state history
+const ACTOR: &str = "card_service";
+
+/// How often a retransmission polls for the result of a request pending elsewhere
+const DUPLICATE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 /// ISO8583 Transaction Service
 /// Handles complete transaction lifecycle from request to response
 pub struct Iso8583TransactionService {
     stan_generator: Arc<StanGenerator>,
     transaction_repo: Arc<CardTransactionRepository>,
-    mock_bank_handler: MockBankResponseHandler,
+    /// Merchant/terminal master data, read through MASTER_DATA_CACHE
+    merchant_repo: MerchantRepository,
+    host_router: HostRouter,
     mac_calculator: MacCalculator,
     kafka_sender: Arc<KafkaMessageSender>,
+    preauth_service: Arc<PreAuthService>,
+    reversal_service: Arc<ReversalService>,
+    saf_service: Arc<SafService>,
+    dcc_service: Arc<DccService>,
+    /// Requests being processed on this instance, joined by terminal retransmissions
+    in_flight: InFlightRegistry,
+    /// How long to wait for the host response before reversing
+    host_timeout: Duration,
+    /// Acquiring institution ID (DE32), also selects host-specific profile overrides
+    acquirer_id: Option<String>,
+    /// Currency of requests that carry none
+    default_currency: &'static Currency,
+    /// Highest tip accepted on a tip adjustment, as a percentage of the sale amount
+    max_tip_pct: u32,
+    /// Floor limits applied to sales approved offline by the card
+    offline_config: OfflineConfig,
+    /// Stand-in authorisation when the host does not answer
+    stip_service: StipService,
+    /// Pre-authorization risk rules
+    risk_engine: RiskEngine,
+    /// Host link state; requests skip the host while it is signed off
+    network_service: Option<Arc<NetworkManagementService>>,
 }
 
 impl Iso8583TransactionService {
     pub fn new(
         stan_generator: Arc<StanGenerator>,
         transaction_repo: Arc<CardTransactionRepository>,
+        preauth_service: Arc<PreAuthService>,
+        reversal_service: Arc<ReversalService>,
+        saf_service: Arc<SafService>,
+        dcc_service: Arc<DccService>,
         ctx: Arc<AppContext>,
     ) -> Self {
         Self {
             stan_generator,
-            transaction_repo,
-            mock_bank_handler: MockBankResponseHandler::default_mock(),
+            transaction_repo: transaction_repo.clone(),
+            merchant_repo: MerchantRepository::new(transaction_repo.pool.clone()),
+            host_router: HostRouter::from_env(),
             mac_calculator: MacCalculator::new_mock(),
             kafka_sender: Arc::new(KafkaMessageSender::new(ctx.kafka_producer.clone())),
+            preauth_service,
+            reversal_service,
+            saf_service,
+            dcc_service,
+            in_flight: InFlightRegistry::new(),
+            host_timeout: Duration::from_millis(
+                std::env::var("HOST_TIMEOUT_MS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(30_000),
+            ),
+            acquirer_id: std::env::var("ACQUIRER_ID")
+                .ok()
+                .filter(|id| !id.is_empty()),
+            default_currency: std::env::var("DEFAULT_CURRENCY")
+                .ok()
+                .and_then(|code| Currency::from_code(&code))
+                .unwrap_or(Currency::vnd()),
+            max_tip_pct: std::env::var("TIP_MAX_PCT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(20),
+            offline_config: OfflineConfig::from_env(),
+            stip_service: StipService::from_config(StipConfig::from_env()),
+            risk_engine: RiskEngine::from_env(RiskRepository::new(transaction_repo.pool.clone())),
+            network_service: None,
         }
     }
 
+    /// Follow the host link state of network management
+    pub fn with_network_service(mut self, network_service: Arc<NetworkManagementService>) -> Self {
+        self.network_service = Some(network_service);
+        self
+    }
+
     /// Process incoming transaction request
     pub async fn process_transaction(
         &self,
@@ -50,38 +152,621 @@ impl Iso8583TransactionService {
             card_request.transaction_id, card_request.amount
         );
 
-        // 1. Generate STAN
-        let stan = self.stan_generator.next().await;
+        // 1. Resolve transaction type through the terminal type mapping
+        // Unknown types are declined, never processed as a sale
+        let tx_type = match TcpTransactionType::try_from(card_request.transaction_type.as_str()) {
+            Ok(tcp_type) => tcp_type.to_internal(),
+            Err(e) => {
+                warn!("{}", e);
+                metrics::increment("transactions.unknown_type", 1);
+                return self
+                    .reject_locally(
+                        card_request,
+                        None,
+                        None,
+                        ResponseCode::InvalidTransaction,
+                        None,
+                    )
+                    .await;
+            }
+        };
+
+        // Only known, active terminals of active merchants may transact
+        let terminal = match MASTER_DATA_CACHE
+            .terminal(&self.merchant_repo, &card_request.trm_id)
+            .await
+        {
+            Ok(Some(terminal)) => terminal,
+            Ok(None) => {
+                warn!("Unknown terminal {}", card_request.trm_id);
+                metrics::increment("transactions.unknown_terminal", 1);
+                return self
+                    .reject_locally(
+                        card_request,
+                        Some(tx_type),
+                        None,
+                        ResponseCode::InvalidMerchant,
+                        None,
+                    )
+                    .await;
+            }
+            Err(e) => {
+                error!("Failed to load terminal {}: {}", card_request.trm_id, e);
+                return self
+                    .reject_locally(
+                        card_request,
+                        Some(tx_type),
+                        None,
+                        ResponseCode::SystemMalfunction,
+                        None,
+                    )
+                    .await;
+            }
+        };
+        if let Err(code) = terminal.check(
+            card_request.merchant_id.as_deref(),
+            card_request.currency.as_deref(),
+            tx_type,
+        ) {
+            warn!(
+                "Transaction {} refused for terminal {} of merchant {}: {}",
+                card_request.transaction_id,
+                terminal.trm_id,
+                terminal.merchant_id,
+                code.description()
+            );
+            metrics::increment("transactions.terminal_refused", 1);
+            return self
+                .reject_locally(card_request, Some(tx_type), None, code, None)
+                .await;
+        }
+
+        // Merchant ID and currency default to the master data
+        let completed = CardRequest {
+            merchant_id: Some(terminal.merchant_id.clone()),
+            currency: card_request
+                .currency
+                .clone()
+                .or_else(|| terminal.currency.clone()),
+            ..card_request.clone()
+        };
+        let card_request = &completed;
+
+        // Resolve the effective profile for the acquirer the merchant is routed through
+        let acquirer_id = terminal
+            .acquirer_id
+            .as_deref()
+            .or(self.acquirer_id.as_deref());
+        let profile = PROFILE_REGISTRY.get(tx_type, acquirer_id);
+
+        // Convert the amount into minor units of the request currency
+        let amount = match self.request_amount(card_request) {
+            Ok(amount) if amount.minor() >= 0 => amount,
+            Ok(_) => {
+                warn!(
+                    "Negative amount in transaction {}",
+                    card_request.transaction_id
+                );
+                return self
+                    .reject_locally(
+                        card_request,
+                        Some(tx_type),
+                        None,
+                        ResponseCode::InvalidAmount,
+                        None,
+                    )
+                    .await;
+            }
+            Err(e) => {
+                warn!("Transaction {}: {}", card_request.transaction_id, e);
+                let code = match e {
+                    AmountError::UnknownCurrency(_) => ResponseCode::FormatError,
+                    _ => ResponseCode::InvalidAmount,
+                };
+                return self
+                    .reject_locally(card_request, Some(tx_type), None, code, None)
+                    .await;
+            }
+        };
+
+        // Purchases with cashback carry the cash handed out on top of the amount
+        let cashback = match self.cashback_amount(card_request, tx_type, &amount) {
+            Ok(cashback) => cashback,
+            Err(e) => {
+                warn!("Transaction {}: {}", card_request.transaction_id, e);
+                return self
+                    .reject_locally(
+                        card_request,
+                        Some(tx_type),
+                        None,
+                        ResponseCode::InvalidAmount,
+                        None,
+                    )
+                    .await;
+            }
+        };
+
+        // 2. Deduplicate terminal retransmissions by (terminal, transactionId)
+        let fingerprint =
+            request_fingerprint(tx_type, profile.as_ref(), &amount, cashback.as_ref());
+        let guard = match self.in_flight.admit(
+            &card_request.trm_id,
+            &card_request.transaction_id,
+            &fingerprint,
+        ) {
+            Admission::Leader(guard) => guard,
+            Admission::Follower(result) => {
+                info!(
+                    "Retransmission of in-flight transaction {}, attaching to its result",
+                    card_request.transaction_id
+                );
+                metrics::increment("transactions.duplicate", 1);
+                if let Some(response) = duplicate_guard::wait_for(result).await {
+                    return Ok(response);
+                }
+                return match self.find_duplicate(card_request).await? {
+                    Some(existing) => {
+                        self.resolve_duplicate(card_request, tx_type, &fingerprint, existing)
+                            .await
+                    }
+                    None => Err(io::Error::other(format!(
+                        "In-flight transaction {} failed",
+                        card_request.transaction_id
+                    ))),
+                };
+            }
+            Admission::Mismatch => {
+                return Ok(self.reject_duplicate(card_request, tx_type));
+            }
+        };
+        if let Some(existing) = self.find_duplicate(card_request).await? {
+            return self
+                .resolve_duplicate(card_request, tx_type, &fingerprint, existing)
+                .await;
+        }
+
+        let result = self
+            .process_new(
+                card_request,
+                tx_type,
+                profile,
+                &terminal,
+                amount,
+                cashback,
+                &fingerprint,
+            )
+            .await;
+        if let Ok(response) = &result {
+            self.save_response(card_request, response).await;
+            guard.complete(response);
+        }
+        result
+    }
+
+    /// Process a request that is not a retransmission
+    #[allow(clippy::too_many_arguments)]
+    async fn process_new(
+        &self,
+        card_request: &CardRequest,
+        tx_type: TransactionType,
+        profile: Option<TransactionProfile>,
+        terminal: &TerminalMaster,
+        amount: Amount,
+        cashback: Option<Amount>,
+        fingerprint: &RequestFingerprint,
+    ) -> Result<serde_json::Value, io::Error> {
+        let amount_minor = amount.minor();
+
+        // Entry mode and PIN capability reported by the terminal drive DE22/DE25/DE26
+        let default_mode = if tx_type == TransactionType::QrPayment {
+            EntryMode::Qr
+        } else {
+            EntryMode::Chip
+        };
+        let pos_entry = match PosEntry::parse(
+            card_request.entry_mode.as_deref(),
+            card_request.pin_capability.as_deref(),
+            default_mode,
+        ) {
+            Ok(pos_entry) => pos_entry,
+            Err(e) => {
+                warn!("Transaction {}: {}", card_request.transaction_id, e);
+                return self
+                    .reject_locally(
+                        card_request,
+                        Some(tx_type),
+                        None,
+                        ResponseCode::FormatError,
+                        None,
+                    )
+                    .await;
+            }
+        };
+
+        // Chip cards must be read from the chip; a swipe is only accepted as fallback
+        if pos_entry.is_chip_card_swiped(card_track2(card_request).as_deref()) {
+            warn!(
+                "Chip card swiped without fallback indicator: transaction {}",
+                card_request.transaction_id
+            );
+            metrics::increment("transactions.chip_card_swiped", 1);
+            return self
+                .reject_locally(
+                    card_request,
+                    Some(tx_type),
+                    None,
+                    ResponseCode::NotPermitted,
+                    Some(("requiredEntryMode", serde_json::json!("CHIP"))),
+                )
+                .await;
+        }
+
+        // Card numbers must pass the Luhn check and belong to a known BIN range
+        let pan = card_pan(card_request);
+        let bin_range = match pan.as_deref() {
+            Some(pan) => match BIN_REGISTRY
+                .lookup(pan)
+                .filter(|_| bin_range::luhn_valid(pan))
+            {
+                Some(range) => Some(range),
+                None => {
+                    warn!(
+                        "Invalid or unknown card number: transaction {}",
+                        card_request.transaction_id
+                    );
+                    metrics::increment("transactions.invalid_card", 1);
+                    return self
+                        .reject_locally(
+                            card_request,
+                            Some(tx_type),
+                            None,
+                            ResponseCode::InvalidCard,
+                            None,
+                        )
+                        .await;
+                }
+            },
+            None => None,
+        };
+
+        // 3. Locate the original transaction for voids, refunds, completions and reversals
+        let original = if tx_type.references_original() {
+            self.find_original(card_request, tx_type).await?
+        } else {
+            None
+        };
+        let original_referenced =
+            card_request.original_rrn.is_some() || card_request.original_stan.is_some();
+        if original.is_none() && (tx_type.requires_original() || original_referenced) {
+            warn!(
+                "Original transaction not found for {:?}: RRN={:?}, STAN={:?}",
+                tx_type, card_request.original_rrn, card_request.original_stan
+            );
+            return self
+                .reject_locally(
+                    card_request,
+                    Some(tx_type),
+                    None,
+                    ResponseCode::UnableToLocate,
+                    None,
+                )
+                .await;
+        }
+
+        if let Some(original) = &original
+            && let Err(code) = check_original(tx_type, original, &amount)
+        {
+            warn!(
+                "{:?} rejected against original {:?} (state {:?}): {}",
+                tx_type,
+                original.tr_uniq_no,
+                original.tr_type,
+                code.description()
+            );
+            return self
+                .reject_locally(card_request, Some(tx_type), None, code, None)
+                .await;
+        }
+
+        // Tips are capped at a percentage of the sale
+        if tx_type == TransactionType::TipAdjustment
+            && let Some(original) = &original
+            && let Err(code) = check_tip(original, &amount, self.max_tip_pct)
+        {
+            warn!(
+                "Tip of {} on {:?} exceeds {}% of the sale",
+                amount.to_major_string(),
+                original.tr_uniq_no,
+                self.max_tip_pct
+            );
+            return self
+                .reject_locally(card_request, Some(tx_type), None, code, None)
+                .await;
+        }
+
+        // Incremental auths, completions and pre-auth voids need an open hold
+        if let Some(original) = &original {
+            match self
+                .preauth_service
+                .check_follow_up(tx_type, original, amount_minor)
+                .await
+            {
+                Ok(()) => {}
+                Err(PreAuthError::Rejected(code)) => {
+                    return self
+                        .reject_locally(card_request, Some(tx_type), None, code, None)
+                        .await;
+                }
+                Err(PreAuthError::Database(e)) => {
+                    return Err(io::Error::other(format!("Database error: {}", e)));
+                }
+            }
+        }
+
+        // Foreign cards at opted-in merchants are offered DCC first; once accepted the
+        // request carries the cardholder billing amount
+        let dcc_quote = match self
+            .dcc_service
+            .decide(card_request, tx_type, pan.as_deref(), amount)
+            .await
+        {
+            DccDecision::Offer(quote) => {
+                info!(
+                    "Offering DCC for transaction {}: {} {}",
+                    card_request.transaction_id,
+                    quote.billing.to_major_string(),
+                    quote.billing.currency().alpha
+                );
+                return Ok(self.dcc_offer_response(card_request, &quote));
+            }
+            DccDecision::Accepted(quote) => Some(quote),
+            DccDecision::NotApplicable => None,
+        };
+
+        // 4. Generate STAN, and the RRN of transactions that do not reference an original
+        let host = match self.select_host(bin_range.as_ref(), original.as_ref()) {
+            Ok(host) => host,
+            Err(e) => {
+                error!(
+                    "Transaction {} not routed: {}",
+                    card_request.transaction_id, e
+                );
+                metrics::increment("transactions.routing_error", 1);
+                return self
+                    .reject_locally(
+                        card_request,
+                        Some(tx_type),
+                        None,
+                        ResponseCode::RoutingError,
+                        None,
+                    )
+                    .await;
+            }
+        };
+        let stan = self
+            .stan_generator
+            .next_for(Some(&card_request.trm_id), Some(host.group()))
+            .await
+            .map_err(|e| io::Error::other(format!("STAN allocation failed: {}", e)))?;
         info!("Generated STAN: {}", stan);
+        let rrn = if tx_type.references_original() {
+            None
+        } else {
+            Some(
+                self.stan_generator
+                    .next_rrn()
+                    .await
+                    .map_err(|e| io::Error::other(format!("RRN allocation failed: {}", e)))?,
+            )
+        };
 
-        // 2. Build ISO8583 request message
-        let request_msg = self.build_iso_message(card_request, &stan)?;
+        // 5. Build the request from the profile
+        let mut request_msg = self.build_iso_message(
+            card_request,
+            &amount,
+            &stan,
+            tx_type,
+            profile.as_ref(),
+            original.as_ref(),
+        )?;
+        apply_additional_amount(
+            &mut request_msg,
+            tx_type,
+            &amount,
+            cashback.as_ref(),
+            original.as_ref(),
+        );
+        apply_pos_entry(&mut request_msg, pos_entry, card_request);
+        apply_master_data(&mut request_msg, terminal);
+        if let Some(rrn) = rrn {
+            request_msg.set_field(37, rrn);
+        }
+        if let Some(quote) = &dcc_quote {
+            quote.apply(&mut request_msg);
+        }
 
-        // 3. Save transaction to database
-        let db_transaction = self.create_db_transaction(&request_msg, card_request)?;
+        // Validate against the transaction profile before anything reaches the host
+        let validation = self.validate_request(tx_type, profile.as_ref(), pos_entry, &request_msg);
+        if !validation.is_valid {
+            error!(
+                "Transaction {} rejected locally: missing DEs {:?}, missing EMV tags {:?}",
+                card_request.transaction_id,
+                validation.missing_iso_des,
+                validation.missing_emv_tags
+            );
+            let details = serde_json::json!({
+                "missingIsoFields": validation.missing_iso_des,
+                "missingEmvTags": validation.missing_emv_tags,
+            });
+            return self
+                .reject_locally(
+                    card_request,
+                    Some(tx_type),
+                    Some(&request_msg),
+                    ResponseCode::FormatError,
+                    Some(("validationErrors", details)),
+                )
+                .await;
+        }
 
-        info!("Saving transaction to database... {:?}", db_transaction);
+        // Offline sales must carry a TC for the uploaded amount
+        let offline_approval = if tx_type == TransactionType::OfflineSale {
+            match self.check_offline_approval(&request_msg, &amount) {
+                Ok(approval) => Some(approval),
+                Err(code) => {
+                    warn!(
+                        "Offline sale {} rejected: {}",
+                        card_request.transaction_id,
+                        code.description()
+                    );
+                    return self
+                        .reject_locally(card_request, Some(tx_type), Some(&request_msg), code, None)
+                        .await;
+                }
+            }
+        } else {
+            None
+        };
 
-        self.transaction_repo
-            .insert(&db_transaction)
+        // Risk rules: decline, require PIN or flag for review
+        let risk_rules = RISK_RULES.rules();
+        let risk_ctx = RiskContext {
+            tx_type,
+            pan: pan.as_deref(),
+            terminal_id: &card_request.trm_id,
+            merchant_id: card_request.merchant_id.as_deref(),
+            mcc: request_msg.get_field(18).map(String::as_str),
+            amount: &amount,
+        };
+        let risk_decision = self
+            .risk_engine
+            .evaluate(&risk_rules, &risk_ctx)
             .await
-            .map_err(|e| {
-                error!("Failed to save transaction: {}", e);
-                io::Error::new(io::ErrorKind::Other, format!("Database error: {}", e))
-            })?;
+            .map_err(|e| io::Error::other(format!("Risk engine error: {}", e)))?;
+        if let Some((rule_id, code)) = &risk_decision.decline {
+            warn!(
+                "Transaction {} declined by risk rule {}",
+                card_request.transaction_id, rule_id
+            );
+            metrics::increment("transactions.risk_declined", 1);
+            return self
+                .reject_locally(
+                    card_request,
+                    Some(tx_type),
+                    Some(&request_msg),
+                    *code,
+                    Some(("riskRule", serde_json::json!(rule_id))),
+                )
+                .await;
+        }
+        if !risk_decision.pin_required.is_empty() && !risk_engine::pin_verified(&request_msg) {
+            warn!(
+                "Transaction {} requires PIN: risk rules {:?}",
+                card_request.transaction_id, risk_decision.pin_required
+            );
+            metrics::increment("transactions.risk_pin_required", 1);
+            return self
+                .reject_locally(
+                    card_request,
+                    Some(tx_type),
+                    Some(&request_msg),
+                    ResponseCode::PinRequired,
+                    Some(("riskRules", serde_json::json!(risk_decision.pin_required))),
+                )
+                .await;
+        }
+
+        // 6. Save transaction to database
+        let mut db_transaction = self.create_db_transaction(&request_msg, card_request)?;
+        if let Some(original) = &original {
+            db_transaction.link_original(original);
+        }
+        db_transaction.cashback_amt = cashback.map(|c| c.minor());
+        db_transaction.risk_flags = risk_decision.flags_str();
+        if let Some(flags) = &db_transaction.risk_flags {
+            warn!(
+                "Transaction {} flagged by risk rules: {}",
+                card_request.transaction_id, flags
+            );
+            metrics::increment("transactions.risk_flagged", 1);
+        }
+        db_transaction.host_group = Some(host.group().to_string());
+        db_transaction.card_scheme = bin_range
+            .as_ref()
+            .zip(pan.as_deref())
+            .and_then(|(range, pan)| range.scheme(pan))
+            .map(|scheme| scheme.as_str().to_string())
+            .or_else(|| original.as_ref().and_then(|o| o.card_scheme.clone()));
+        db_transaction.offline_flags = offline_approval
+            .as_ref()
+            .and_then(OfflineApproval::flags_str);
+        if tx_type == TransactionType::TipAdjustment {
+            db_transaction.tip_amt = Some(amount_minor);
+        }
+
+        // Reserve the refund against the original so concurrent refunds cannot exceed it
+        // Reserved before the record exists, so a refused refund leaves nothing behind;
+        // every later return that does not keep the reservation releases it
+        let mut refund_reservation = match &original {
+            Some(original) if tx_type == TransactionType::Refund => {
+                match self.reserve_refund(original, amount_minor).await? {
+                    Some(reservation) => Some(reservation),
+                    None => {
+                        warn!(
+                            "Refund of {} exceeds refundable amount of original {:?}",
+                            amount_minor, original.tr_uniq_no
+                        );
+                        return self
+                            .reject_locally(
+                                card_request,
+                                Some(tx_type),
+                                Some(&request_msg),
+                                ResponseCode::InvalidAmount,
+                                None,
+                            )
+                            .await;
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        info!("Saving transaction to database... {:?}", db_transaction);
+
+        if let Err(e) = self.transaction_repo.insert(&db_transaction, ACTOR).await {
+            // Another instance stored the same (terminal, transactionId) first
+            if e.as_database_error()
+                .is_some_and(|db_err| db_err.is_unique_violation())
+                && let Some(existing) = self.find_duplicate(card_request).await?
+            {
+                warn!(
+                    "Concurrent retransmission of transaction {}",
+                    card_request.transaction_id
+                );
+                return self
+                    .resolve_duplicate(card_request, tx_type, fingerprint, existing)
+                    .await;
+            }
+            error!("Failed to save transaction: {}", e);
+            return Err(io::Error::other(format!("Database error: {}", e)));
+        }
 
         info!("Transaction saved to database: STAN={}", stan);
 
-        let tr_uniq_no = match db_transaction.tr_uniq_no {
+        let tr_uniq_no = match db_transaction.tr_uniq_no.clone() {
             Some(tr_uniq_no) => tr_uniq_no,
             None => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "Missing transaction unique number",
-                ));
+                return Err(io::Error::other("Missing transaction unique number"));
             }
         };
 
+        // Approved by the card: record the approval and advise the host through SAF
+        if let Some(approval) = &offline_approval {
+            return self
+                .accept_offline(card_request, &request_msg, &db_transaction, approval)
+                .await;
+        }
+
         // Update state to SENT
         self.transaction_repo
             .update_response(
@@ -91,18 +776,67 @@ impl Iso8583TransactionService {
                 None,
                 None,
                 None,
-                &TransactionState::Sent,
+                &StateTransition::new(TransactionEvent::Send, ACTOR, "sent to host"),
             )
             .await
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Database error: {}", e)))?;
+            .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
 
-        // 4. Send to mock bank and get response (simulating network call)
-        info!("Sending request to mock bank...");
-        self.mock_bank_handler.simulate_delay().await;
-
-        let response_msg = self.mock_bank_handler.process_request(&request_msg).await;
+        // 7. Send to the host of the card's BIN range and get the response
+        // A link known to be down is handled as a timeout without waiting for one
+        let host_response = if self.host_link_down(&host) {
+            warn!(
+                "Host link down: {} not sent to host {}",
+                card_request.transaction_id,
+                host.group()
+            );
+            metrics::increment("transactions.host_link_down", 1);
+            None
+        } else {
+            info!("Sending request to host {}...", host.group());
+            let host_call = host.exchange(&request_msg);
+            tokio::time::timeout(self.host_timeout, host_call)
+                .await
+                .ok()
+        };
+        let mut stand_in = false;
+        let mut stored = false;
+        let response_msg = match host_response {
+            Some(response_msg) => response_msg,
+            // Advices and reversals are accepted locally and delivered through SAF;
+            // reversals and voids only touch the original once the host has them
+            None if request_msg.is_advice() || request_msg.is_reversal() => {
+                stored = matches!(tx_type, TransactionType::Reversal | TransactionType::Void);
+                self.store_and_forward(tx_type, &request_msg, &db_transaction, original.as_ref())
+                    .await?
+            }
+            // Approve within the stand-in limits, or reverse as for any timeout
+            None => match self
+                .stip_service
+                .check(tx_type, pan.as_deref(), &request_msg, &amount)
+            {
+                Ok(()) => {
+                    stand_in = true;
+                    self.stand_in(&request_msg, &db_transaction).await?
+                }
+                Err(refusal) => {
+                    info!(
+                        "Transaction {} not approved in stand-in: {}",
+                        card_request.transaction_id, refusal
+                    );
+                    if let Some(reservation) = refund_reservation.take() {
+                        reservation
+                            .release()
+                            .await
+                            .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
+                    }
+                    return self
+                        .handle_host_timeout(card_request, tx_type, &request_msg, db_transaction)
+                        .await;
+                }
+            },
+        };
 
-        // 5. Parse response
+        // 8. Parse response
         let (state, _response_code) = ResponseHandler::parse_response(&response_msg);
         let response_code_str = response_msg.get_field(39).map(|s| s.as_str());
         let auth_code = response_msg.get_field(38).map(|s| s.as_str());
@@ -113,7 +847,40 @@ impl Iso8583TransactionService {
             response_code_str, state
         );
 
-        // 6. Update transaction with response
+        // Repeated host declines on the card feed the risk rules
+        if state == TransactionState::Declined
+            && let Some(pan) = pan.as_deref()
+            && let Err(e) = self.risk_engine.record_decline(&risk_rules, pan).await
+        {
+            error!("Failed to record decline for risk rules: {}", e);
+        }
+
+        // Amount actually approved: DE4 of a partial approval, otherwise the request amount
+        let approved_amount = if state == TransactionState::Approved {
+            approved_amount(&response_msg, &amount, cashback.as_ref())
+        } else {
+            None
+        };
+        if ResponseHandler::is_partial_approval(&response_msg) {
+            info!(
+                "Partial approval: {:?} of {} requested",
+                approved_amount.map(Amount::to_major_string),
+                amount.to_major_string()
+            );
+            metrics::increment("transactions.partial_approval", 1);
+        }
+
+        // 9. Update transaction with response
+        let (event, reason) = if stand_in {
+            (TransactionEvent::StandIn, "stand-in approval".to_string())
+        } else if stored {
+            (TransactionEvent::Store, "stored for forwarding".to_string())
+        } else {
+            (
+                TransactionEvent::from_outcome(&state),
+                format!("host response {}", response_code_str.unwrap_or("none")),
+            )
+        };
         self.transaction_repo
             .update_response(
                 &db_transaction.tr_dt,
@@ -122,67 +889,643 @@ impl Iso8583TransactionService {
                 response_code_str,
                 auth_code,
                 rrn,
-                &state,
+                &StateTransition::new(event, ACTOR, &reason),
+            )
+            .await
+            .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
+
+        if let Some(approved) = approved_amount {
+            self.transaction_repo
+                .update_approved_amount(
+                    &db_transaction.tr_dt,
+                    &db_transaction.tr_tm,
+                    tr_uniq_no.as_str(),
+                    approved.minor(),
+                )
+                .await
+                .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
+        }
+
+        // Reflect the outcome on pre-auth holds and the original transaction
+        if let Some(approved) = approved_amount {
+            self.preauth_service
+                .on_approved(
+                    tx_type,
+                    &db_transaction,
+                    original.as_ref(),
+                    approved.minor(),
+                )
+                .await
+                .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
+        }
+        if let Some(original) = &original
+            && !stored
+        {
+            let approved = state == TransactionState::Approved;
+            if approved && tx_type == TransactionType::Void {
+                self.update_original_state(original, TransactionEvent::Void, "void approved")
+                    .await?;
+                if original.is_refund() {
+                    self.transaction_repo
+                        .release_voided_refund(original)
+                        .await
+                        .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
+                }
+            } else if approved && tx_type == TransactionType::Reversal {
+                self.update_original_state(
+                    original,
+                    TransactionEvent::Reverse,
+                    "reversal approved",
+                )
+                .await?;
+            } else if approved && tx_type == TransactionType::TipAdjustment {
+                self.apply_tip(original, amount_minor).await?;
+            }
+        }
+        // Keep the approved part of the refund and give back the rest
+        if let Some(reservation) = refund_reservation.take() {
+            reservation
+                .keep(approved_amount.map_or(0, |a| a.minor()))
+                .await
+                .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
+        }
+
+        // 10. Build response JSON
+        let state = if stored {
+            TransactionState::Stored
+        } else {
+            state
+        };
+        let response_json =
+            self.build_response_json(card_request, Some(tx_type), &response_msg, &state);
+
+        // 11. Send to Kafka
+        self.publish_response(card_request, &response_json).await;
+
+        info!("Transaction completed: STAN={}, State={:?}", stan, state);
+
+        Ok(response_json)
+    }
+
+    /// Whether network management reports the link to a host as down (not signed on)
+    fn host_link_down(&self, host: &HostConnector) -> bool {
+        self.network_service
+            .as_ref()
+            .is_some_and(|network| !network.is_signed_on(host.group()))
+    }
+
+    /// Host for a request: follow-ups go to the host of the original, card
+    /// transactions to the host group of their BIN range
+    fn select_host(
+        &self,
+        bin_range: Option<&BinRange>,
+        original: Option<&Iso8583Transaction>,
+    ) -> Result<Arc<HostConnector>, RoutingError> {
+        match (original.and_then(|o| o.host_group.as_deref()), bin_range) {
+            (Some(group), _) => self.host_router.connector(group),
+            (None, Some(range)) => self.host_router.connector(&range.host_group),
+            (None, None) => Ok(self.host_router.default_connector()),
+        }
+    }
+
+    /// Validate the outbound message against the profile of its transaction type
+    /// Warnings are logged and counted but never block the transaction
+    fn validate_request(
+        &self,
+        tx_type: TransactionType,
+        profile: Option<&TransactionProfile>,
+        pos_entry: PosEntry,
+        msg: &Iso8583Message,
+    ) -> ValidationResult {
+        let present_des: HashSet<u8> = msg.fields.keys().copied().collect();
+
+        let emv_data = msg
+            .get_field(55)
+            .and_then(|de55| ParsedEmvData::from_de55(de55).ok());
+        let present_tags: HashSet<&str> = emv_data
+            .as_ref()
+            .map(|data| data.elements.keys().map(String::as_str).collect())
+            .unwrap_or_default();
+
+        // Card data the entry mode cannot capture is not required (DE55 of a swipe, ...)
+        let result = match profile {
+            Some(profile) => {
+                let mut result = profile.validate(&present_des, &present_tags);
+                result.waive(&pos_entry.uncaptured_des(), !pos_entry.mode.is_chip());
+                result
+            }
+            None => ValidationResult::unknown_type(),
+        };
+
+        if !result.warnings.is_empty() {
+            warn!(
+                "Profile validation warnings for {:?}: {}",
+                tx_type,
+                result.warnings.join(", ")
+            );
+            metrics::increment("profile_validation.warnings", result.warnings.len() as u64);
+        }
+
+        if result.is_valid {
+            metrics::increment("profile_validation.passed", 1);
+        } else {
+            metrics::increment("profile_validation.rejected", 1);
+        }
+
+        result
+    }
+
+    /// Find the original transaction referenced by the request
+    async fn find_original(
+        &self,
+        card_request: &CardRequest,
+        tx_type: TransactionType,
+    ) -> Result<Option<Iso8583Transaction>, io::Error> {
+        self.transaction_repo
+            .find_original(
+                &card_request.trm_id,
+                card_request.original_rrn.as_deref(),
+                card_request.original_stan.as_deref(),
+                tx_type == TransactionType::Void,
             )
             .await
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Database error: {}", e)))?;
+            .map_err(|e| io::Error::other(format!("Database error: {}", e)))
+    }
 
-        // 7. Build response JSON
-        let response_json = self.build_response_json(card_request, &response_msg, &stan, &state);
+    /// No host response in time: mark the transaction TIMEOUT and queue its reversal
+    async fn handle_host_timeout(
+        &self,
+        card_request: &CardRequest,
+        tx_type: TransactionType,
+        request_msg: &Iso8583Message,
+        mut db_transaction: Iso8583Transaction,
+    ) -> Result<serde_json::Value, io::Error> {
+        error!(
+            "Host timeout after {:?}: transaction {}",
+            self.host_timeout, card_request.transaction_id
+        );
+        metrics::increment("transactions.host_timeout", 1);
 
-        // 8. Send to Kafka
+        let tr_uniq_no = db_transaction.tr_uniq_no.clone().unwrap_or_default();
+        self.transaction_repo
+            .update_response(
+                &db_transaction.tr_dt,
+                &db_transaction.tr_tm,
+                &tr_uniq_no,
+                None,
+                None,
+                None,
+                &StateTransition::new(TransactionEvent::TimeOut, ACTOR, "host timeout"),
+            )
+            .await
+            .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
+        db_transaction.tr_type = Some(TransactionState::Timeout.as_str().to_string());
+
+        // Balance inquiries move no funds, nothing to reverse
+        if tx_type != TransactionType::BalanceInquiry
+            && let Err(e) = self
+                .reversal_service
+                .queue_reversal(&db_transaction, ReversalReason::Timeout)
+                .await
+        {
+            error!("Failed to queue reversal for {}: {}", tr_uniq_no, e);
+        }
+
+        self.reject_locally(
+            card_request,
+            Some(tx_type),
+            Some(request_msg),
+            ResponseCode::ResponseTimeout,
+            None,
+        )
+        .await
+    }
+
+    /// Queue an advice or reversal the host did not acknowledge in time
+    /// Returns a local acknowledgement so the transaction completes for the terminal;
+    /// the SAF delivery applies reversals and voids to their original
+    async fn store_and_forward(
+        &self,
+        tx_type: TransactionType,
+        request_msg: &Iso8583Message,
+        db_transaction: &Iso8583Transaction,
+        original: Option<&Iso8583Transaction>,
+    ) -> Result<Iso8583Message, io::Error> {
+        warn!(
+            "Host timeout after {:?}: {} {} stored for forwarding",
+            self.host_timeout,
+            request_msg.mti,
+            db_transaction.tr_uniq_no.as_deref().unwrap_or_default()
+        );
+        metrics::increment("transactions.host_timeout", 1);
+
+        // Reversals are keyed by the transaction they reverse, which is marked
+        // REVERSED once the host confirms
+        let (saf_type, record) = match (tx_type, original) {
+            (TransactionType::Reversal, Some(original)) => (SafType::Reversal, original),
+            (TransactionType::PreAuthCompletion, _) => (SafType::CompletionAdvice, db_transaction),
+            (TransactionType::TipAdjustment, _) => (SafType::AdjustmentAdvice, db_transaction),
+            _ => (SafType::VoidAdvice, db_transaction),
+        };
+        self.saf_service
+            .enqueue(saf_type, record, request_msg)
+            .await
+            .map_err(|e| io::Error::other(format!("SAF error: {}", e)))?;
+
+        let mut response =
+            Iso8583Message::new(&request_msg.get_response_mti().unwrap_or("0210".to_string()));
+        for de in [2, 3, 4, 6, 10, 11, 12, 13, 37, 38, 41, 42, 49, 51] {
+            if let Some(value) = request_msg.get_field(de) {
+                response.set_field(de, value.clone());
+            }
+        }
+        response.set_field(39, ResponseCode::Approved.as_str().to_string());
+        Ok(response)
+    }
+
+    /// Approve a request in stand-in: flag it for exposure tracking and queue the
+    /// 0120/0220 advice that tells the host about the approval
+    async fn stand_in(
+        &self,
+        request_msg: &Iso8583Message,
+        db_transaction: &Iso8583Transaction,
+    ) -> Result<Iso8583Message, io::Error> {
+        let tr_uniq_no = db_transaction.tr_uniq_no.as_deref().unwrap_or_default();
+        warn!(
+            "Host timeout after {:?}: {} approved in stand-in",
+            self.host_timeout, tr_uniq_no
+        );
+        metrics::increment("transactions.host_timeout", 1);
+        metrics::increment("transactions.stand_in", 1);
+
+        let response = self.stip_service.approve(request_msg);
+        self.transaction_repo
+            .mark_stand_in(&db_transaction.tr_dt, &db_transaction.tr_tm, tr_uniq_no)
+            .await
+            .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
+        self.saf_service
+            .enqueue(
+                SafType::StandInAdvice,
+                db_transaction,
+                &StipService::advice(request_msg, &response),
+            )
+            .await
+            .map_err(|e| io::Error::other(format!("SAF error: {}", e)))?;
+        Ok(response)
+    }
+
+    /// Validate the TC of an offline sale and flag it against the floor limit
+    fn check_offline_approval(
+        &self,
+        request_msg: &Iso8583Message,
+        amount: &Amount,
+    ) -> Result<OfflineApproval, ResponseCode> {
+        let emv_data = request_msg
+            .get_field(55)
+            .and_then(|de55| ParsedEmvData::from_de55(de55).ok())
+            .ok_or(ResponseCode::FormatError)?;
+        let floor_limit = self.offline_config.floor_limit(amount.currency());
+        OfflineApproval::check(&emv_data, amount, &floor_limit)
+    }
+
+    /// Record a sale the card approved offline and queue its 0220 advice (Y1 in DE39)
+    /// The terminal is answered without waiting for the host
+    async fn accept_offline(
+        &self,
+        card_request: &CardRequest,
+        request_msg: &Iso8583Message,
+        db_transaction: &Iso8583Transaction,
+        approval: &OfflineApproval,
+    ) -> Result<serde_json::Value, io::Error> {
+        let tr_uniq_no = db_transaction.tr_uniq_no.as_deref().unwrap_or_default();
+        let code = ResponseCode::OfflineApproved;
+        self.transaction_repo
+            .update_response(
+                &db_transaction.tr_dt,
+                &db_transaction.tr_tm,
+                tr_uniq_no,
+                Some(code.as_str()),
+                None,
+                None,
+                &StateTransition::new(TransactionEvent::ApproveOffline, ACTOR, "offline TC"),
+            )
+            .await
+            .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
+
+        if let Some(flags) = approval.flags_str() {
+            warn!("Offline sale {} flagged for review: {}", tr_uniq_no, flags);
+            metrics::increment("transactions.offline_flagged", 1);
+        }
+        metrics::increment("transactions.offline_approved", 1);
+
+        let mut advice = request_msg.clone();
+        advice.set_field(39, code.as_str().to_string());
+        self.saf_service
+            .enqueue(SafType::OfflineUpload, db_transaction, &advice)
+            .await
+            .map_err(|e| io::Error::other(format!("SAF error: {}", e)))?;
+
+        let mut response_msg =
+            Iso8583Message::new(&advice.get_response_mti().unwrap_or("0230".to_string()));
+        for de in [2, 3, 4, 11, 12, 13, 37, 41, 42, 49] {
+            if let Some(value) = advice.get_field(de) {
+                response_msg.set_field(de, value.clone());
+            }
+        }
+        response_msg.set_field(39, code.as_str().to_string());
+
+        let response_json = self.build_response_json(
+            card_request,
+            Some(TransactionType::OfflineSale),
+            &response_msg,
+            &TransactionState::Approved,
+        );
+        self.publish_response(card_request, &response_json).await;
+
+        info!("Offline sale {} stored, advice queued", tr_uniq_no);
+        Ok(response_json)
+    }
+
+    /// Reserve a refund amount on the original's refundable balance
+    /// None when it would take cumulative refunds past the refundable amount
+    async fn reserve_refund(
+        &self,
+        original: &Iso8583Transaction,
+        amount_minor: i64,
+    ) -> Result<Option<RefundReservation>, io::Error> {
+        let tr_uniq_no = original.tr_uniq_no.as_deref().unwrap_or_default();
+        let reserved = self
+            .transaction_repo
+            .reserve_refund_amount(&original.tr_dt, &original.tr_tm, tr_uniq_no, amount_minor)
+            .await
+            .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
+        Ok(reserved.then(|| RefundReservation {
+            repo: self.transaction_repo.clone(),
+            tr_dt: original.tr_dt.clone(),
+            tr_tm: original.tr_tm.clone(),
+            tr_uniq_no: tr_uniq_no.to_string(),
+            amount_minor,
+            settled: false,
+        }))
+    }
+
+    /// Set the tip on the sale once its tip adjustment is approved
+    async fn apply_tip(
+        &self,
+        original: &Iso8583Transaction,
+        tip_amt: i64,
+    ) -> Result<(), io::Error> {
+        let tr_uniq_no = original.tr_uniq_no.as_deref().unwrap_or_default();
+        let applied = self
+            .transaction_repo
+            .update_tip_amount(&original.tr_dt, &original.tr_tm, tr_uniq_no, tip_amt)
+            .await
+            .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
+        if applied {
+            info!("Tip of {} set on {}", tip_amt, tr_uniq_no);
+            metrics::increment("transactions.tip_adjusted", 1);
+        } else {
+            warn!(
+                "Tip adjustment approved but {} is no longer open, tip not applied",
+                tr_uniq_no
+            );
+        }
+        Ok(())
+    }
+
+    /// Move the original transaction to a new state (e.g. VOIDED after an approved void)
+    async fn update_original_state(
+        &self,
+        original: &Iso8583Transaction,
+        event: TransactionEvent,
+        reason: &str,
+    ) -> Result<(), io::Error> {
+        let tr_uniq_no = original.tr_uniq_no.as_deref().unwrap_or_default();
+        let state = self
+            .transaction_repo
+            .update_state(
+                &original.tr_dt,
+                &original.tr_tm,
+                tr_uniq_no,
+                &StateTransition::new(event, ACTOR, reason),
+            )
+            .await
+            .map_err(|e| io::Error::other(format!("Database error: {}", e)))?;
+        info!("Original transaction {} -> {:?}", tr_uniq_no, state);
+        Ok(())
+    }
+
+    /// Find a stored transaction with the request's (terminal, transactionId)
+    async fn find_duplicate(
+        &self,
+        card_request: &CardRequest,
+    ) -> Result<Option<Iso8583Transaction>, io::Error> {
+        self.transaction_repo
+            .find_by_transaction_id_and_trm_id(
+                card_request.transaction_id.clone(),
+                card_request.trm_id.clone(),
+            )
+            .await
+            .map_err(|e| io::Error::other(format!("Database error: {}", e)))
+    }
+
+    /// Answer a retransmission of a stored transaction
+    /// Completed: replay the stored response. Pending: wait for its result.
+    /// Different payload, or no result in time: reject as duplicate
+    async fn resolve_duplicate(
+        &self,
+        card_request: &CardRequest,
+        tx_type: TransactionType,
+        fingerprint: &RequestFingerprint,
+        existing: Iso8583Transaction,
+    ) -> Result<serde_json::Value, io::Error> {
+        metrics::increment("transactions.duplicate", 1);
+
+        match duplicate_guard::classify(&existing, fingerprint) {
+            Duplicate::Completed(response) => {
+                info!(
+                    "Retransmission of completed transaction {}, replaying response",
+                    card_request.transaction_id
+                );
+                Ok(response)
+            }
+            Duplicate::Pending => match self.await_stored(card_request, fingerprint).await? {
+                Some(response) => Ok(response),
+                None => {
+                    warn!(
+                        "Transaction {} still pending, rejecting retransmission",
+                        card_request.transaction_id
+                    );
+                    Ok(self.reject_duplicate(card_request, tx_type))
+                }
+            },
+            Duplicate::Mismatch => Ok(self.reject_duplicate(card_request, tx_type)),
+        }
+    }
+
+    /// Poll for the response of a transaction pending on another instance
+    /// Gives up after the host timeout, by which the original must have completed
+    async fn await_stored(
+        &self,
+        card_request: &CardRequest,
+        fingerprint: &RequestFingerprint,
+    ) -> Result<Option<serde_json::Value>, io::Error> {
+        let deadline = Instant::now() + self.host_timeout;
+        while Instant::now() < deadline {
+            tokio::time::sleep(DUPLICATE_POLL_INTERVAL).await;
+            if let Some(existing) = self.find_duplicate(card_request).await?
+                && let Duplicate::Completed(response) =
+                    duplicate_guard::classify(&existing, fingerprint)
+            {
+                return Ok(Some(response));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reject a retransmission whose payload differs from the original request
+    /// Not published: the original transaction's notification stands
+    fn reject_duplicate(
+        &self,
+        card_request: &CardRequest,
+        tx_type: TransactionType,
+    ) -> serde_json::Value {
+        warn!(
+            "Duplicate transactionId {} from terminal {} rejected",
+            card_request.transaction_id, card_request.trm_id
+        );
+        metrics::increment("transactions.duplicate_rejected", 1);
+        self.local_response(
+            card_request,
+            Some(tx_type),
+            None,
+            ResponseCode::DuplicateTransmission,
+        )
+    }
+
+    /// Store the final response so retransmissions get the same answer
+    async fn save_response(&self, card_request: &CardRequest, response: &serde_json::Value) {
+        if let Err(e) = self
+            .transaction_repo
+            .save_response_json(
+                &card_request.trm_id,
+                &card_request.transaction_id,
+                &response.to_string(),
+            )
+            .await
+        {
+            error!(
+                "Failed to store response of transaction {}: {}",
+                card_request.transaction_id, e
+            );
+        }
+    }
+
+    /// Reject a request locally without contacting the host
+    /// `details` is attached to the terminal response under the given key
+    async fn reject_locally(
+        &self,
+        card_request: &CardRequest,
+        tx_type: Option<TransactionType>,
+        request_msg: Option<&Iso8583Message>,
+        code: ResponseCode,
+        details: Option<(&str, serde_json::Value)>,
+    ) -> Result<serde_json::Value, io::Error> {
+        info!(
+            "Rejecting transaction {} locally with response code {}",
+            card_request.transaction_id,
+            code.as_str()
+        );
+        metrics::increment("transactions.rejected_locally", 1);
+
+        let mut response_json = self.local_response(card_request, tx_type, request_msg, code);
+        if let (Some((key, value)), Some(obj)) = (details, response_json.as_object_mut()) {
+            obj.insert(key.to_string(), value);
+        }
+
+        self.publish_response(card_request, &response_json).await;
+
+        Ok(response_json)
+    }
+
+    /// Build the terminal response for a request answered without the host
+    fn local_response(
+        &self,
+        card_request: &CardRequest,
+        tx_type: Option<TransactionType>,
+        request_msg: Option<&Iso8583Message>,
+        code: ResponseCode,
+    ) -> serde_json::Value {
+        let response_mti = request_msg
+            .and_then(|m| m.get_response_mti())
+            .unwrap_or("0210".to_string());
+        let mut response_msg = Iso8583Message::new(&response_mti);
+        if let Some(request_msg) = request_msg {
+            for de in [2, 3, 4, 11, 12, 13, 41, 42, 49] {
+                if let Some(value) = request_msg.get_field(de) {
+                    response_msg.set_field(de, value.clone());
+                }
+            }
+        }
+        response_msg.set_field(41, card_request.trm_id.clone());
+        response_msg.set_field(39, code.as_str().to_string());
+
+        let state = code.to_transaction_state();
+        self.build_response_json(card_request, tx_type, &response_msg, &state)
+    }
+
+    /// Publish the terminal response to Kafka
+    async fn publish_response(
+        &self,
+        card_request: &CardRequest,
+        response_json: &serde_json::Value,
+    ) {
         info!("Sending ISO8583 transaction response to Kafka...");
         if let Err(e) = self
             .kafka_sender
             .send(
                 "payment_notifications",
                 format!("CARD_{}", card_request.transaction_id).as_str(),
-                &response_json,
+                response_json,
             )
             .await
         {
             error!("Failed to send ISO8583 response to Kafka: {}", e);
             // Don't fail the transaction if Kafka send fails, just log it
         }
-
-        info!("Transaction completed: STAN={}, State={:?}", stan, state);
-
-        Ok(response_json)
     }
 
     /// Build ISO8583 message from card request
     fn build_iso_message(
         &self,
         card_request: &CardRequest,
+        amount: &Amount,
         stan: &str,
+        tx_type: TransactionType,
+        profile: Option<&TransactionProfile>,
+        original: Option<&Iso8583Transaction>,
     ) -> Result<Iso8583Message, io::Error> {
-        // let mti = match card_request.transaction_type.as_str() {
-        //     "SALE" => "0200",
-        //     "VOID" => "0200",
-        //     "REVERSAL" => "0400",
-        //     "SETTLEMENT" => "0500",
-        //     _ => "0000"
-        // };
-        let mut msg = Iso8583Message::new("0200");
+        // MTI per transaction type (0100 pre-auth, 0220 completion advice, 0400 void, ...)
+        let mti = profile
+            .map(|p| p.mti.clone())
+            .unwrap_or_else(|| tx_type.get_mti());
+        let mut msg = Iso8583Message::new(&mti);
 
         let now = Local::now();
 
-        // DE3: Processing Code (based on transaction type)
-        let processing_code = match card_request.transaction_type.as_str() {
-            "SALE" | "PURCHASE" => "000000",
-            "CASH_WITHDRAWAL" => "010000",
-            "BALANCE_INQUIRY" => "310000",
-            "REFUND" => "200000",
-            _ => "000000",
-        };
-        msg.set_field(3, processing_code.to_string());
+        // DE3: Processing Code (from the transaction profile)
+        let processing_code = profile
+            .map(|p| p.processing_code.clone())
+            .unwrap_or_else(|| tx_type.get_processing_code());
+        msg.set_field(3, processing_code);
 
-        // DE4: Amount (12 digits, no decimal)
-        let amount_str = format!("{:012}", (card_request.amount * 100.0) as u64);
-        msg.set_field(4, amount_str);
+        // DE4: Amount (12 digits, minor units of the currency) - not sent for balance inquiry
+        if tx_type != TransactionType::BalanceInquiry {
+            msg.set_field(4, amount.to_iso());
+        }
 
-        // DE7: Transmission Date & Time (MMDDhhmmss)
-        msg.set_field(7, now.format("%m%d%H%M%S").to_string());
+        // DE7: Transmission Date & Time (MMDDhhmmss, GMT)
+        msg.set_field(7, business_calendar::transmission_date_time());
 
         // DE11: STAN
         msg.set_field(11, stan.to_string());
@@ -193,22 +1536,68 @@ impl Iso8583TransactionService {
         // DE13: Date, Local Transaction (MMDD)
         msg.set_field(13, now.format("%m%d").to_string());
 
-        // DE22: POS Entry Mode
-        msg.set_field(22, "051".to_string()); // ICC (Chip)
+        // DE15: Settlement Date (MMDD of the business date, not of the wall clock)
+        msg.set_field(15, BUSINESS_CALENDAR.settlement_date());
+
+        // DE25: POS Condition Code (00 normal presentment, 06 pre-authorization)
+        let pos_condition_code = profile
+            .map(|p| p.pos_condition_code.clone())
+            .unwrap_or_else(|| "00".to_string());
+        msg.set_field(25, pos_condition_code);
 
-        // DE25: POS Condition Code
-        msg.set_field(25, "00".to_string()); // Normal presentment
+        // DE32: Acquiring Institution ID (if configured)
+        if let Some(acquirer_id) = &self.acquirer_id {
+            msg.set_field(32, acquirer_id.clone());
+        }
 
         // DE41: Terminal ID
         msg.set_field(41, card_request.trm_id.clone());
 
+        // DE37/DE38: Original RRN and authorization code
+        if tx_type.references_original() {
+            let original_rrn = original
+                .and_then(|o| o.field_037.clone())
+                .or_else(|| card_request.original_rrn.clone());
+            if let Some(rrn) = original_rrn {
+                msg.set_field(37, rrn);
+            }
+
+            let original_auth_code = original
+                .and_then(|o| o.field_038.clone())
+                .or_else(|| card_request.original_auth_code.clone());
+            if let Some(auth_code) = original_auth_code {
+                msg.set_field(38, auth_code);
+            }
+        }
+
         // DE42: Merchant ID (if available)
         if let Some(merchant_id) = &card_request.merchant_id {
             msg.set_field(42, format!("{:15}", merchant_id));
         }
 
-        // DE49: Currency Code (VND = 704)
-        msg.set_field(49, "704".to_string());
+        // DE49: Currency Code (ISO 4217 numeric)
+        msg.set_field(49, amount.currency().numeric.to_string());
+
+        // DE60: partial approval indicator, when the terminal can take a partial amount
+        if card_request.partial_auth_supported
+            && matches!(
+                tx_type,
+                TransactionType::Purchase | TransactionType::PreAuth
+            )
+        {
+            msg.set_partial_approval_supported();
+        }
+
+        // DE90: Original Data Elements, built and checked the same way as for reversals
+        if tx_type.requires_original()
+            && let Some(original) = original
+        {
+            let de90 = self
+                .reversal_service
+                .original_data(original)
+                .map_err(|e| io::Error::other(format!("Original data error: {}", e)))?;
+            msg.set_field(90, de90);
+        }
 
         // DE55: EMV Data (from cardData if available)
         if let Ok(Some(de55)) = card_request.get_de55() {
@@ -232,6 +1621,11 @@ impl Iso8583TransactionService {
                 if let Some(psn) = emv_data.get_value("5F34") {
                     msg.set_field(23, format!("{:03}", psn));
                 }
+
+                // DE35: Track 2 Equivalent Data (tag 57, strip BCD padding)
+                if let Some(track2) = emv_data.get_value("57") {
+                    msg.set_field(35, track2.trim_end_matches('F').to_string());
+                }
             }
         }
 
@@ -262,6 +1656,13 @@ impl Iso8583TransactionService {
 
         db_tx.tr_uniq_no = Some(card_request.transaction_id.clone());
 
+        // Business date the transaction settles on, as sent in DE15
+        let business_date = msg
+            .get_field(15)
+            .and_then(|de15| BUSINESS_CALENDAR.resolve_settlement_date(de15).ok())
+            .unwrap_or_else(|| BUSINESS_CALENDAR.business_date());
+        db_tx.busi_dt = Some(business_date.format(BUSINESS_DATE_FORMAT).to_string());
+
         // db_tx.transaction
 
         // Set bitmap
@@ -274,26 +1675,611 @@ impl Iso8583TransactionService {
     fn build_response_json(
         &self,
         request: &CardRequest,
+        tx_type: Option<TransactionType>,
         response_msg: &Iso8583Message,
-        stan: &str,
         state: &TransactionState,
     ) -> serde_json::Value {
         let is_approved = ResponseHandler::is_approved(response_msg);
         let response_desc = ResponseHandler::get_response_description(response_msg);
+        let status = match (is_approved, tx_type) {
+            _ if *state == TransactionState::Stored => "STORED",
+            _ if ResponseHandler::is_partial_approval(response_msg) => "PARTIALLY_APPROVED",
+            (true, Some(tx_type)) => tx_type.approved_status(),
+            (true, None) => "APPROVED",
+            (false, _) => "DECLINED",
+        };
 
-        serde_json::json!({
-            "status": if is_approved { "APPROVED" } else { "DECLINED" },
+        // Amount as requested and as approved, in major units of the request currency
+        let (amount, approved_amount, currency) = match self.request_amount(request) {
+            Ok(amount) => (
+                amount.to_json(),
+                is_approved
+                    .then(|| {
+                        let cashback = tx_type
+                            .and_then(|t| self.cashback_amount(request, t, &amount).ok())
+                            .flatten();
+                        approved_amount(response_msg, &amount, cashback.as_ref())
+                    })
+                    .flatten()
+                    .map(Amount::to_json),
+                Some(amount.currency().alpha),
+            ),
+            Err(_) => (
+                serde_json::json!(request.amount),
+                None,
+                request.currency.as_deref(),
+            ),
+        };
+
+        let mut response = serde_json::json!({
+            "status": status,
             "transactionId": request.transaction_id,
             "transactionType": request.transaction_type,
             "terminalId": request.trm_id,
-            "stan": stan,
+            "stan": response_msg.get_field(11),
             "responseCode": response_msg.get_field(39),
             "authorizationCode": response_msg.get_field(38),
             "rrn": response_msg.get_field(37),
             "responseMessage": response_desc,
             "transactionState": state.as_str(),
-            "amount": request.amount,
+            "amount": amount,
+            "approvedAmount": approved_amount,
+            "currency": currency,
+            "timestamp": Local::now().to_rfc3339(),
+        });
+
+        // Tip set by a tip adjustment, cash handed out on a purchase with cashback
+        match tx_type {
+            Some(TransactionType::TipAdjustment) => response["tipAmount"] = amount,
+            Some(tx_type @ TransactionType::PurchaseWithCashback) => {
+                if let Ok(Some(cashback)) = self
+                    .request_amount(request)
+                    .and_then(|amount| self.cashback_amount(request, tx_type, &amount))
+                {
+                    response["cashbackAmount"] = cashback.to_json();
+                }
+            }
+            _ => {}
+        }
+
+        // DE6/DE10/DE51: amount charged to the cardholder after DCC
+        if let (Some(de6), Some(currency)) = (
+            response_msg.get_field(6),
+            response_msg
+                .get_field(51)
+                .and_then(|c| Currency::from_code(c)),
+        ) && let Ok(billing) = Amount::from_iso(de6, currency)
+        {
+            let rate = response_msg
+                .get_field(10)
+                .and_then(|de10| ConversionRate::from_de10(de10).ok())
+                .map(ConversionRate::to_decimal_string);
+            response["dcc"] = serde_json::json!({
+                "cardholderAmount": billing.to_json(),
+                "cardholderCurrency": currency.alpha,
+                "conversionRate": rate,
+            });
+        }
+
+        // DE54: balances and other additional amounts returned by the host
+        if let Some(de54) = response_msg.get_field(54) {
+            match AdditionalAmount::parse_de54(de54) {
+                Ok(entries) => {
+                    if let Some(balances) = Balances::from_additional_amounts(&entries) {
+                        response["balances"] = balances.to_json();
+                    }
+                    response["additionalAmounts"] =
+                        entries.iter().map(AdditionalAmount::to_json).collect();
+                }
+                Err(e) => warn!("Ignoring malformed DE54 {}: {}", de54, e),
+            }
+        }
+
+        response
+    }
+
+    /// Offer returned to the terminal instead of a host response; the terminal
+    /// repeats the request with `dccAccepted` once the cardholder has chosen
+    fn dcc_offer_response(&self, request: &CardRequest, quote: &DccQuote) -> serde_json::Value {
+        serde_json::json!({
+            "status": "DCC_OFFER",
+            "transactionId": request.transaction_id,
+            "transactionType": request.transaction_type,
+            "terminalId": request.trm_id,
+            "amount": quote.local.to_json(),
+            "currency": quote.local.currency().alpha,
+            "dccOffer": quote.to_json(),
             "timestamp": Local::now().to_rfc3339(),
         })
     }
+
+    /// Request amount in minor units of its currency (or the default currency)
+    fn request_amount(&self, card_request: &CardRequest) -> Result<Amount, AmountError> {
+        let currency = match card_request.currency.as_deref() {
+            Some(code) => Currency::from_code(code)
+                .ok_or_else(|| AmountError::UnknownCurrency(code.to_string()))?,
+            None => self.default_currency,
+        };
+        Amount::from_major(card_request.amount, currency)
+    }
+
+    /// Cashback of a purchase with cashback, in the request currency
+    /// Required and positive for that type, ignored for every other
+    fn cashback_amount(
+        &self,
+        card_request: &CardRequest,
+        tx_type: TransactionType,
+        amount: &Amount,
+    ) -> Result<Option<Amount>, AmountError> {
+        if tx_type != TransactionType::PurchaseWithCashback {
+            return Ok(None);
+        }
+        let value = card_request
+            .cashback_amount
+            .ok_or_else(|| AmountError::Invalid("missing cashback amount".to_string()))?;
+        let cashback = Amount::from_major(value, amount.currency())?;
+        if cashback.minor() <= 0 {
+            return Err(AmountError::Invalid(value.to_string()));
+        }
+        Ok(Some(cashback))
+    }
+}
+
+/// Refund amount reserved against the refundable balance of the original
+/// Released when dropped without being kept or released, so an error or an early
+/// return between the reservation and the host response cannot leak it
+struct RefundReservation {
+    repo: Arc<CardTransactionRepository>,
+    tr_dt: String,
+    tr_tm: String,
+    tr_uniq_no: String,
+    amount_minor: i64,
+    settled: bool,
+}
+
+impl RefundReservation {
+    /// Keep the approved part of the reservation and release the rest
+    async fn keep(mut self, approved_minor: i64) -> Result<(), sqlx::Error> {
+        self.settled = true;
+        let unused = self.amount_minor - approved_minor.clamp(0, self.amount_minor);
+        if unused > 0 {
+            self.repo
+                .release_refund_amount(&self.tr_dt, &self.tr_tm, &self.tr_uniq_no, unused)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Release the whole reservation: the refund was not approved
+    async fn release(self) -> Result<(), sqlx::Error> {
+        self.keep(0).await
+    }
+}
+
+impl Drop for RefundReservation {
+    fn drop(&mut self) {
+        if self.settled {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            error!(
+                "Refund reservation of {} on {} not released: no runtime",
+                self.amount_minor, self.tr_uniq_no
+            );
+            return;
+        };
+        let repo = self.repo.clone();
+        let (tr_dt, tr_tm, tr_uniq_no) = (
+            std::mem::take(&mut self.tr_dt),
+            std::mem::take(&mut self.tr_tm),
+            std::mem::take(&mut self.tr_uniq_no),
+        );
+        let amount_minor = self.amount_minor;
+        runtime.spawn(async move {
+            match repo
+                .release_refund_amount(&tr_dt, &tr_tm, &tr_uniq_no, amount_minor)
+                .await
+            {
+                Ok(()) => info!(
+                    "Refund reservation of {} on {} released",
+                    amount_minor, tr_uniq_no
+                ),
+                Err(e) => error!(
+                    "Failed to release refund reservation of {} on {}: {}",
+                    amount_minor, tr_uniq_no, e
+                ),
+            }
+        });
+    }
+}
+
+/// Approved amount of an approved response
+/// A partial approval (code 10) carries it in DE4; it can never exceed the request.
+/// DE4 of a purchase with cashback includes the cashback, which is not part of the
+/// approved amount
+fn approved_amount(
+    response_msg: &Iso8583Message,
+    requested: &Amount,
+    cashback: Option<&Amount>,
+) -> Option<Amount> {
+    if !ResponseHandler::is_partial_approval(response_msg) {
+        return Some(*requested);
+    }
+    let cashback = cashback.map_or(0, |c| c.minor());
+    let approved = response_msg
+        .get_field(4)
+        .and_then(|de4| Amount::from_iso(de4, requested.currency()).ok())
+        .map(|de4| Amount::from_minor(de4.minor() - cashback, requested.currency()))
+        .filter(|approved| approved.minor() >= 0 && approved.minor() <= requested.minor());
+    if approved.is_none() {
+        warn!(
+            "Partial approval without a valid approved amount: DE4={:?}",
+            response_msg.get_field(4)
+        );
+    }
+    approved
+}
+
+/// Purchases with cashback and tip adjustments send the total in DE4 and the
+/// cashback or tip in DE54; a tip adds to the approved amount of the sale
+fn apply_additional_amount(
+    msg: &mut Iso8583Message,
+    tx_type: TransactionType,
+    amount: &Amount,
+    cashback: Option<&Amount>,
+    original: Option<&Iso8583Transaction>,
+) {
+    let (amount_type, additional, base) = match (tx_type, cashback, original) {
+        (TransactionType::PurchaseWithCashback, Some(cashback), _) => {
+            (AdditionalAmount::CASHBACK, *cashback, amount.minor())
+        }
+        (TransactionType::TipAdjustment, _, Some(original)) => (
+            AdditionalAmount::GRATUITY,
+            *amount,
+            original.approved_amount().unwrap_or(0),
+        ),
+        _ => return,
+    };
+    let total = Amount::from_minor(base + additional.minor(), amount.currency());
+    msg.set_field(4, total.to_iso());
+    msg.set_field(
+        54,
+        AdditionalAmount::new("00", amount_type, additional).to_de54(),
+    );
+}
+
+/// PAN from the card's EMV data (tag 5A)
+fn card_pan(card_request: &CardRequest) -> Option<String> {
+    let emv_pan = card_request
+        .get_card_data_string()
+        .ok()
+        .flatten()
+        .and_then(|card_data| ParsedEmvData::from_de55(&card_data).ok())
+        .and_then(|emv_data| emv_data.get_pan());
+    emv_pan
+        .or_else(|| {
+            card_track2(card_request)
+                .as_deref()
+                .and_then(pos_entry::track2_pan_and_expiry)
+                .map(|(pan, _)| pan.to_string())
+        })
+        .or_else(|| card_request.pan.clone())
+}
+
+/// Track 2 of the request: as swiped, or the chip's track 2 equivalent (tag 57)
+fn card_track2(card_request: &CardRequest) -> Option<String> {
+    card_request.track2.clone().or_else(|| {
+        let card_data = card_request.get_card_data_string().ok().flatten()?;
+        let track2 = ParsedEmvData::from_de55(&card_data).ok()?.get_value("57")?;
+        Some(track2.trim_end_matches('F').to_string())
+    })
+}
+
+/// DE22/DE25/DE26 from the terminal entry mode, and the card data of cards not read
+/// from the chip (DE35 of a swipe, DE2/DE14 of a keyed card)
+fn apply_pos_entry(msg: &mut Iso8583Message, pos_entry: PosEntry, card_request: &CardRequest) {
+    msg.set_field(22, pos_entry.to_de22());
+    if let Some(pos_condition_code) = pos_entry.mode.pos_condition_code() {
+        msg.set_field(25, pos_condition_code.to_string());
+    }
+    if let Some(pin_capture_code) = pos_entry.to_de26() {
+        msg.set_field(26, pin_capture_code.to_string());
+    }
+
+    if pos_entry.mode.reads_track2()
+        && let Some(track2) = &card_request.track2
+    {
+        if let Some((pan, expiry)) = pos_entry::track2_pan_and_expiry(track2) {
+            msg.set_field(2, pan.to_string());
+            msg.set_field(14, expiry.to_string());
+        }
+        msg.set_field(35, track2.clone());
+    }
+    if let Some(pin_block) = &card_request.pin_block {
+        msg.set_field(52, pin_block.clone());
+    }
+    if matches!(pos_entry.mode, EntryMode::Manual | EntryMode::Ecommerce) {
+        if let Some(pan) = &card_request.pan {
+            msg.set_field(2, pan.clone());
+        }
+        if let Some(expiry) = &card_request.expiry_date {
+            msg.set_field(14, expiry.clone());
+        }
+    }
+}
+
+/// Card acceptor data from the terminal's master data: DE18 MCC, DE43 name/location
+/// and DE32 when the merchant is routed through its own acquirer
+fn apply_master_data(msg: &mut Iso8583Message, terminal: &TerminalMaster) {
+    msg.set_field(18, terminal.mcc.clone());
+    msg.set_field(43, terminal.to_de43());
+    if let Some(acquirer_id) = &terminal.acquirer_id {
+        msg.set_field(32, acquirer_id.clone());
+    }
+}
+
+/// Fingerprint of a request, compared against retransmissions of its transactionId
+/// Balance inquiries send no DE4, so their amount never counts; cashback is part of DE4
+fn request_fingerprint(
+    tx_type: TransactionType,
+    profile: Option<&TransactionProfile>,
+    amount: &Amount,
+    cashback: Option<&Amount>,
+) -> RequestFingerprint {
+    let processing_code = profile
+        .map(|p| p.processing_code.clone())
+        .unwrap_or_else(|| tx_type.get_processing_code());
+    let amount_minor = if tx_type == TransactionType::BalanceInquiry {
+        0
+    } else {
+        amount.minor() + cashback.map_or(0, |c| c.minor())
+    };
+    RequestFingerprint::new(&processing_code, amount_minor, amount.currency().numeric)
+}
+
+/// Check that a follow-up transaction is allowed against its original
+/// Voids need an approved, unsettled and unrefunded original; refunds are capped at the
+/// amount not yet refunded, in the original currency. Tips go on open, unrefunded sales
+fn check_original(
+    tx_type: TransactionType,
+    original: &Iso8583Transaction,
+    amount: &Amount,
+) -> Result<(), ResponseCode> {
+    let state = original.state();
+    let amount_minor = amount.minor();
+    match tx_type {
+        TransactionType::Void
+            if state != Some(TransactionState::Approved)
+                || original.refund_amt.unwrap_or(0) > 0 =>
+        {
+            Err(ResponseCode::InvalidTransaction)
+        }
+        TransactionType::Refund
+            if !matches!(
+                state,
+                Some(TransactionState::Approved) | Some(TransactionState::Settled)
+            ) =>
+        {
+            Err(ResponseCode::InvalidTransaction)
+        }
+        TransactionType::TipAdjustment
+            if state != Some(TransactionState::Approved)
+                || !original
+                    .field_003
+                    .as_deref()
+                    .is_some_and(|pc| pc.starts_with("00"))
+                || original.refund_amt.unwrap_or(0) > 0 =>
+        {
+            Err(ResponseCode::InvalidTransaction)
+        }
+        TransactionType::Refund | TransactionType::TipAdjustment
+            if original
+                .amount()
+                .is_some_and(|a| a.currency() != amount.currency()) =>
+        {
+            Err(ResponseCode::InvalidTransaction)
+        }
+        TransactionType::Refund => match original.refundable_amount() {
+            Some(refundable) if amount_minor > 0 && amount_minor <= refundable => Ok(()),
+            _ => Err(ResponseCode::InvalidAmount),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Tips are capped at a percentage of the sale's approved amount
+fn check_tip(
+    original: &Iso8583Transaction,
+    tip: &Amount,
+    max_pct: u32,
+) -> Result<(), ResponseCode> {
+    match original.approved_amount() {
+        Some(base) if tip.minor() * 100 <= base * i64::from(max_pct) => Ok(()),
+        _ => Err(ResponseCode::InvalidAmount),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn original(state: TransactionState, amount: &str, refunded: i64) -> Iso8583Transaction {
+        let mut tx = Iso8583Transaction::new("000123", "0200");
+        tx.field_004 = Some(amount.to_string());
+        tx.tr_type = Some(state.as_str().to_string());
+        tx.refund_amt = Some(refunded);
+        tx
+    }
+
+    fn vnd(minor: i64) -> Amount {
+        Amount::from_minor(minor, Currency::vnd())
+    }
+
+    #[test]
+    fn test_void_requires_open_original() {
+        let sale = original(TransactionState::Approved, "000000010000", 0);
+        assert!(check_original(TransactionType::Void, &sale, &vnd(10000)).is_ok());
+
+        for state in [TransactionState::Voided, TransactionState::Settled] {
+            let sale = original(state, "000000010000", 0);
+            assert_eq!(
+                check_original(TransactionType::Void, &sale, &vnd(10000)),
+                Err(ResponseCode::InvalidTransaction)
+            );
+        }
+
+        let refunded = original(TransactionState::Approved, "000000010000", 2500);
+        assert!(check_original(TransactionType::Void, &refunded, &vnd(10000)).is_err());
+    }
+
+    #[test]
+    fn test_tip_adjustment_checks() {
+        let mut sale = original(TransactionState::Approved, "000000100000", 0);
+        sale.field_003 = Some("000000".to_string());
+        assert!(check_original(TransactionType::TipAdjustment, &sale, &vnd(20000)).is_ok());
+        assert!(check_tip(&sale, &vnd(20000), 20).is_ok());
+        assert_eq!(
+            check_tip(&sale, &vnd(20001), 20),
+            Err(ResponseCode::InvalidAmount)
+        );
+
+        let mut settled = sale.clone();
+        settled.tr_type = Some(TransactionState::Settled.as_str().to_string());
+        assert_eq!(
+            check_original(TransactionType::TipAdjustment, &settled, &vnd(100)),
+            Err(ResponseCode::InvalidTransaction)
+        );
+
+        let mut refund = sale.clone();
+        refund.field_003 = Some("200000".to_string());
+        assert_eq!(
+            check_original(TransactionType::TipAdjustment, &refund, &vnd(100)),
+            Err(ResponseCode::InvalidTransaction)
+        );
+    }
+
+    #[test]
+    fn test_tip_and_cashback_in_de4_and_de54() {
+        let mut msg = Iso8583Message::new("0200");
+        apply_additional_amount(
+            &mut msg,
+            TransactionType::PurchaseWithCashback,
+            &vnd(100000),
+            Some(&vnd(50000)),
+            None,
+        );
+        assert_eq!(msg.get_field(4).unwrap(), "000000150000");
+        assert_eq!(msg.get_field(54).unwrap(), "0040704C000000050000");
+
+        let fingerprint = request_fingerprint(
+            TransactionType::PurchaseWithCashback,
+            None,
+            &vnd(100000),
+            Some(&vnd(50000)),
+        );
+        assert_eq!(fingerprint.processing_code, "090000");
+        assert_eq!(fingerprint.amount_minor, 150000);
+
+        let mut sale = original(TransactionState::Approved, "000000100000", 0);
+        sale.apprv_amt = Some(80000);
+        let mut msg = Iso8583Message::new("0220");
+        apply_additional_amount(
+            &mut msg,
+            TransactionType::TipAdjustment,
+            &vnd(12000),
+            None,
+            Some(&sale),
+        );
+        assert_eq!(msg.get_field(4).unwrap(), "000000092000");
+        assert_eq!(msg.get_field(54).unwrap(), "0044704C000000012000");
+    }
+
+    #[test]
+    fn test_partial_approval_amount() {
+        let requested = vnd(100000);
+        let mut response = Iso8583Message::new("0210");
+        response.set_field(39, "00".to_string());
+        assert_eq!(
+            approved_amount(&response, &requested, None),
+            Some(requested)
+        );
+
+        response.set_field(39, "10".to_string());
+        response.set_field(4, "000000060000".to_string());
+        assert_eq!(
+            approved_amount(&response, &requested, None),
+            Some(vnd(60000))
+        );
+
+        // An approved amount above the request is not trusted
+        response.set_field(4, "000000160000".to_string());
+        assert_eq!(approved_amount(&response, &requested, None), None);
+    }
+
+    #[test]
+    fn test_partial_approval_with_cashback() {
+        // 100,000 purchase + 20,000 cashback, host approves 80,000 in total
+        let requested = vnd(100000);
+        let cashback = vnd(20000);
+        let mut response = Iso8583Message::new("0210");
+        response.set_field(39, "10".to_string());
+        response.set_field(4, "000000080000".to_string());
+        let approved = approved_amount(&response, &requested, Some(&cashback));
+        assert_eq!(approved, Some(vnd(60000)));
+
+        // Settlement counts the cashback once
+        let mut sale = original(TransactionState::Approved, "000000120000", 0);
+        sale.cashback_amt = Some(cashback.minor());
+        sale.apprv_amt = approved.map(|a| a.minor());
+        assert_eq!(sale.settlement_amount(), Some(80000));
+
+        // More than the purchase plus cashback is not trusted
+        response.set_field(4, "000000120001".to_string());
+        assert_eq!(
+            approved_amount(&response, &requested, Some(&cashback)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_refund_cap_uses_approved_amount() {
+        let mut sale = original(TransactionState::Approved, "000000010000", 0);
+        sale.apprv_amt = Some(6000);
+        assert!(check_original(TransactionType::Refund, &sale, &vnd(6000)).is_ok());
+        assert_eq!(
+            check_original(TransactionType::Refund, &sale, &vnd(6001)),
+            Err(ResponseCode::InvalidAmount)
+        );
+
+        // Cash handed out on a purchase with cashback is never refunded
+        let mut cashback_sale = original(TransactionState::Approved, "000000012000", 0);
+        cashback_sale.cashback_amt = Some(2000);
+        assert!(check_original(TransactionType::Refund, &cashback_sale, &vnd(10000)).is_ok());
+        assert_eq!(
+            check_original(TransactionType::Refund, &cashback_sale, &vnd(10001)),
+            Err(ResponseCode::InvalidAmount)
+        );
+    }
+
+    #[test]
+    fn test_cumulative_refund_cap() {
+        let sale = original(TransactionState::Settled, "000000010000", 6000);
+        assert!(check_original(TransactionType::Refund, &sale, &vnd(4000)).is_ok());
+        assert_eq!(
+            check_original(TransactionType::Refund, &sale, &vnd(4001)),
+            Err(ResponseCode::InvalidAmount)
+        );
+
+        let usd = Amount::from_minor(4000, Currency::from_code("USD").unwrap());
+        assert_eq!(
+            check_original(TransactionType::Refund, &sale, &usd),
+            Err(ResponseCode::InvalidTransaction)
+        );
+
+        let voided = original(TransactionState::Voided, "000000010000", 0);
+        assert_eq!(
+            check_original(TransactionType::Refund, &voided, &vnd(100)),
+            Err(ResponseCode::InvalidTransaction)
+        );
+    }
 }
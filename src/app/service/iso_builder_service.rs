@@ -1,12 +1,22 @@
 use crate::app::service::transaction_profile::TransactionType;
 
+/// Transaction types as sent by terminals in the TCP JSON `transactionType`
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-#[allow(dead_code)]
 pub enum TcpTransactionType {
     Sale,
+    SaleCashback,
+    TipAdjust,
     Void,
     Reversal,
     Qr,
+    Refund,
+    PreAuth,
+    Incremental,
+    Completion,
+    Balance,
+    CashWithdrawal,
+    CashAdvance,
+    OfflineSale,
 }
 
 impl TryFrom<&str> for TcpTransactionType {
@@ -14,23 +24,73 @@ impl TryFrom<&str> for TcpTransactionType {
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value.to_uppercase().as_str() {
-            "SALE" => Ok(TcpTransactionType::Sale),
-            "VOID" => Ok(TcpTransactionType::Void),
+            "SALE" | "PURCHASE" => Ok(TcpTransactionType::Sale),
+            "SALE_CASHBACK" | "PURCHASE_CASHBACK" | "CASHBACK" => {
+                Ok(TcpTransactionType::SaleCashback)
+            }
+            "TIP_ADJUST" | "TIP_ADJUSTMENT" | "TIP" => Ok(TcpTransactionType::TipAdjust),
+            "VOID" | "CANCEL" => Ok(TcpTransactionType::Void),
             "REVERSAL" => Ok(TcpTransactionType::Reversal),
-            "QR" => Ok(TcpTransactionType::Qr),
+            "QR" | "QR_PAYMENT" | "VIETQR" => Ok(TcpTransactionType::Qr),
+            "REFUND" | "RETURN" => Ok(TcpTransactionType::Refund),
+            "PRE_AUTH" | "PREAUTH" | "AUTH" => Ok(TcpTransactionType::PreAuth),
+            "INCREMENTAL" | "INCREMENTAL_AUTH" | "TOP_UP" => Ok(TcpTransactionType::Incremental),
+            "COMPLETION" | "PRE_AUTH_COMPLETION" => Ok(TcpTransactionType::Completion),
+            "BALANCE" | "BALANCE_INQUIRY" => Ok(TcpTransactionType::Balance),
+            "CASH_WITHDRAWAL" | "WITHDRAWAL" => Ok(TcpTransactionType::CashWithdrawal),
+            "CASH_ADVANCE" => Ok(TcpTransactionType::CashAdvance),
+            "OFFLINE_SALE" | "OFFLINE" | "OFFLINE_ADVICE" => Ok(TcpTransactionType::OfflineSale),
             _ => Err(format!("Unsupported TCP transactionType: {}", value)),
         }
     }
 }
 
 impl TcpTransactionType {
-    #[allow(dead_code)]
     pub fn to_internal(self) -> TransactionType {
         match self {
             TcpTransactionType::Sale => TransactionType::Purchase,
+            TcpTransactionType::SaleCashback => TransactionType::PurchaseWithCashback,
+            TcpTransactionType::TipAdjust => TransactionType::TipAdjustment,
             TcpTransactionType::Void => TransactionType::Void,
             TcpTransactionType::Reversal => TransactionType::Reversal,
             TcpTransactionType::Qr => TransactionType::QrPayment,
+            TcpTransactionType::Refund => TransactionType::Refund,
+            TcpTransactionType::PreAuth => TransactionType::PreAuth,
+            TcpTransactionType::Incremental => TransactionType::IncrementalAuth,
+            TcpTransactionType::Completion => TransactionType::PreAuthCompletion,
+            TcpTransactionType::Balance => TransactionType::BalanceInquiry,
+            TcpTransactionType::CashWithdrawal => TransactionType::CashWithdrawal,
+            TcpTransactionType::CashAdvance => TransactionType::CashAdvance,
+            TcpTransactionType::OfflineSale => TransactionType::OfflineSale,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_terminal_transaction_type() {
+        let resolve =
+            |s: &str| TcpTransactionType::try_from(s).map(TcpTransactionType::to_internal);
+
+        assert_eq!(resolve("sale"), Ok(TransactionType::Purchase));
+        assert_eq!(resolve("VOID"), Ok(TransactionType::Void));
+        assert_eq!(
+            resolve("COMPLETION"),
+            Ok(TransactionType::PreAuthCompletion)
+        );
+        assert_eq!(resolve("INCREMENTAL"), Ok(TransactionType::IncrementalAuth));
+        assert_eq!(resolve("BALANCE"), Ok(TransactionType::BalanceInquiry));
+        assert_eq!(
+            resolve("CASHBACK"),
+            Ok(TransactionType::PurchaseWithCashback)
+        );
+        assert_eq!(resolve("TIP_ADJUST"), Ok(TransactionType::TipAdjustment));
+        assert_eq!(resolve("OFFLINE_SALE"), Ok(TransactionType::OfflineSale));
+        assert!(resolve("SETTLEMENT").is_err());
+        assert!(resolve("TRANSFER").is_err());
+        assert!(resolve("").is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::env;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::models::merchant::TerminalMaster;
+use crate::repository::merchant_repository::MerchantRepository;
+
+/// Cached lookup result; unknown terminals are cached too so a misconfigured
+/// terminal cannot hammer the database
+struct CacheEntry {
+    loaded_at: Instant,
+    terminal: Option<TerminalMaster>,
+}
+
+/// Merchant/terminal master data cache
+/// Entries expire after `MASTER_DATA_CACHE_TTL_SECS` (default 300); changes made
+/// to the tables are picked up at expiry or through POST /admin/master-data/cache/clear
+pub struct MasterDataCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<String, CacheEntry>>,
+}
+
+impl MasterDataCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        Self::new(Duration::from_secs(
+            env::var("MASTER_DATA_CACHE_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+        ))
+    }
+
+    /// Terminal with its merchant, None when the terminal is unknown
+    pub async fn terminal(
+        &self,
+        repo: &MerchantRepository,
+        trm_id: &str,
+    ) -> Result<Option<TerminalMaster>, sqlx::Error> {
+        if let Some(cached) = self.cached(trm_id) {
+            return Ok(cached);
+        }
+        let terminal = repo.find_terminal(trm_id).await?;
+        self.store(trm_id, terminal.clone());
+        Ok(terminal)
+    }
+
+    fn cached(&self, trm_id: &str) -> Option<Option<TerminalMaster>> {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(trm_id)
+            .filter(|entry| entry.loaded_at.elapsed() < self.ttl)
+            .map(|entry| entry.terminal.clone())
+    }
+
+    fn store(&self, trm_id: &str, terminal: Option<TerminalMaster>) {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, entry| entry.loaded_at.elapsed() < self.ttl);
+        entries.insert(
+            trm_id.to_string(),
+            CacheEntry {
+                loaded_at: Instant::now(),
+                terminal,
+            },
+        );
+    }
+
+    /// Drop every entry; returns the number of entries dropped
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        let len = entries.len();
+        entries.clear();
+        info!("Cleared {} master data cache entries", len);
+        len
+    }
+}
+
+/// Global master data cache
+pub static MASTER_DATA_CACHE: Lazy<MasterDataCache> = Lazy::new(MasterDataCache::from_env);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_expire() {
+        let cache = MasterDataCache::new(Duration::from_millis(50));
+        assert!(cache.cached("TERM0001").is_none());
+
+        // Unknown terminals are cached as well
+        cache.store("TERM0001", None);
+        assert_eq!(cache.cached("TERM0001").map(|t| t.is_none()), Some(true));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(cache.cached("TERM0001").is_none());
+
+        cache.store("TERM0001", None);
+        assert_eq!(cache.clear(), 1);
+        assert!(cache.cached("TERM0001").is_none());
+    }
+}
@@ -1,11 +1,25 @@
+pub mod bin_table;
+pub mod business_calendar;
+pub mod dcc_service;
+pub mod duplicate_guard;
 pub mod emv_iso_mapping;
+pub mod host_router;
 pub mod iso_builder_service;
 pub mod iso8583_parser;
 pub mod iso8583_transaction_service;
+pub mod master_data_cache;
+pub mod network_management_service;
+pub mod offline_advice;
 pub mod pay_os_service;
+pub mod preauth_service;
 pub mod response_handler;
 pub mod reversal_service;
+pub mod risk_engine;
+pub mod saf_service;
+pub mod settlement_service;
 pub mod stan_generator;
+pub mod stip_service;
+pub mod terminal_management_service;
 pub mod tlv_parser;
 pub mod transaction_profile;
 
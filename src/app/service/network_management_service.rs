@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::app::config::network_config::NetworkConfig;
+use crate::app::service::business_calendar::{
+    self, BUSINESS_CALENDAR, CutoverError, CutoverSource,
+};
+use crate::app::service::host_router::{HostConnector, HostRouter};
+use crate::app::service::response_handler::{ResponseCode, ResponseHandler};
+use crate::app::service::saf_service::SafService;
+use crate::app::service::stan_generator::{StanGenerator, TraceError};
+use crate::models::iso8583_message::Iso8583Message;
+use crate::repository::business_day_repository::BusinessDayRepository;
+
+/// Network Management Information Code (DE70)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkCode {
+    SignOn,
+    /// Business day cutover; DE15 carries the new business date
+    Cutover,
+    Echo,
+}
+
+impl NetworkCode {
+    pub fn as_code(&self) -> &str {
+        match self {
+            NetworkCode::SignOn => "001",
+            NetworkCode::Cutover => "201",
+            NetworkCode::Echo => "301",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "001" => Some(NetworkCode::SignOn),
+            "201" => Some(NetworkCode::Cutover),
+            "301" => Some(NetworkCode::Echo),
+            _ => None,
+        }
+    }
+}
+
+/// Network Management Service
+/// Signs on to every host, keeps the links alive with echo tests (0800/0810) and
+/// drains the store-and-forward queue whenever a host answers. Answers the
+/// host's own 0800s, cutting over the business day on a cutover (201)
+pub struct NetworkManagementService {
+    stan_generator: Arc<StanGenerator>,
+    saf_service: Arc<SafService>,
+    business_day_repo: BusinessDayRepository,
+    host_router: HostRouter,
+    config: NetworkConfig,
+    /// Sign-on state per host group
+    signed_on: HashMap<String, AtomicBool>,
+}
+
+impl NetworkManagementService {
+    pub fn new(
+        stan_generator: Arc<StanGenerator>,
+        saf_service: Arc<SafService>,
+        business_day_repo: BusinessDayRepository,
+        config: NetworkConfig,
+    ) -> Self {
+        let host_router = HostRouter::from_env();
+        let signed_on = host_router
+            .connectors()
+            .map(|host| (host.group().to_string(), AtomicBool::new(false)))
+            .collect();
+        Self {
+            stan_generator,
+            saf_service,
+            business_day_repo,
+            host_router,
+            config,
+            signed_on,
+        }
+    }
+
+    /// Build a network management request (0800)
+    pub async fn build_request(&self, code: NetworkCode) -> Result<Iso8583Message, TraceError> {
+        let mut msg = Iso8583Message::new("0800");
+        msg.set_field(7, business_calendar::transmission_date_time());
+        msg.set_field(11, self.stan_generator.next().await?);
+        msg.set_field(70, code.as_code().to_string());
+        Ok(msg)
+    }
+
+    /// Sign on to a host
+    pub async fn sign_on(&self, host: &HostConnector) -> bool {
+        let ok = self.send(host, NetworkCode::SignOn).await;
+        self.set_signed_on(host.group(), ok);
+        ok
+    }
+
+    /// Echo test; a failed echo requires a new sign-on
+    pub async fn echo(&self, host: &HostConnector) -> bool {
+        let ok = self.send(host, NetworkCode::Echo).await;
+        if !ok {
+            self.set_signed_on(host.group(), false);
+        }
+        ok
+    }
+
+    /// Whether the host group is signed on; unknown groups never are
+    pub fn is_signed_on(&self, group: &str) -> bool {
+        self.signed_on
+            .get(group)
+            .is_some_and(|signed_on| signed_on.load(Ordering::SeqCst))
+    }
+
+    fn set_signed_on(&self, group: &str, signed_on: bool) {
+        if let Some(state) = self.signed_on.get(group) {
+            state.store(signed_on, Ordering::SeqCst);
+        }
+    }
+
+    /// Answer a network management request (0800) from the host with an 0810
+    /// A cutover moves the business date to DE15, or to the next day without one
+    pub async fn handle_host_request(&self, request: &Iso8583Message) -> Iso8583Message {
+        let mut response = Iso8583Message::new("0810");
+        for de in [7, 11, 15, 70] {
+            if let Some(value) = request.get_field(de) {
+                response.set_field(de, value.clone());
+            }
+        }
+
+        let code = request
+            .get_field(70)
+            .and_then(|code| NetworkCode::from_code(code));
+        let response_code = match (request.mti.as_str(), code) {
+            ("0800", Some(NetworkCode::Cutover)) => self.host_cutover(request).await,
+            ("0800", Some(_)) => ResponseCode::Approved,
+            _ => ResponseCode::InvalidTransaction,
+        };
+        info!(
+            "Host network management {:?} answered {}",
+            code,
+            response_code.as_str()
+        );
+        response.set_field(39, response_code.as_str().to_string());
+        response
+    }
+
+    async fn host_cutover(&self, request: &Iso8583Message) -> ResponseCode {
+        let to = match request
+            .get_field(15)
+            .map(|de15| BUSINESS_CALENDAR.resolve_settlement_date(de15))
+            .transpose()
+        {
+            Ok(to) => to,
+            Err(e) => {
+                warn!("Host cutover rejected: {}", e);
+                return ResponseCode::FormatError;
+            }
+        };
+        match BUSINESS_CALENDAR
+            .cutover(&self.business_day_repo, to, CutoverSource::Host)
+            .await
+        {
+            Ok(_) => ResponseCode::Approved,
+            Err(CutoverError::Database(e)) => {
+                error!("Host cutover failed: {}", e);
+                ResponseCode::SystemMalfunction
+            }
+            Err(e) => {
+                warn!("Host cutover rejected: {}", e);
+                ResponseCode::InvalidTransaction
+            }
+        }
+    }
+
+    /// Sign on, then echo periodically (signing on again after a failure)
+    /// Each tick first picks up business days recorded by other instances, then
+    /// records the one opened by the cutover time
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        let period = Duration::from_secs(self.config.echo_interval_secs.max(1));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = BUSINESS_CALENDAR.refresh(&self.business_day_repo).await {
+                    error!("Failed to refresh the business date: {}", e);
+                }
+                if let Err(e) = BUSINESS_CALENDAR
+                    .record_scheduled(&self.business_day_repo)
+                    .await
+                {
+                    error!("Failed to record the business day: {}", e);
+                }
+                for host in self.host_router.connectors() {
+                    if self.is_signed_on(host.group()) {
+                        self.echo(host).await;
+                    } else {
+                        self.sign_on(host).await;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Send an 0800 to a host and wait for an approved 0810
+    /// Success means the host is reachable, so the SAF queue is drained
+    async fn send(&self, host: &HostConnector, code: NetworkCode) -> bool {
+        let request = match self.build_request(code).await {
+            Ok(request) => request,
+            Err(e) => {
+                error!("Failed to build network management request: {}", e);
+                return false;
+            }
+        };
+        let timeout = Duration::from_millis(self.config.response_timeout_ms);
+        let host_call = host.exchange(&request);
+
+        let ok = match tokio::time::timeout(timeout, host_call).await {
+            Ok(response) => response.mti == "0810" && ResponseHandler::is_approved(&response),
+            Err(_) => false,
+        };
+
+        if ok {
+            info!("Network management {:?} with {} succeeded", code, host.group());
+            self.trigger_drain();
+        } else {
+            warn!("Network management {:?} with {} failed", code, host.group());
+        }
+        ok
+    }
+
+    fn trigger_drain(&self) {
+        let saf_service = self.saf_service.clone();
+        tokio::spawn(async move {
+            if let Err(e) = saf_service.drain().await {
+                error!("SAF drain failed: {}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,187 @@
+use crate::app::service::response_handler::ResponseCode;
+use crate::app::service::tlv_parser::ParsedEmvData;
+use crate::models::amount::Amount;
+
+/// Cryptogram type bits of the CID (9F27): 00 AAC, 01 TC, 10 ARQC
+const CID_TYPE_MASK: u8 = 0xC0;
+const CID_TC: u8 = 0x40;
+
+/// TVR (95) byte 1: SDA, DDA or CDA failed
+const TVR_DATA_AUTH_FAILED: u8 = 0x40 | 0x08 | 0x04;
+/// TVR (95) byte 4: transaction exceeds floor limit
+const TVR_FLOOR_LIMIT_EXCEEDED: u8 = 0x80;
+/// TVR (95) byte 4: lower or upper consecutive offline limit exceeded
+const TVR_OFFLINE_LIMIT_EXCEEDED: u8 = 0x40 | 0x20;
+
+/// Reason an offline approval is flagged for review
+/// The card has already approved, so flags never decline the upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OfflineFlag {
+    /// Amount above the acquirer floor limit
+    AboveFloorLimit,
+    /// Terminal saw the floor limit exceeded and the card still approved offline
+    FloorLimitExceeded,
+    /// Consecutive offline limit exceeded
+    OfflineLimitExceeded,
+    /// Offline data authentication failed
+    DataAuthFailed,
+}
+
+impl OfflineFlag {
+    pub fn as_str(&self) -> &str {
+        match self {
+            OfflineFlag::AboveFloorLimit => "ABOVE_FLOOR_LIMIT",
+            OfflineFlag::FloorLimitExceeded => "TVR_FLOOR_LIMIT_EXCEEDED",
+            OfflineFlag::OfflineLimitExceeded => "TVR_OFFLINE_LIMIT_EXCEEDED",
+            OfflineFlag::DataAuthFailed => "TVR_DATA_AUTH_FAILED",
+        }
+    }
+}
+
+/// Offline approval (TC) that passed validation, with its review flags
+#[derive(Debug, Clone, Default)]
+pub struct OfflineApproval {
+    pub flags: Vec<OfflineFlag>,
+}
+
+impl OfflineApproval {
+    /// Validate an offline approval uploaded by a terminal
+    /// The card must have generated a TC for the uploaded amount; the TVR and the
+    /// floor limit only raise flags
+    pub fn check(
+        emv_data: &ParsedEmvData,
+        amount: &Amount,
+        floor_limit: &Amount,
+    ) -> Result<Self, ResponseCode> {
+        let cid = emv_data
+            .elements
+            .get("9F27")
+            .and_then(|e| e.value.first().copied())
+            .ok_or(ResponseCode::FormatError)?;
+        if cid & CID_TYPE_MASK != CID_TC {
+            return Err(ResponseCode::InvalidTransaction);
+        }
+
+        let cryptogram = emv_data
+            .elements
+            .get("9F26")
+            .ok_or(ResponseCode::FormatError)?;
+        if cryptogram.value.len() != 8 {
+            return Err(ResponseCode::FormatError);
+        }
+
+        // The TC only covers the amount authorised on the card
+        if let Some(authorised) = emv_data.get_amount()
+            && authorised != amount.to_iso()
+        {
+            return Err(ResponseCode::InvalidAmount);
+        }
+
+        let mut flags = Vec::new();
+        if amount.minor() > floor_limit.minor() {
+            flags.push(OfflineFlag::AboveFloorLimit);
+        }
+        if let Some(tvr) = emv_data.elements.get("95").map(|e| &e.value)
+            && tvr.len() == 5
+        {
+            if tvr[3] & TVR_FLOOR_LIMIT_EXCEEDED != 0 {
+                flags.push(OfflineFlag::FloorLimitExceeded);
+            }
+            if tvr[3] & TVR_OFFLINE_LIMIT_EXCEEDED != 0 {
+                flags.push(OfflineFlag::OfflineLimitExceeded);
+            }
+            if tvr[0] & TVR_DATA_AUTH_FAILED != 0 {
+                flags.push(OfflineFlag::DataAuthFailed);
+            }
+        }
+
+        Ok(Self { flags })
+    }
+
+    pub fn is_flagged(&self) -> bool {
+        !self.flags.is_empty()
+    }
+
+    /// Flags as stored with the transaction, None when there are none
+    pub fn flags_str(&self) -> Option<String> {
+        self.is_flagged().then(|| {
+            self.flags
+                .iter()
+                .map(OfflineFlag::as_str)
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Currency;
+
+    fn emv(cid: &str, amount: &str, tvr: &str) -> ParsedEmvData {
+        let de55 = format!(
+            "9F2701{}9F26081122334455667788{}9F36020012{}",
+            cid, amount, tvr
+        );
+        ParsedEmvData::from_de55(&de55).unwrap()
+    }
+
+    fn vnd(minor: i64) -> Amount {
+        Amount::from_minor(minor, Currency::vnd())
+    }
+
+    #[test]
+    fn test_offline_approval_requires_tc() {
+        let floor_limit = vnd(1_000_000);
+        let amount = vnd(50_000);
+
+        let approval = OfflineApproval::check(&emv("40", "", ""), &amount, &floor_limit).unwrap();
+        assert!(!approval.is_flagged());
+        assert_eq!(approval.flags_str(), None);
+
+        // ARQC must go online, AAC was declined by the card
+        assert_eq!(
+            OfflineApproval::check(&emv("80", "", ""), &amount, &floor_limit).unwrap_err(),
+            ResponseCode::InvalidTransaction
+        );
+        assert_eq!(
+            OfflineApproval::check(&emv("00", "", ""), &amount, &floor_limit).unwrap_err(),
+            ResponseCode::InvalidTransaction
+        );
+
+        // Uploaded amount differs from the amount the TC covers
+        assert_eq!(
+            OfflineApproval::check(&emv("40", "9F0206000000060000", ""), &amount, &floor_limit)
+                .unwrap_err(),
+            ResponseCode::InvalidAmount
+        );
+        assert!(
+            OfflineApproval::check(&emv("40", "9F0206000000050000", ""), &amount, &floor_limit)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_offline_approval_flags() {
+        let approval = OfflineApproval::check(
+            &emv("40", "", "95054000008000"),
+            &vnd(2_000_000),
+            &vnd(1_000_000),
+        )
+        .unwrap();
+
+        assert_eq!(
+            approval.flags,
+            vec![
+                OfflineFlag::AboveFloorLimit,
+                OfflineFlag::FloorLimitExceeded,
+                OfflineFlag::DataAuthFailed,
+            ]
+        );
+        assert_eq!(
+            approval.flags_str().as_deref(),
+            Some("ABOVE_FLOOR_LIMIT,TVR_FLOOR_LIMIT_EXCEEDED,TVR_DATA_AUTH_FAILED")
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use chrono::Local;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::app::config::preauth_config::PreAuthConfig;
+use crate::app::service::response_handler::ResponseCode;
+use crate::app::service::transaction_profile::TransactionType;
+use crate::models::preauth_hold::{HoldStatus, PreAuthHold};
+use crate::models::transaction::Iso8583Transaction;
+use crate::repository::preauth_repository::PreAuthRepository;
+
+#[derive(Debug, Error)]
+pub enum PreAuthError {
+    #[error("Pre-auth rejected: {}", .0.description())]
+    Rejected(ResponseCode),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Pre-Authorization Service
+/// Tracks open holds: incremental authorizations, completion within tolerance,
+/// release on void and expiry after the configured number of days
+pub struct PreAuthService {
+    repo: Arc<PreAuthRepository>,
+    config: PreAuthConfig,
+}
+
+impl PreAuthService {
+    pub fn new(repo: Arc<PreAuthRepository>, config: PreAuthConfig) -> Self {
+        Self { repo, config }
+    }
+
+    /// Check a follow-up against the hold of its original pre-auth before sending
+    pub async fn check_follow_up(
+        &self,
+        tx_type: TransactionType,
+        original: &Iso8583Transaction,
+        amount: i64,
+    ) -> Result<(), PreAuthError> {
+        if !matches!(
+            tx_type,
+            TransactionType::IncrementalAuth
+                | TransactionType::PreAuthCompletion
+                | TransactionType::Void
+        ) {
+            return Ok(());
+        }
+
+        let hold = self.find_hold(original).await?;
+        let hold = match (tx_type, hold) {
+            // Void of a regular sale, nothing held
+            (TransactionType::Void, None) => return Ok(()),
+            (_, None) => return Err(PreAuthError::Rejected(ResponseCode::UnableToLocate)),
+            (_, Some(hold)) => hold,
+        };
+
+        if !hold.is_active(Local::now().naive_local()) {
+            warn!(
+                "Pre-auth hold {} is not open (status {})",
+                hold.tr_uniq_no, hold.status
+            );
+            return Err(PreAuthError::Rejected(ResponseCode::InvalidTransaction));
+        }
+
+        if tx_type == TransactionType::PreAuthCompletion
+            && !hold.can_complete(amount, self.config.completion_tolerance_pct)
+        {
+            warn!(
+                "Completion of {} exceeds hold {} (max {})",
+                amount,
+                hold.tr_uniq_no,
+                hold.max_completion_amount(self.config.completion_tolerance_pct)
+            );
+            return Err(PreAuthError::Rejected(ResponseCode::InvalidAmount));
+        }
+
+        Ok(())
+    }
+
+    /// Update holds after the host approved the transaction
+    /// `record` is the stored transaction itself, `original` the pre-auth it references
+    pub async fn on_approved(
+        &self,
+        tx_type: TransactionType,
+        record: &Iso8583Transaction,
+        original: Option<&Iso8583Transaction>,
+        amount: i64,
+    ) -> Result<(), sqlx::Error> {
+        match (tx_type, original) {
+            (TransactionType::PreAuth, _) => {
+                let hold = PreAuthHold::open(
+                    &record.tr_dt,
+                    &record.tr_tm,
+                    record.tr_uniq_no.as_deref().unwrap_or_default(),
+                    record.trm_id.clone(),
+                    amount,
+                    self.config.expiry_days,
+                );
+                info!(
+                    "Pre-auth hold opened: {} amount={} expires={}",
+                    hold.tr_uniq_no, hold.authorized_amt, hold.expire_dtm
+                );
+                self.repo.insert(&hold).await
+            }
+            (TransactionType::IncrementalAuth, Some(original)) => {
+                let (tr_dt, tr_tm, tr_uniq_no) = hold_key(original);
+                if !self
+                    .repo
+                    .add_incremental(tr_dt, tr_tm, tr_uniq_no, amount)
+                    .await?
+                {
+                    warn!(
+                        "Incremental auth approved but hold {} is closed",
+                        tr_uniq_no
+                    );
+                }
+                Ok(())
+            }
+            (TransactionType::PreAuthCompletion, Some(original)) => {
+                self.close(original, HoldStatus::Completed, Some(amount))
+                    .await
+            }
+            (TransactionType::Void, Some(original)) => {
+                self.close(original, HoldStatus::Released, None).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Expire open holds past their expiry date
+    pub async fn expire_due(&self) -> Result<usize, sqlx::Error> {
+        let expired = self.repo.expire_due().await?;
+        for hold in &expired {
+            info!(
+                "Pre-auth hold expired: {} amount={} terminal={:?}",
+                hold.tr_uniq_no, hold.authorized_amt, hold.trm_id
+            );
+        }
+        Ok(expired.len())
+    }
+
+    /// Periodically expire holds in the background
+    pub fn spawn_expiry_sweeper(self: Arc<Self>) -> JoinHandle<()> {
+        let period = Duration::from_secs(self.config.sweep_interval_secs.max(1));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.expire_due().await {
+                    error!("Failed to expire pre-auth holds: {}", e);
+                }
+            }
+        })
+    }
+
+    async fn find_hold(
+        &self,
+        original: &Iso8583Transaction,
+    ) -> Result<Option<PreAuthHold>, sqlx::Error> {
+        let (tr_dt, tr_tm, tr_uniq_no) = hold_key(original);
+        self.repo.find_by_key(tr_dt, tr_tm, tr_uniq_no).await
+    }
+
+    async fn close(
+        &self,
+        original: &Iso8583Transaction,
+        status: HoldStatus,
+        completed_amt: Option<i64>,
+    ) -> Result<(), sqlx::Error> {
+        let (tr_dt, tr_tm, tr_uniq_no) = hold_key(original);
+        // Voids of regular sales have no hold, nothing to close
+        if self
+            .repo
+            .close(tr_dt, tr_tm, tr_uniq_no, status, completed_amt)
+            .await?
+        {
+            info!("Pre-auth hold {} -> {}", tr_uniq_no, status.as_str());
+        }
+        Ok(())
+    }
+}
+
+/// Holds are keyed by the original pre-auth transaction
+fn hold_key(original: &Iso8583Transaction) -> (&str, &str, &str) {
+    (
+        &original.tr_dt,
+        &original.tr_tm,
+        original.tr_uniq_no.as_deref().unwrap_or_default(),
+    )
+}
@@ -1,3 +1,5 @@
+use crate::app::service::business_calendar;
+use crate::models::amount::{AdditionalAmount, Amount, Currency};
 use crate::models::iso8583_message::Iso8583Message;
 use crate::models::transaction::TransactionState;
 use chrono::Local;
@@ -7,16 +9,24 @@ use chrono::Local;
 pub enum ResponseCode {
     /// 00 - Approved
     Approved,
+    /// 03 - Invalid merchant (unknown or blocked terminal/merchant)
+    InvalidMerchant,
     /// 05 - Do not honor
     DoNotHonor,
+    /// 10 - Partial approval (approved amount in DE4)
+    PartialApproval,
     /// 12 - Invalid transaction
     InvalidTransaction,
     /// 13 - Invalid amount
     InvalidAmount,
     /// 14 - Invalid card number
     InvalidCard,
+    /// 25 - Unable to locate record (original transaction)
+    UnableToLocate,
     /// 30 - Format error
     FormatError,
+    /// 43 - Stolen card (hot card list)
+    StolenCard,
     /// 51 - Insufficient funds
     InsufficientFunds,
     /// 54 - Expired card
@@ -29,55 +39,98 @@ pub enum ResponseCode {
     NotPermittedTerminal,
     /// 61 - Exceeds withdrawal limit
     ExceedsLimit,
+    /// 63 - Security violation (terminal not logged on or spoofed terminal ID)
+    SecurityViolation,
+    /// 65 - Exceeds withdrawal frequency limit
+    ExceedsFrequency,
+    /// 68 - Response received too late (host timeout)
+    ResponseTimeout,
+    /// 70 - PIN data required
+    PinRequired,
     /// 91 - Issuer or switch inoperative
     IssuerInoperative,
+    /// 92 - No route to the issuer (host group not configured)
+    RoutingError,
+    /// 94 - Duplicate transmission
+    DuplicateTransmission,
+    /// 95 - Reconcile error (batch out of balance, upload required)
+    ReconcileError,
     /// 96 - System malfunction
     SystemMalfunction,
+    /// Y1 - Approved offline by the card (EMV TC)
+    OfflineApproved,
 }
 
 impl ResponseCode {
     pub fn as_str(&self) -> &str {
         match self {
             ResponseCode::Approved => "00",
+            ResponseCode::InvalidMerchant => "03",
             ResponseCode::DoNotHonor => "05",
+            ResponseCode::PartialApproval => "10",
             ResponseCode::InvalidTransaction => "12",
             ResponseCode::InvalidAmount => "13",
             ResponseCode::InvalidCard => "14",
+            ResponseCode::UnableToLocate => "25",
             ResponseCode::FormatError => "30",
+            ResponseCode::StolenCard => "43",
             ResponseCode::InsufficientFunds => "51",
             ResponseCode::ExpiredCard => "54",
             ResponseCode::IncorrectPin => "55",
             ResponseCode::NotPermitted => "57",
             ResponseCode::NotPermittedTerminal => "58",
             ResponseCode::ExceedsLimit => "61",
+            ResponseCode::SecurityViolation => "63",
+            ResponseCode::ExceedsFrequency => "65",
+            ResponseCode::ResponseTimeout => "68",
+            ResponseCode::PinRequired => "70",
             ResponseCode::IssuerInoperative => "91",
+            ResponseCode::RoutingError => "92",
+            ResponseCode::DuplicateTransmission => "94",
+            ResponseCode::ReconcileError => "95",
             ResponseCode::SystemMalfunction => "96",
+            ResponseCode::OfflineApproved => "Y1",
         }
     }
 
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "00" => Some(ResponseCode::Approved),
+            "03" => Some(ResponseCode::InvalidMerchant),
             "05" => Some(ResponseCode::DoNotHonor),
+            "10" => Some(ResponseCode::PartialApproval),
             "12" => Some(ResponseCode::InvalidTransaction),
             "13" => Some(ResponseCode::InvalidAmount),
             "14" => Some(ResponseCode::InvalidCard),
+            "25" => Some(ResponseCode::UnableToLocate),
             "30" => Some(ResponseCode::FormatError),
+            "43" => Some(ResponseCode::StolenCard),
             "51" => Some(ResponseCode::InsufficientFunds),
             "54" => Some(ResponseCode::ExpiredCard),
             "55" => Some(ResponseCode::IncorrectPin),
             "57" => Some(ResponseCode::NotPermitted),
             "58" => Some(ResponseCode::NotPermittedTerminal),
             "61" => Some(ResponseCode::ExceedsLimit),
+            "63" => Some(ResponseCode::SecurityViolation),
+            "65" => Some(ResponseCode::ExceedsFrequency),
+            "68" => Some(ResponseCode::ResponseTimeout),
+            "70" => Some(ResponseCode::PinRequired),
             "91" => Some(ResponseCode::IssuerInoperative),
+            "92" => Some(ResponseCode::RoutingError),
+            "94" => Some(ResponseCode::DuplicateTransmission),
+            "95" => Some(ResponseCode::ReconcileError),
             "96" => Some(ResponseCode::SystemMalfunction),
+            "Y1" => Some(ResponseCode::OfflineApproved),
             _ => None,
         }
     }
 
     pub fn to_transaction_state(&self) -> TransactionState {
         match self {
-            ResponseCode::Approved => TransactionState::Approved,
+            ResponseCode::Approved
+            | ResponseCode::PartialApproval
+            | ResponseCode::OfflineApproved => TransactionState::Approved,
+            ResponseCode::ResponseTimeout => TransactionState::Timeout,
             _ => TransactionState::Declined,
         }
     }
@@ -85,19 +138,31 @@ impl ResponseCode {
     pub fn description(&self) -> &str {
         match self {
             ResponseCode::Approved => "Approved",
+            ResponseCode::InvalidMerchant => "Invalid merchant",
             ResponseCode::DoNotHonor => "Do not honor",
+            ResponseCode::PartialApproval => "Partial approval",
             ResponseCode::InvalidTransaction => "Invalid transaction",
             ResponseCode::InvalidAmount => "Invalid amount",
             ResponseCode::InvalidCard => "Invalid card number",
+            ResponseCode::UnableToLocate => "Unable to locate original transaction",
             ResponseCode::FormatError => "Format error",
+            ResponseCode::StolenCard => "Stolen card",
             ResponseCode::InsufficientFunds => "Insufficient funds",
             ResponseCode::ExpiredCard => "Expired card",
             ResponseCode::IncorrectPin => "Incorrect PIN",
             ResponseCode::NotPermitted => "Transaction not permitted",
             ResponseCode::NotPermittedTerminal => "Transaction not permitted to terminal",
             ResponseCode::ExceedsLimit => "Exceeds withdrawal limit",
+            ResponseCode::SecurityViolation => "Security violation",
+            ResponseCode::ExceedsFrequency => "Exceeds withdrawal frequency limit",
+            ResponseCode::ResponseTimeout => "Response received too late",
+            ResponseCode::PinRequired => "PIN data required",
             ResponseCode::IssuerInoperative => "Issuer or switch inoperative",
+            ResponseCode::RoutingError => "Unable to route transaction",
+            ResponseCode::DuplicateTransmission => "Duplicate transmission",
+            ResponseCode::ReconcileError => "Reconcile error, batch upload required",
             ResponseCode::SystemMalfunction => "System malfunction",
+            ResponseCode::OfflineApproved => "Approved offline",
         }
     }
 }
@@ -129,18 +194,41 @@ impl MockBankResponseHandler {
         );
 
         // Copy request fields to response
-        for de in [2, 3, 4, 11, 12, 13, 14, 22, 41, 42, 49] {
+        for de in [2, 3, 4, 6, 10, 11, 12, 13, 14, 15, 22, 41, 42, 49, 51] {
             if let Some(value) = request.get_field(de) {
                 response.set_field(de, value.clone());
             }
         }
 
-        // Generate mock RRN (Retrieval Reference Number)
-        let rrn = self.generate_rrn();
+        // RRN (Retrieval Reference Number) allocated by the switch, or a mock one for
+        // requests that carry none
+        let rrn = request
+            .get_field(37)
+            .cloned()
+            .unwrap_or_else(|| self.generate_rrn());
         response.set_field(37, rrn);
 
-        // Determine response code based on success rate
-        let response_code = self.determine_response_code();
+        // Advices, reversals, batch uploads and network management are always
+        // acknowledged, settlement is checked for balance, others use the success rate
+        let response_code = if request.is_advice()
+            || request.is_reversal()
+            || request.is_batch_upload()
+            || request.is_network_management()
+        {
+            ResponseCode::Approved
+        } else if request.is_settlement() {
+            self.determine_settlement_code(request)
+        } else {
+            self.determine_response_code()
+        };
+        // Insufficient funds becomes a partial approval when the terminal accepts one
+        let response_code = if response_code == ResponseCode::InsufficientFunds
+            && request.supports_partial_approval()
+        {
+            self.approve_partially(request, &mut response)
+        } else {
+            response_code
+        };
         response.set_field(39, response_code.as_str().to_string());
 
         // Generate authorization code for approved transactions
@@ -149,9 +237,26 @@ impl MockBankResponseHandler {
             response.set_field(38, auth_code);
         }
 
-        // Add transmission date/time
-        let now = Local::now();
-        response.set_field(7, now.format("%m%d%H%M%S").to_string());
+        // Balance inquiries return ledger and available balance in DE54
+        let is_balance_inquiry = request.get_field(3).is_some_and(|pc| pc.starts_with("31"));
+        if response_code == ResponseCode::Approved && is_balance_inquiry {
+            let currency = request
+                .get_field(49)
+                .and_then(|code| Currency::from_code(code))
+                .unwrap_or(Currency::vnd());
+            let balance = Amount::from_minor(self.generate_balance(currency), currency);
+            let de54: String = [
+                AdditionalAmount::new("00", AdditionalAmount::LEDGER_BALANCE, balance),
+                AdditionalAmount::new("00", AdditionalAmount::AVAILABLE_BALANCE, balance),
+            ]
+            .iter()
+            .map(AdditionalAmount::to_de54)
+            .collect();
+            response.set_field(54, de54);
+        }
+
+        // Add transmission date/time (GMT)
+        response.set_field(7, business_calendar::transmission_date_time());
 
         // Copy bitmap from request as base
         response.bitmap = request.bitmap.clone();
@@ -188,6 +293,20 @@ impl MockBankResponseHandler {
         }
     }
 
+    /// Settlement is out of balance at the failure rate until the batch is uploaded;
+    /// the retry after an upload (DE3 96xxxx) always balances
+    fn determine_settlement_code(&self, request: &Iso8583Message) -> ResponseCode {
+        use rand::Rng;
+        let after_upload = request.get_field(3).is_some_and(|pc| pc.starts_with("96"));
+        let roll: f64 = rand::thread_rng().sample(rand::distributions::Standard);
+
+        if after_upload || roll < self.success_rate {
+            ResponseCode::Approved
+        } else {
+            ResponseCode::ReconcileError
+        }
+    }
+
     /// Generate a mock RRN (Retrieval Reference Number)
     /// Format: YYDDDHHNNNNNN (12 digits)
     fn generate_rrn(&self) -> String {
@@ -202,6 +321,39 @@ impl MockBankResponseHandler {
         format!("{}{}{}{}", yy, ddd, hh, nnnnnn)
     }
 
+    /// Approve half of the requested amount: DE4 carries the approved amount and
+    /// DE54 the original amount
+    fn approve_partially(&self, request: &Iso8583Message, response: &mut Iso8583Message) -> ResponseCode {
+        let currency = request
+            .get_field(49)
+            .and_then(|code| Currency::from_code(code))
+            .unwrap_or(Currency::vnd());
+        let Some(requested) = request
+            .get_field(4)
+            .and_then(|de4| Amount::from_iso(de4, currency).ok())
+        else {
+            return ResponseCode::InsufficientFunds;
+        };
+        let approved = Amount::from_minor(requested.minor() / 2, currency);
+        if approved.minor() == 0 {
+            return ResponseCode::InsufficientFunds;
+        }
+
+        response.set_field(4, approved.to_iso());
+        response.set_field(
+            54,
+            AdditionalAmount::new("00", AdditionalAmount::ORIGINAL_AMOUNT, requested).to_de54(),
+        );
+        ResponseCode::PartialApproval
+    }
+
+    /// Generate a mock account balance in minor units of the currency
+    fn generate_balance(&self, currency: &Currency) -> i64 {
+        let mut rng = rand::thread_rng();
+        let major = rand::Rng::gen_range(&mut rng, 100..10_000_000i64);
+        major * 10i64.pow(currency.exponent)
+    }
+
     /// Generate a mock authorization code
     fn generate_auth_code(&self) -> String {
         let mut rng = rand::thread_rng();
@@ -238,9 +390,14 @@ impl ResponseHandler {
         (TransactionState::Failed, None)
     }
 
-    /// Check if response is approved
+    /// Check if response is approved (fully or partially)
     pub fn is_approved(response: &Iso8583Message) -> bool {
-        response.get_field(39).map(|c| c == "00").unwrap_or(false)
+        response.get_field(39).map(|c| c == "00" || c == "10" || c == "Y1").unwrap_or(false)
+    }
+
+    /// Check if only part of the amount was approved
+    pub fn is_partial_approval(response: &Iso8583Message) -> bool {
+        response.get_field(39).is_some_and(|c| c == "10")
     }
 
     /// Get response description
@@ -273,6 +430,75 @@ mod tests {
         assert!(response.has_field(39)); // Response Code
     }
 
+    #[tokio::test]
+    async fn test_mock_acknowledges_advice_and_reversal() {
+        let handler = MockBankResponseHandler::new(0.0);
+
+        let advice = handler.process_request(&Iso8583Message::new("0220")).await;
+        assert_eq!(advice.mti, "0230");
+        assert_eq!(advice.get_field(39).map(String::as_str), Some("00"));
+
+        let reversal = handler.process_request(&Iso8583Message::new("0400")).await;
+        assert_eq!(reversal.mti, "0410");
+        assert_eq!(reversal.get_field(39).map(String::as_str), Some("00"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_settlement_balances_after_upload() {
+        let handler = MockBankResponseHandler::new(0.0);
+
+        let mut settlement = Iso8583Message::new("0500");
+        settlement.set_field(3, "920000".to_string());
+        let response = handler.process_request(&settlement).await;
+        assert_eq!(response.mti, "0510");
+        assert_eq!(response.get_field(39).map(String::as_str), Some("95"));
+
+        let upload = handler.process_request(&Iso8583Message::new("0320")).await;
+        assert_eq!(upload.mti, "0330");
+        assert_eq!(upload.get_field(39).map(String::as_str), Some("00"));
+
+        settlement.set_field(3, "960000".to_string());
+        let response = handler.process_request(&settlement).await;
+        assert_eq!(response.get_field(39).map(String::as_str), Some("00"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_balance_inquiry_returns_de54() {
+        let handler = MockBankResponseHandler::new(1.0);
+        let mut request = Iso8583Message::new("0100");
+        request.set_field(3, "310000".to_string());
+        request.set_field(49, "840".to_string());
+
+        let response = handler.process_request(&request).await;
+        let balances = AdditionalAmount::parse_de54(response.get_field(54).unwrap()).unwrap();
+        assert_eq!(balances.len(), 2);
+        assert_eq!(balances[1].amount_type, "02");
+        assert_eq!(balances[1].amount.currency().alpha, "USD");
+    }
+
+    #[test]
+    fn test_mock_partial_approval() {
+        let handler = MockBankResponseHandler::default_mock();
+        let mut request = Iso8583Message::new("0200");
+        request.set_field(4, "000000100000".to_string());
+        request.set_field(49, "704".to_string());
+        request.set_partial_approval_supported();
+
+        let mut response = Iso8583Message::new("0210");
+        let code = handler.approve_partially(&request, &mut response);
+        assert_eq!(code, ResponseCode::PartialApproval);
+        assert_eq!(code.to_transaction_state(), TransactionState::Approved);
+        assert_eq!(response.get_field(4).map(String::as_str), Some("000000050000"));
+
+        let original = AdditionalAmount::parse_de54(response.get_field(54).unwrap()).unwrap();
+        assert_eq!(original[0].amount_type, AdditionalAmount::ORIGINAL_AMOUNT);
+        assert_eq!(original[0].amount.minor(), 100000);
+
+        response.set_field(39, code.as_str().to_string());
+        assert!(ResponseHandler::is_approved(&response));
+        assert!(ResponseHandler::is_partial_approval(&response));
+    }
+
     #[test]
     fn test_response_code_conversion() {
         let code = ResponseCode::Approved;
@@ -1,25 +1,42 @@
+use crate::app::config::reversal_config::ReversalConfig;
+use crate::app::service::business_calendar::{self, BUSINESS_CALENDAR};
+use crate::app::service::iso8583_parser::Iso8583Parser;
 use crate::app::service::stan_generator::StanGenerator;
 use crate::models::iso8583_message::Iso8583Message;
-use crate::models::transaction::{Iso8583Transaction, TransactionState};
-use crate::repository::card_transaction_repository::CardTransactionRepository;
+use crate::models::original_data::OriginalDataElements;
+use crate::models::saf_entry::SafType;
+use crate::models::transaction::{
+    Iso8583Transaction, StateTransition, TransactionEvent, TransactionState,
+};
+use crate::repository::card_transaction_repository::{
+    CardTransactionRepository, TransitionError,
+};
+use crate::repository::saf_repository::SafRepository;
 use chrono::Local;
 use std::sync::Arc;
 
 /// Reversal Service
 /// Handles transaction reversals (0400 messages)
+/// Reversals are queued for store-and-forward and repeated (0401) until the host answers 0410
 pub struct ReversalService {
     stan_generator: Arc<StanGenerator>,
     transaction_repo: Arc<CardTransactionRepository>,
+    saf_repo: Arc<SafRepository>,
+    config: ReversalConfig,
 }
 
 impl ReversalService {
     pub fn new(
         stan_generator: Arc<StanGenerator>,
         transaction_repo: Arc<CardTransactionRepository>,
+        saf_repo: Arc<SafRepository>,
+        config: ReversalConfig,
     ) -> Self {
         Self {
             stan_generator,
             transaction_repo,
+            saf_repo,
+            config,
         }
     }
 
@@ -33,14 +50,22 @@ impl ReversalService {
         let mut reversal = Iso8583Message::new("0400");
 
         // Generate new STAN for reversal
-        let reversal_stan = self.stan_generator.next().await;
+        let reversal_stan = self
+            .stan_generator
+            .next_for(
+                original_tx.trm_id.as_deref(),
+                original_tx.host_group.as_deref(),
+            )
+            .await
+            .map_err(|e| ReversalError::DatabaseError(e.to_string()))?;
         reversal.set_field(11, reversal_stan.clone());
 
-        // Set transmission date/time
+        // Set transmission date/time (DE7 in GMT) and the settlement date
         let now = Local::now();
-        reversal.set_field(7, now.format("%m%d%H%M%S").to_string());
+        reversal.set_field(7, business_calendar::transmission_date_time());
         reversal.set_field(12, now.format("%H%M%S").to_string());
         reversal.set_field(13, now.format("%m%d").to_string());
+        reversal.set_field(15, BUSINESS_CALENDAR.settlement_date());
 
         // Copy key fields from original transaction
         if let Some(pan) = &original_tx.field_002 {
@@ -52,6 +77,9 @@ impl ReversalService {
         if let Some(amount) = &original_tx.field_004 {
             reversal.set_field(4, amount.clone());
         }
+        if let Some(acquirer_id) = &original_tx.field_032 {
+            reversal.set_field(32, acquirer_id.clone());
+        }
         if let Some(terminal_id) = &original_tx.field_041 {
             reversal.set_field(41, terminal_id.clone());
         }
@@ -62,14 +90,8 @@ impl ReversalService {
             reversal.set_field(49, currency.clone());
         }
 
-        // DE90: Original Data Elements (OMMDDHHMMSS + original STAN + original MTI)
-        let original_data = format!(
-            "{}{}{:?}",
-            original_tx.field_013.as_deref().unwrap_or("0000"),
-            original_tx.field_012.as_deref().unwrap_or("000000"),
-            original_tx.tr_uniq_no
-        );
-        reversal.set_field(90, original_data);
+        // DE90: Original Data Elements
+        reversal.set_field(90, self.original_data(original_tx)?);
 
         // Add reason code (typically in DE56 or private field)
         reversal.set_field(56, reason_code.as_code().to_string());
@@ -84,6 +106,60 @@ impl ReversalService {
         Ok(reversal)
     }
 
+    /// DE90 for a message that refers to the original transaction (reversal or void)
+    /// MTI + STAN + DE7 + acquirer ID + forwarding ID, checked against the n42 layout
+    pub fn original_data(
+        &self,
+        original_tx: &Iso8583Transaction,
+    ) -> Result<String, ReversalError> {
+        let mut original_data = OriginalDataElements::from_transaction(original_tx);
+        if let Some(forwarding_id) = &self.config.forwarding_id {
+            original_data = original_data.with_forwarding_id(forwarding_id);
+        }
+        let de90 = original_data.to_de90();
+        Iso8583Parser::new()
+            .validate_field(90, &de90)
+            .map_err(|e| ReversalError::InvalidOriginalData(e.to_string()))?;
+        Ok(de90)
+    }
+
+    /// Build a reversal for the original transaction and queue it for store-and-forward
+    /// Returns the reversal; queueing twice for the same transaction is a no-op
+    pub async fn queue_reversal(
+        &self,
+        original_tx: &Iso8583Transaction,
+        reason: ReversalReason,
+    ) -> Result<Iso8583Message, ReversalError> {
+        let reversal = self.create_reversal(original_tx, reason).await?;
+        let message = serde_json::to_string(&reversal)
+            .map_err(|e| ReversalError::Serialization(e.to_string()))?;
+
+        let queued = self
+            .saf_repo
+            .enqueue(
+                SafType::Reversal,
+                &original_tx.tr_dt,
+                &original_tx.tr_tm,
+                original_tx.tr_uniq_no.as_deref().unwrap_or_default(),
+                original_tx.trm_id.as_deref(),
+                &message,
+            )
+            .await
+            .map_err(|e| ReversalError::DatabaseError(e.to_string()))?;
+
+        if queued {
+            tracing::info!(
+                "Reversal queued for {:?}: Reason={}",
+                original_tx.tr_uniq_no,
+                reason.description()
+            );
+        } else {
+            tracing::warn!("Reversal already queued for {:?}", original_tx.tr_uniq_no);
+        }
+
+        Ok(reversal)
+    }
+
     /// Perform automatic reversal for a timeout transaction
     pub async fn auto_reverse_timeout(
         &self,
@@ -108,8 +184,8 @@ impl ReversalService {
             }
         }
 
-        // Create reversal message
-        self.create_reversal(&original_tx, ReversalReason::Timeout)
+        // Create reversal message and queue it until the host confirms
+        self.queue_reversal(&original_tx, ReversalReason::Timeout)
             .await
     }
 
@@ -129,25 +205,46 @@ impl ReversalService {
         self.create_reversal(&original_tx, reason).await
     }
 
-    /// Mark transaction as reversed in database
+    /// Mark transaction as reversed in database; the original keeps its DE39
+    /// Marking an already reversed transaction again is a no-op
     pub async fn mark_as_reversed(
         &self,
         tr_dt: &str,
         tr_tm: &str,
         tr_uniq_no: &str,
     ) -> Result<(), ReversalError> {
-        self.transaction_repo
+        let result = self
+            .transaction_repo
             .update_response(
                 tr_dt,
                 tr_tm,
                 tr_uniq_no,
-                Some("99"), // Reversal response code
                 None,
                 None,
-                &TransactionState::Reversed,
+                None,
+                &StateTransition::new(
+                    TransactionEvent::Reverse,
+                    "reversal_service",
+                    "reversal confirmed by host",
+                ),
             )
-            .await
-            .map_err(|e| ReversalError::DatabaseError(e.to_string()))?;
+            .await;
+        match result {
+            Ok(_) => {}
+            Err(TransitionError::Illegal {
+                from: TransactionState::Reversed,
+                ..
+            }) => {
+                tracing::info!(
+                    "Transaction already reversed: {}/{}/{}",
+                    tr_dt,
+                    tr_tm,
+                    tr_uniq_no
+                );
+                return Ok(());
+            }
+            Err(e) => return Err(ReversalError::DatabaseError(e.to_string())),
+        }
 
         tracing::info!(
             "Transaction marked as reversed: {}/{}/{}",
@@ -214,6 +311,12 @@ pub enum ReversalError {
 
     #[error("Invalid transaction state")]
     InvalidState,
+
+    #[error("Invalid original data elements: {0}")]
+    InvalidOriginalData(String),
+
+    #[error("Serialization error: {0}")]
+    Serialization(String),
 }
 
 #[cfg(test)]
@@ -0,0 +1,599 @@
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::{error, info};
+
+use crate::app::service::response_handler::ResponseCode;
+use crate::app::service::tlv_parser::ParsedEmvData;
+use crate::app::service::transaction_profile::TransactionType;
+use crate::models::amount::Amount;
+use crate::models::iso8583_message::Iso8583Message;
+use crate::models::risk_rule::{self, RiskRule, RuleCondition, RuleOutcome, RuleScope};
+use crate::models::transaction::TransactionState;
+use crate::repository::risk_repository::RiskRepository;
+
+/// Default rules shipped with the application
+const DEFAULT_RISK_RULES_JSON: &str = include_str!("../../../config/risk_rules.json");
+
+/// Window kept for host declines when no rule counts them
+const DEFAULT_DECLINE_WINDOW_SECS: u64 = 3600;
+
+/// Risk engine errors
+#[derive(Debug, Error)]
+pub enum RiskError {
+    #[error("Failed to read risk rules {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid risk rules: {0}")]
+    Parse(#[from] serde_json::Error),
+
+    #[error("Invalid risk rule {rule_id}: {reason}")]
+    InvalidRule { rule_id: String, reason: String },
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Deserialize)]
+struct RiskRulesConfig {
+    rules: Vec<RiskRule>,
+}
+
+/// Check rules that can only fail at evaluation time
+fn validate_rules(rules: Vec<RiskRule>) -> Result<Vec<RiskRule>, RiskError> {
+    let mut ids = HashSet::new();
+    for rule in &rules {
+        let invalid = |reason: &str| RiskError::InvalidRule {
+            rule_id: rule.id.clone(),
+            reason: reason.to_string(),
+        };
+        if !ids.insert(rule.id.as_str()) {
+            return Err(invalid("duplicate rule ID"));
+        }
+        if let RuleOutcome::Decline { response_code } = &rule.outcome
+            && ResponseCode::from_str(response_code)
+                .is_none_or(|code| code.to_transaction_state() == TransactionState::Approved)
+        {
+            return Err(invalid("unknown or approving response code"));
+        }
+        if let RuleCondition::Velocity {
+            max_count: None,
+            max_amount: None,
+            ..
+        } = rule.condition
+        {
+            return Err(invalid("velocity rule without maxCount or maxAmount"));
+        }
+    }
+    Ok(rules)
+}
+
+/// Where the risk rules are loaded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RiskRuleSource {
+    /// JSON file, or the bundled rules when no path is configured
+    File(Option<PathBuf>),
+    /// `risk_rule` table
+    Database,
+}
+
+/// Hot-reloadable risk rules
+/// Loaded from `RISK_RULES_PATH` (or the bundled JSON), or from the database when
+/// `RISK_RULES_SOURCE=db`; evaluation keeps the old rules until a reload succeeds
+pub struct RiskRuleRegistry {
+    source: RiskRuleSource,
+    rules: RwLock<Arc<Vec<RiskRule>>>,
+}
+
+impl RiskRuleRegistry {
+    /// Build the registry from environment configuration
+    /// Starts with the bundled rules; the database source is loaded at service start
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let source = match env::var("RISK_RULES_SOURCE").as_deref() {
+            Ok("db") | Ok("DB") => RiskRuleSource::Database,
+            _ => RiskRuleSource::File(env::var("RISK_RULES_PATH").ok().map(PathBuf::from)),
+        };
+        let registry = Self {
+            source,
+            rules: RwLock::new(Arc::new(
+                Self::parse(DEFAULT_RISK_RULES_JSON).expect("Bundled risk rules must be valid"),
+            )),
+        };
+        if let RiskRuleSource::File(Some(_)) = registry.source
+            && let Err(e) = registry.reload_file()
+        {
+            error!("Failed to load risk rules, using bundled rules: {}", e);
+        }
+        registry
+    }
+
+    fn parse(json: &str) -> Result<Vec<RiskRule>, RiskError> {
+        let config: RiskRulesConfig = serde_json::from_str(json)?;
+        validate_rules(config.rules)
+    }
+
+    /// Reload from the configured source; returns the number of rules
+    pub async fn reload(&self, repo: &RiskRepository) -> Result<usize, RiskError> {
+        match self.source {
+            RiskRuleSource::Database => {
+                let mut rules = Vec::new();
+                for row in repo.find_active_rules().await? {
+                    let mut definition: serde_json::Value = serde_json::from_str(&row.definition)?;
+                    definition["id"] = serde_json::Value::String(row.rule_id);
+                    rules.push(serde_json::from_value(definition)?);
+                }
+                Ok(self.replace(validate_rules(rules)?))
+            }
+            RiskRuleSource::File(_) => self.reload_file(),
+        }
+    }
+
+    fn reload_file(&self) -> Result<usize, RiskError> {
+        let rules = match &self.source {
+            RiskRuleSource::File(Some(path)) => {
+                let json = fs::read_to_string(path).map_err(|source| RiskError::Io {
+                    path: path.display().to_string(),
+                    source,
+                })?;
+                Self::parse(&json)?
+            }
+            _ => Self::parse(DEFAULT_RISK_RULES_JSON)?,
+        };
+        Ok(self.replace(rules))
+    }
+
+    fn replace(&self, rules: Vec<RiskRule>) -> usize {
+        let len = rules.len();
+        info!("Loaded {} risk rules", len);
+        *self.rules.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(rules);
+        len
+    }
+
+    /// Current rules, in evaluation order
+    pub fn rules(&self) -> Arc<Vec<RiskRule>> {
+        self.rules.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Global risk rules
+pub static RISK_RULES: Lazy<RiskRuleRegistry> = Lazy::new(RiskRuleRegistry::from_env);
+
+/// Count and total amount (minor units) of counter events within a window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CounterTotals {
+    pub count: u64,
+    pub amount: i64,
+}
+
+/// Velocity counters
+#[async_trait]
+pub trait CounterStore: Send + Sync {
+    /// Record an event and return the totals of the window, including it
+    async fn add(
+        &self,
+        key: &str,
+        amount: i64,
+        window: Duration,
+    ) -> Result<CounterTotals, RiskError>;
+
+    /// Totals of the window
+    async fn totals(&self, key: &str, window: Duration) -> Result<CounterTotals, RiskError>;
+}
+
+/// Counters of this instance only
+#[derive(Default)]
+pub struct InMemoryCounterStore {
+    events: Mutex<HashMap<String, VecDeque<(Instant, i64)>>>,
+}
+
+impl InMemoryCounterStore {
+    fn window_totals(events: &VecDeque<(Instant, i64)>, window: Duration) -> CounterTotals {
+        let now = Instant::now();
+        events
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) <= window)
+            .fold(CounterTotals::default(), |totals, (_, amount)| {
+                CounterTotals {
+                    count: totals.count + 1,
+                    amount: totals.amount + amount,
+                }
+            })
+    }
+}
+
+#[async_trait]
+impl CounterStore for InMemoryCounterStore {
+    async fn add(
+        &self,
+        key: &str,
+        amount: i64,
+        window: Duration,
+    ) -> Result<CounterTotals, RiskError> {
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        let key_events = events.entry(key.to_string()).or_default();
+        let now = Instant::now();
+        while key_events
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > window)
+        {
+            key_events.pop_front();
+        }
+        key_events.push_back((now, amount));
+        Ok(Self::window_totals(key_events, window))
+    }
+
+    async fn totals(&self, key: &str, window: Duration) -> Result<CounterTotals, RiskError> {
+        let events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(events
+            .get(key)
+            .map(|key_events| Self::window_totals(key_events, window))
+            .unwrap_or_default())
+    }
+}
+
+/// Counters shared by every instance
+#[async_trait]
+impl CounterStore for RiskRepository {
+    async fn add(
+        &self,
+        key: &str,
+        amount: i64,
+        window: Duration,
+    ) -> Result<CounterTotals, RiskError> {
+        let (count, amount) = self
+            .add_counter_event(key, amount, window.as_secs() as i64)
+            .await?;
+        Ok(CounterTotals {
+            count: count as u64,
+            amount,
+        })
+    }
+
+    async fn totals(&self, key: &str, window: Duration) -> Result<CounterTotals, RiskError> {
+        let (count, amount) = self.counter_totals(key, window.as_secs() as i64).await?;
+        Ok(CounterTotals {
+            count: count as u64,
+            amount,
+        })
+    }
+}
+
+/// Transaction as seen by the risk rules
+#[derive(Debug, Clone, Copy)]
+pub struct RiskContext<'a> {
+    pub tx_type: TransactionType,
+    pub pan: Option<&'a str>,
+    pub terminal_id: &'a str,
+    pub merchant_id: Option<&'a str>,
+    pub mcc: Option<&'a str>,
+    pub amount: &'a Amount,
+}
+
+/// Combined outcome of the matching rules
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RiskDecision {
+    /// First declining rule and its response code
+    pub decline: Option<(String, ResponseCode)>,
+    /// Rules that flagged the transaction for review
+    pub flags: Vec<String>,
+    /// Rules that require the cardholder to be verified by PIN
+    pub pin_required: Vec<String>,
+}
+
+impl RiskDecision {
+    /// Flags as stored with the transaction, None when there are none
+    pub fn flags_str(&self) -> Option<String> {
+        (!self.flags.is_empty()).then(|| self.flags.join(","))
+    }
+}
+
+/// Pre-authorization risk checks
+/// Evaluates the configured rules in order; the first declining rule stops the
+/// evaluation, flags and PIN requirements accumulate
+pub struct RiskEngine {
+    counters: Arc<dyn CounterStore>,
+}
+
+impl RiskEngine {
+    pub fn new(counters: Arc<dyn CounterStore>) -> Self {
+        Self { counters }
+    }
+
+    /// Counters in Postgres, shared by every instance; in memory when
+    /// `RISK_COUNTER_STORE=memory`
+    pub fn from_env(repo: RiskRepository) -> Self {
+        match env::var("RISK_COUNTER_STORE").as_deref() {
+            Ok("memory") | Ok("MEMORY") => Self::new(Arc::new(InMemoryCounterStore::default())),
+            _ => Self::new(Arc::new(repo)),
+        }
+    }
+
+    /// Every applicable rule is evaluated, so each velocity counter sees the
+    /// transaction, before the outcomes apply; the first declining rule wins
+    pub async fn evaluate(
+        &self,
+        rules: &[RiskRule],
+        ctx: &RiskContext<'_>,
+    ) -> Result<RiskDecision, RiskError> {
+        let mut matched = Vec::new();
+        for rule in rules.iter().filter(|rule| rule.applies_to(ctx.tx_type)) {
+            if self.matches(rule, ctx).await? {
+                matched.push(rule);
+            }
+        }
+
+        let mut decision = RiskDecision::default();
+        for rule in matched {
+            match &rule.outcome {
+                RuleOutcome::Decline { response_code } => {
+                    let code =
+                        ResponseCode::from_str(response_code).unwrap_or(ResponseCode::DoNotHonor);
+                    decision.decline = Some((rule.id.clone(), code));
+                    break;
+                }
+                RuleOutcome::Flag => decision.flags.push(rule.id.clone()),
+                RuleOutcome::RequirePin => decision.pin_required.push(rule.id.clone()),
+            }
+        }
+        Ok(decision)
+    }
+
+    /// Count a host decline towards the repeated-decline rules of the card
+    pub async fn record_decline(&self, rules: &[RiskRule], pan: &str) -> Result<(), RiskError> {
+        let window_secs = rules
+            .iter()
+            .filter_map(|rule| match rule.condition {
+                RuleCondition::RepeatedDeclines { window_secs, .. } => Some(window_secs),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(DEFAULT_DECLINE_WINDOW_SECS);
+        self.counters
+            .add(&decline_key(pan), 0, Duration::from_secs(window_secs))
+            .await?;
+        Ok(())
+    }
+
+    async fn matches(&self, rule: &RiskRule, ctx: &RiskContext<'_>) -> Result<bool, RiskError> {
+        let currency = ctx.amount.currency();
+        let major = |amount: &str| Amount::from_major_str(amount, currency).ok();
+
+        Ok(match &rule.condition {
+            RuleCondition::MaxAmount {
+                scope,
+                keys,
+                amount,
+            } => {
+                let in_scope = keys.is_empty()
+                    || subject(*scope, ctx).is_some_and(|subject| keys.contains(&subject));
+                in_scope && major(amount).is_some_and(|limit| ctx.amount.minor() > limit.minor())
+            }
+            RuleCondition::Velocity {
+                scope,
+                window_secs,
+                max_count,
+                max_amount,
+            } => {
+                let Some(subject) = subject(*scope, ctx) else {
+                    return Ok(false);
+                };
+                // Amounts only add up within one currency
+                let key = match max_amount {
+                    Some(_) => format!("{}:{}:{}", rule.id, subject, currency.alpha),
+                    None => format!("{}:{}", rule.id, subject),
+                };
+                let totals = self
+                    .counters
+                    .add(&key, ctx.amount.minor(), Duration::from_secs(*window_secs))
+                    .await?;
+                max_count.is_some_and(|max| totals.count > max)
+                    || max_amount
+                        .as_deref()
+                        .and_then(major)
+                        .is_some_and(|max| totals.amount > max.minor())
+            }
+            RuleCondition::NegativeList { pan_hashes } => ctx.pan.is_some_and(|pan| {
+                let hash = risk_rule::pan_hash(pan);
+                pan_hashes.iter().any(|h| h.eq_ignore_ascii_case(&hash))
+            }),
+            RuleCondition::BlockedMcc { mccs } => {
+                ctx.mcc.is_some_and(|mcc| mccs.iter().any(|m| m == mcc))
+            }
+            RuleCondition::RepeatedDeclines {
+                max_declines,
+                window_secs,
+            } => match ctx.pan {
+                Some(pan) => {
+                    let totals = self
+                        .counters
+                        .totals(&decline_key(pan), Duration::from_secs(*window_secs))
+                        .await?;
+                    totals.count >= *max_declines
+                }
+                None => false,
+            },
+        })
+    }
+}
+
+/// Key a rule counts by: PAN hash, terminal ID or merchant ID
+fn subject(scope: RuleScope, ctx: &RiskContext<'_>) -> Option<String> {
+    match scope {
+        RuleScope::Pan => ctx.pan.map(risk_rule::pan_hash),
+        RuleScope::Terminal => Some(ctx.terminal_id.to_string()),
+        RuleScope::Merchant => ctx.merchant_id.map(|mid| mid.trim().to_string()),
+    }
+}
+
+fn decline_key(pan: &str) -> String {
+    format!("declines:{}", risk_rule::pan_hash(pan))
+}
+
+/// Was the cardholder verified by PIN: online PIN block (DE52), or offline PIN
+/// reported successful in the CVM results (9F34)
+pub fn pin_verified(msg: &Iso8583Message) -> bool {
+    if msg.has_field(52) {
+        return true;
+    }
+    msg.get_field(55)
+        .and_then(|de55| ParsedEmvData::from_de55(de55).ok())
+        .and_then(|emv_data| emv_data.elements.get("9F34").map(|e| e.value.clone()))
+        .is_some_and(|cvm| {
+            cvm.len() == 3 && matches!(cvm[0] & 0x3F, 0x01 | 0x03 | 0x04 | 0x05) && cvm[2] == 0x02
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Currency;
+
+    const PAN: &str = "4111111111111111";
+
+    fn rules(json: &str) -> Vec<RiskRule> {
+        RiskRuleRegistry::parse(json).unwrap()
+    }
+
+    fn ctx<'a>(amount: &'a Amount, mcc: Option<&'a str>) -> RiskContext<'a> {
+        RiskContext {
+            tx_type: TransactionType::Purchase,
+            pan: Some(PAN),
+            terminal_id: "TERM0001",
+            merchant_id: Some("MERCHANT01"),
+            mcc,
+            amount,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rules_decline_flag_and_require_pin() {
+        let rules = rules(&format!(
+            r#"{{"rules": [
+                {{"id": "HIGH_VALUE", "type": "MAX_AMOUNT", "scope": "TERMINAL",
+                  "amount": "1000000", "outcome": {{"action": "REQUIRE_PIN"}}}},
+                {{"id": "GAMBLING", "type": "BLOCKED_MCC", "mccs": ["7995"],
+                  "outcome": {{"action": "DECLINE", "responseCode": "57"}}}},
+                {{"id": "HOT_CARD", "type": "NEGATIVE_LIST", "panHashes": ["{}"],
+                  "transactionTypes": ["CASH_WITHDRAWAL"],
+                  "outcome": {{"action": "DECLINE", "responseCode": "43"}}}},
+                {{"id": "PAN_PER_HOUR", "type": "VELOCITY", "scope": "PAN", "windowSecs": 3600,
+                  "maxCount": 1, "outcome": {{"action": "FLAG"}}}}
+            ]}}"#,
+            risk_rule::pan_hash(PAN)
+        ));
+        let engine = RiskEngine::new(Arc::new(InMemoryCounterStore::default()));
+        let amount = Amount::from_minor(2_000_000, Currency::vnd());
+
+        let decision = engine.evaluate(&rules, &ctx(&amount, None)).await.unwrap();
+        assert_eq!(decision.pin_required, vec!["HIGH_VALUE"]);
+        assert_eq!(decision.decline, None);
+        assert_eq!(decision.flags_str(), None);
+
+        let decision = engine
+            .evaluate(&rules, &ctx(&amount, Some("7995")))
+            .await
+            .unwrap();
+        assert_eq!(
+            decision.decline,
+            Some(("GAMBLING".to_string(), ResponseCode::NotPermitted))
+        );
+
+        // Third transaction on the card within the hour; the declined one counted too
+        let decision = engine.evaluate(&rules, &ctx(&amount, None)).await.unwrap();
+        assert_eq!(decision.flags, vec!["PAN_PER_HOUR"]);
+
+        // Hot card rule only applies to withdrawals
+        let withdrawal = RiskContext {
+            tx_type: TransactionType::CashWithdrawal,
+            ..ctx(&amount, None)
+        };
+        let decision = engine.evaluate(&rules, &withdrawal).await.unwrap();
+        assert_eq!(
+            decision.decline.map(|(_, code)| code.as_str().to_string()),
+            Some("43".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_velocity_counts_behind_a_decline() {
+        let rules = rules(
+            r#"{"rules": [
+                {"id": "PAN_BURST", "type": "VELOCITY", "scope": "PAN", "windowSecs": 60,
+                 "maxCount": 1, "outcome": {"action": "DECLINE", "responseCode": "65"}},
+                {"id": "TERMINAL_PER_HOUR", "type": "VELOCITY", "scope": "TERMINAL",
+                 "windowSecs": 3600, "maxCount": 2, "outcome": {"action": "FLAG"}}
+            ]}"#,
+        );
+        let engine = RiskEngine::new(Arc::new(InMemoryCounterStore::default()));
+        let amount = Amount::from_minor(50_000, Currency::vnd());
+
+        let decision = engine.evaluate(&rules, &ctx(&amount, None)).await.unwrap();
+        assert_eq!(decision, RiskDecision::default());
+
+        // Declined by the first rule, still counted by the terminal rule
+        let decision = engine.evaluate(&rules, &ctx(&amount, None)).await.unwrap();
+        assert_eq!(
+            decision.decline.map(|(id, _)| id),
+            Some("PAN_BURST".to_string())
+        );
+
+        let other_card = RiskContext {
+            pan: Some("5555555555554444"),
+            ..ctx(&amount, None)
+        };
+        let decision = engine.evaluate(&rules, &other_card).await.unwrap();
+        assert_eq!(decision.decline, None);
+        assert_eq!(decision.flags, vec!["TERMINAL_PER_HOUR"]);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_declines() {
+        let rules = rules(
+            r#"{"rules": [
+                {"id": "DECLINES", "type": "REPEATED_DECLINES", "maxDeclines": 2, "windowSecs": 600,
+                 "outcome": {"action": "DECLINE", "responseCode": "05"}}
+            ]}"#,
+        );
+        let engine = RiskEngine::new(Arc::new(InMemoryCounterStore::default()));
+        let amount = Amount::from_minor(50_000, Currency::vnd());
+
+        engine.record_decline(&rules, PAN).await.unwrap();
+        let decision = engine.evaluate(&rules, &ctx(&amount, None)).await.unwrap();
+        assert_eq!(decision.decline, None);
+
+        engine.record_decline(&rules, PAN).await.unwrap();
+        let decision = engine.evaluate(&rules, &ctx(&amount, None)).await.unwrap();
+        assert_eq!(
+            decision.decline,
+            Some(("DECLINES".to_string(), ResponseCode::DoNotHonor))
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for rule in [
+            r#"{"id": "A", "type": "BLOCKED_MCC", "mccs": [], "outcome": {"action": "DECLINE", "responseCode": "00"}}"#,
+            r#"{"id": "A", "type": "VELOCITY", "scope": "PAN", "windowSecs": 60, "outcome": {"action": "FLAG"}}"#,
+            r#"{"id": "A", "type": "UNKNOWN", "outcome": {"action": "FLAG"}}"#,
+        ] {
+            assert!(RiskRuleRegistry::parse(&format!(r#"{{"rules": [{}]}}"#, rule)).is_err());
+        }
+        assert!(
+            !RiskRuleRegistry::parse(DEFAULT_RISK_RULES_JSON)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
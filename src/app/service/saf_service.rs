@@ -0,0 +1,415 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{error, info, warn};
+
+use crate::app::config::saf_config::SafConfig;
+use crate::app::service::host_router::HostRouter;
+use crate::app::service::response_handler::ResponseCode;
+use crate::app::service::reversal_service::{ReversalError, ReversalService};
+use crate::app::utils::metrics;
+use crate::models::iso8583_message::Iso8583Message;
+use crate::models::saf_entry::{SafEntry, SafStatus, SafType};
+use crate::models::transaction::{
+    Iso8583Transaction, StateTransition, TransactionEvent, TransactionState,
+};
+use crate::repository::card_transaction_repository::{CardTransactionRepository, TransitionError};
+use crate::repository::saf_repository::SafRepository;
+
+#[derive(Debug, Error)]
+pub enum SafError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("Reversal error: {0}")]
+    Reversal(#[from] ReversalError),
+
+    #[error("State transition error: {0}")]
+    Transition(#[from] TransitionError),
+}
+
+/// Store-and-Forward Service
+/// Delivers queued reversals and advices in order per terminal, repeating with
+/// 0221/0401 and exponential backoff until acknowledged or marked dead
+pub struct SafService {
+    saf_repo: Arc<SafRepository>,
+    transaction_repo: Arc<CardTransactionRepository>,
+    reversal_service: Arc<ReversalService>,
+    host_router: HostRouter,
+    config: SafConfig,
+    draining: AtomicBool,
+}
+
+impl SafService {
+    pub fn new(
+        saf_repo: Arc<SafRepository>,
+        transaction_repo: Arc<CardTransactionRepository>,
+        reversal_service: Arc<ReversalService>,
+        config: SafConfig,
+    ) -> Self {
+        Self {
+            saf_repo,
+            transaction_repo,
+            reversal_service,
+            host_router: HostRouter::from_env(),
+            config,
+            draining: AtomicBool::new(false),
+        }
+    }
+
+    /// Queue a message that belongs to the given transaction record
+    pub async fn enqueue(
+        &self,
+        saf_type: SafType,
+        record: &Iso8583Transaction,
+        msg: &Iso8583Message,
+    ) -> Result<bool, SafError> {
+        let message = serde_json::to_string(msg)?;
+        let queued = self
+            .saf_repo
+            .enqueue(
+                saf_type,
+                &record.tr_dt,
+                &record.tr_tm,
+                record.tr_uniq_no.as_deref().unwrap_or_default(),
+                record.trm_id.as_deref(),
+                &message,
+            )
+            .await?;
+
+        if queued {
+            info!(
+                "SAF {} queued for {:?} (MTI {})",
+                saf_type.as_str(),
+                record.tr_uniq_no,
+                msg.mti
+            );
+            metrics::increment("saf.queued", 1);
+        }
+        Ok(queued)
+    }
+
+    /// Deliver every due entry, terminal by terminal in queue order
+    /// Only one drain runs at a time; returns the number of delivered entries
+    pub async fn drain(&self) -> Result<usize, SafError> {
+        if self.draining.swap(true, Ordering::SeqCst) {
+            return Ok(0);
+        }
+        let result = self.drain_pending().await;
+        self.draining.store(false, Ordering::SeqCst);
+
+        if let Ok(delivered) = result
+            && delivered > 0
+        {
+            info!("SAF drain delivered {} message(s)", delivered);
+        }
+        result
+    }
+
+    async fn drain_pending(&self) -> Result<usize, SafError> {
+        let mut delivered = 0;
+
+        loop {
+            let heads = self.saf_repo.find_due_heads().await?;
+            if heads.is_empty() {
+                break;
+            }
+
+            let mut progressed = false;
+            for entry in heads {
+                match self.deliver(&entry).await {
+                    Ok(response_code) => {
+                        if response_code.as_deref() != Some("00") {
+                            info!(
+                                "SAF {} for {} delivered, host answered {:?}",
+                                entry.saf_type, entry.tr_uniq_no, response_code
+                            );
+                        }
+                        self.saf_repo
+                            .mark_delivered(entry.saf_id, response_code.as_deref())
+                            .await?;
+                        // The host has the message; a failed side effect must not
+                        // hold up the rest of the queue
+                        if let Err(e) = self.on_delivered(&entry, response_code.as_deref()).await {
+                            error!(
+                                "SAF {} for {} delivered but not applied: {}",
+                                entry.saf_type, entry.tr_uniq_no, e
+                            );
+                            metrics::increment("saf.apply_failed", 1);
+                            self.saf_repo
+                                .update_status(
+                                    entry.saf_id,
+                                    SafStatus::Delivered,
+                                    Some(&e.to_string()),
+                                )
+                                .await?;
+                        }
+                        metrics::increment("saf.delivered", 1);
+                        delivered += 1;
+                        progressed = true;
+                    }
+                    Err(e) => self.on_failed(&entry, &e).await?,
+                }
+            }
+
+            // Failed heads were rescheduled; stop until the next trigger
+            if !progressed {
+                break;
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    /// Send one attempt and wait for the matching reply (0230/0410/...)
+    /// Returns the DE39 of the reply; only a timeout or a reply to another message
+    /// is a failed attempt
+    async fn deliver(&self, entry: &SafEntry) -> Result<Option<String>, String> {
+        let msg = entry.next_message().map_err(|e| e.to_string())?;
+        let expected_mti = msg
+            .get_response_mti()
+            .ok_or_else(|| format!("no response MTI for {}", msg.mti))?;
+
+        info!(
+            "SAF sending {} {} for {} (attempt {})",
+            entry.saf_type,
+            msg.mti,
+            entry.tr_uniq_no,
+            entry.attempts + 1
+        );
+
+        let timeout = Duration::from_millis(self.config.response_timeout_ms);
+        // Advices and reversals go to the host that authorised the card
+        let host = self.host_router.route(&msg).map_err(|e| e.to_string())?;
+        let host_call = host.exchange(&msg);
+        let response = tokio::time::timeout(timeout, host_call)
+            .await
+            .map_err(|_| "host timeout".to_string())?;
+
+        if response.mti != expected_mti {
+            return Err(format!(
+                "expected {} but received {}",
+                expected_mti, response.mti
+            ));
+        }
+        check_acknowledgement(&msg, &response)?;
+        Ok(response.get_field(39).cloned())
+    }
+
+    /// Side effects once the host acknowledged the entry
+    /// Stored reversals and voids are approved now and only then reverse or void
+    /// their original; a void the host declined ends declined and its sale stays open
+    async fn on_delivered(
+        &self,
+        entry: &SafEntry,
+        response_code: Option<&str>,
+    ) -> Result<(), SafError> {
+        match entry.saf_type() {
+            // Reversals are keyed by the transaction they reverse
+            Some(SafType::Reversal) => {
+                self.reversal_service
+                    .mark_as_reversed(&entry.tr_dt, &entry.tr_tm, &entry.tr_uniq_no)
+                    .await?;
+                let follow_ups = self
+                    .transaction_repo
+                    .find_follow_ups(&entry.tr_dt, &entry.tr_tm, &entry.tr_uniq_no)
+                    .await?;
+                for reversal in follow_ups.iter().filter(|tx| {
+                    tx.state() == Some(TransactionState::Stored)
+                        && tx
+                            .msg_typ
+                            .as_deref()
+                            .is_some_and(|mti| mti.starts_with("04"))
+                }) {
+                    self.approve_stored(reversal).await?;
+                }
+            }
+            Some(SafType::VoidAdvice) => {
+                let Some(void) = self
+                    .transaction_repo
+                    .find_by_key(&entry.tr_dt, &entry.tr_tm, &entry.tr_uniq_no)
+                    .await?
+                    .filter(|tx| tx.state() == Some(TransactionState::Stored))
+                else {
+                    return Ok(());
+                };
+                // A declined void leaves the sale as it was
+                let approved = response_code
+                    .and_then(ResponseCode::from_str)
+                    .is_some_and(|code| code.to_transaction_state() == TransactionState::Approved);
+                if !approved {
+                    warn!(
+                        "Void {} declined by host ({:?}), sale left open",
+                        entry.tr_uniq_no, response_code
+                    );
+                    return self.decline_stored(&void, response_code).await;
+                }
+                self.approve_stored(&void).await?;
+                if let (Some(tr_dt), Some(tr_tm), Some(tr_uniq_no)) = (
+                    void.orig_tr_dt.as_deref(),
+                    void.orig_tr_tm.as_deref(),
+                    void.orig_tr_uniq_no.as_deref(),
+                ) {
+                    let voided = self
+                        .transaction_repo
+                        .update_state(
+                            tr_dt,
+                            tr_tm,
+                            tr_uniq_no,
+                            &StateTransition::new(
+                                TransactionEvent::Void,
+                                "saf_service",
+                                "void confirmed by host",
+                            ),
+                        )
+                        .await;
+                    match voided {
+                        // A voided refund no longer counts against its sale
+                        Ok(_) => {
+                            if let Some(refund) = self
+                                .transaction_repo
+                                .find_by_key(tr_dt, tr_tm, tr_uniq_no)
+                                .await?
+                                .filter(Iso8583Transaction::is_refund)
+                            {
+                                self.transaction_repo.release_voided_refund(&refund).await?;
+                            }
+                        }
+                        Err(TransitionError::Illegal {
+                            from: TransactionState::Voided,
+                            ..
+                        }) => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Approve a record that was stored for forwarding
+    async fn approve_stored(&self, record: &Iso8583Transaction) -> Result<(), SafError> {
+        self.transaction_repo
+            .update_state(
+                &record.tr_dt,
+                &record.tr_tm,
+                record.tr_uniq_no.as_deref().unwrap_or_default(),
+                &StateTransition::new(
+                    TransactionEvent::Approve,
+                    "saf_service",
+                    "acknowledged by host",
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Stored record whose delivery the host declined
+    async fn decline_stored(
+        &self,
+        record: &Iso8583Transaction,
+        response_code: Option<&str>,
+    ) -> Result<(), SafError> {
+        self.transaction_repo
+            .update_state(
+                &record.tr_dt,
+                &record.tr_tm,
+                record.tr_uniq_no.as_deref().unwrap_or_default(),
+                &StateTransition::new(
+                    TransactionEvent::Decline,
+                    "saf_service",
+                    &format!("declined by host ({})", response_code.unwrap_or("none")),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Reschedule with backoff, or mark dead once max attempts is reached
+    async fn on_failed(&self, entry: &SafEntry, reason: &str) -> Result<(), SafError> {
+        let attempts = entry.attempts + 1;
+        if attempts >= self.config.max_attempts {
+            error!(
+                "SAF {} for {} is dead after {} attempts: {}",
+                entry.saf_type, entry.tr_uniq_no, attempts, reason
+            );
+            metrics::increment("saf.dead", 1);
+            self.saf_repo
+                .update_status(entry.saf_id, SafStatus::Dead, Some(reason))
+                .await?;
+        } else {
+            warn!(
+                "SAF {} for {} failed (attempt {}): {}",
+                entry.saf_type, entry.tr_uniq_no, attempts, reason
+            );
+            let next_attempt = SafEntry::retry_at(
+                entry.attempts,
+                self.config.base_backoff_secs,
+                self.config.max_backoff_secs,
+            );
+            self.saf_repo
+                .record_attempt(entry.saf_id, &next_attempt, reason)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// The response acknowledges `sent` when it echoes DE11 (and DE37 when sent),
+/// whatever its DE39: a reversal answered 25 or an advice declined was delivered
+fn check_acknowledgement(sent: &Iso8583Message, response: &Iso8583Message) -> Result<(), String> {
+    for de in [11, 37] {
+        if let Some(expected) = sent.get_field(de)
+            && response.get_field(de) != Some(expected)
+        {
+            return Err(format!(
+                "DE{} mismatch: sent {} but received {:?}",
+                de,
+                expected,
+                response.get_field(de)
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(mti: &str, fields: &[(u8, &str)]) -> Iso8583Message {
+        let mut msg = Iso8583Message::new(mti);
+        for (de, value) in fields {
+            msg.set_field(*de, value.to_string());
+        }
+        msg
+    }
+
+    #[test]
+    fn test_acknowledgement_requires_matching_keys() {
+        let sent = message("0400", &[(11, "000321"), (37, "123456789012")]);
+
+        let ack = message("0410", &[(11, "000321"), (37, "123456789012"), (39, "00")]);
+        assert!(check_acknowledgement(&sent, &ack).is_ok());
+
+        let other_stan = message("0410", &[(11, "000999"), (37, "123456789012"), (39, "00")]);
+        assert!(check_acknowledgement(&sent, &other_stan).is_err());
+
+        let other_rrn = message("0410", &[(11, "000321"), (37, "999999999999"), (39, "00")]);
+        assert!(check_acknowledgement(&sent, &other_rrn).is_err());
+
+        // The host never saw the original: delivered all the same
+        let not_found = message("0410", &[(11, "000321"), (37, "123456789012"), (39, "25")]);
+        assert!(check_acknowledgement(&sent, &not_found).is_ok());
+
+        // Without DE37 on the request only DE11 has to match
+        let advice = message("0220", &[(11, "000322")]);
+        let advice_ack = message("0230", &[(11, "000322"), (39, "00")]);
+        assert!(check_acknowledgement(&advice, &advice_ack).is_ok());
+    }
+}
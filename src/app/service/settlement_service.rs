@@ -0,0 +1,508 @@
+use chrono::Local;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{error, info, warn};
+
+use crate::app::config::settlement_config::SettlementConfig;
+use crate::app::service::business_calendar::{self, BUSINESS_CALENDAR, BUSINESS_DATE_FORMAT};
+use crate::app::service::host_router::{HostConnector, HostRouter};
+use crate::app::service::response_handler::ResponseCode;
+use crate::app::service::stan_generator::{StanGenerator, TraceError};
+use crate::app::utils::metrics;
+use crate::models::amount::Currency;
+use crate::models::card_request::CardRequest;
+use crate::models::iso8583_message::Iso8583Message;
+use crate::models::settlement_batch::{BatchStatus, BatchTotals, SettlementBatch, TotalsCategory};
+use crate::models::transaction::{
+    Iso8583Transaction, StateTransition, TransactionEvent, TransactionState,
+};
+use crate::repository::card_transaction_repository::CardTransactionRepository;
+use crate::repository::settlement_repository::SettlementRepository;
+
+/// Actor recorded in the transaction state history
+const ACTOR: &str = "settlement_service";
+
+/// DE3 of a settlement request, and of its retry after a batch upload
+const SETTLEMENT_PROCESSING_CODE: &str = "920000";
+const SETTLEMENT_AFTER_UPLOAD_PROCESSING_CODE: &str = "960000";
+
+#[derive(Debug, Error)]
+pub enum SettlementError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("STAN allocation failed: {0}")]
+    Trace(#[from] TraceError),
+}
+
+/// Terminal transaction types that close the batch instead of moving funds
+pub fn is_settlement_request(transaction_type: &str) -> bool {
+    matches!(
+        transaction_type.to_uppercase().as_str(),
+        "SETTLEMENT" | "SETTLE" | "BATCH_CLOSE" | "END_OF_DAY"
+    )
+}
+
+/// Result of settling a terminal's batch
+#[derive(Debug)]
+pub struct SettlementOutcome {
+    pub batch: SettlementBatch,
+    pub totals: BatchTotals,
+    /// DE39 of the last 0510, None when the host did not answer
+    pub response_code: Option<String>,
+    /// Transactions uploaded with 0320
+    pub uploaded: usize,
+    /// Business date the batch settled on (YYYYMMDD)
+    pub business_date: String,
+}
+
+impl SettlementOutcome {
+    pub fn is_settled(&self) -> bool {
+        self.response_code.as_deref() == Some(ResponseCode::Approved.as_str())
+    }
+}
+
+/// Terminal batch settlement
+/// Transactions accumulate in the terminal's open batch until the terminal settles;
+/// the batch totals go to the host in an 0500. An out-of-balance answer (95) is
+/// followed by a batch upload (0320 per transaction) and a new 0500
+pub struct SettlementService {
+    stan_generator: Arc<StanGenerator>,
+    transaction_repo: Arc<CardTransactionRepository>,
+    settlement_repo: Arc<SettlementRepository>,
+    host_router: HostRouter,
+    config: SettlementConfig,
+    /// Settlement currency; transactions in other currencies are not in the totals
+    currency: &'static Currency,
+}
+
+impl SettlementService {
+    pub fn new(
+        stan_generator: Arc<StanGenerator>,
+        transaction_repo: Arc<CardTransactionRepository>,
+        settlement_repo: Arc<SettlementRepository>,
+        config: SettlementConfig,
+    ) -> Self {
+        Self {
+            stan_generator,
+            transaction_repo,
+            settlement_repo,
+            host_router: HostRouter::from_env(),
+            config,
+            currency: std::env::var("DEFAULT_CURRENCY")
+                .ok()
+                .and_then(|code| Currency::from_code(&code))
+                .unwrap_or(Currency::vnd()),
+        }
+    }
+
+    /// Settle the open batch of a terminal
+    /// The batch stays open when the host declines or does not answer, so the
+    /// terminal can settle again. The batch settles on the business date current
+    /// when settlement starts, sent in DE15 of every 0500
+    pub async fn settle(
+        &self,
+        trm_id: &str,
+        merchant_id: Option<&str>,
+    ) -> Result<SettlementOutcome, SettlementError> {
+        let business_date = BUSINESS_CALENDAR.business_date();
+        let batch = self
+            .settlement_repo
+            .open_batch(trm_id, self.currency.numeric)
+            .await?;
+        let assigned = self
+            .settlement_repo
+            .assign_transactions(batch.batch_id, trm_id)
+            .await?;
+        let transactions = self
+            .settlement_repo
+            .find_transactions(batch.batch_id)
+            .await?;
+        let totals = BatchTotals::from_transactions(&transactions, self.currency);
+
+        info!(
+            "Settling batch {} of terminal {}: {} transaction(s) ({} new), net {}",
+            batch.batch_no,
+            trm_id,
+            transactions.len(),
+            assigned,
+            totals.net_amount()
+        );
+
+        // The batch settles with the host its transactions were routed to
+        let groups = batch_host_groups(&transactions);
+        let host = match groups.as_slice() {
+            [group] => self.host_router.connector(group),
+            [] => Ok(self.host_router.default_connector()),
+            _ => {
+                warn!(
+                    "Batch {} of terminal {} spans host groups {:?}, settling with the default host",
+                    batch.batch_no, trm_id, groups
+                );
+                Ok(self.host_router.default_connector())
+            }
+        };
+
+        let mut uploaded = 0;
+        let mut uploads = 0;
+        let response_code = match host {
+            Ok(host) => loop {
+                let processing_code = if uploads == 0 {
+                    SETTLEMENT_PROCESSING_CODE
+                } else {
+                    SETTLEMENT_AFTER_UPLOAD_PROCESSING_CODE
+                };
+                let request = self
+                    .build_settlement_request(
+                        &batch,
+                        &totals,
+                        merchant_id,
+                        processing_code,
+                        &business_date.format("%m%d").to_string(),
+                        host.group(),
+                    )
+                    .await?;
+                let code = self.exchange(&host, &request).await;
+
+                if code.as_deref() == Some(ResponseCode::ReconcileError.as_str())
+                    && uploads < self.config.max_uploads
+                {
+                    warn!(
+                        "Batch {} of terminal {} out of balance, uploading transactions",
+                        batch.batch_no, trm_id
+                    );
+                    metrics::increment("settlement.out_of_balance", 1);
+                    uploads += 1;
+                    match self.upload_batch(&host, &batch, &transactions).await {
+                        Ok(count) => {
+                            uploaded += count;
+                            continue;
+                        }
+                        Err(code) => break code,
+                    }
+                }
+                break code;
+            },
+            Err(e) => {
+                error!(
+                    "Batch {} of terminal {} not routed: {}",
+                    batch.batch_no, trm_id, e
+                );
+                Some(ResponseCode::RoutingError.as_str().to_string())
+            }
+        };
+
+        let outcome = SettlementOutcome {
+            batch,
+            totals,
+            response_code,
+            uploaded,
+            business_date: business_date.format(BUSINESS_DATE_FORMAT).to_string(),
+        };
+        let status = if outcome.is_settled() {
+            BatchStatus::Closed
+        } else {
+            BatchStatus::Open
+        };
+        self.settlement_repo
+            .save_attempt(
+                outcome.batch.batch_id,
+                &totals,
+                status,
+                outcome.response_code.as_deref(),
+                uploaded as i32,
+                &outcome.business_date,
+            )
+            .await?;
+
+        if outcome.is_settled() {
+            let settled = self.mark_settled(&outcome.batch, &transactions).await;
+            info!(
+                "Batch {} of terminal {} closed, {} transaction(s) settled",
+                outcome.batch.batch_no, trm_id, settled
+            );
+            metrics::increment("settlement.closed", 1);
+        } else {
+            error!(
+                "Settlement of batch {} of terminal {} failed: {:?}",
+                outcome.batch.batch_no, trm_id, outcome.response_code
+            );
+            metrics::increment("settlement.failed", 1);
+        }
+
+        Ok(outcome)
+    }
+
+    /// Settle for a terminal request and build the terminal response
+    pub async fn process_request(
+        &self,
+        card_request: &CardRequest,
+    ) -> Result<serde_json::Value, SettlementError> {
+        let outcome = self
+            .settle(&card_request.trm_id, card_request.merchant_id.as_deref())
+            .await?;
+
+        let code = outcome
+            .response_code
+            .as_deref()
+            .unwrap_or(ResponseCode::ResponseTimeout.as_str());
+        let description = ResponseCode::from_str(code)
+            .map(|c| c.description().to_string())
+            .unwrap_or_else(|| format!("Unknown response code: {}", code));
+
+        Ok(serde_json::json!({
+            "status": if outcome.is_settled() { "SETTLED" } else { "SETTLEMENT_FAILED" },
+            "transactionId": card_request.transaction_id,
+            "transactionType": card_request.transaction_type,
+            "terminalId": card_request.trm_id,
+            "batchNo": outcome.batch.batch_no_de60(),
+            "businessDate": outcome.business_date,
+            "responseCode": code,
+            "responseMessage": description,
+            "uploadedTransactions": outcome.uploaded,
+            "totals": outcome.totals.to_json(self.currency),
+            "timestamp": Local::now().to_rfc3339(),
+        }))
+    }
+
+    /// Reconciliation request (0500) with the batch totals
+    /// `settlement_date` is the DE15 (MMDD) of the business date being settled
+    async fn build_settlement_request(
+        &self,
+        batch: &SettlementBatch,
+        totals: &BatchTotals,
+        merchant_id: Option<&str>,
+        processing_code: &str,
+        settlement_date: &str,
+        host_group: &str,
+    ) -> Result<Iso8583Message, TraceError> {
+        let mut msg = Iso8583Message::new("0500");
+        let now = Local::now();
+
+        msg.set_field(3, processing_code.to_string());
+        msg.set_field(7, business_calendar::transmission_date_time());
+        msg.set_field(
+            11,
+            self.stan_generator
+                .next_for(Some(&batch.trm_id), Some(host_group))
+                .await?,
+        );
+        msg.set_field(12, now.format("%H%M%S").to_string());
+        msg.set_field(13, now.format("%m%d").to_string());
+        msg.set_field(15, settlement_date.to_string());
+        msg.set_field(41, batch.trm_id.clone());
+        if let Some(merchant_id) = merchant_id {
+            msg.set_field(42, format!("{:15}", merchant_id));
+        }
+        msg.set_field(49, self.currency.numeric.to_string());
+        msg.set_field(60, batch.batch_no_de60());
+        totals.apply(&mut msg);
+        Ok(msg)
+    }
+
+    /// Upload every approved sale and refund of the batch (0320)
+    /// Stops at the first upload the host does not accept, returning its code
+    async fn upload_batch(
+        &self,
+        host: &HostConnector,
+        batch: &SettlementBatch,
+        transactions: &[Iso8583Transaction],
+    ) -> Result<usize, Option<String>> {
+        let mut uploaded = 0;
+        for tx in transactions.iter().filter(|tx| is_settled_on_close(tx)) {
+            let request = match self.build_upload(batch, tx, host.group()).await {
+                Ok(request) => request,
+                Err(e) => {
+                    error!("Batch upload of {:?} not sent: {}", tx.tr_uniq_no, e);
+                    return Err(None);
+                }
+            };
+            let code = self.exchange(host, &request).await;
+            if code.as_deref() != Some(ResponseCode::Approved.as_str()) {
+                warn!("Batch upload of {:?} rejected: {:?}", tx.tr_uniq_no, code);
+                return Err(code);
+            }
+            uploaded += 1;
+        }
+        metrics::increment("settlement.uploaded", uploaded as u64);
+        Ok(uploaded)
+    }
+
+    /// Batch upload (0320) of one transaction
+    /// DE60 carries the original MTI and STAN, DE62 the batch number
+    async fn build_upload(
+        &self,
+        batch: &SettlementBatch,
+        tx: &Iso8583Transaction,
+        host_group: &str,
+    ) -> Result<Iso8583Message, TraceError> {
+        let mut msg = Iso8583Message::new("0320");
+        for de in [2, 3, 4, 12, 13, 14, 15, 22, 25, 37, 38, 39, 41, 42, 49, 54] {
+            if let Some(value) = tx.get_field(de) {
+                msg.set_field(de, value.clone());
+            }
+        }
+        msg.set_field(7, business_calendar::transmission_date_time());
+        msg.set_field(
+            11,
+            self.stan_generator
+                .next_for(Some(&batch.trm_id), Some(host_group))
+                .await?,
+        );
+        msg.set_field(
+            60,
+            format!(
+                "{}{}",
+                tx.msg_typ.as_deref().unwrap_or("0200"),
+                tx.field_011.as_deref().unwrap_or("000000")
+            ),
+        );
+        msg.set_field(62, batch.batch_no_de60());
+        Ok(msg)
+    }
+
+    /// Move the batch's approved sales and refunds to SETTLED
+    async fn mark_settled(
+        &self,
+        batch: &SettlementBatch,
+        transactions: &[Iso8583Transaction],
+    ) -> usize {
+        let reason = format!("batch {} closed", batch.batch_no_de60());
+        let mut settled = 0;
+        for tx in transactions.iter().filter(|tx| is_settled_on_close(tx)) {
+            let tr_uniq_no = tx.tr_uniq_no.as_deref().unwrap_or_default();
+            match self
+                .transaction_repo
+                .update_state(
+                    &tx.tr_dt,
+                    &tx.tr_tm,
+                    tr_uniq_no,
+                    &StateTransition::new(TransactionEvent::Settle, ACTOR, &reason),
+                )
+                .await
+            {
+                Ok(_) => settled += 1,
+                Err(e) => warn!("Transaction {} not settled: {}", tr_uniq_no, e),
+            }
+        }
+        settled
+    }
+
+    /// Send a request and return the host response code
+    /// None when the host does not answer in time or answers with another message
+    async fn exchange(&self, host: &HostConnector, request: &Iso8583Message) -> Option<String> {
+        let expected_mti = request.get_response_mti()?;
+        let timeout = Duration::from_millis(self.config.response_timeout_ms);
+        let host_call = host.exchange(request);
+
+        match tokio::time::timeout(timeout, host_call).await {
+            Ok(response) if response.mti == expected_mti => response.get_field(39).cloned(),
+            Ok(response) => {
+                warn!("Expected {} but received {}", expected_mti, response.mti);
+                None
+            }
+            Err(_) => {
+                warn!("Host timeout on {} after {:?}", request.mti, timeout);
+                None
+            }
+        }
+    }
+}
+
+/// Sales and refunds still approved are settled when their batch closes
+fn is_settled_on_close(tx: &Iso8583Transaction) -> bool {
+    tx.state() == Some(TransactionState::Approved)
+        && matches!(
+            TotalsCategory::of(tx),
+            Some(TotalsCategory::Debit) | Some(TotalsCategory::Credit)
+        )
+}
+
+/// Host groups the transactions of a batch were routed to
+fn batch_host_groups(transactions: &[Iso8583Transaction]) -> Vec<&str> {
+    transactions
+        .iter()
+        .filter_map(|tx| tx.host_group.as_deref())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settlement_request_types() {
+        assert!(is_settlement_request("SETTLEMENT"));
+        assert!(is_settlement_request("batch_close"));
+        assert!(!is_settlement_request("SALE"));
+    }
+
+    #[test]
+    fn test_batch_host_groups() {
+        let tx = |host_group: Option<&str>| {
+            let mut tx = Iso8583Transaction::new("000001", "0200");
+            tx.host_group = host_group.map(str::to_string);
+            tx
+        };
+
+        assert!(batch_host_groups(&[]).is_empty());
+        assert_eq!(
+            batch_host_groups(&[tx(Some("NAPAS")), tx(None), tx(Some("NAPAS"))]),
+            vec!["NAPAS"]
+        );
+        assert_eq!(
+            batch_host_groups(&[tx(Some("NAPAS")), tx(Some("INTERNATIONAL"))]),
+            vec!["INTERNATIONAL", "NAPAS"]
+        );
+    }
+
+    #[test]
+    fn test_only_open_sales_and_refunds_settle() {
+        let tx = |mti: &str, processing_code: &str, state: TransactionState| {
+            let mut tx = Iso8583Transaction::new("000001", mti);
+            tx.field_003 = Some(processing_code.to_string());
+            tx.tr_type = Some(state.as_str().to_string());
+            tx
+        };
+
+        assert!(is_settled_on_close(&tx(
+            "0200",
+            "000000",
+            TransactionState::Approved
+        )));
+        assert!(is_settled_on_close(&tx(
+            "0200",
+            "200000",
+            TransactionState::Approved
+        )));
+        assert!(is_settled_on_close(&tx(
+            "0220",
+            "000000",
+            TransactionState::Approved
+        )));
+        assert!(!is_settled_on_close(&tx(
+            "0200",
+            "000000",
+            TransactionState::Voided
+        )));
+        assert!(!is_settled_on_close(&tx(
+            "0400",
+            "000000",
+            TransactionState::Approved
+        )));
+        assert!(!is_settled_on_close(&tx(
+            "0100",
+            "000000",
+            TransactionState::Approved
+        )));
+        assert!(!is_settled_on_close(&tx(
+            "0220",
+            "020000",
+            TransactionState::Approved
+        )));
+    }
+}
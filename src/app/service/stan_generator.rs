@@ -1,71 +1,349 @@
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
-use chrono::Local;
-use tokio::sync::RwLock;
-
-/// STAN (System Trace Audit Number) Generator
-/// Generates unique sequential numbers from 000001 to 999999
-/// Resets daily at midnight
-#[derive(Debug)]
+use async_trait::async_trait;
+use chrono::{Datelike, Duration as ChronoDuration, Local, NaiveDate};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::app::config::trace_config::{StanScope, TraceConfig, TraceStoreKind};
+use crate::app::service::business_calendar::{BUSINESS_CALENDAR, BUSINESS_DATE_FORMAT};
+use crate::repository::trace_repository::TraceRepository;
+
+/// Space of RRNs; STAN spaces are named by `stan_space`
+const RRN_SPACE: &str = "RRN";
+
+/// Space of STANs when no terminal or host space applies
+const GLOBAL_STAN_SPACE: &str = "STAN";
+
+/// Highest STAN (DE11 is 6 digits, 000000 is not used)
+const MAX_STAN: i64 = 999_999;
+
+/// RRN sequence digits after the YDDD date prefix
+const RRN_MODULUS: i64 = 100_000_000;
+
+/// Low bits of a block cursor holding the offset of the next value in the block
+const OFFSET_BITS: u32 = 24;
+const OFFSET_MASK: u64 = (1 << OFFSET_BITS) - 1;
+
+/// Largest block a space leases; the rest of the offset bits absorb the attempts
+/// made on a used up block while the next one is swapped in
+pub const MAX_BLOCK_SIZE: u32 = 1 << 20;
+
+/// Largest block start a cursor can hold
+const MAX_BLOCK_START: i64 = (1 << (64 - OFFSET_BITS)) - 1;
+
+/// Trace number errors
+#[derive(Debug, Error)]
+pub enum TraceError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Trace number space {0} exhausted")]
+    Exhausted(String),
+}
+
+/// Durable source of trace numbers
+/// Values of a space are handed out in blocks and never handed out twice, so
+/// every instance leasing from the same store allocates unique numbers
+#[async_trait]
+pub trait TraceNumberStore: Send + Sync {
+    /// Lease `size` consecutive values of a space; returns the first one
+    async fn lease(&self, space: &str, size: u32) -> Result<i64, TraceError>;
+}
+
+/// Trace numbers of this instance only, restarting from 1
+#[derive(Default)]
+pub struct InMemoryTraceStore {
+    next: Mutex<HashMap<String, i64>>,
+}
+
+#[async_trait]
+impl TraceNumberStore for InMemoryTraceStore {
+    async fn lease(&self, space: &str, size: u32) -> Result<i64, TraceError> {
+        let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+        let value = next.entry(space.to_string()).or_insert(1);
+        let start = *value;
+        *value += size as i64;
+        Ok(start)
+    }
+}
+
+#[async_trait]
+impl TraceNumberStore for TraceRepository {
+    async fn lease(&self, space: &str, size: u32) -> Result<i64, TraceError> {
+        Ok(self.lease_block(space, size as i64).await?)
+    }
+}
+
+fn pack(start: i64, offset: u64) -> u64 {
+    ((start as u64) << OFFSET_BITS) | offset
+}
+
+fn unpack(cursor: u64) -> (i64, u64) {
+    ((cursor >> OFFSET_BITS) as i64, cursor & OFFSET_MASK)
+}
+
+/// Allocator of one trace number space
+/// Allocation is a single `fetch_add` on a cursor packing the start of the current
+/// block and the offset of its next value. Only when a block is used up do callers
+/// wait, on a mutex, for the next one: usually the block prefetched in the
+/// background once half of the current one was handed out
+struct SpaceAllocator {
+    space: String,
+    store: Arc<dyn TraceNumberStore>,
+    block_size: u32,
+    cursor: AtomicU64,
+    /// Start of the block leased ahead of time
+    prefetched: Mutex<Option<i64>>,
+    prefetching: AtomicBool,
+    refill: tokio::sync::Mutex<()>,
+}
+
+impl SpaceAllocator {
+    fn new(space: &str, store: Arc<dyn TraceNumberStore>, block_size: u32) -> Arc<Self> {
+        Arc::new(Self {
+            space: space.to_string(),
+            store,
+            block_size,
+            // Starts out used up, the first allocation leases a block
+            cursor: AtomicU64::new(pack(0, block_size as u64)),
+            prefetched: Mutex::new(None),
+            prefetching: AtomicBool::new(false),
+            refill: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    async fn next(self: &Arc<Self>) -> Result<i64, TraceError> {
+        let block_size = self.block_size as u64;
+        loop {
+            let (start, offset) = unpack(self.cursor.fetch_add(1, Ordering::AcqRel));
+            if offset < block_size {
+                if offset == block_size / 2 {
+                    self.prefetch();
+                }
+                return Ok(start + offset as i64);
+            }
+            self.refill(start).await?;
+        }
+    }
+
+    /// Lease the next block in the background, unless one is leased or on its way
+    fn prefetch(self: &Arc<Self>) {
+        if self
+            .prefetched
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+            || self.prefetching.swap(true, Ordering::AcqRel)
+        {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            self.prefetching.store(false, Ordering::Release);
+            return;
+        };
+        let allocator = self.clone();
+        runtime.spawn(async move {
+            match allocator
+                .store
+                .lease(&allocator.space, allocator.block_size)
+                .await
+            {
+                Ok(start) => {
+                    *allocator
+                        .prefetched
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner()) = Some(start)
+                }
+                Err(e) => warn!("Prefetch of {} block failed: {}", allocator.space, e),
+            }
+            allocator.prefetching.store(false, Ordering::Release);
+        });
+    }
+
+    /// Swap in the next block once the block starting at `used_up` is used up
+    async fn refill(&self, used_up: i64) -> Result<(), TraceError> {
+        let _guard = self.refill.lock().await;
+
+        // Another caller swapped the block in while this one waited
+        let (start, offset) = unpack(self.cursor.load(Ordering::Acquire));
+        if start != used_up || offset < self.block_size as u64 {
+            return Ok(());
+        }
+
+        let prefetched = self
+            .prefetched
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let start = match prefetched {
+            Some(start) => start,
+            None => self.store.lease(&self.space, self.block_size).await?,
+        };
+        if !(0..=MAX_BLOCK_START).contains(&start) {
+            return Err(TraceError::Exhausted(self.space.clone()));
+        }
+        self.cursor.store(pack(start, 0), Ordering::Release);
+        Ok(())
+    }
+}
+
+/// RRN date prefix (YDDD) of the local day
+/// Packs the end of the day (unix seconds) and the prefix in one atomic, so the
+/// date is only formatted again once the day is over
+#[derive(Default)]
+struct RrnDate {
+    packed: AtomicU64,
+}
+
+impl RrnDate {
+    fn prefix(&self) -> u64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let packed = self.packed.load(Ordering::Acquire);
+        if now < packed >> 16 {
+            return packed & 0xFFFF;
+        }
+
+        let today = Local::now();
+        let prefix = (today.year() % 10) as u64 * 1000 + today.ordinal() as u64;
+        let day_end = (today.date_naive() + ChronoDuration::days(1))
+            .and_hms_opt(0, 0, 0)
+            .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+            .map_or(now + 60, |midnight| midnight.timestamp() as u64);
+        self.packed
+            .store((day_end << 16) | prefix, Ordering::Release);
+        prefix
+    }
+}
+
+/// STAN (System Trace Audit Number) and RRN (Retrieval Reference Number) generator
+/// Numbers come from blocks leased from a durable store, so they survive restarts
+/// and stay unique across instances; values of a block not used before a restart
+/// are skipped. STANs run from 000001 to 999999, wrap, and start again at 000001
+/// on every business date; RRNs are YDDD followed by 8 digits of their own sequence
+/// The RRN space never takes a lock outside of a block swap; STAN spaces are
+/// looked up under a read lock first
 pub struct StanGenerator {
-    current: AtomicU32,
-    last_date: Arc<RwLock<String>>,
+    store: Arc<dyn TraceNumberStore>,
+    block_size: u32,
+    scope: StanScope,
+    rrn: Arc<SpaceAllocator>,
+    rrn_date: RrnDate,
+    /// Business date (YYYYMMDD) of the STAN spaces in use
+    epoch: AtomicU32,
+    spaces: RwLock<HashMap<String, Arc<SpaceAllocator>>>,
 }
 
 impl StanGenerator {
-    /// Create a new STAN generator
+    /// Create a generator over an in-memory store (single instance, tests)
     pub fn new() -> Self {
+        Self::with_store(
+            Arc::new(InMemoryTraceStore::default()),
+            TraceConfig::default(),
+        )
+    }
+
+    pub fn with_store(store: Arc<dyn TraceNumberStore>, config: TraceConfig) -> Self {
+        let block_size = config.block_size.clamp(1, MAX_BLOCK_SIZE);
         Self {
-            current: AtomicU32::new(0),
-            last_date: Arc::new(RwLock::new(Self::get_current_date())),
+            rrn: SpaceAllocator::new(RRN_SPACE, store.clone(), block_size),
+            store,
+            block_size,
+            scope: config.stan_scope,
+            rrn_date: RrnDate::default(),
+            epoch: AtomicU32::new(0),
+            spaces: RwLock::new(HashMap::new()),
         }
     }
 
-    /// Get next STAN number
-    /// Returns a 6-digit string (000001-999999)
-    pub async fn next(&self) -> String {
-        let current_date = Self::get_current_date();
-        
-        // Check if date has changed
-        let mut last_date = self.last_date.write().await;
-        if *last_date != current_date {
-            // Reset counter for new day
-            self.current.store(0, Ordering::SeqCst);
-            *last_date = current_date;
-            tracing::info!("STAN counter reset for new day: {}", last_date);
-        }
-        drop(last_date);
-
-        // Increment and get next value
-        let next_val = self.current.fetch_add(1, Ordering::SeqCst) + 1;
-        
-        // Wrap around if exceeds 999999
-        let stan = if next_val > 999999 {
-            self.current.store(1, Ordering::SeqCst);
-            1
-        } else {
-            next_val
+    /// Generator over the configured store (Postgres unless TRACE_NUMBER_STORE=memory)
+    pub fn from_config(repo: TraceRepository, config: TraceConfig) -> Self {
+        let store: Arc<dyn TraceNumberStore> = match config.store {
+            TraceStoreKind::Postgres => Arc::new(repo),
+            TraceStoreKind::Memory => Arc::new(InMemoryTraceStore::default()),
         };
+        info!(
+            "Trace numbers from {:?} store, STAN scope {:?}, blocks of {}",
+            config.store, config.stan_scope, config.block_size
+        );
+        Self::with_store(store, config)
+    }
 
-        format!("{:06}", stan)
+    /// Next STAN of the global space (network management, messages of no terminal)
+    /// Returns a 6-digit string (000001-999999)
+    pub async fn next(&self) -> Result<String, TraceError> {
+        self.next_on(BUSINESS_CALENDAR.business_date(), None, None)
+            .await
     }
 
-    /// Get current STAN value without incrementing
-    pub fn current(&self) -> u32 {
-        self.current.load(Ordering::SeqCst)
+    /// Next STAN for a message of a terminal sent to a host group
+    /// The configured scope decides whether terminals or hosts get their own space
+    pub async fn next_for(
+        &self,
+        trm_id: Option<&str>,
+        host_group: Option<&str>,
+    ) -> Result<String, TraceError> {
+        self.next_on(BUSINESS_CALENDAR.business_date(), trm_id, host_group)
+            .await
     }
 
-    /// Get current date as YYYYMMDD string
-    fn get_current_date() -> String {
-        Local::now().format("%Y%m%d").to_string()
+    /// Next STAN of a business date; each business date has its own spaces, so
+    /// STANs start again at 000001 on every instance once the date moves on
+    async fn next_on(
+        &self,
+        business_date: NaiveDate,
+        trm_id: Option<&str>,
+        host_group: Option<&str>,
+    ) -> Result<String, TraceError> {
+        let suffix = business_date.format(BUSINESS_DATE_FORMAT).to_string();
+        let epoch: u32 = suffix.parse().unwrap_or_default();
+        if self.epoch.fetch_max(epoch, Ordering::AcqRel) < epoch {
+            // Spaces of earlier business dates are not used again
+            self.spaces
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .retain(|space, _| space.ends_with(&suffix));
+            info!("STANs start again for business date {}", suffix);
+        }
+
+        let space = stan_space(self.scope, trm_id, host_group)
+            .unwrap_or_else(|| GLOBAL_STAN_SPACE.to_string());
+        let allocator = self.space(format!("{}:{}", space, suffix));
+        Ok(stan_from(allocator.next().await?))
     }
 
-    /// Reset counter (for testing or manual reset)
-    pub async fn reset(&self) {
-        self.current.store(0, Ordering::SeqCst);
-        let mut last_date = self.last_date.write().await;
-        *last_date = Self::get_current_date();
-        tracing::info!("STAN counter manually reset");
+    /// Next RRN: YDDD (last digit of the year, day of the year) and 8 digits
+    pub async fn next_rrn(&self) -> Result<String, TraceError> {
+        let value = self.rrn.next().await?;
+        Ok(format!(
+            "{:04}{:08}",
+            self.rrn_date.prefix(),
+            value % RRN_MODULUS
+        ))
+    }
+
+    /// Allocator of a terminal or host space, created on first use
+    fn space(&self, space: String) -> Arc<SpaceAllocator> {
+        if let Some(allocator) = self
+            .spaces
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&space)
+        {
+            return allocator.clone();
+        }
+        self.spaces
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(space)
+            .or_insert_with_key(|space| {
+                SpaceAllocator::new(space, self.store.clone(), self.block_size)
+            })
+            .clone()
     }
 }
 
@@ -75,18 +353,34 @@ impl Default for StanGenerator {
     }
 }
 
+/// STAN space of a message, None for the global space
+fn stan_space(scope: StanScope, trm_id: Option<&str>, host_group: Option<&str>) -> Option<String> {
+    match (scope, trm_id, host_group) {
+        (StanScope::Terminal, Some(trm_id), _) => Some(format!("STAN:TERMINAL:{}", trm_id)),
+        (StanScope::Host, _, Some(group)) => Some(format!("STAN:HOST:{}", group)),
+        _ => None,
+    }
+}
+
+/// STAN of a sequence value: 1..=999999, wrapping
+fn stan_from(value: i64) -> String {
+    format!("{:06}", (value - 1).rem_euclid(MAX_STAN) + 1)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
+    use std::time::{Duration, Instant};
 
     #[tokio::test]
     async fn test_stan_generation() {
         let generator = StanGenerator::new();
-        
-        let stan1 = generator.next().await;
-        let stan2 = generator.next().await;
-        let stan3 = generator.next().await;
-        
+
+        let stan1 = generator.next().await.unwrap();
+        let stan2 = generator.next().await.unwrap();
+        let stan3 = generator.next().await.unwrap();
+
         assert_eq!(stan1, "000001");
         assert_eq!(stan2, "000002");
         assert_eq!(stan3, "000003");
@@ -95,22 +389,185 @@ mod tests {
     #[tokio::test]
     async fn test_stan_format() {
         let generator = StanGenerator::new();
-        
-        let stan = generator.next().await;
+
+        let stan = generator.next().await.unwrap();
         assert_eq!(stan.len(), 6);
-        assert!(stan.chars().all(|c| c.is_digit(10)));
+        assert!(stan.chars().all(|c| c.is_ascii_digit()));
+
+        assert_eq!(stan_from(999_999), "999999");
+        assert_eq!(stan_from(1_000_000), "000001");
     }
 
     #[tokio::test]
-    async fn test_stan_reset() {
-        let generator = StanGenerator::new();
-        
-        let _ = generator.next().await;
-        let _ = generator.next().await;
-        
-        generator.reset().await;
-        
-        let stan = generator.next().await;
-        assert_eq!(stan, "000001");
+    async fn test_stan_reset_on_business_date() {
+        let day = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+        let store = Arc::new(InMemoryTraceStore::default());
+        let first = StanGenerator::with_store(store.clone(), TraceConfig::default());
+        let second = StanGenerator::with_store(store, TraceConfig::default());
+
+        let today = day("2026-10-18");
+        let next_day = day("2026-10-19");
+        assert_eq!(first.next_on(today, None, None).await.unwrap(), "000001");
+        assert_eq!(first.next_on(today, None, None).await.unwrap(), "000002");
+
+        // A new business date starts again at 000001, and instances share it
+        assert_eq!(first.next_on(next_day, None, None).await.unwrap(), "000001");
+        assert_eq!(
+            second.next_on(next_day, None, None).await.unwrap(),
+            "000101"
+        );
+        assert_eq!(first.spaces.read().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_restart_continues_after_leased_block() {
+        let store = Arc::new(InMemoryTraceStore::default());
+        let config = TraceConfig {
+            block_size: 10,
+            ..TraceConfig::default()
+        };
+
+        // Two instances (or one before and after a restart) never share a block
+        let first = StanGenerator::with_store(store.clone(), config.clone());
+        let second = StanGenerator::with_store(store, config);
+        assert_eq!(first.next().await.unwrap(), "000001");
+        assert_eq!(second.next().await.unwrap(), "000011");
+        assert_eq!(first.next().await.unwrap(), "000002");
+
+        let rrn = first.next_rrn().await.unwrap();
+        assert_eq!(rrn.len(), 12);
+        assert!(rrn.ends_with("00000001"));
+        let today = Local::now();
+        assert_eq!(
+            rrn[..4],
+            format!("{}{:03}", today.year() % 10, today.ordinal())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stan_scopes() {
+        let config = TraceConfig {
+            stan_scope: StanScope::Terminal,
+            ..TraceConfig::default()
+        };
+        let generator = StanGenerator::with_store(Arc::new(InMemoryTraceStore::default()), config);
+
+        let stan = |trm_id| generator.next_for(Some(trm_id), Some("NAPAS"));
+        assert_eq!(stan("TERM0001").await.unwrap(), "000001");
+        assert_eq!(stan("TERM0002").await.unwrap(), "000001");
+        assert_eq!(stan("TERM0001").await.unwrap(), "000002");
+        assert_eq!(generator.next().await.unwrap(), "000001");
+    }
+
+    /// Store with a database-like round trip per lease
+    struct SlowStore {
+        inner: InMemoryTraceStore,
+        latency: Duration,
+    }
+
+    #[async_trait]
+    impl TraceNumberStore for SlowStore {
+        async fn lease(&self, space: &str, size: u32) -> Result<i64, TraceError> {
+            tokio::time::sleep(self.latency).await;
+            self.inner.lease(space, size).await
+        }
+    }
+
+    /// Allocate `per_task` values from each of `tasks` concurrent tasks
+    async fn allocate_concurrently(
+        generator: Arc<StanGenerator>,
+        tasks: usize,
+        per_task: usize,
+    ) -> Vec<i64> {
+        let handles: Vec<_> = (0..tasks)
+            .map(|_| {
+                let allocator = generator.space(GLOBAL_STAN_SPACE.to_string());
+                tokio::spawn(async move {
+                    let mut values = Vec::with_capacity(per_task);
+                    for _ in 0..per_task {
+                        values.push(allocator.next().await.unwrap());
+                    }
+                    values
+                })
+            })
+            .collect();
+        let mut values = Vec::with_capacity(tasks * per_task);
+        for handle in handles {
+            values.extend(handle.await.unwrap());
+        }
+        values
+    }
+
+    fn slow_generator(block_size: u32, stan_scope: StanScope) -> Arc<StanGenerator> {
+        let store = SlowStore {
+            inner: InMemoryTraceStore::default(),
+            latency: Duration::from_millis(2),
+        };
+        let config = TraceConfig {
+            block_size,
+            stan_scope,
+            ..TraceConfig::default()
+        };
+        Arc::new(StanGenerator::with_store(Arc::new(store), config))
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_allocation_is_unique() {
+        let values = allocate_concurrently(slow_generator(50, StanScope::Global), 16, 500).await;
+        let unique: HashSet<i64> = values.iter().copied().collect();
+        assert_eq!(unique.len(), 16 * 500);
+    }
+
+    /// Throughput benchmark of `next_for` from concurrent terminals, in the global
+    /// space and with a space per terminal; run with
+    /// `cargo test --release bench_stan_throughput -- --ignored`
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn bench_stan_throughput() {
+        const TERMINALS: usize = 64;
+        const PER_TERMINAL: usize = 10_000;
+
+        for scope in [StanScope::Global, StanScope::Terminal] {
+            let generator = slow_generator(1_000, scope);
+            let started = Instant::now();
+            let handles: Vec<_> = (0..TERMINALS)
+                .map(|terminal| {
+                    let generator = generator.clone();
+                    tokio::spawn(async move {
+                        let trm_id = format!("TERM{:04}", terminal);
+                        let mut stans = Vec::with_capacity(PER_TERMINAL);
+                        for _ in 0..PER_TERMINAL {
+                            let stan = generator
+                                .next_for(Some(&trm_id), Some("NAPAS"))
+                                .await
+                                .unwrap();
+                            stans.push((trm_id.clone(), stan));
+                        }
+                        stans
+                    })
+                })
+                .collect();
+            let mut stans = Vec::with_capacity(TERMINALS * PER_TERMINAL);
+            for handle in handles {
+                stans.extend(handle.await.unwrap());
+            }
+            let tps = stans.len() as f64 / started.elapsed().as_secs_f64();
+
+            // STANs never repeat within their space
+            let unique: HashSet<_> = stans
+                .iter()
+                .map(|(trm_id, stan)| match scope {
+                    StanScope::Terminal => (trm_id.as_str(), stan.as_str()),
+                    _ => ("", stan.as_str()),
+                })
+                .collect();
+            assert_eq!(unique.len(), stans.len());
+            assert!(
+                tps >= 10_000.0,
+                "{:?} scope: {:.0} TPS is below 10k",
+                scope,
+                tps
+            );
+        }
     }
 }
@@ -0,0 +1,306 @@
+use thiserror::Error;
+
+use crate::app::config::stip_config::StipConfig;
+use crate::app::security::soft_hsm::SoftHsm;
+use crate::app::service::response_handler::ResponseCode;
+use crate::app::service::tlv_parser::ParsedEmvData;
+use crate::app::service::transaction_profile::TransactionType;
+use crate::models::amount::Amount;
+use crate::models::iso8583_message::Iso8583Message;
+use crate::models::pos_entry::EntryMode;
+
+/// Cryptogram type bits of the CID (9F27): 10 ARQC
+const CID_TYPE_MASK: u8 = 0xC0;
+const CID_ARQC: u8 = 0x80;
+
+/// Transaction data the ARQC is generated over (CDOL1 order)
+const ARQC_DATA_TAGS: [&str; 11] = [
+    "9F02", "9F03", "9F1A", "95", "5F2A", "9A", "9C", "9F37", "82", "9F36", "9F10",
+];
+
+/// Why a request the host did not answer is not approved in stand-in
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum StandInRefusal {
+    #[error("stand-in processing disabled")]
+    Disabled,
+
+    #[error("{0:?} is not authorised in stand-in")]
+    NotEligible(TransactionType),
+
+    #[error("no stand-in limit for this card and merchant")]
+    NoLimit,
+
+    #[error("amount above stand-in limit of {0}")]
+    OverLimit(String),
+
+    #[error("ARQC missing or not an ARQC")]
+    ArqcMissing,
+
+    #[error("ARQC verification failed")]
+    ArqcFailed,
+}
+
+/// Stand-in processing (STIP)
+/// Authorises purchases and pre-auths locally when the issuer host is unavailable,
+/// within per-BIN and per-merchant limits and with the ARQC verified by the
+/// software HSM; the host is advised afterwards through SAF (0120/0220)
+pub struct StipService {
+    config: StipConfig,
+    hsm: SoftHsm,
+}
+
+impl StipService {
+    pub fn from_config(config: StipConfig) -> Self {
+        let hsm = match config.issuer_master_key.as_deref().map(hex::decode) {
+            Some(Ok(key)) => SoftHsm::with_key(key),
+            _ => SoftHsm::new_mock(),
+        };
+        Self { config, hsm }
+    }
+
+    /// Decide whether a request can be approved in stand-in
+    pub fn check(
+        &self,
+        tx_type: TransactionType,
+        pan: Option<&str>,
+        request_msg: &Iso8583Message,
+        amount: &Amount,
+    ) -> Result<(), StandInRefusal> {
+        if !self.config.enabled {
+            return Err(StandInRefusal::Disabled);
+        }
+        if !matches!(
+            tx_type,
+            TransactionType::Purchase | TransactionType::PreAuth
+        ) {
+            return Err(StandInRefusal::NotEligible(tx_type));
+        }
+
+        let limit = self
+            .config
+            .limit(
+                pan,
+                request_msg.get_field(42).map(String::as_str),
+                amount.currency(),
+            )
+            .ok_or(StandInRefusal::NoLimit)?;
+        if amount.minor() > limit.minor() {
+            return Err(StandInRefusal::OverLimit(limit.to_major_string()));
+        }
+
+        // Chip cards must prove the card is genuine; magstripe has no cryptogram.
+        // The entry mode (DE22) decides, an unknown one counts as chip
+        let chip = request_msg
+            .get_field(22)
+            .and_then(|de22| EntryMode::from_de22(de22))
+            .is_none_or(|mode| mode.is_chip());
+        if self.config.verify_arqc && chip {
+            let emv_data = request_msg
+                .get_field(55)
+                .and_then(|de55| ParsedEmvData::from_de55(de55).ok())
+                .ok_or(StandInRefusal::ArqcMissing)?;
+            self.verify_arqc(&emv_data, pan)?;
+        }
+        Ok(())
+    }
+
+    fn verify_arqc(
+        &self,
+        emv_data: &ParsedEmvData,
+        pan: Option<&str>,
+    ) -> Result<(), StandInRefusal> {
+        let value = |tag: &str| emv_data.elements.get(tag).map(|e| e.value.as_slice());
+
+        let cid = value("9F27").and_then(|cid| cid.first().copied());
+        if cid.is_none_or(|cid| cid & CID_TYPE_MASK != CID_ARQC) {
+            return Err(StandInRefusal::ArqcMissing);
+        }
+        let (Some(arqc), Some(atc), Some(pan)) = (value("9F26"), value("9F36"), pan) else {
+            return Err(StandInRefusal::ArqcMissing);
+        };
+        let psn = emv_data
+            .get_tag_hex("5F34")
+            .unwrap_or_else(|| "00".to_string());
+        let data: Vec<u8> = ARQC_DATA_TAGS
+            .iter()
+            .filter_map(|tag| value(tag))
+            .flatten()
+            .copied()
+            .collect();
+
+        if self.hsm.verify_arqc(pan, &psn, atc, &data, arqc) {
+            Ok(())
+        } else {
+            Err(StandInRefusal::ArqcFailed)
+        }
+    }
+
+    /// Local approval of a request approved in stand-in, with a generated auth code
+    pub fn approve(&self, request_msg: &Iso8583Message) -> Iso8583Message {
+        let mut response =
+            Iso8583Message::new(&request_msg.get_response_mti().unwrap_or("0210".to_string()));
+        for de in [2, 3, 4, 11, 12, 13, 14, 22, 37, 41, 42, 49] {
+            if let Some(value) = request_msg.get_field(de) {
+                response.set_field(de, value.clone());
+            }
+        }
+        response.set_field(38, generate_auth_code());
+        response.set_field(39, ResponseCode::Approved.as_str().to_string());
+        response
+    }
+
+    /// Advice (0120/0220) telling the host about a stand-in approval
+    pub fn advice(request_msg: &Iso8583Message, response: &Iso8583Message) -> Iso8583Message {
+        let mut advice = request_msg.clone();
+        advice.mti = request_msg.advice_mti().unwrap_or("0220".to_string());
+        for de in [38, 39] {
+            if let Some(value) = response.get_field(de) {
+                advice.set_field(de, value.clone());
+            }
+        }
+        advice
+    }
+}
+
+fn generate_auth_code() -> String {
+    let mut rng = rand::thread_rng();
+    format!("{:06}", rand::Rng::gen_range(&mut rng, 100000..999999))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::amount::Currency;
+    use std::collections::HashMap;
+
+    const PAN: &str = "4111111111111111";
+
+    fn stip(verify_arqc: bool) -> StipService {
+        StipService::from_config(StipConfig {
+            enabled: true,
+            default_limit: Some("500000".to_string()),
+            bin_limits: HashMap::from([("411111".to_string(), "2000000".to_string())]),
+            merchant_limits: HashMap::from([("MERCHANT01".to_string(), "1000000".to_string())]),
+            verify_arqc,
+            issuer_master_key: None,
+        })
+    }
+
+    fn request(merchant_id: &str, de55: Option<String>) -> Iso8583Message {
+        let mut msg = Iso8583Message::new("0200");
+        msg.set_field(22, "051".to_string());
+        msg.set_field(42, merchant_id.to_string());
+        if let Some(de55) = de55 {
+            msg.set_field(55, de55);
+        }
+        msg
+    }
+
+    /// DE55 with an ARQC generated by the mock HSM over the given amount
+    fn de55(cid: &str, amount: &str, signed_amount: &str) -> String {
+        let atc = [0x00, 0x12];
+        let arqc = SoftHsm::new_mock().generate_arqc(
+            PAN,
+            "01",
+            &atc,
+            &hex::decode(format!("{}0012", signed_amount)).unwrap(),
+        );
+        format!(
+            "9F2701{}9F2608{}9F0206{}9F360200125F340101",
+            cid, arqc, amount
+        )
+    }
+
+    fn vnd(minor: i64) -> Amount {
+        Amount::from_minor(minor, Currency::vnd())
+    }
+
+    #[test]
+    fn test_stand_in_limits() {
+        let stip = stip(false);
+        let purchase = TransactionType::Purchase;
+
+        // BIN limit 2,000,000 and merchant limit 1,000,000: the lower applies
+        let msg = request("MERCHANT01", None);
+        assert!(
+            stip.check(purchase, Some(PAN), &msg, &vnd(1_000_000))
+                .is_ok()
+        );
+        assert_eq!(
+            stip.check(purchase, Some(PAN), &msg, &vnd(1_500_000)),
+            Err(StandInRefusal::OverLimit("1000000".to_string()))
+        );
+
+        // Default limit for other cards and merchants
+        let msg = request("MERCHANT02", None);
+        assert!(
+            stip.check(purchase, Some(PAN), &msg, &vnd(1_500_000))
+                .is_ok()
+        );
+        assert!(
+            stip.check(purchase, Some("5555555555554444"), &msg, &vnd(1_500_000))
+                .is_err()
+        );
+
+        assert_eq!(
+            stip.check(TransactionType::CashWithdrawal, Some(PAN), &msg, &vnd(1)),
+            Err(StandInRefusal::NotEligible(TransactionType::CashWithdrawal))
+        );
+    }
+
+    #[test]
+    fn test_stand_in_verifies_arqc() {
+        let stip = stip(true);
+        let purchase = TransactionType::Purchase;
+        let amount = vnd(50_000);
+
+        let genuine = request(
+            "MERCHANT01",
+            Some(de55("80", "000000050000", "000000050000")),
+        );
+        assert!(stip.check(purchase, Some(PAN), &genuine, &amount).is_ok());
+
+        // Amount changed after the card generated the cryptogram
+        let altered = request(
+            "MERCHANT01",
+            Some(de55("80", "000000050000", "000000040000")),
+        );
+        assert_eq!(
+            stip.check(purchase, Some(PAN), &altered, &amount),
+            Err(StandInRefusal::ArqcFailed)
+        );
+
+        let aac = request(
+            "MERCHANT01",
+            Some(de55("00", "000000050000", "000000050000")),
+        );
+        assert_eq!(
+            stip.check(purchase, Some(PAN), &aac, &amount),
+            Err(StandInRefusal::ArqcMissing)
+        );
+
+        // A chip read without a usable DE55 is not approved
+        let corrupt = request("MERCHANT01", Some("9F2701".to_string()));
+        assert_eq!(
+            stip.check(purchase, Some(PAN), &corrupt, &amount),
+            Err(StandInRefusal::ArqcMissing)
+        );
+        let missing = request("MERCHANT01", None);
+        assert_eq!(
+            stip.check(purchase, Some(PAN), &missing, &amount),
+            Err(StandInRefusal::ArqcMissing)
+        );
+
+        // Magstripe has no cryptogram to verify
+        let mut swipe = request("MERCHANT01", None);
+        swipe.set_field(22, "901".to_string());
+        assert!(stip.check(purchase, Some(PAN), &swipe, &amount).is_ok());
+
+        // Advice carries the stand-in auth code and response code
+        let response = stip.approve(&genuine);
+        let advice = StipService::advice(&genuine, &response);
+        assert_eq!(advice.mti, "0220");
+        assert_eq!(advice.get_field(39).map(String::as_str), Some("00"));
+        assert_eq!(advice.get_field(38), response.get_field(38));
+    }
+}
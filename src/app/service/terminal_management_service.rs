@@ -0,0 +1,375 @@
+use chrono::Local;
+use rand::RngCore;
+use ring::hmac;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::app::config::terminal_config::TerminalConfig;
+use crate::app::service::response_handler::ResponseCode;
+use crate::app::utils::metrics;
+use crate::models::card_request::CardRequest;
+use crate::models::merchant::MasterStatus;
+use crate::models::terminal_params::{ManagedTerminal, ParamDownload, ParamSet};
+use crate::models::terminal_session::TerminalSession;
+use crate::repository::terminal_repository::TerminalRepository;
+
+/// Terminal management errors
+#[derive(Debug, Error)]
+pub enum TerminalManagementError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Invalid parameter set {param_set_id} version {version}: {reason}")]
+    InvalidParameters {
+        param_set_id: String,
+        version: i32,
+        reason: String,
+    },
+}
+
+/// Terminal management requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminalRequest {
+    /// Challenge (no logonMac) or answer (logonMac)
+    Logon,
+    /// One block of the latest parameters
+    ParamDownload,
+    /// Terminal applied a downloaded version
+    ParamConfirm,
+}
+
+impl TerminalRequest {
+    pub fn from_str(transaction_type: &str) -> Option<Self> {
+        match transaction_type.to_uppercase().as_str() {
+            "LOGON" | "SIGN_ON" => Some(TerminalRequest::Logon),
+            "PARAM_DOWNLOAD" | "PARAMETER_DOWNLOAD" => Some(TerminalRequest::ParamDownload),
+            "PARAM_CONFIRM" | "PARAMETER_CONFIRM" => Some(TerminalRequest::ParamConfirm),
+            _ => None,
+        }
+    }
+}
+
+/// Constant-time check of the logon MAC sent by the terminal: HMAC-SHA256 of the
+/// challenge under the terminal's logon key, hex
+fn verify_logon_mac(logon_key: &[u8], challenge: &str, mac_hex: &str) -> bool {
+    let Ok(mac) = hex::decode(mac_hex) else {
+        return false;
+    };
+    let key = hmac::Key::new(hmac::HMAC_SHA256, logon_key);
+    hmac::verify(&key, challenge.as_bytes(), &mac).is_ok()
+}
+
+/// Terminal logon and parameter download
+/// Every connection logs on with a challenge-response under the terminal's logon
+/// key and is bound to that terminal; parameters are downloaded in blocks and the
+/// downloaded and applied versions are tracked per terminal
+pub struct TerminalManagementService {
+    repo: Arc<TerminalRepository>,
+    config: TerminalConfig,
+}
+
+impl TerminalManagementService {
+    pub fn new(repo: Arc<TerminalRepository>, config: TerminalConfig) -> Self {
+        Self { repo, config }
+    }
+
+    /// Refusal of a request the connection may not send, None when allowed
+    /// Requests must come from the terminal the connection is logged on as
+    pub fn authorize(
+        &self,
+        session: &TerminalSession,
+        card_request: &CardRequest,
+    ) -> Option<serde_json::Value> {
+        if session.authorizes(&card_request.trm_id)
+            || (!self.config.logon_required && !session.is_logged_on())
+        {
+            return None;
+        }
+        match session.trm_id() {
+            Some(bound) => warn!(
+                "Connection logged on as {} sent a request for terminal {}",
+                bound, card_request.trm_id
+            ),
+            None => warn!(
+                "Terminal {} sent {} without logging on",
+                card_request.trm_id, card_request.transaction_type
+            ),
+        }
+        metrics::increment("terminals.unauthorized_requests", 1);
+        Some(response(
+            card_request,
+            "NOT_LOGGED_ON",
+            ResponseCode::SecurityViolation,
+        ))
+    }
+
+    /// Handle a terminal management request
+    pub async fn process_request(
+        &self,
+        request: TerminalRequest,
+        card_request: &CardRequest,
+        session: &mut TerminalSession,
+    ) -> Result<serde_json::Value, TerminalManagementError> {
+        if request != TerminalRequest::Logon
+            && let Some(refusal) = self.authorize(session, card_request)
+        {
+            return Ok(refusal);
+        }
+        match request {
+            TerminalRequest::Logon => self.logon(card_request, session).await,
+            TerminalRequest::ParamDownload => self.param_download(card_request).await,
+            TerminalRequest::ParamConfirm => self.param_confirm(card_request).await,
+        }
+    }
+
+    /// Issue a challenge, or bind the connection when the answer is right
+    async fn logon(
+        &self,
+        card_request: &CardRequest,
+        session: &mut TerminalSession,
+    ) -> Result<serde_json::Value, TerminalManagementError> {
+        let trm_id = &card_request.trm_id;
+        if !session.can_bind(trm_id) {
+            warn!(
+                "Terminal {} tried to log on over a connection bound to {}",
+                trm_id,
+                session.trm_id().unwrap_or_default()
+            );
+            metrics::increment("terminals.logon_failed", 1);
+            return Ok(response(
+                card_request,
+                "LOGON_FAILED",
+                ResponseCode::SecurityViolation,
+            ));
+        }
+
+        // First step: a fresh challenge for this connection only
+        let Some(mac) = &card_request.logon_mac else {
+            let mut nonce = [0u8; 16];
+            rand::thread_rng().fill_bytes(&mut nonce);
+            let challenge = hex::encode_upper(nonce);
+            session.set_challenge(trm_id, challenge.clone());
+
+            let mut json = response(card_request, "CHALLENGE", ResponseCode::Approved);
+            json["challenge"] = serde_json::json!(challenge);
+            return Ok(json);
+        };
+
+        let challenge =
+            session.take_challenge(trm_id, Duration::from_secs(self.config.challenge_ttl_secs));
+        let terminal = self.repo.find_terminal(trm_id).await?;
+        let authenticated = match (&challenge, &terminal) {
+            (Some(challenge), Some(terminal)) => {
+                MasterStatus::from_str(&terminal.status) == Some(MasterStatus::Active)
+                    && terminal
+                        .logon_key
+                        .as_deref()
+                        .and_then(|key| hex::decode(key).ok())
+                        .is_some_and(|key| verify_logon_mac(&key, challenge, mac))
+            }
+            _ => false,
+        };
+        let Some(terminal) = terminal.filter(|_| authenticated) else {
+            warn!(
+                "Logon of terminal {} failed (challenge issued: {})",
+                trm_id,
+                challenge.is_some()
+            );
+            metrics::increment("terminals.logon_failed", 1);
+            return Ok(response(
+                card_request,
+                "LOGON_FAILED",
+                ResponseCode::SecurityViolation,
+            ));
+        };
+
+        session.bind(trm_id);
+        self.repo.record_logon(trm_id).await?;
+        info!("Terminal {} logged on", trm_id);
+        metrics::increment("terminals.logon", 1);
+
+        let latest = self.repo.latest_param_set(&terminal.param_set_id).await?;
+        let mut json = response(card_request, "LOGGED_ON", ResponseCode::Approved);
+        json["paramVersion"] = serde_json::json!(latest.as_ref().map(|p| p.version));
+        json["paramDownloadRequired"] =
+            serde_json::json!(download_required(&terminal, latest.as_ref()));
+        Ok(json)
+    }
+
+    /// Send one block of the latest parameters, or of the version being downloaded
+    async fn param_download(
+        &self,
+        card_request: &CardRequest,
+    ) -> Result<serde_json::Value, TerminalManagementError> {
+        let Some(terminal) = self.repo.find_terminal(&card_request.trm_id).await? else {
+            return Ok(response(
+                card_request,
+                "PARAM_DOWNLOAD_FAILED",
+                ResponseCode::InvalidMerchant,
+            ));
+        };
+        let block = card_request.param_block.unwrap_or(0);
+
+        // Later blocks come from the version the download started with, even if a
+        // newer version was published in the meantime
+        let param_set = match (block, card_request.param_version) {
+            (0, _) => self.repo.latest_param_set(&terminal.param_set_id).await?,
+            (_, Some(version)) => {
+                self.repo
+                    .find_param_set(&terminal.param_set_id, version)
+                    .await?
+            }
+            (_, None) => None,
+        };
+        let Some(param_set) = param_set else {
+            return Ok(response(
+                card_request,
+                "PARAM_DOWNLOAD_FAILED",
+                ResponseCode::UnableToLocate,
+            ));
+        };
+        if block == 0 && card_request.param_version >= Some(param_set.version) {
+            let mut json = response(card_request, "UP_TO_DATE", ResponseCode::Approved);
+            json["paramVersion"] = serde_json::json!(param_set.version);
+            return Ok(json);
+        }
+
+        // Re-serialised so the terminal only ever receives validated parameters
+        let parameters = param_set.parameters().map_err(|reason| {
+            TerminalManagementError::InvalidParameters {
+                param_set_id: param_set.param_set_id.clone(),
+                version: param_set.version,
+                reason,
+            }
+        })?;
+        let content = serde_json::to_string(&parameters).unwrap_or_default();
+        let download = ParamDownload::new(&content, self.config.param_block_size);
+        let Some(data) = download.block(block) else {
+            return Ok(response(
+                card_request,
+                "PARAM_DOWNLOAD_FAILED",
+                ResponseCode::FormatError,
+            ));
+        };
+
+        if block == 0 {
+            self.repo
+                .record_download(&terminal.trm_id, param_set.version)
+                .await?;
+            info!(
+                "Terminal {} downloading parameter set {} version {} ({} blocks)",
+                terminal.trm_id,
+                param_set.param_set_id,
+                param_set.version,
+                download.total_blocks()
+            );
+        }
+
+        let mut json = response(card_request, "PARAM_BLOCK", ResponseCode::Approved);
+        json["paramSetId"] = serde_json::json!(param_set.param_set_id);
+        json["paramVersion"] = serde_json::json!(param_set.version);
+        json["paramBlock"] = serde_json::json!(block);
+        json["totalBlocks"] = serde_json::json!(download.total_blocks());
+        json["checksum"] = serde_json::json!(download.checksum);
+        json["data"] = serde_json::json!(data);
+        Ok(json)
+    }
+
+    /// Record the version the terminal applied
+    async fn param_confirm(
+        &self,
+        card_request: &CardRequest,
+    ) -> Result<serde_json::Value, TerminalManagementError> {
+        let terminal = self.repo.find_terminal(&card_request.trm_id).await?;
+        let param_set = match (&terminal, card_request.param_version) {
+            (Some(terminal), Some(version)) => {
+                self.repo
+                    .find_param_set(&terminal.param_set_id, version)
+                    .await?
+            }
+            _ => None,
+        };
+        let Some(param_set) = param_set else {
+            return Ok(response(
+                card_request,
+                "PARAM_CONFIRM_FAILED",
+                ResponseCode::UnableToLocate,
+            ));
+        };
+
+        self.repo
+            .confirm_param_version(&card_request.trm_id, param_set.version)
+            .await?;
+        info!(
+            "Terminal {} applied parameter set {} version {}",
+            card_request.trm_id, param_set.param_set_id, param_set.version
+        );
+
+        let mut json = response(card_request, "PARAM_CONFIRMED", ResponseCode::Approved);
+        json["paramVersion"] = serde_json::json!(param_set.version);
+        Ok(json)
+    }
+}
+
+/// Terminal has not applied the latest version of its parameter set
+fn download_required(terminal: &ManagedTerminal, latest: Option<&ParamSet>) -> bool {
+    latest.is_some_and(|latest| terminal.param_version < Some(latest.version))
+}
+
+/// Terminal management response
+fn response(card_request: &CardRequest, status: &str, code: ResponseCode) -> serde_json::Value {
+    serde_json::json!({
+        "status": status,
+        "transactionId": card_request.transaction_id,
+        "transactionType": card_request.transaction_type,
+        "terminalId": card_request.trm_id,
+        "responseCode": code.as_str(),
+        "responseMessage": code.description(),
+        "timestamp": Local::now().to_rfc3339(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Logon MAC as the terminal computes it
+    fn logon_mac(logon_key: &[u8], challenge: &str) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, logon_key);
+        hex::encode_upper(hmac::sign(&key, challenge.as_bytes()).as_ref())
+    }
+
+    #[test]
+    fn test_logon_mac() {
+        let key = hex::decode("00112233445566778899AABBCCDDEEFF").unwrap();
+        let mac = logon_mac(&key, "5F1C2A9B0D3E4F60718293A4B5C6D7E8");
+        assert_eq!(mac.len(), 64);
+        assert!(verify_logon_mac(
+            &key,
+            "5F1C2A9B0D3E4F60718293A4B5C6D7E8",
+            &mac
+        ));
+        assert!(verify_logon_mac(
+            &key,
+            "5F1C2A9B0D3E4F60718293A4B5C6D7E8",
+            &mac.to_lowercase()
+        ));
+        assert!(!verify_logon_mac(
+            &key,
+            "00000000000000000000000000000000",
+            &mac
+        ));
+        assert!(!verify_logon_mac(
+            &key,
+            "5F1C2A9B0D3E4F60718293A4B5C6D7E8",
+            "not hex"
+        ));
+        assert_eq!(
+            TerminalRequest::from_str("logon"),
+            Some(TerminalRequest::Logon)
+        );
+        assert_eq!(TerminalRequest::from_str("PURCHASE"), None);
+    }
+}
@@ -1,12 +1,25 @@
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use thiserror::Error;
+use tracing::{error, info, warn};
+
+/// Default profile configuration shipped with the application
+const DEFAULT_PROFILES_JSON: &str = include_str!("../../../config/transaction_profiles.json");
 
 /// Transaction types supported by the system
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 #[allow(dead_code)]
 pub enum TransactionType {
     /// Purchase transaction (MTI 0200)
     Purchase,
+    /// Purchase with cashback (MTI 0200, DE3 09xxxx)
+    PurchaseWithCashback,
     /// Cash withdrawal (MTI 0200)
     CashWithdrawal,
     /// Balance inquiry (MTI 0200)
@@ -15,8 +28,12 @@ pub enum TransactionType {
     Refund,
     /// Pre-authorization (MTI 0100)
     PreAuth,
-    /// Pre-auth completion (MTI 0200)
+    /// Incremental authorization on an open pre-auth hold (MTI 0100)
+    IncrementalAuth,
+    /// Pre-auth completion advice (MTI 0220)
     PreAuthCompletion,
+    /// Tip adjustment advice on an approved sale (MTI 0220, DE3 02xxxx)
+    TipAdjustment,
     /// Void/Reversal (MTI 0400)
     Void,
     /// Reversal (MTI 0400)
@@ -25,41 +42,62 @@ pub enum TransactionType {
     CashAdvance,
     /// QR Payment (MTI 0200)
     QrPayment,
+    /// Upload of a sale approved offline by the card (MTI 0220, EMV TC)
+    OfflineSale,
 }
 
 impl TransactionType {
-    /// Get ISO8583 MTI for this transaction type
-    #[allow(dead_code)]
-    pub fn get_mti(&self) -> &'static str {
+    /// Does this transaction reference an original transaction (DE37/DE38/DE90)?
+    pub fn references_original(&self) -> bool {
+        matches!(
+            self,
+            TransactionType::Void
+                | TransactionType::Reversal
+                | TransactionType::IncrementalAuth
+                | TransactionType::PreAuthCompletion
+                | TransactionType::TipAdjustment
+                | TransactionType::Refund
+        )
+    }
+
+    /// Must the original transaction be located before sending?
+    pub fn requires_original(&self) -> bool {
+        matches!(
+            self,
+            TransactionType::Void
+                | TransactionType::Reversal
+                | TransactionType::IncrementalAuth
+                | TransactionType::PreAuthCompletion
+                | TransactionType::TipAdjustment
+        )
+    }
+
+    /// Terminal-facing status for an approved transaction of this type
+    pub fn approved_status(&self) -> &'static str {
         match self {
-            TransactionType::Purchase => "0200",
-            TransactionType::CashWithdrawal => "0200",
-            TransactionType::BalanceInquiry => "0200",
-            TransactionType::Refund => "0200",
-            TransactionType::PreAuth => "0100",
-            TransactionType::PreAuthCompletion => "0200",
-            TransactionType::Void => "0400",
-            TransactionType::Reversal => "0400",
-            TransactionType::CashAdvance => "0200",
-            TransactionType::QrPayment => "0200",
+            TransactionType::Void => "VOIDED",
+            TransactionType::Reversal => "REVERSED",
+            TransactionType::PreAuth | TransactionType::IncrementalAuth => "AUTHORIZED",
+            TransactionType::PreAuthCompletion => "COMPLETED",
+            TransactionType::TipAdjustment => "ADJUSTED",
+            _ => "APPROVED",
         }
     }
 
-    /// Get Processing Code (DE3) for this transaction type
+    /// Get ISO8583 MTI for this transaction type (default profile)
     #[allow(dead_code)]
-    pub fn get_processing_code(&self) -> &'static str {
-        match self {
-            TransactionType::Purchase => "000000",
-            TransactionType::CashWithdrawal => "010000",
-            TransactionType::BalanceInquiry => "310000",
-            TransactionType::Refund => "200000",
-            TransactionType::PreAuth => "000000",
-            TransactionType::PreAuthCompletion => "000000",
-            TransactionType::Void => "000000",
-            TransactionType::Reversal => "000000",
-            TransactionType::CashAdvance => "010000",
-            TransactionType::QrPayment => "000000",
-        }
+    pub fn get_mti(&self) -> String {
+        get_profile(*self)
+            .map(|p| p.mti)
+            .unwrap_or_else(|| "0200".to_string())
+    }
+
+    /// Get Processing Code (DE3) for this transaction type (default profile)
+    #[allow(dead_code)]
+    pub fn get_processing_code(&self) -> String {
+        get_profile(*self)
+            .map(|p| p.processing_code)
+            .unwrap_or_else(|| "000000".to_string())
     }
 
     /// Get EMV Transaction Type (Tag 9C) value
@@ -67,348 +105,371 @@ impl TransactionType {
     pub fn get_emv_transaction_type(&self) -> u8 {
         match self {
             TransactionType::Purchase => 0x00,
+            TransactionType::PurchaseWithCashback => 0x09,
             TransactionType::CashWithdrawal => 0x01,
             TransactionType::BalanceInquiry => 0x31,
             TransactionType::Refund => 0x20,
             TransactionType::PreAuth => 0x00,
+            TransactionType::IncrementalAuth => 0x00,
             TransactionType::PreAuthCompletion => 0x00,
+            TransactionType::TipAdjustment => 0x00,
             TransactionType::Void => 0x00,
             TransactionType::Reversal => 0x00,
             TransactionType::CashAdvance => 0x01,
             TransactionType::QrPayment => 0x00,
+            TransactionType::OfflineSale => 0x00,
         }
     }
 }
 
-/// Profile defining required and optional fields for a transaction
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
+/// Profile defining MTI, processing code and required/optional fields for a transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TransactionProfile {
     pub transaction_type: TransactionType,
-    pub name: &'static str,
-    pub description: &'static str,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Message Type Indicator sent to the host
+    pub mti: String,
+    /// Processing Code (DE3)
+    pub processing_code: String,
+    /// POS Condition Code (DE25)
+    #[serde(default = "default_pos_condition_code")]
+    pub pos_condition_code: String,
     /// Required ISO8583 Data Elements
     pub required_iso_des: HashSet<u8>,
     /// Optional ISO8583 Data Elements
+    #[serde(default)]
     pub optional_iso_des: HashSet<u8>,
-    /// Required EMV tags (for chip transactions)
-    pub required_emv_tags: HashSet<&'static str>,
-    /// Optional EMV tags
-    pub optional_emv_tags: HashSet<&'static str>,
-    /// Tags that must be present in DE55 for online authorization
-    pub de55_required_tags: HashSet<&'static str>,
+    /// EMV tags that must be present (for chip transactions)
+    #[serde(default)]
+    pub mandatory_emv_tags: HashSet<String>,
+    /// EMV tags allowed in DE55 (empty = no restriction)
+    #[serde(default)]
+    pub allowed_emv_tags: HashSet<String>,
 }
 
-/// Create a HashSet from a list of items
-#[allow(unused_mut)]
-macro_rules! hashset {
-    ($($x:expr),* $(,)?) => {{
-        #[allow(unused_mut)]
-        let mut set = HashSet::new();
-        $(set.insert($x);)*
-        set
-    }};
+fn default_pos_condition_code() -> String {
+    "00".to_string()
 }
 
-/// All transaction profiles
-#[allow(dead_code)]
-pub static TRANSACTION_PROFILES: Lazy<HashMap<TransactionType, TransactionProfile>> =
-    Lazy::new(|| {
-        let mut profiles = HashMap::new();
-
-        // ========================================
-        // PURCHASE TRANSACTION PROFILE
-        // ========================================
-        profiles.insert(
-            TransactionType::Purchase,
-            TransactionProfile {
-                transaction_type: TransactionType::Purchase,
-                name: "Purchase",
-                description: "Standard purchase transaction",
-                required_iso_des: hashset![
-                    2,  // PAN
-                    3,  // Processing Code
-                    4,  // Transaction Amount
-                    11, // STAN
-                    12, // Local Transaction Time
-                    13, // Local Transaction Date
-                    14, // Expiration Date
-                    22, // POS Entry Mode
-                    23, // Card Sequence Number
-                    25, // POS Condition Code
-                    26, // POS PIN Capture Code
-                    35, // Track 2 Data
-                    41, // Terminal ID
-                    42, // Merchant ID
-                    49, // Currency Code
-                    55, // EMV Data (ICC)
-                ],
-                optional_iso_des: hashset![
-                    32, // Acquiring Institution ID
-                    37, // Retrieval Reference Number
-                    38, // Authorization ID Response
-                    39, // Response Code
-                    43, // Card Acceptor Name/Location
-                    52, // PIN Data
-                    54, // Additional Amounts
-                ],
-                required_emv_tags: hashset![
-                    "5A",   // PAN
-                    "5F24", // Expiry Date
-                    "9F26", // Application Cryptogram
-                    "9F27", // CID
-                    "9F10", // IAD
-                    "9F36", // ATC
-                    "9F37", // Unpredictable Number
-                    "95",   // TVR
-                ],
-                optional_emv_tags: hashset![
-                    "5F20", // Cardholder Name
-                    "5F34", // PAN Sequence Number
-                    "9F33", // Terminal Capabilities
-                    "9F34", // CVM Results
-                    "9F35", // Terminal Type
-                ],
-                de55_required_tags: hashset![
-                    "9F26", // Application Cryptogram (ARQC)
-                    "9F27", // CID
-                    "9F10", // IAD
-                    "9F37", // Unpredictable Number
-                    "9F36", // ATC
-                    "95",   // TVR
-                    "9A",   // Transaction Date
-                    "9C",   // Transaction Type
-                    "9F02", // Amount Authorized
-                    "5F2A", // Transaction Currency Code
-                    "82",   // AIP
-                    "9F1A", // Terminal Country Code
-                    "9F34", // CVM Results
-                    "9F33", // Terminal Capabilities
-                    "9F35", // Terminal Type
-                    "4F",   // AID
-                    "84",   // DF Name
-                ],
-            },
-        );
+impl TransactionProfile {
+    /// Validate present DEs and EMV tags against this profile
+    pub fn validate(
+        &self,
+        present_iso_des: &HashSet<u8>,
+        present_emv_tags: &HashSet<&str>,
+    ) -> ValidationResult {
+        let mut missing_iso: Vec<u8> = self
+            .required_iso_des
+            .iter()
+            .filter(|de| !present_iso_des.contains(*de))
+            .copied()
+            .collect();
+        missing_iso.sort_unstable();
+
+        let mut missing_emv: Vec<String> = self
+            .mandatory_emv_tags
+            .iter()
+            .filter(|tag| !present_emv_tags.contains(tag.as_str()))
+            .cloned()
+            .collect();
+        missing_emv.sort_unstable();
+
+        let mut warnings = Vec::new();
+
+        // Check optional but recommended fields
+        let mut optional_des: Vec<&u8> = self.optional_iso_des.iter().collect();
+        optional_des.sort_unstable();
+        for de in optional_des {
+            if !present_iso_des.contains(de) {
+                warnings.push(format!("Optional DE{} is missing", de));
+            }
+        }
 
-        // ========================================
-        // CASH WITHDRAWAL PROFILE
-        // ========================================
-        profiles.insert(
-            TransactionType::CashWithdrawal,
-            TransactionProfile {
-                transaction_type: TransactionType::CashWithdrawal,
-                name: "Cash Withdrawal",
-                description: "ATM cash withdrawal transaction",
-                required_iso_des: hashset![
-                    2,  // PAN
-                    3,  // Processing Code (010000)
-                    4,  // Transaction Amount
-                    11, // STAN
-                    12, // Time
-                    13, // Date
-                    14, // Expiration Date
-                    22, // POS Entry Mode
-                    23, // Card Sequence Number
-                    25, // POS Condition Code
-                    35, // Track 2
-                    41, // Terminal ID
-                    42, // Merchant ID
-                    49, // Currency Code
-                    52, // PIN Data (required for ATM)
-                    55, // EMV Data
-                ],
-                optional_iso_des: hashset![32, 37, 38, 39, 43, 54,],
-                required_emv_tags: hashset![
-                    "5A", "5F24", "9F26", "9F27", "9F10", "9F36", "9F37", "95",
-                ],
-                optional_emv_tags: hashset!["5F20", "5F34", "9F33", "9F34", "9F35",],
-                de55_required_tags: hashset![
-                    "9F26", "9F27", "9F10", "9F37", "9F36", "95", "9A", "9C", "9F02", "5F2A", "82",
-                    "9F1A", "9F34", "9F33", "9F35", "4F", "84",
-                ],
-            },
-        );
+        // Flag EMV tags the profile does not allow
+        if !self.allowed_emv_tags.is_empty() {
+            let mut unexpected: Vec<&&str> = present_emv_tags
+                .iter()
+                .filter(|tag| !self.allowed_emv_tags.contains(**tag))
+                .collect();
+            unexpected.sort_unstable();
+            for tag in unexpected {
+                warnings.push(format!("EMV tag {} is not allowed", tag));
+            }
+        }
 
-        // ========================================
-        // BALANCE INQUIRY PROFILE
-        // ========================================
-        profiles.insert(
-            TransactionType::BalanceInquiry,
-            TransactionProfile {
-                transaction_type: TransactionType::BalanceInquiry,
-                name: "Balance Inquiry",
-                description: "Balance inquiry transaction",
-                required_iso_des: hashset![
-                    2,  // PAN
-                    3,  // Processing Code (310000)
-                    11, // STAN
-                    12, // Time
-                    13, // Date
-                    14, // Expiration
-                    22, // POS Entry Mode
-                    35, // Track 2
-                    41, // Terminal ID
-                    42, // Merchant ID
-                    49, // Currency
-                ],
-                optional_iso_des: hashset![23, 25, 32, 37, 38, 39, 43, 52, 54, 55,],
-                required_emv_tags: hashset!["5A", "5F24",],
-                optional_emv_tags: hashset!["9F26", "9F27", "9F10", "9F36", "95",],
-                de55_required_tags: hashset!["4F", "9A", "9C", "5F2A", "9F1A",],
-            },
-        );
+        ValidationResult {
+            is_valid: missing_iso.is_empty() && missing_emv.is_empty(),
+            missing_iso_des: missing_iso,
+            missing_emv_tags: missing_emv,
+            warnings,
+        }
+    }
 
-        // ========================================
-        // REFUND PROFILE
-        // ========================================
-        profiles.insert(
-            TransactionType::Refund,
-            TransactionProfile {
-                transaction_type: TransactionType::Refund,
-                name: "Refund",
-                description: "Refund/Return transaction",
-                required_iso_des: hashset![
-                    2,  // PAN
-                    3,  // Processing Code (200000)
-                    4,  // Amount
-                    11, // STAN
-                    12, // Time
-                    13, // Date
-                    14, // Expiration
-                    22, // POS Entry Mode
-                    25, // POS Condition Code
-                    35, // Track 2
-                    37, // Original RRN (reference to original transaction)
-                    41, // Terminal ID
-                    42, // Merchant ID
-                    49, // Currency
-                ],
-                optional_iso_des: hashset![23, 32, 38, 39, 43, 55,],
-                required_emv_tags: hashset!["5A", "5F24",],
-                optional_emv_tags: hashset!["9F26", "9F27", "9F10", "9F36", "95",],
-                de55_required_tags: hashset!["4F", "9A", "9C", "9F02", "5F2A", "9F1A",],
-            },
-        );
+    /// Apply host-specific overrides on top of this profile
+    fn with_override(&self, ovr: &ProfileOverride) -> Self {
+        let mut profile = self.clone();
+        if let Some(mti) = &ovr.mti {
+            profile.mti = mti.clone();
+        }
+        if let Some(processing_code) = &ovr.processing_code {
+            profile.processing_code = processing_code.clone();
+        }
+        if let Some(pos_condition_code) = &ovr.pos_condition_code {
+            profile.pos_condition_code = pos_condition_code.clone();
+        }
+        if let Some(des) = &ovr.required_iso_des {
+            profile.required_iso_des = des.clone();
+        }
+        if let Some(des) = &ovr.optional_iso_des {
+            profile.optional_iso_des = des.clone();
+        }
+        if let Some(tags) = &ovr.mandatory_emv_tags {
+            profile.mandatory_emv_tags = tags.clone();
+        }
+        if let Some(tags) = &ovr.allowed_emv_tags {
+            profile.allowed_emv_tags = tags.clone();
+        }
+        profile
+    }
+}
 
-        // ========================================
-        // PRE-AUTHORIZATION PROFILE
-        // ========================================
-        profiles.insert(
-            TransactionType::PreAuth,
-            TransactionProfile {
-                transaction_type: TransactionType::PreAuth,
-                name: "Pre-Authorization",
-                description: "Pre-authorization hold transaction",
-                required_iso_des: hashset![
-                    2,  // PAN
-                    3,  // Processing Code
-                    4,  // Amount
-                    11, // STAN
-                    12, // Time
-                    13, // Date
-                    14, // Expiration
-                    22, // POS Entry Mode
-                    23, // Card Sequence Number
-                    25, // POS Condition Code
-                    35, // Track 2
-                    41, // Terminal ID
-                    42, // Merchant ID
-                    49, // Currency
-                    55, // EMV Data
-                ],
-                optional_iso_des: hashset![32, 37, 38, 39, 43, 52, 54,],
-                required_emv_tags: hashset![
-                    "5A", "5F24", "9F26", "9F27", "9F10", "9F36", "9F37", "95",
-                ],
-                optional_emv_tags: hashset!["5F20", "5F34", "9F33", "9F34", "9F35",],
-                de55_required_tags: hashset![
-                    "9F26", "9F27", "9F10", "9F37", "9F36", "95", "9A", "9C", "9F02", "5F2A", "82",
-                    "9F1A", "9F34", "9F33", "4F", "84",
-                ],
-            },
-        );
+/// Host-specific override; any field present replaces the base profile value
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileOverride {
+    pub mti: Option<String>,
+    pub processing_code: Option<String>,
+    pub pos_condition_code: Option<String>,
+    pub required_iso_des: Option<HashSet<u8>>,
+    pub optional_iso_des: Option<HashSet<u8>>,
+    pub mandatory_emv_tags: Option<HashSet<String>>,
+    pub allowed_emv_tags: Option<HashSet<String>>,
+}
 
-        // ========================================
-        // VOID/REVERSAL PROFILE
-        // ========================================
-        profiles.insert(
-            TransactionType::Void,
-            TransactionProfile {
-                transaction_type: TransactionType::Void,
-                name: "Void",
-                description: "Void/Cancel transaction",
-                required_iso_des: hashset![
-                    2,  // PAN
-                    3,  // Processing Code
-                    4,  // Amount
-                    11, // STAN
-                    12, // Time
-                    13, // Date
-                    22, // POS Entry Mode
-                    25, // POS Condition Code
-                    37, // Original RRN
-                    38, // Original Auth Code
-                    41, // Terminal ID
-                    42, // Merchant ID
-                    49, // Currency
-                ],
-                optional_iso_des: hashset![14, 23, 32, 35, 39, 43, 55,],
-                required_emv_tags: hashset!["5A",],
-                optional_emv_tags: hashset!["5F24", "9F26", "9F27", "9F10", "9F36", "95",],
-                de55_required_tags: hashset!["4F", "9A", "9C", "9F02", "5F2A", "9F1A",],
-            },
+/// Base profile configuration file
+#[derive(Debug, Deserialize)]
+struct ProfileConfig {
+    profiles: Vec<TransactionProfile>,
+}
+
+/// Per-acquirer override file (`<acquirer dir>/<acquirer id>.json`)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AcquirerProfileConfig {
+    acquirer_id: String,
+    #[serde(default)]
+    overrides: HashMap<TransactionType, ProfileOverride>,
+}
+
+/// Profile registry errors
+#[derive(Debug, Error)]
+pub enum ProfileError {
+    #[error("Failed to read profile file {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid profile configuration: {0}")]
+    Parse(#[from] serde_json::Error),
+
+    #[error("Acquirer file declares id {found}, expected {expected}")]
+    AcquirerMismatch { expected: String, found: String },
+
+    #[error("Acquirer profile directory is not configured")]
+    NoAcquirerDir,
+}
+
+/// Data-driven transaction profile registry
+/// Base profiles come from `TRANSACTION_PROFILES_PATH` (or the bundled default),
+/// host-specific overrides from `TRANSACTION_PROFILES_ACQUIRER_DIR`
+pub struct ProfileRegistry {
+    base_path: Option<PathBuf>,
+    acquirer_dir: Option<PathBuf>,
+    profiles: RwLock<HashMap<TransactionType, TransactionProfile>>,
+    acquirer_overrides: RwLock<HashMap<String, HashMap<TransactionType, ProfileOverride>>>,
+}
+
+impl ProfileRegistry {
+    /// Build the registry from environment configuration
+    /// Falls back to the bundled profiles if the configured file cannot be loaded
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let base_path = env::var("TRANSACTION_PROFILES_PATH")
+            .ok()
+            .map(PathBuf::from);
+        let acquirer_dir = env::var("TRANSACTION_PROFILES_ACQUIRER_DIR")
+            .ok()
+            .map(PathBuf::from);
+
+        let registry = Self::with_sources(base_path, acquirer_dir);
+        if let Err(e) = registry.reload() {
+            error!(
+                "Failed to load transaction profiles, using bundled defaults: {}",
+                e
+            );
+        }
+        registry
+    }
+
+    /// Build a registry with the bundled profiles and the given sources (not yet loaded)
+    fn with_sources(base_path: Option<PathBuf>, acquirer_dir: Option<PathBuf>) -> Self {
+        let profiles = Self::parse_profiles(DEFAULT_PROFILES_JSON)
+            .expect("Bundled transaction profiles must be valid");
+
+        Self {
+            base_path,
+            acquirer_dir,
+            profiles: RwLock::new(profiles),
+            acquirer_overrides: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Reload base profiles and every acquirer override file
+    pub fn reload(&self) -> Result<(), ProfileError> {
+        let profiles = match &self.base_path {
+            Some(path) => Self::parse_profiles(&Self::read_file(path)?)?,
+            None => Self::parse_profiles(DEFAULT_PROFILES_JSON)?,
+        };
+
+        let mut overrides = HashMap::new();
+        if let Some(dir) = &self.acquirer_dir {
+            let entries = fs::read_dir(dir).map_err(|source| ProfileError::Io {
+                path: dir.display().to_string(),
+                source,
+            })?;
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+                let cfg: AcquirerProfileConfig = serde_json::from_str(&Self::read_file(&path)?)?;
+                overrides.insert(cfg.acquirer_id, cfg.overrides);
+            }
+        }
+
+        info!(
+            "Loaded {} transaction profiles, {} acquirer override sets",
+            profiles.len(),
+            overrides.len()
         );
 
-        // ========================================
-        // QR PAYMENT PROFILE
-        // ========================================
-        profiles.insert(
-            TransactionType::QrPayment,
-            TransactionProfile {
-                transaction_type: TransactionType::QrPayment,
-                name: "QR Payment",
-                description: "QR code based payment (VietQR, etc.)",
-                required_iso_des: hashset![
-                    3,  // Processing Code
-                    4,  // Amount
-                    11, // STAN
-                    12, // Time
-                    13, // Date
-                    25, // POS Condition Code
-                    41, // Terminal ID
-                    42, // Merchant ID
-                    49, // Currency
-                ],
-                optional_iso_des: hashset![
-                    2,   // PAN (may be present)
-                    32,  // Acquiring Institution
-                    37,  // RRN
-                    38,  // Auth Code
-                    39,  // Response Code
-                    43,  // Merchant Name/Location
-                    102, // Account ID 1
-                    103, // Account ID 2
-                ],
-                required_emv_tags: hashset![], // QR typically doesn't have EMV data
-                optional_emv_tags: hashset![],
-                de55_required_tags: hashset![],
-            },
+        *self.profiles.write().unwrap_or_else(|e| e.into_inner()) = profiles;
+        *self
+            .acquirer_overrides
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = overrides;
+
+        Ok(())
+    }
+
+    /// Reload overrides of a single acquirer without touching the others
+    /// A missing file removes the acquirer's overrides
+    pub fn reload_acquirer(&self, acquirer_id: &str) -> Result<(), ProfileError> {
+        let dir = self
+            .acquirer_dir
+            .as_ref()
+            .ok_or(ProfileError::NoAcquirerDir)?;
+        let path = dir.join(format!("{}.json", acquirer_id));
+
+        let mut overrides = self
+            .acquirer_overrides
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+
+        if !path.exists() {
+            warn!(
+                "No profile overrides for acquirer {}, using base profiles",
+                acquirer_id
+            );
+            overrides.remove(acquirer_id);
+            return Ok(());
+        }
+
+        let cfg: AcquirerProfileConfig = serde_json::from_str(&Self::read_file(&path)?)?;
+        if cfg.acquirer_id != acquirer_id {
+            return Err(ProfileError::AcquirerMismatch {
+                expected: acquirer_id.to_string(),
+                found: cfg.acquirer_id,
+            });
+        }
+
+        info!(
+            "Reloaded {} profile overrides for acquirer {}",
+            cfg.overrides.len(),
+            acquirer_id
         );
+        overrides.insert(cfg.acquirer_id, cfg.overrides);
 
-        profiles
-    });
+        Ok(())
+    }
 
-/// Get transaction profile by type
-#[allow(dead_code)]
-pub fn get_profile(tx_type: TransactionType) -> Option<&'static TransactionProfile> {
-    TRANSACTION_PROFILES.get(&tx_type)
+    /// Get the effective profile for a transaction type, applying acquirer overrides
+    pub fn get(
+        &self,
+        tx_type: TransactionType,
+        acquirer_id: Option<&str>,
+    ) -> Option<TransactionProfile> {
+        let profiles = self.profiles.read().unwrap_or_else(|e| e.into_inner());
+        let base = profiles.get(&tx_type)?;
+
+        let overrides = self
+            .acquirer_overrides
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+        match acquirer_id
+            .and_then(|id| overrides.get(id))
+            .and_then(|o| o.get(&tx_type))
+        {
+            Some(ovr) => Some(base.with_override(ovr)),
+            None => Some(base.clone()),
+        }
+    }
+
+    /// Get all base profiles
+    #[allow(dead_code)]
+    pub fn all(&self) -> HashMap<TransactionType, TransactionProfile> {
+        self.profiles
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn parse_profiles(
+        json: &str,
+    ) -> Result<HashMap<TransactionType, TransactionProfile>, ProfileError> {
+        let cfg: ProfileConfig = serde_json::from_str(json)?;
+        Ok(cfg
+            .profiles
+            .into_iter()
+            .map(|p| (p.transaction_type, p))
+            .collect())
+    }
+
+    fn read_file(path: &Path) -> Result<String, ProfileError> {
+        fs::read_to_string(path).map_err(|source| ProfileError::Io {
+            path: path.display().to_string(),
+            source,
+        })
+    }
+}
+
+/// Global transaction profile registry
+pub static PROFILE_REGISTRY: Lazy<ProfileRegistry> = Lazy::new(ProfileRegistry::from_env);
+
+/// Get transaction profile by type (without acquirer overrides)
+pub fn get_profile(tx_type: TransactionType) -> Option<TransactionProfile> {
+    PROFILE_REGISTRY.get(tx_type, None)
 }
 
 /// Get all available transaction profiles
 #[allow(dead_code)]
-pub fn get_all_profiles() -> &'static HashMap<TransactionType, TransactionProfile> {
-    &TRANSACTION_PROFILES
+pub fn get_all_profiles() -> HashMap<TransactionType, TransactionProfile> {
+    PROFILE_REGISTRY.all()
 }
 
 /// Validate if all required fields are present for a transaction
@@ -418,52 +479,14 @@ pub fn validate_transaction_fields(
     present_iso_des: &HashSet<u8>,
     present_emv_tags: &HashSet<&str>,
 ) -> ValidationResult {
-    let profile = match get_profile(tx_type) {
-        Some(p) => p,
-        None => {
-            return ValidationResult {
-                is_valid: false,
-                missing_iso_des: vec![],
-                missing_emv_tags: vec![],
-                warnings: vec!["Unknown transaction type".to_string()],
-            };
-        }
-    };
-
-    let missing_iso: Vec<u8> = profile
-        .required_iso_des
-        .iter()
-        .filter(|de| !present_iso_des.contains(*de))
-        .copied()
-        .collect();
-
-    let missing_emv: Vec<&str> = profile
-        .required_emv_tags
-        .iter()
-        .filter(|tag| !present_emv_tags.contains(*tag))
-        .copied()
-        .collect();
-
-    let mut warnings = Vec::new();
-
-    // Check optional but recommended fields
-    for de in &profile.optional_iso_des {
-        if !present_iso_des.contains(de) {
-            warnings.push(format!("Optional DE{} is missing", de));
-        }
-    }
-
-    ValidationResult {
-        is_valid: missing_iso.is_empty() && missing_emv.is_empty(),
-        missing_iso_des: missing_iso,
-        missing_emv_tags: missing_emv.iter().map(|s| s.to_string()).collect(),
-        warnings,
+    match get_profile(tx_type) {
+        Some(profile) => profile.validate(present_iso_des, present_emv_tags),
+        None => ValidationResult::unknown_type(),
     }
 }
 
 /// Result of field validation
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct ValidationResult {
     pub is_valid: bool,
     pub missing_iso_des: Vec<u8>,
@@ -471,10 +494,41 @@ pub struct ValidationResult {
     pub warnings: Vec<String>,
 }
 
+impl ValidationResult {
+    /// Stop requiring DEs (and EMV tags) the request cannot carry
+    pub fn waive(&mut self, iso_des: &[u8], emv_tags: bool) {
+        self.missing_iso_des.retain(|de| !iso_des.contains(de));
+        if emv_tags {
+            self.missing_emv_tags.clear();
+        }
+        self.is_valid = self.missing_iso_des.is_empty() && self.missing_emv_tags.is_empty();
+    }
+
+    /// Result for a transaction type without a configured profile
+    pub fn unknown_type() -> Self {
+        Self {
+            is_valid: false,
+            missing_iso_des: vec![],
+            missing_emv_tags: vec![],
+            warnings: vec!["Unknown transaction type".to_string()],
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Create a HashSet from a list of items
+    macro_rules! hashset {
+        ($($x:expr),* $(,)?) => {{
+            #[allow(unused_mut)]
+            let mut set = HashSet::new();
+            $(set.insert($x);)*
+            set
+        }};
+    }
+
     #[test]
     fn test_get_purchase_profile() {
         let profile = get_profile(TransactionType::Purchase).unwrap();
@@ -488,6 +542,7 @@ mod tests {
         assert_eq!(TransactionType::Purchase.get_mti(), "0200");
         assert_eq!(TransactionType::PreAuth.get_mti(), "0100");
         assert_eq!(TransactionType::Void.get_mti(), "0400");
+        assert_eq!(TransactionType::PreAuthCompletion.get_mti(), "0220");
     }
 
     #[test]
@@ -502,6 +557,14 @@ mod tests {
             "310000"
         );
         assert_eq!(TransactionType::Refund.get_processing_code(), "200000");
+        assert_eq!(
+            TransactionType::PurchaseWithCashback.get_processing_code(),
+            "090000"
+        );
+        assert_eq!(
+            TransactionType::TipAdjustment.get_processing_code(),
+            "020000"
+        );
     }
 
     #[test]
@@ -531,13 +594,101 @@ mod tests {
     }
 
     #[test]
-    fn test_de55_required_tags() {
+    fn test_missing_fields_are_sorted() {
+        let present_des: HashSet<u8> = hashset![2, 3, 4, 11];
+        let present_tags: HashSet<&str> = hashset![];
+
+        let result =
+            validate_transaction_fields(TransactionType::Purchase, &present_des, &present_tags);
+
+        assert_eq!(
+            result.missing_iso_des,
+            vec![12, 13, 14, 22, 23, 25, 26, 35, 41, 42, 49, 55]
+        );
+        assert_eq!(
+            result.missing_emv_tags.first().map(String::as_str),
+            Some("5A")
+        );
+    }
+
+    #[test]
+    fn test_mandatory_emv_tags() {
         let profile = get_profile(TransactionType::Purchase).unwrap();
 
         // These tags must be in DE55 for online authorization
-        assert!(profile.de55_required_tags.contains("9F26")); // Cryptogram
-        assert!(profile.de55_required_tags.contains("9F27")); // CID
-        assert!(profile.de55_required_tags.contains("9F10")); // IAD
-        assert!(profile.de55_required_tags.contains("9F36")); // ATC
+        assert!(profile.mandatory_emv_tags.contains("9F26")); // Cryptogram
+        assert!(profile.mandatory_emv_tags.contains("9F27")); // CID
+        assert!(profile.mandatory_emv_tags.contains("9F10")); // IAD
+        assert!(profile.mandatory_emv_tags.contains("9F36")); // ATC
+    }
+
+    #[test]
+    fn test_every_transaction_type_has_profile() {
+        for tx_type in [
+            TransactionType::Purchase,
+            TransactionType::PurchaseWithCashback,
+            TransactionType::CashWithdrawal,
+            TransactionType::BalanceInquiry,
+            TransactionType::Refund,
+            TransactionType::PreAuth,
+            TransactionType::IncrementalAuth,
+            TransactionType::PreAuthCompletion,
+            TransactionType::TipAdjustment,
+            TransactionType::Void,
+            TransactionType::Reversal,
+            TransactionType::CashAdvance,
+            TransactionType::QrPayment,
+            TransactionType::OfflineSale,
+        ] {
+            assert!(
+                get_profile(tx_type).is_some(),
+                "missing profile {:?}",
+                tx_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_acquirer_override_and_reload() {
+        let dir = std::env::temp_dir().join(format!("profiles_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("970436.json"),
+            r#"{"acquirerId":"970436","overrides":{"PURCHASE":{"mti":"0100","requiredIsoDes":[2,3,4]}}}"#,
+        )
+        .unwrap();
+
+        let registry = ProfileRegistry::with_sources(None, Some(dir.clone()));
+        registry.reload().unwrap();
+
+        let base = registry.get(TransactionType::Purchase, None).unwrap();
+        assert_eq!(base.mti, "0200");
+
+        let host = registry
+            .get(TransactionType::Purchase, Some("970436"))
+            .unwrap();
+        assert_eq!(host.mti, "0100");
+        assert_eq!(host.required_iso_des, hashset![2, 3, 4]);
+        assert_eq!(host.processing_code, base.processing_code);
+
+        fs::write(
+            dir.join("970436.json"),
+            r#"{"acquirerId":"970436","overrides":{"PURCHASE":{"processingCode":"003000"}}}"#,
+        )
+        .unwrap();
+        registry.reload_acquirer("970436").unwrap();
+
+        let host = registry
+            .get(TransactionType::Purchase, Some("970436"))
+            .unwrap();
+        assert_eq!(host.mti, "0200");
+        assert_eq!(host.processing_code, "003000");
+
+        fs::remove_dir_all(&dir).unwrap();
+        registry.reload_acquirer("970436").unwrap();
+        let host = registry
+            .get(TransactionType::Purchase, Some("970436"))
+            .unwrap();
+        assert_eq!(host.processing_code, "000000");
     }
 }
@@ -6,6 +6,7 @@ use tokio::{io, time::timeout};
 use tokio::time::Duration;
 use tracing::{info, error, warn};
 use crate::app::handlers::iso8583_msg_handler::handle_message;
+use crate::models::terminal_session::TerminalSession;
 
 #[allow(dead_code)]
 pub enum ConnectionMode {
@@ -70,6 +71,8 @@ pub async fn handle_client_logic(
     mut connection: Box<dyn Connection + Send>,
 ) -> io::Result<()> {
     let mut buffer = [0u8; 4096];
+    // Logon state lives as long as the connection
+    let mut session = TerminalSession::new();
 
     loop {
         match timeout(Duration::from_secs(30), connection.read_data(&mut buffer)).await {
@@ -92,7 +95,7 @@ pub async fn handle_client_logic(
 
                 // --- Bước 2: Logic Processing ---
                 // receive string from client
-                let response_bytes = handle_message(trimmed_data)
+                let response_bytes = handle_message(trimmed_data, &mut session)
                     .await
                     .map_err(|e| {
                         error!("Business logic error: {}", e);
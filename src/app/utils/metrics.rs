@@ -0,0 +1,65 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// In-process metric counters
+/// Counters are keyed by name and exposed via `snapshot` for scraping/logging
+static COUNTERS: Lazy<RwLock<HashMap<String, AtomicU64>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Increment a named counter by `value`
+pub fn increment(name: &str, value: u64) {
+    if let Ok(counters) = COUNTERS.read()
+        && let Some(counter) = counters.get(name)
+    {
+        counter.fetch_add(value, Ordering::Relaxed);
+        return;
+    }
+
+    if let Ok(mut counters) = COUNTERS.write() {
+        counters
+            .entry(name.to_string())
+            .or_insert_with(|| AtomicU64::new(0))
+            .fetch_add(value, Ordering::Relaxed);
+    }
+}
+
+/// Get the current value of a named counter
+#[allow(dead_code)]
+pub fn get(name: &str) -> u64 {
+    COUNTERS
+        .read()
+        .ok()
+        .and_then(|counters| counters.get(name).map(|c| c.load(Ordering::Relaxed)))
+        .unwrap_or(0)
+}
+
+/// Snapshot of all counters (name -> value)
+#[allow(dead_code)]
+pub fn snapshot() -> HashMap<String, u64> {
+    COUNTERS
+        .read()
+        .map(|counters| {
+            counters
+                .iter()
+                .map(|(name, c)| (name.clone(), c.load(Ordering::Relaxed)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_increment_counter() {
+        increment("test.metrics.counter", 1);
+        increment("test.metrics.counter", 2);
+
+        assert_eq!(get("test.metrics.counter"), 3);
+        assert_eq!(get("test.metrics.unknown"), 0);
+        assert_eq!(snapshot().get("test.metrics.counter"), Some(&3));
+    }
+}
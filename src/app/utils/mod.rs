@@ -6,5 +6,6 @@ pub mod kafka_message_sender;
 pub mod kafka_producer;
 pub mod kafka_topic_manager;
 pub mod logging;
+pub mod metrics;
 
 
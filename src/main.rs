@@ -7,10 +7,23 @@ use std::env;
 use std::sync::Arc;
 
 use crate::app::config::kafka_config::KafkaConfig;
+use crate::app::handlers::bin_admin_handler::reload_bins;
+use crate::app::handlers::master_data_admin_handler::clear_master_data_cache;
+use crate::app::handlers::network_admin_handler::{current_business_date, receive_host_message};
 use crate::app::handlers::pay_os_qr_handler::index;
+use crate::app::handlers::profile_admin_handler::{reload_acquirer_profiles, reload_profiles};
+use crate::app::handlers::risk_admin_handler::reload_risk_rules;
+use crate::app::handlers::saf_admin_handler::{list_dead_saf, requeue_saf};
+use crate::app::handlers::terminal_admin_handler::publish_terminal_params;
+use crate::app::security::admin_auth::{self, AdminAuth};
 use crate::app::service::pay_os_service::PayOsConfig;
 use crate::app::utils::kafka_producer::create_producer;
+use crate::repository::bin_repository::BinRepository;
+use crate::repository::risk_repository::RiskRepository;
+use crate::repository::saf_repository::SafRepository;
+use crate::repository::terminal_repository::TerminalRepository;
 use crate::app::{handlers::pay_os_qr_handler::create_qr, service::pay_os_service::PayOsQrService};
+use actix_web::middleware::from_fn;
 use actix_web::{App, HttpServer, web};
 use app::builder::builder::run;
 use app::utils::logging::setup_tracing;
@@ -46,11 +59,36 @@ async fn main() -> std::io::Result<()> {
     // 1. Khởi tạo service with Kafka producer
     let qr_service = PayOsQrService::new(db_pool.clone(), config_arc.clone(), kafka_producer);
     let qr_service_data = web::Data::new(qr_service);
+    let saf_repo_data = web::Data::new(SafRepository::new(db_pool.clone()));
+    let bin_repo_data = web::Data::new(BinRepository::new(db_pool.clone()));
+    let risk_repo_data = web::Data::new(RiskRepository::new(db_pool.clone()));
+    let terminal_repo_data = web::Data::new(TerminalRepository::new(db_pool.clone()));
+    // Admin endpoints require the X-Admin-Token shared secret
+    let admin_auth_data = web::Data::new(AdminAuth::from_env());
 
     let http_server = HttpServer::new(move || {
         App::new()
             .app_data(qr_service_data.clone())
+            .app_data(saf_repo_data.clone())
+            .app_data(bin_repo_data.clone())
+            .app_data(risk_repo_data.clone())
+            .app_data(terminal_repo_data.clone())
+            .app_data(admin_auth_data.clone())
             .service(create_qr)
+            .service(
+                web::scope("/admin")
+                    .wrap(from_fn(admin_auth::require_admin_token))
+                    .service(reload_profiles)
+                    .service(reload_acquirer_profiles)
+                    .service(list_dead_saf)
+                    .service(requeue_saf)
+                    .service(reload_bins)
+                    .service(reload_risk_rules)
+                    .service(clear_master_data_cache)
+                    .service(publish_terminal_params)
+                    .service(receive_host_message)
+                    .service(current_business_date),
+            )
             .route("/", web::get().to(index))
     })
     .bind((host.as_str(), port))?
@@ -0,0 +1,505 @@
+use thiserror::Error;
+
+/// ISO 4217 currency
+#[derive(Debug, PartialEq, Eq)]
+pub struct Currency {
+    /// Alphabetic code (e.g. VND)
+    pub alpha: &'static str,
+    /// Numeric code, as sent in DE49/DE50/DE51 (e.g. 704)
+    pub numeric: &'static str,
+    /// Number of minor unit digits (0 for VND and JPY, 2 for USD, 3 for KWD)
+    pub exponent: u32,
+}
+
+/// Supported ISO 4217 currencies
+#[rustfmt::skip]
+pub static CURRENCIES: &[Currency] = &[
+    Currency { alpha: "VND", numeric: "704", exponent: 0 },
+    Currency { alpha: "USD", numeric: "840", exponent: 2 },
+    Currency { alpha: "EUR", numeric: "978", exponent: 2 },
+    Currency { alpha: "GBP", numeric: "826", exponent: 2 },
+    Currency { alpha: "JPY", numeric: "392", exponent: 0 },
+    Currency { alpha: "KRW", numeric: "410", exponent: 0 },
+    Currency { alpha: "CNY", numeric: "156", exponent: 2 },
+    Currency { alpha: "SGD", numeric: "702", exponent: 2 },
+    Currency { alpha: "THB", numeric: "764", exponent: 2 },
+    Currency { alpha: "AUD", numeric: "036", exponent: 2 },
+    Currency { alpha: "HKD", numeric: "344", exponent: 2 },
+    Currency { alpha: "KWD", numeric: "414", exponent: 3 },
+    Currency { alpha: "BHD", numeric: "048", exponent: 3 },
+];
+
+impl Currency {
+    /// Look up a currency by alphabetic or numeric code
+    pub fn from_code(code: &str) -> Option<&'static Currency> {
+        let code = code.trim();
+        CURRENCIES
+            .iter()
+            .find(|c| c.numeric == code || c.alpha.eq_ignore_ascii_case(code))
+    }
+
+    /// Vietnamese dong, the local currency
+    pub fn vnd() -> &'static Currency {
+        &CURRENCIES[0]
+    }
+
+    /// Currency of an ISO 3166 alpha-2 country (cardholder billing currency)
+    pub fn for_country(country: &str) -> Option<&'static Currency> {
+        COUNTRY_CURRENCIES
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case(country.trim()))
+            .and_then(|(_, numeric)| Self::from_code(numeric))
+    }
+}
+
+/// ISO 3166 alpha-2 country to ISO 4217 numeric currency
+#[rustfmt::skip]
+static COUNTRY_CURRENCIES: &[(&str, &str)] = &[
+    ("VN", "704"), ("US", "840"), ("GB", "826"), ("JP", "392"), ("KR", "410"),
+    ("CN", "156"), ("SG", "702"), ("TH", "764"), ("AU", "036"), ("HK", "344"),
+    ("KW", "414"), ("BH", "048"), ("DE", "978"), ("FR", "978"), ("IT", "978"),
+    ("ES", "978"), ("NL", "978"), ("BE", "978"), ("AT", "978"), ("FI", "978"),
+    ("IE", "978"), ("PT", "978"),
+];
+
+/// Amount conversion errors
+#[derive(Debug, Error, PartialEq)]
+pub enum AmountError {
+    #[error("Invalid amount: {0}")]
+    Invalid(String),
+
+    #[error("Amount {amount} has more decimals than {currency} allows")]
+    TooPrecise {
+        amount: String,
+        currency: &'static str,
+    },
+
+    #[error("Unknown currency: {0}")]
+    UnknownCurrency(String),
+}
+
+/// Monetary amount in minor units of its currency
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Amount {
+    minor: i64,
+    currency: &'static Currency,
+}
+
+impl Amount {
+    pub fn from_minor(minor: i64, currency: &'static Currency) -> Self {
+        Self { minor, currency }
+    }
+
+    /// Parse a decimal major-unit amount ("12.50" USD = 1250 minor units)
+    /// Rejects more fractional digits than the currency has, unless they are zeros
+    pub fn from_major_str(value: &str, currency: &'static Currency) -> Result<Self, AmountError> {
+        let value = value.trim();
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+        let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+
+        let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if int_part.is_empty() || !is_digits(int_part) || !is_digits(frac_part) {
+            return Err(AmountError::Invalid(value.to_string()));
+        }
+
+        let exponent = currency.exponent as usize;
+        let frac_part = frac_part.trim_end_matches('0');
+        if frac_part.len() > exponent {
+            return Err(AmountError::TooPrecise {
+                amount: value.to_string(),
+                currency: currency.alpha,
+            });
+        }
+
+        let minor_digits = format!("{}{:0<width$}", int_part, frac_part, width = exponent);
+        let minor: i64 = minor_digits
+            .parse()
+            .map_err(|_| AmountError::Invalid(value.to_string()))?;
+        Ok(Self {
+            minor: if negative { -minor } else { minor },
+            currency,
+        })
+    }
+
+    /// Convert a JSON number in major units
+    /// Goes through the shortest decimal representation, so 0.29 stays 29 cents
+    pub fn from_major(value: f64, currency: &'static Currency) -> Result<Self, AmountError> {
+        if !value.is_finite() {
+            return Err(AmountError::Invalid(value.to_string()));
+        }
+        Self::from_major_str(&value.to_string(), currency)
+    }
+
+    /// Parse a 12-digit ISO amount field (DE4, DE5, DE6)
+    pub fn from_iso(value: &str, currency: &'static Currency) -> Result<Self, AmountError> {
+        let value = value.trim();
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(AmountError::Invalid(value.to_string()));
+        }
+        let minor = value
+            .parse()
+            .map_err(|_| AmountError::Invalid(value.to_string()))?;
+        Ok(Self { minor, currency })
+    }
+
+    pub fn minor(&self) -> i64 {
+        self.minor
+    }
+
+    pub fn currency(&self) -> &'static Currency {
+        self.currency
+    }
+
+    /// Format as a 12-digit ISO amount field (DE4, DE5, DE6)
+    pub fn to_iso(self) -> String {
+        format!("{:012}", self.minor.unsigned_abs())
+    }
+
+    /// Decimal major-unit representation ("12.50" for 1250 USD cents)
+    pub fn to_major_string(self) -> String {
+        format_decimal(self.minor, self.currency.exponent)
+    }
+
+    /// JSON number in major units, for the terminal APIs
+    pub fn to_json(self) -> serde_json::Value {
+        serde_json::from_str(&self.to_major_string()).unwrap_or(serde_json::Value::Null)
+    }
+
+    /// Convert into another currency (one major unit of ours = `rate` major units of `to`)
+    /// Rounded half up to the minor unit of the target currency
+    pub fn convert(self, rate: ConversionRate, to: &'static Currency) -> Amount {
+        let numerator =
+            self.minor.unsigned_abs() as u128 * rate.mantissa as u128 * 10u128.pow(to.exponent);
+        let denominator = 10u128.pow(rate.decimals + self.currency.exponent);
+        let minor = ((numerator + denominator / 2) / denominator) as i64;
+        Amount::from_minor(if self.minor < 0 { -minor } else { minor }, to)
+    }
+}
+
+/// Format an integer scaled by 10^exponent as a decimal string
+fn format_decimal(value: i64, exponent: u32) -> String {
+    let exponent = exponent as usize;
+    let sign = if value < 0 { "-" } else { "" };
+    let digits = format!("{:0>width$}", value.unsigned_abs(), width = exponent + 1);
+    if exponent == 0 {
+        return format!("{}{}", sign, digits);
+    }
+    let (int_part, frac_part) = digits.split_at(digits.len() - exponent);
+    format!("{}{}.{}", sign, int_part, frac_part)
+}
+
+/// Currency conversion rate, as carried in DE10 (Conversion Rate, Cardholder Billing)
+/// DE10 is n8: the first digit is the number of decimal places, the other seven the rate
+/// (61234567 = 1.234567), so rates are kept to seven significant digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConversionRate {
+    mantissa: u64,
+    decimals: u32,
+}
+
+impl ConversionRate {
+    const MAX_MANTISSA: u128 = 9_999_999;
+    const MAX_DECIMALS: u32 = 9;
+
+    /// Parse a decimal rate ("0.0000393"), rounded to what DE10 can carry
+    pub fn from_decimal_str(value: &str) -> Result<Self, AmountError> {
+        let value = value.trim();
+        let (int_part, frac_part) = value.split_once('.').unwrap_or((value, ""));
+        let digits = format!("{}{}", int_part, frac_part);
+        if int_part.is_empty() || digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(AmountError::Invalid(value.to_string()));
+        }
+        let mantissa: u128 = digits
+            .parse()
+            .map_err(|_| AmountError::Invalid(value.to_string()))?;
+        Self::normalize(mantissa, frac_part.len() as u32)
+            .filter(|rate| rate.mantissa > 0)
+            .ok_or_else(|| AmountError::Invalid(value.to_string()))
+    }
+
+    /// Round to at most seven significant digits and nine decimals
+    fn normalize(mut mantissa: u128, mut decimals: u32) -> Option<Self> {
+        while decimals > 0 && mantissa.is_multiple_of(10) {
+            mantissa /= 10;
+            decimals -= 1;
+        }
+        while mantissa > Self::MAX_MANTISSA || decimals > Self::MAX_DECIMALS {
+            decimals = decimals.checked_sub(1)?;
+            mantissa = (mantissa + 5) / 10;
+        }
+        Some(Self {
+            mantissa: mantissa as u64,
+            decimals,
+        })
+    }
+
+    /// Rate increased by a markup in basis points (300 = 3%)
+    pub fn with_markup(self, markup_bps: u32) -> Option<Self> {
+        Self::normalize(
+            self.mantissa as u128 * (10_000 + markup_bps as u128),
+            self.decimals + 4,
+        )
+    }
+
+    /// Parse a DE10 value
+    pub fn from_de10(value: &str) -> Result<Self, AmountError> {
+        let invalid = || AmountError::Invalid(value.to_string());
+        if value.len() != 8 || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let decimals = value[..1].parse().map_err(|_| invalid())?;
+        let mantissa = value[1..].parse().map_err(|_| invalid())?;
+        Ok(Self { mantissa, decimals })
+    }
+
+    /// DE10 value
+    pub fn to_de10(self) -> String {
+        format!("{}{:07}", self.decimals, self.mantissa)
+    }
+
+    /// Decimal representation ("0.0000393")
+    pub fn to_decimal_string(self) -> String {
+        format_decimal(self.mantissa as i64, self.decimals)
+    }
+}
+
+/// One DE54 Additional Amounts entry
+/// Layout: account type n2, amount type n2, currency n3, sign C/D, amount n12
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdditionalAmount {
+    pub account_type: String,
+    pub amount_type: String,
+    pub amount: Amount,
+}
+
+impl AdditionalAmount {
+    const LEN: usize = 20;
+
+    /// Amount types (DE54 positions 3-4)
+    pub const LEDGER_BALANCE: &'static str = "01";
+    pub const AVAILABLE_BALANCE: &'static str = "02";
+    pub const CASHBACK: &'static str = "40";
+    pub const GRATUITY: &'static str = "44";
+    pub const ORIGINAL_AMOUNT: &'static str = "57";
+
+    pub fn new(account_type: &str, amount_type: &str, amount: Amount) -> Self {
+        Self {
+            account_type: account_type.to_string(),
+            amount_type: amount_type.to_string(),
+            amount,
+        }
+    }
+
+    pub fn to_de54(&self) -> String {
+        let sign = if self.amount.minor() < 0 { 'D' } else { 'C' };
+        format!(
+            "{:0>2}{:0>2}{}{}{}",
+            self.account_type,
+            self.amount_type,
+            self.amount.currency().numeric,
+            sign,
+            self.amount.to_iso()
+        )
+    }
+
+    /// Parse all entries of a DE54 value
+    pub fn parse_de54(value: &str) -> Result<Vec<Self>, AmountError> {
+        if value.is_empty() || !value.len().is_multiple_of(Self::LEN) || !value.is_ascii() {
+            return Err(AmountError::Invalid(value.to_string()));
+        }
+
+        (0..value.len())
+            .step_by(Self::LEN)
+            .map(|start| {
+                let entry = &value[start..start + Self::LEN];
+                let currency = Currency::from_code(&entry[4..7])
+                    .ok_or_else(|| AmountError::UnknownCurrency(entry[4..7].to_string()))?;
+                let amount = Amount::from_iso(&entry[8..], currency)?;
+                let amount = match &entry[7..8] {
+                    "C" => amount,
+                    "D" => Amount::from_minor(-amount.minor(), currency),
+                    _ => return Err(AmountError::Invalid(entry.to_string())),
+                };
+                Ok(Self::new(&entry[0..2], &entry[2..4], amount))
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "accountType": self.account_type,
+            "amountType": self.amount_type,
+            "amount": self.amount.to_json(),
+            "currency": self.amount.currency().alpha,
+        })
+    }
+}
+
+/// Account balances returned in DE54 (balance inquiry, partial approval)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Balances {
+    pub ledger: Option<Amount>,
+    pub available: Option<Amount>,
+}
+
+impl Balances {
+    /// Pick the ledger and available balance out of DE54; None when neither is present
+    pub fn from_additional_amounts(entries: &[AdditionalAmount]) -> Option<Self> {
+        let find = |amount_type: &str| {
+            entries
+                .iter()
+                .find(|e| e.amount_type == amount_type)
+                .map(|e| e.amount)
+        };
+        let balances = Self {
+            ledger: find(AdditionalAmount::LEDGER_BALANCE),
+            available: find(AdditionalAmount::AVAILABLE_BALANCE),
+        };
+        (balances.ledger.is_some() || balances.available.is_some()).then_some(balances)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let currency = self.ledger.or(self.available).map(|a| a.currency().alpha);
+        serde_json::json!({
+            "ledger": self.ledger.map(Amount::to_json),
+            "available": self.available.map(Amount::to_json),
+            "currency": currency,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usd() -> &'static Currency {
+        Currency::from_code("USD").unwrap()
+    }
+
+    #[test]
+    fn test_currency_lookup() {
+        assert_eq!(Currency::from_code("704"), Some(Currency::vnd()));
+        assert_eq!(Currency::from_code("vnd"), Some(Currency::vnd()));
+        assert_eq!(Currency::from_code("392").unwrap().exponent, 0);
+        assert_eq!(Currency::from_code("KWD").unwrap().exponent, 3);
+        assert!(Currency::from_code("XXX").is_none());
+    }
+
+    #[test]
+    fn test_major_to_minor_uses_exponent() {
+        let vnd = Currency::vnd();
+        let jpy = Currency::from_code("JPY").unwrap();
+        let kwd = Currency::from_code("KWD").unwrap();
+
+        assert_eq!(
+            Amount::from_major(150000.0, vnd).unwrap().to_iso(),
+            "000000150000"
+        );
+        assert_eq!(Amount::from_major(1200.0, jpy).unwrap().minor(), 1200);
+        assert_eq!(Amount::from_major(12.5, usd()).unwrap().minor(), 1250);
+        assert_eq!(Amount::from_major(0.29, usd()).unwrap().minor(), 29);
+        assert_eq!(Amount::from_major_str("1.234", kwd).unwrap().minor(), 1234);
+
+        assert!(matches!(
+            Amount::from_major(100.5, vnd),
+            Err(AmountError::TooPrecise { .. })
+        ));
+        assert!(Amount::from_major_str("1.999", usd()).is_err());
+        assert!(Amount::from_major_str("1.2.3", usd()).is_err());
+        assert!(Amount::from_major(f64::NAN, usd()).is_err());
+    }
+
+    #[test]
+    fn test_iso_and_json_round_trip() {
+        let amount = Amount::from_iso("000000001250", usd()).unwrap();
+        assert_eq!(amount.to_major_string(), "12.50");
+        assert_eq!(amount.to_json(), serde_json::json!(12.5));
+        assert_eq!(Amount::from_minor(5, usd()).to_major_string(), "0.05");
+        assert_eq!(
+            Amount::from_minor(150000, Currency::vnd()).to_json(),
+            serde_json::json!(150000)
+        );
+        assert!(Amount::from_iso("12AB", usd()).is_err());
+    }
+
+    #[test]
+    fn test_conversion_rate_de10() {
+        let rate = ConversionRate::from_decimal_str("0.0000393").unwrap();
+        assert_eq!(rate.to_de10(), "70000393");
+        assert_eq!(rate.to_decimal_string(), "0.0000393");
+        assert_eq!(ConversionRate::from_de10("70000393"), Ok(rate));
+        assert!(ConversionRate::from_de10("7000039").is_err());
+        assert_eq!(
+            ConversionRate::from_decimal_str("1.234567")
+                .unwrap()
+                .to_de10(),
+            "61234567"
+        );
+        // More than seven significant digits are rounded
+        assert_eq!(
+            ConversionRate::from_decimal_str("25432.123")
+                .unwrap()
+                .to_de10(),
+            "22543212"
+        );
+        assert!(ConversionRate::from_decimal_str("0").is_err());
+        assert!(ConversionRate::from_decimal_str("abc").is_err());
+
+        // 3% markup
+        assert_eq!(
+            rate.with_markup(300).unwrap().to_decimal_string(),
+            "0.000040479"
+        );
+    }
+
+    #[test]
+    fn test_convert_between_exponents() {
+        let rate = ConversionRate::from_decimal_str("0.0000393").unwrap();
+        let local = Amount::from_minor(1_500_000, Currency::vnd());
+        assert_eq!(local.convert(rate, usd()).minor(), 5895); // 58.95 USD
+
+        let jpy = Currency::from_code("JPY").unwrap();
+        let rate = ConversionRate::from_decimal_str("0.0058").unwrap();
+        assert_eq!(local.convert(rate, jpy).minor(), 8700);
+
+        let rate = ConversionRate::from_decimal_str("25432").unwrap();
+        assert_eq!(
+            Amount::from_minor(1250, usd())
+                .convert(rate, Currency::vnd())
+                .minor(),
+            317900
+        );
+
+        assert_eq!(Currency::for_country("us"), Some(usd()));
+        assert_eq!(Currency::for_country("DE").unwrap().alpha, "EUR");
+    }
+
+    #[test]
+    fn test_de54_round_trip() {
+        let entries = vec![
+            AdditionalAmount::new("00", "01", Amount::from_minor(150000, Currency::vnd())),
+            AdditionalAmount::new("00", "02", Amount::from_minor(-2500, usd())),
+        ];
+        let de54: String = entries.iter().map(AdditionalAmount::to_de54).collect();
+        assert_eq!(de54, "0001704C0000001500000002840D000000002500");
+        assert_eq!(AdditionalAmount::parse_de54(&de54).unwrap(), entries);
+        assert!(AdditionalAmount::parse_de54("0001704X000000150000").is_err());
+        assert!(AdditionalAmount::parse_de54("0001704C").is_err());
+    }
+
+    #[test]
+    fn test_balances_from_de54() {
+        let entries =
+            AdditionalAmount::parse_de54("0001704C0000001500000002704C000000120000").unwrap();
+        let balances = Balances::from_additional_amounts(&entries).unwrap();
+        assert_eq!(balances.ledger.unwrap().minor(), 150000);
+        assert_eq!(balances.available.unwrap().minor(), 120000);
+        assert_eq!(balances.to_json()["currency"], "VND");
+
+        let original = AdditionalAmount::parse_de54("0057704C000000100000").unwrap();
+        assert!(Balances::from_additional_amounts(&original).is_none());
+    }
+}
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Card scheme
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CardScheme {
+    Visa,
+    Mastercard,
+    Jcb,
+    /// Vietnamese domestic scheme (BIN 9704)
+    Napas,
+    UnionPay,
+}
+
+impl CardScheme {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CardScheme::Visa => "VISA",
+            CardScheme::Mastercard => "MASTERCARD",
+            CardScheme::Jcb => "JCB",
+            CardScheme::Napas => "NAPAS",
+            CardScheme::UnionPay => "UNIONPAY",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.trim().to_uppercase().as_str() {
+            "VISA" => Some(CardScheme::Visa),
+            "MASTERCARD" | "MC" => Some(CardScheme::Mastercard),
+            "JCB" => Some(CardScheme::Jcb),
+            "NAPAS" => Some(CardScheme::Napas),
+            "UNIONPAY" | "CUP" => Some(CardScheme::UnionPay),
+            _ => None,
+        }
+    }
+
+    /// Scheme from the scheme-assigned PAN ranges
+    pub fn detect(pan: &str) -> Option<Self> {
+        let prefix = |len: usize| pan.get(..len).and_then(|p| p.parse::<u32>().ok());
+        match (prefix(1), prefix(2), prefix(4)) {
+            (_, _, Some(9704)) => Some(CardScheme::Napas),
+            (_, _, Some(3528..=3589)) => Some(CardScheme::Jcb),
+            (_, _, Some(2221..=2720)) | (_, Some(51..=55), _) => Some(CardScheme::Mastercard),
+            (_, Some(62), _) => Some(CardScheme::UnionPay),
+            (Some(4), _, _) => Some(CardScheme::Visa),
+            _ => None,
+        }
+    }
+}
+
+/// BIN table entry: PANs whose leading digits fall in [low_bin, high_bin]
+/// Both bounds have the same length; the longest matching range wins
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct BinRange {
+    pub low_bin: String,
+    pub high_bin: String,
+    pub scheme: Option<String>,
+    pub issuer: Option<String>,
+    pub country: Option<String>,   // ISO 3166 alpha-2
+    pub card_type: Option<String>, // CREDIT, DEBIT or PREPAID
+    pub host_group: String,        // Host connector the range is routed to
+}
+
+impl BinRange {
+    pub fn matches(&self, pan: &str) -> bool {
+        pan.get(..self.low_bin.len()).is_some_and(|prefix| {
+            prefix >= self.low_bin.as_str() && prefix <= self.high_bin.as_str()
+        })
+    }
+
+    /// Scheme of the range, or detected from the PAN when the table has none
+    pub fn scheme(&self, pan: &str) -> Option<CardScheme> {
+        self.scheme
+            .as_deref()
+            .and_then(CardScheme::from_str)
+            .or_else(|| CardScheme::detect(pan))
+    }
+}
+
+/// Luhn (mod 10) check of a PAN
+pub fn luhn_valid(pan: &str) -> bool {
+    if !(12..=19).contains(&pan.len()) || !pan.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    let sum: u32 = pan
+        .bytes()
+        .rev()
+        .map(|b| (b - b'0') as u32)
+        .enumerate()
+        .map(|(i, digit)| match (i % 2 == 1, digit * 2) {
+            (true, doubled) if doubled > 9 => doubled - 9,
+            (true, doubled) => doubled,
+            (false, _) => digit,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_luhn() {
+        assert!(luhn_valid("4111111111111111"));
+        assert!(luhn_valid("5555555555554444"));
+        assert!(luhn_valid("9704000000000018"));
+        assert!(!luhn_valid("4111111111111112"));
+        assert!(!luhn_valid("41111111111A1111"));
+        assert!(!luhn_valid("4111"));
+    }
+
+    #[test]
+    fn test_detect_scheme() {
+        assert_eq!(
+            CardScheme::detect("4111111111111111"),
+            Some(CardScheme::Visa)
+        );
+        assert_eq!(
+            CardScheme::detect("5555555555554444"),
+            Some(CardScheme::Mastercard)
+        );
+        assert_eq!(
+            CardScheme::detect("2223000048400011"),
+            Some(CardScheme::Mastercard)
+        );
+        assert_eq!(
+            CardScheme::detect("3530111333300000"),
+            Some(CardScheme::Jcb)
+        );
+        assert_eq!(
+            CardScheme::detect("9704000000000018"),
+            Some(CardScheme::Napas)
+        );
+        assert_eq!(
+            CardScheme::detect("6250941006528599"),
+            Some(CardScheme::UnionPay)
+        );
+        assert_eq!(CardScheme::detect("3782822463100050"), None);
+    }
+}
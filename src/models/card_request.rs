@@ -7,7 +7,14 @@ pub struct CardRequest {
     pub msg_type: String,
     pub trm_id: String,
     pub transaction_id: String,
+    /// Amount in major units of `currency` (the tip on a tip adjustment)
     pub amount: f64,
+    /// Cash handed out on a purchase with cashback, on top of `amount`
+    #[serde(default)]
+    pub cashback_amount: Option<f64>,
+    /// ISO 4217 alphabetic or numeric currency code (defaults to the acquirer currency)
+    #[serde(default)]
+    pub currency: Option<String>,
     pub transaction_type: String,
     #[serde(default)]
     pub merchant_id: Option<String>,
@@ -17,6 +24,50 @@ pub struct CardRequest {
     pub qr_data: Option<String>,
     #[serde(default)]
     pub additional_data: Option<String>,
+    /// RRN of the original transaction (void, refund, completion, reversal)
+    #[serde(default)]
+    pub original_rrn: Option<String>,
+    /// Authorization code of the original transaction
+    #[serde(default)]
+    pub original_auth_code: Option<String>,
+    /// STAN of the original transaction
+    #[serde(default)]
+    pub original_stan: Option<String>,
+    /// Terminal can accept a partial approval (response code 10)
+    #[serde(default)]
+    pub partial_auth_supported: bool,
+    /// Cardholder's answer to a DCC offer (absent on the first request)
+    #[serde(default)]
+    pub dcc_accepted: Option<bool>,
+    /// How the card was read: CHIP, CONTACTLESS, CONTACTLESS_MAGSTRIPE, SWIPE, FALLBACK,
+    /// MANUAL, ECOMMERCE or QR (chip when absent)
+    #[serde(default)]
+    pub entry_mode: Option<String>,
+    /// Terminal PIN entry capability: CAPABLE, NOT_CAPABLE or PIN_PAD_DOWN (capable when absent)
+    #[serde(default)]
+    pub pin_capability: Option<String>,
+    /// Track 2 data of a swiped card
+    #[serde(default)]
+    pub track2: Option<String>,
+    /// PAN of a keyed or e-commerce card
+    #[serde(default)]
+    pub pan: Option<String>,
+    /// Expiry date (YYMM) of a keyed or e-commerce card
+    #[serde(default)]
+    pub expiry_date: Option<String>,
+    /// Encrypted PIN block of an online PIN (DE52)
+    #[serde(default)]
+    pub pin_block: Option<String>,
+    /// Logon challenge signed with the terminal's logon key (HMAC-SHA256, hex)
+    #[serde(default)]
+    pub logon_mac: Option<String>,
+    /// Parameter version the terminal holds, or the version being downloaded when
+    /// `param_block` is past the first block
+    #[serde(default)]
+    pub param_version: Option<i32>,
+    /// Parameter download block requested (0 when absent)
+    #[serde(default)]
+    pub param_block: Option<usize>,
 }
 
 /// Parsed card data from the cardData field
@@ -2,6 +2,9 @@ use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// DE60 position 1: terminal accepts a partial approval (response code 10)
+const PARTIAL_APPROVAL_INDICATOR: &str = "1";
+
 /// ISO8583 Message structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Iso8583Message {
@@ -67,20 +70,87 @@ impl Iso8583Message {
 
     /// Is this a request message?
     pub fn is_request(&self) -> bool {
-        matches!(self.mti.as_str(), "0100" | "0200" | "0400" | "0800")
+        matches!(
+            self.mti.as_str(),
+            "0100" | "0120" | "0121" | "0200" | "0220" | "0221" | "0320" | "0321" | "0400"
+                | "0401" | "0420" | "0421" | "0500" | "0501" | "0800"
+        )
     }
 
     /// Is this a response message?
     pub fn is_response(&self) -> bool {
-        matches!(self.mti.as_str(), "0110" | "0210" | "0410" | "0810")
+        matches!(
+            self.mti.as_str(),
+            "0110" | "0130" | "0210" | "0230" | "0330" | "0410" | "0430" | "0510" | "0810"
+        )
+    }
+
+    /// Is this an advice (0x2x) message?
+    pub fn is_advice(&self) -> bool {
+        matches!(self.mti.as_str(), "0120" | "0121" | "0220" | "0221" | "0420" | "0421")
+    }
+
+    /// Is this a reversal (04xx) message?
+    pub fn is_reversal(&self) -> bool {
+        self.mti.starts_with("04")
+    }
+
+    /// Is this a batch upload (032x) message?
+    pub fn is_batch_upload(&self) -> bool {
+        self.mti.starts_with("032")
+    }
+
+    /// Is this a reconciliation (05xx) message?
+    pub fn is_settlement(&self) -> bool {
+        self.mti.starts_with("05")
+    }
+
+    /// Is this a network management (08xx) message?
+    pub fn is_network_management(&self) -> bool {
+        self.mti.starts_with("08")
+    }
+
+    /// Flag the terminal as able to accept a partial approval (DE60 position 1)
+    pub fn set_partial_approval_supported(&mut self) {
+        self.set_field(60, PARTIAL_APPROVAL_INDICATOR.to_string());
+    }
+
+    /// Does the request accept a partial approval?
+    pub fn supports_partial_approval(&self) -> bool {
+        self.get_field(60).is_some_and(|de60| de60.starts_with(PARTIAL_APPROVAL_INDICATOR))
+    }
+
+    /// Advice MTI reporting the outcome of this request decided without the host
+    pub fn advice_mti(&self) -> Option<String> {
+        match self.mti.as_str() {
+            "0100" => Some("0120".to_string()),
+            "0200" => Some("0220".to_string()),
+            _ => None,
+        }
+    }
+
+    /// MTI used when this message is repeated (store-and-forward retries)
+    pub fn repeat_mti(&self) -> Option<String> {
+        match self.mti.as_str() {
+            "0120" | "0121" => Some("0121".to_string()),
+            "0220" | "0221" => Some("0221".to_string()),
+            "0400" | "0401" => Some("0401".to_string()),
+            "0420" | "0421" => Some("0421".to_string()),
+            _ => None,
+        }
     }
 
     /// Get response MTI for this request
     pub fn get_response_mti(&self) -> Option<String> {
         match self.mti.as_str() {
             "0100" => Some("0110".to_string()),
+            "0120" | "0121" => Some("0130".to_string()),
             "0200" => Some("0210".to_string()),
-            "0400" => Some("0410".to_string()),
+            "0220" | "0221" => Some("0230".to_string()),
+            "0320" | "0321" => Some("0330".to_string()),
+            "0400" | "0401" => Some("0410".to_string()),
+            "0420" | "0421" => Some("0430".to_string()),
+            "0500" | "0501" => Some("0510".to_string()),
             "0800" => Some("0810".to_string()),
             _ => None,
         }
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::app::service::response_handler::ResponseCode;
+use crate::app::service::transaction_profile::TransactionType;
+use crate::models::amount::Currency;
+
+/// Status of a merchant or terminal
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum MasterStatus {
+    Active,
+    /// Temporarily blocked (risk, unpaid fees)
+    Suspended,
+    /// Terminated contract or decommissioned terminal
+    Closed,
+}
+
+impl MasterStatus {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_uppercase().as_str() {
+            "ACTIVE" => Some(MasterStatus::Active),
+            "SUSPENDED" => Some(MasterStatus::Suspended),
+            "CLOSED" => Some(MasterStatus::Closed),
+            _ => None,
+        }
+    }
+}
+
+/// Terminal with the master data of its merchant
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TerminalMaster {
+    pub trm_id: String,
+    pub trm_status: String,
+    pub merchant_id: String,
+    pub merchant_status: String,
+    pub merchant_name: String,
+    pub city: Option<String>,
+    pub country: Option<String>,          // ISO 3166 alpha-2
+    pub mcc: String,                      // Merchant category code (DE18)
+    pub currency: Option<String>,         // ISO 4217 alpha; the only currency the merchant accepts
+    pub acquirer_id: Option<String>,      // Acquirer the merchant is routed through (DE32)
+    pub allowed_tx_types: Option<String>, // Terminal list, else merchant list; NULL = all
+}
+
+impl TerminalMaster {
+    /// Terminal and merchant are both active
+    pub fn is_active(&self) -> bool {
+        MasterStatus::from_str(&self.trm_status) == Some(MasterStatus::Active)
+            && MasterStatus::from_str(&self.merchant_status) == Some(MasterStatus::Active)
+    }
+
+    pub fn currency(&self) -> Option<&'static Currency> {
+        self.currency.as_deref().and_then(Currency::from_code)
+    }
+
+    /// Transaction type enabled for the terminal
+    pub fn allows(&self, tx_type: TransactionType) -> bool {
+        match &self.allowed_tx_types {
+            Some(types) => types
+                .split(',')
+                .filter_map(|t| serde_json::from_value(serde_json::json!(t.trim())).ok())
+                .any(|allowed: TransactionType| allowed == tx_type),
+            None => true,
+        }
+    }
+
+    /// Check a terminal request against the master data
+    pub fn check(
+        &self,
+        merchant_id: Option<&str>,
+        currency: Option<&str>,
+        tx_type: TransactionType,
+    ) -> Result<(), ResponseCode> {
+        if !self.is_active() {
+            return Err(ResponseCode::InvalidMerchant);
+        }
+        // A terminal can only submit for the merchant it is installed at
+        if merchant_id.is_some_and(|mid| mid.trim() != self.merchant_id) {
+            return Err(ResponseCode::InvalidMerchant);
+        }
+        if !self.allows(tx_type) {
+            return Err(ResponseCode::NotPermittedTerminal);
+        }
+        if let (Some(accepted), Some(requested)) = (self.currency(), currency)
+            && Currency::from_code(requested).is_some_and(|c| c != accepted)
+        {
+            return Err(ResponseCode::NotPermittedTerminal);
+        }
+        Ok(())
+    }
+
+    /// DE43 Card Acceptor Name/Location: name (25), city (13), country (2)
+    pub fn to_de43(&self) -> String {
+        format!(
+            "{:<25.25}{:<13.13}{:<2.2}",
+            self.merchant_name,
+            self.city.as_deref().unwrap_or_default(),
+            self.country.as_deref().unwrap_or_default()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminal(allowed_tx_types: Option<&str>) -> TerminalMaster {
+        TerminalMaster {
+            trm_id: "TERM0001".to_string(),
+            trm_status: "ACTIVE".to_string(),
+            merchant_id: "MERCHANT01".to_string(),
+            merchant_status: "ACTIVE".to_string(),
+            merchant_name: "Pho Hanoi Restaurant District One".to_string(),
+            city: Some("Ho Chi Minh".to_string()),
+            country: Some("VN".to_string()),
+            mcc: "5812".to_string(),
+            currency: Some("VND".to_string()),
+            acquirer_id: None,
+            allowed_tx_types: allowed_tx_types.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_terminal_checks() {
+        let purchase = TransactionType::Purchase;
+        let term = terminal(Some("PURCHASE, VOID,REFUND"));
+        assert_eq!(term.check(None, None, purchase), Ok(()));
+        assert_eq!(
+            term.check(Some("MERCHANT01"), Some("704"), purchase),
+            Ok(())
+        );
+        assert_eq!(
+            term.check(Some("MERCHANT02"), None, purchase),
+            Err(ResponseCode::InvalidMerchant)
+        );
+        assert_eq!(
+            term.check(None, None, TransactionType::CashAdvance),
+            Err(ResponseCode::NotPermittedTerminal)
+        );
+        assert_eq!(
+            term.check(None, Some("USD"), purchase),
+            Err(ResponseCode::NotPermittedTerminal)
+        );
+        assert!(terminal(None).allows(TransactionType::CashAdvance));
+
+        let suspended = TerminalMaster {
+            merchant_status: "SUSPENDED".to_string(),
+            ..terminal(None)
+        };
+        assert_eq!(
+            suspended.check(None, None, purchase),
+            Err(ResponseCode::InvalidMerchant)
+        );
+    }
+
+    #[test]
+    fn test_de43() {
+        let de43 = terminal(None).to_de43();
+        assert_eq!(de43.len(), 40);
+        assert_eq!(de43, "Pho Hanoi Restaurant DistHo Chi Minh  VN");
+    }
+}
@@ -1,10 +1,20 @@
+pub mod amount;
 pub mod app_context;
+pub mod bin_range;
 pub mod card_request;
 pub mod card_resp;
 pub mod iso8583_message;
-pub mod iso8583_profile;
+pub mod merchant;
+pub mod original_data;
 pub mod payos_qr_req;
 pub mod payos_qr_resp;
+pub mod pos_entry;
+pub mod preauth_hold;
+pub mod risk_rule;
+pub mod saf_entry;
+pub mod settlement_batch;
+pub mod terminal_params;
+pub mod terminal_session;
 pub mod transaction;
 
 
@@ -0,0 +1,85 @@
+use crate::models::transaction::Iso8583Transaction;
+
+/// DE90 Original Data Elements
+/// Identifies the original transaction in voids, reversals and advices
+#[derive(Debug, Clone, PartialEq)]
+pub struct OriginalDataElements {
+    /// Original Message Type Indicator (n4)
+    pub mti: String,
+    /// Original STAN (n6)
+    pub stan: String,
+    /// Original Transmission Date & Time, DE7 MMDDhhmmss (n10)
+    pub transmission_date_time: String,
+    /// Original Acquiring Institution ID, DE32 (n11)
+    pub acquirer_id: String,
+    /// Original Forwarding Institution ID, DE33 (n11)
+    pub forwarding_id: String,
+}
+
+impl OriginalDataElements {
+    /// Collect the original data elements from a stored transaction
+    pub fn from_transaction(tx: &Iso8583Transaction) -> Self {
+        Self {
+            mti: tx
+                .field_000
+                .clone()
+                .or_else(|| tx.msg_typ.clone())
+                .unwrap_or_default(),
+            stan: tx.field_011.clone().unwrap_or_default(),
+            // Older records have no DE7: fall back to local date + time (MMDD + hhmmss)
+            transmission_date_time: tx.field_007.clone().unwrap_or_else(|| {
+                format!(
+                    "{}{}",
+                    tx.field_013.as_deref().unwrap_or("0000"),
+                    tx.field_012.as_deref().unwrap_or("000000")
+                )
+            }),
+            acquirer_id: tx.field_032.clone().unwrap_or_default(),
+            forwarding_id: String::new(),
+        }
+    }
+
+    /// Set the forwarding institution ID (DE33 of the original)
+    pub fn with_forwarding_id(mut self, forwarding_id: &str) -> Self {
+        self.forwarding_id = forwarding_id.to_string();
+        self
+    }
+
+    /// Format as the 42-digit DE90 value
+    pub fn to_de90(&self) -> String {
+        format!(
+            "{:0>4}{:0>6}{:0>10}{:0>11}{:0>11}",
+            self.mti, self.stan, self.transmission_date_time, self.acquirer_id, self.forwarding_id
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_de90_layout() {
+        let mut tx = Iso8583Transaction::new("1234", "0200");
+        tx.field_007 = Some("1018093015".to_string());
+        tx.field_032 = Some("970436".to_string());
+
+        let de90 = OriginalDataElements::from_transaction(&tx)
+            .with_forwarding_id("970400")
+            .to_de90();
+
+        assert_eq!(de90.len(), 42);
+        assert_eq!(de90, "020000123410180930150000097043600000970400");
+    }
+
+    #[test]
+    fn test_de90_falls_back_to_local_date_time() {
+        let mut tx = Iso8583Transaction::new("000042", "0100");
+        tx.field_007 = None;
+        tx.field_012 = Some("093015".to_string());
+        tx.field_013 = Some("1018".to_string());
+
+        let de90 = OriginalDataElements::from_transaction(&tx).to_de90();
+        assert_eq!(&de90[10..20], "1018093015");
+    }
+}
@@ -0,0 +1,253 @@
+use thiserror::Error;
+
+/// How the card was read at the terminal (terminal `entryMode`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryMode {
+    Chip,
+    ContactlessChip,
+    ContactlessMagstripe,
+    Swipe,
+    /// Swipe after the chip could not be read
+    Fallback,
+    Manual,
+    Ecommerce,
+    Qr,
+}
+
+impl EntryMode {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_uppercase().as_str() {
+            "CHIP" | "ICC" | "EMV" => Some(EntryMode::Chip),
+            "CONTACTLESS" | "CONTACTLESS_CHIP" | "NFC" => Some(EntryMode::ContactlessChip),
+            "CONTACTLESS_MAGSTRIPE" | "MSD" => Some(EntryMode::ContactlessMagstripe),
+            "SWIPE" | "MAGSTRIPE" => Some(EntryMode::Swipe),
+            "FALLBACK" => Some(EntryMode::Fallback),
+            "MANUAL" | "KEYED" => Some(EntryMode::Manual),
+            "ECOMMERCE" | "E_COMMERCE" => Some(EntryMode::Ecommerce),
+            "QR" => Some(EntryMode::Qr),
+            _ => None,
+        }
+    }
+
+    /// PAN entry mode, DE22 positions 1-2
+    pub fn pan_entry_code(&self) -> &'static str {
+        match self {
+            EntryMode::Chip => "05",
+            EntryMode::ContactlessChip => "07",
+            EntryMode::ContactlessMagstripe => "91",
+            EntryMode::Swipe => "90",
+            EntryMode::Fallback => "80",
+            EntryMode::Manual => "01",
+            EntryMode::Ecommerce => "81",
+            EntryMode::Qr => "03",
+        }
+    }
+
+    /// Entry mode of a DE22 (PAN entry mode in positions 1-2)
+    pub fn from_de22(de22: &str) -> Option<Self> {
+        match de22.get(..2)? {
+            "05" => Some(EntryMode::Chip),
+            "07" => Some(EntryMode::ContactlessChip),
+            "91" => Some(EntryMode::ContactlessMagstripe),
+            "90" => Some(EntryMode::Swipe),
+            "80" => Some(EntryMode::Fallback),
+            "01" => Some(EntryMode::Manual),
+            "81" => Some(EntryMode::Ecommerce),
+            "03" => Some(EntryMode::Qr),
+            _ => None,
+        }
+    }
+
+    /// Card data comes from the chip (DE55 present)
+    pub fn is_chip(&self) -> bool {
+        matches!(self, EntryMode::Chip | EntryMode::ContactlessChip)
+    }
+
+    /// Card data comes from track 2 (DE35 present)
+    pub fn reads_track2(&self) -> bool {
+        matches!(
+            self,
+            EntryMode::ContactlessMagstripe | EntryMode::Swipe | EntryMode::Fallback
+        )
+    }
+
+    /// DE25 when the entry mode is not a normal presentment
+    pub fn pos_condition_code(&self) -> Option<&'static str> {
+        match self {
+            EntryMode::Ecommerce => Some("59"),
+            _ => None,
+        }
+    }
+
+    /// Card data DEs this entry mode cannot capture
+    fn uncaptured_des(&self) -> &'static [u8] {
+        match self {
+            EntryMode::Chip | EntryMode::ContactlessChip => &[],
+            EntryMode::ContactlessMagstripe | EntryMode::Swipe | EntryMode::Fallback => &[23, 55],
+            EntryMode::Manual | EntryMode::Ecommerce => &[23, 35, 55],
+            EntryMode::Qr => &[14, 23, 35, 55],
+        }
+    }
+}
+
+/// PIN entry capability of the terminal (terminal `pinCapability`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinCapability {
+    Capable,
+    NotCapable,
+    /// PIN pad present but inoperative
+    PinPadDown,
+}
+
+impl PinCapability {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_uppercase().as_str() {
+            "CAPABLE" | "YES" | "PIN" => Some(PinCapability::Capable),
+            "NOT_CAPABLE" | "NO" | "NO_PIN" => Some(PinCapability::NotCapable),
+            "PIN_PAD_DOWN" | "INOPERATIVE" => Some(PinCapability::PinPadDown),
+            _ => None,
+        }
+    }
+
+    /// PIN entry capability, DE22 position 3
+    pub fn code(&self) -> char {
+        match self {
+            PinCapability::Capable => '1',
+            PinCapability::NotCapable => '2',
+            PinCapability::PinPadDown => '8',
+        }
+    }
+}
+
+/// Entry mode errors
+#[derive(Debug, Error, PartialEq)]
+pub enum PosEntryError {
+    #[error("Unknown entry mode: {0}")]
+    UnknownEntryMode(String),
+
+    #[error("Unknown PIN capability: {0}")]
+    UnknownPinCapability(String),
+}
+
+/// Point of service entry reported by the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PosEntry {
+    pub mode: EntryMode,
+    pub pin: PinCapability,
+}
+
+impl PosEntry {
+    /// Parse the terminal values; terminals that send none read the chip with a PIN pad
+    pub fn parse(
+        entry_mode: Option<&str>,
+        pin_capability: Option<&str>,
+        default_mode: EntryMode,
+    ) -> Result<Self, PosEntryError> {
+        let mode = match entry_mode {
+            Some(s) => {
+                EntryMode::from_str(s).ok_or_else(|| PosEntryError::UnknownEntryMode(s.into()))?
+            }
+            None => default_mode,
+        };
+        let pin = match pin_capability {
+            Some(s) => PinCapability::from_str(s)
+                .ok_or_else(|| PosEntryError::UnknownPinCapability(s.into()))?,
+            None => PinCapability::Capable,
+        };
+        Ok(Self { mode, pin })
+    }
+
+    /// DE22 POS Entry Mode: PAN entry mode and PIN entry capability
+    pub fn to_de22(self) -> String {
+        format!("{}{}", self.mode.pan_entry_code(), self.pin.code())
+    }
+
+    /// DE26 POS PIN Capture Code (up to 12 digits), only when a PIN can be captured
+    pub fn to_de26(self) -> Option<&'static str> {
+        (self.pin == PinCapability::Capable && self.mode != EntryMode::Ecommerce).then_some("12")
+    }
+
+    /// DEs a profile may require that this entry cannot provide
+    pub fn uncaptured_des(&self) -> Vec<u8> {
+        let mut des = self.mode.uncaptured_des().to_vec();
+        if self.to_de26().is_none() {
+            des.push(26);
+        }
+        des
+    }
+
+    /// A chip card swiped without the fallback indicator must be inserted instead
+    pub fn is_chip_card_swiped(&self, track2: Option<&str>) -> bool {
+        self.mode == EntryMode::Swipe
+            && track2
+                .and_then(service_code)
+                .is_some_and(|code| code.starts_with('2') || code.starts_with('6'))
+    }
+}
+
+/// Service code of track 2 data: 3 digits after the separator and the expiry date
+pub fn service_code(track2: &str) -> Option<&str> {
+    let (_, rest) = track2.split_once(['=', 'D'])?;
+    rest.get(4..7)
+        .filter(|code| code.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// PAN and expiry date (YYMM) of track 2 data
+pub fn track2_pan_and_expiry(track2: &str) -> Option<(&str, &str)> {
+    let (pan, rest) = track2.split_once(['=', 'D'])?;
+    Some((pan, rest.get(..4)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_de22_and_de26() {
+        let chip = PosEntry::parse(None, None, EntryMode::Chip).unwrap();
+        assert_eq!(chip.to_de22(), "051");
+        assert_eq!(chip.to_de26(), Some("12"));
+        assert!(chip.uncaptured_des().is_empty());
+
+        let keyed = PosEntry::parse(Some("manual"), Some("NOT_CAPABLE"), EntryMode::Chip).unwrap();
+        assert_eq!(keyed.to_de22(), "012");
+        assert_eq!(keyed.to_de26(), None);
+        assert_eq!(keyed.uncaptured_des(), vec![23, 35, 55, 26]);
+
+        let ecommerce = PosEntry::parse(Some("ECOMMERCE"), None, EntryMode::Chip).unwrap();
+        assert_eq!(ecommerce.to_de22(), "811");
+        assert_eq!(ecommerce.mode.pos_condition_code(), Some("59"));
+
+        let fallback = PosEntry::parse(Some("FALLBACK"), Some("PIN_PAD_DOWN"), EntryMode::Chip);
+        assert_eq!(fallback.unwrap().to_de22(), "808");
+        assert_eq!(EntryMode::from_de22("808"), Some(EntryMode::Fallback));
+        assert_eq!(
+            EntryMode::from_de22("071"),
+            Some(EntryMode::ContactlessChip)
+        );
+        assert_eq!(EntryMode::from_de22("9"), None);
+
+        assert_eq!(
+            PosEntry::parse(Some("TELEPATHY"), None, EntryMode::Chip),
+            Err(PosEntryError::UnknownEntryMode("TELEPATHY".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_chip_card_swiped_without_fallback() {
+        let chip_card = "4111111111111111=28122011234567890";
+        let magstripe_card = "4111111111111111D28121011234567890";
+        assert_eq!(service_code(chip_card), Some("201"));
+        assert_eq!(
+            track2_pan_and_expiry(magstripe_card),
+            Some(("4111111111111111", "2812"))
+        );
+
+        let swipe = PosEntry::parse(Some("SWIPE"), None, EntryMode::Chip).unwrap();
+        assert!(swipe.is_chip_card_swiped(Some(chip_card)));
+        assert!(!swipe.is_chip_card_swiped(Some(magstripe_card)));
+
+        let fallback = PosEntry::parse(Some("FALLBACK"), None, EntryMode::Chip).unwrap();
+        assert!(!fallback.is_chip_card_swiped(Some(chip_card)));
+    }
+}
@@ -0,0 +1,125 @@
+use chrono::{Duration, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Pre-authorization hold status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HoldStatus {
+    Open,
+    Completed,
+    Released,
+    Expired,
+}
+
+impl HoldStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            HoldStatus::Open => "OPEN",
+            HoldStatus::Completed => "COMPLETED",
+            HoldStatus::Released => "RELEASED",
+            HoldStatus::Expired => "EXPIRED",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_uppercase().as_str() {
+            "OPEN" => Some(HoldStatus::Open),
+            "COMPLETED" => Some(HoldStatus::Completed),
+            "RELEASED" => Some(HoldStatus::Released),
+            "EXPIRED" => Some(HoldStatus::Expired),
+            _ => None,
+        }
+    }
+}
+
+/// Open amount held by an approved pre-authorization
+/// Keyed by the original pre-auth transaction (tr_dt, tr_tm, tr_uniq_no)
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PreAuthHold {
+    pub tr_dt: String,
+    pub tr_tm: String,
+    pub tr_uniq_no: String,
+    pub trm_id: Option<String>,
+    pub authorized_amt: i64, // Initial + incremental authorizations (minor units)
+    pub completed_amt: Option<i64>,
+    pub status: String,
+    pub expire_dtm: String, // YYYYMMDDhhmmss
+    pub inst_dtm: Option<String>,
+    pub updt_dtm: Option<String>,
+}
+
+impl PreAuthHold {
+    /// New open hold for an approved pre-authorization
+    pub fn open(
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+        trm_id: Option<String>,
+        amount: i64,
+        expiry_days: i64,
+    ) -> Self {
+        let now = Local::now();
+        Self {
+            tr_dt: tr_dt.to_string(),
+            tr_tm: tr_tm.to_string(),
+            tr_uniq_no: tr_uniq_no.to_string(),
+            trm_id,
+            authorized_amt: amount,
+            completed_amt: None,
+            status: HoldStatus::Open.as_str().to_string(),
+            expire_dtm: (now + Duration::days(expiry_days))
+                .format("%Y%m%d%H%M%S")
+                .to_string(),
+            inst_dtm: Some(now.format("%Y%m%d%H%M%S").to_string()),
+            updt_dtm: None,
+        }
+    }
+
+    pub fn status(&self) -> Option<HoldStatus> {
+        HoldStatus::from_str(&self.status)
+    }
+
+    /// Open and not past its expiry
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        self.status() == Some(HoldStatus::Open)
+            && now.format("%Y%m%d%H%M%S").to_string() < self.expire_dtm
+    }
+
+    /// Highest amount a completion may settle (authorized amount plus tolerance)
+    pub fn max_completion_amount(&self, tolerance_pct: u32) -> i64 {
+        self.authorized_amt + self.authorized_amt * tolerance_pct as i64 / 100
+    }
+
+    /// Partial completion or over-completion within tolerance
+    pub fn can_complete(&self, amount: i64, tolerance_pct: u32) -> bool {
+        amount > 0 && amount <= self.max_completion_amount(tolerance_pct)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion_tolerance() {
+        let hold = PreAuthHold::open("20261018", "120000", "TX1", None, 100_000, 7);
+
+        assert!(hold.can_complete(60_000, 15)); // partial
+        assert!(hold.can_complete(115_000, 15)); // over-completion within tolerance
+        assert!(!hold.can_complete(115_001, 15));
+        assert!(!hold.can_complete(0, 15));
+    }
+
+    #[test]
+    fn test_hold_expiry() {
+        let hold = PreAuthHold::open("20261018", "120000", "TX1", None, 100_000, 7);
+        let now = Local::now().naive_local();
+
+        assert!(hold.is_active(now));
+        assert!(!hold.is_active(now + Duration::days(8)));
+
+        let mut released = hold.clone();
+        released.status = HoldStatus::Released.as_str().to_string();
+        assert!(!released.is_active(now));
+    }
+}
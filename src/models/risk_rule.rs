@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::app::service::transaction_profile::TransactionType;
+
+/// What a rule counts or limits by
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RuleScope {
+    Pan,
+    Terminal,
+    Merchant,
+}
+
+/// Condition that makes a rule match
+/// Amounts are in major units of the transaction currency
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RuleCondition {
+    /// Single transaction above `amount`, optionally only for the listed keys
+    /// (terminal IDs, merchant IDs or PAN hashes)
+    #[serde(rename_all = "camelCase")]
+    MaxAmount {
+        scope: RuleScope,
+        #[serde(default)]
+        keys: Vec<String>,
+        amount: String,
+    },
+    /// More than `max_count` transactions, or more than `max_amount` in total,
+    /// within the window
+    #[serde(rename_all = "camelCase")]
+    Velocity {
+        scope: RuleScope,
+        window_secs: u64,
+        #[serde(default)]
+        max_count: Option<u64>,
+        #[serde(default)]
+        max_amount: Option<String>,
+    },
+    /// Hot card / negative list, as SHA-256 hashes of the PAN
+    #[serde(rename_all = "camelCase")]
+    NegativeList { pan_hashes: Vec<String> },
+    /// Merchant category codes (DE18) not accepted
+    #[serde(rename_all = "camelCase")]
+    BlockedMcc { mccs: Vec<String> },
+    /// At least `max_declines` host declines on the card within the window
+    #[serde(rename_all = "camelCase")]
+    RepeatedDeclines { max_declines: u64, window_secs: u64 },
+}
+
+/// What happens when a rule matches
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RuleOutcome {
+    /// Decline locally with the response code (DE39)
+    #[serde(rename_all = "camelCase")]
+    Decline { response_code: String },
+    /// Approve as usual, flag the transaction for review
+    Flag,
+    /// Decline unless the cardholder was verified by PIN
+    RequirePin,
+}
+
+/// Pre-authorization risk rule
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskRule {
+    pub id: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Transaction types the rule applies to; empty = every new authorization
+    #[serde(default)]
+    pub transaction_types: Vec<TransactionType>,
+    #[serde(flatten)]
+    pub condition: RuleCondition,
+    pub outcome: RuleOutcome,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl RiskRule {
+    pub fn applies_to(&self, tx_type: TransactionType) -> bool {
+        self.enabled
+            && (self.transaction_types.is_empty() || self.transaction_types.contains(&tx_type))
+    }
+}
+
+/// SHA-256 of a PAN (hex), how cards appear in rules and counters
+pub fn pan_hash(pan: &str) -> String {
+    hex::encode(Sha256::digest(pan.as_bytes()))
+}
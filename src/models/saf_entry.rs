@@ -0,0 +1,163 @@
+use chrono::{Duration, Local};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::models::iso8583_message::Iso8583Message;
+
+/// Kind of message held in the store-and-forward queue
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SafType {
+    /// 0400 reversal of a timed-out transaction
+    Reversal,
+    /// 0220 pre-auth completion advice
+    CompletionAdvice,
+    /// 0220 upload of an offline-approved EMV transaction
+    OfflineUpload,
+    /// 0220 adjustment advice (tip adjustment)
+    AdjustmentAdvice,
+    /// Void that could not be delivered online
+    VoidAdvice,
+    /// 0120/0220 advice of an approval made in stand-in
+    StandInAdvice,
+}
+
+impl SafType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SafType::Reversal => "REVERSAL",
+            SafType::CompletionAdvice => "COMPLETION_ADVICE",
+            SafType::OfflineUpload => "OFFLINE_UPLOAD",
+            SafType::AdjustmentAdvice => "ADJUSTMENT_ADVICE",
+            SafType::VoidAdvice => "VOID_ADVICE",
+            SafType::StandInAdvice => "STAND_IN_ADVICE",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_uppercase().as_str() {
+            "REVERSAL" => Some(SafType::Reversal),
+            "COMPLETION_ADVICE" => Some(SafType::CompletionAdvice),
+            "OFFLINE_UPLOAD" => Some(SafType::OfflineUpload),
+            "ADJUSTMENT_ADVICE" => Some(SafType::AdjustmentAdvice),
+            "VOID_ADVICE" => Some(SafType::VoidAdvice),
+            "STAND_IN_ADVICE" => Some(SafType::StandInAdvice),
+            _ => None,
+        }
+    }
+}
+
+/// Store-and-forward entry status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SafStatus {
+    Pending,
+    Delivered,
+    /// Max attempts reached, needs operator action
+    Dead,
+}
+
+impl SafStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SafStatus::Pending => "PENDING",
+            SafStatus::Delivered => "DELIVERED",
+            SafStatus::Dead => "DEAD",
+        }
+    }
+}
+
+/// Message waiting in the store-and-forward queue
+/// One entry per (type, transaction); delivered in saf_id order per terminal
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SafEntry {
+    pub saf_id: i64,
+    pub saf_type: String,
+    pub tr_dt: String,
+    pub tr_tm: String,
+    pub tr_uniq_no: String,
+    pub trm_id: Option<String>,
+    pub message: String, // Serialized ISO8583 message
+    pub attempts: i32,
+    pub status: String,
+    pub next_attempt_dtm: String, // YYYYMMDDhhmmss
+    pub last_error: Option<String>,
+    pub response_code: Option<String>, // DE39 of the host reply once delivered
+    pub inst_dtm: Option<String>,
+    pub updt_dtm: Option<String>,
+}
+
+impl SafEntry {
+    pub fn saf_type(&self) -> Option<SafType> {
+        SafType::from_str(&self.saf_type)
+    }
+
+    /// Message to send on the next attempt: original MTI first, repeat MTI (0221/0401) after
+    pub fn next_message(&self) -> Result<Iso8583Message, serde_json::Error> {
+        let mut msg: Iso8583Message = serde_json::from_str(&self.message)?;
+        if self.attempts > 0
+            && let Some(repeat_mti) = msg.repeat_mti()
+        {
+            msg.mti = repeat_mti;
+        }
+        Ok(msg)
+    }
+
+    /// Exponential backoff after `attempts` failed attempts, capped at `max_secs`
+    pub fn backoff_secs(attempts: i32, base_secs: i64, max_secs: i64) -> i64 {
+        let exp = attempts.clamp(0, 20) as u32;
+        base_secs.saturating_mul(1 << exp).min(max_secs)
+    }
+
+    /// Next attempt time after `attempts` failed attempts
+    pub fn retry_at(attempts: i32, base_secs: i64, max_secs: i64) -> String {
+        let delay = Self::backoff_secs(attempts, base_secs, max_secs);
+        (Local::now() + Duration::seconds(delay))
+            .format("%Y%m%d%H%M%S")
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(mti: &str, attempts: i32) -> SafEntry {
+        let mut msg = Iso8583Message::new(mti);
+        msg.set_field(11, "000321".to_string());
+
+        SafEntry {
+            saf_id: 1,
+            saf_type: SafType::CompletionAdvice.as_str().to_string(),
+            tr_dt: "20261018".to_string(),
+            tr_tm: "120000".to_string(),
+            tr_uniq_no: "TX1".to_string(),
+            trm_id: Some("TERM0001".to_string()),
+            message: serde_json::to_string(&msg).unwrap(),
+            attempts,
+            status: SafStatus::Pending.as_str().to_string(),
+            next_attempt_dtm: "20261018120000".to_string(),
+            last_error: None,
+            response_code: None,
+            inst_dtm: None,
+            updt_dtm: None,
+        }
+    }
+
+    #[test]
+    fn test_repeat_mti() {
+        assert_eq!(entry("0220", 0).next_message().unwrap().mti, "0220");
+        assert_eq!(entry("0220", 1).next_message().unwrap().mti, "0221");
+        assert_eq!(entry("0400", 0).next_message().unwrap().mti, "0400");
+
+        let repeat = entry("0400", 3).next_message().unwrap();
+        assert_eq!(repeat.mti, "0401");
+        assert_eq!(repeat.get_field(11).map(String::as_str), Some("000321"));
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(SafEntry::backoff_secs(0, 30, 3600), 30);
+        assert_eq!(SafEntry::backoff_secs(1, 30, 3600), 60);
+        assert_eq!(SafEntry::backoff_secs(3, 30, 3600), 240);
+        assert_eq!(SafEntry::backoff_secs(10, 30, 3600), 3600);
+    }
+}
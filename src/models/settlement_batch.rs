@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::models::amount::{Amount, Currency};
+use crate::models::iso8583_message::Iso8583Message;
+use crate::models::transaction::{Iso8583Transaction, TransactionState};
+
+/// Settlement batch status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BatchStatus {
+    /// Accumulating transactions; a failed settlement leaves the batch open
+    Open,
+    /// Settled in balance with the host
+    Closed,
+}
+
+impl BatchStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            BatchStatus::Open => "OPEN",
+            BatchStatus::Closed => "CLOSED",
+        }
+    }
+}
+
+/// Settlement batch of a terminal, with the totals of its last settlement attempt
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SettlementBatch {
+    pub batch_id: i64,
+    pub trm_id: String,
+    pub batch_no: i32,
+    pub status: String,
+    pub currency: String, // ISO 4217 numeric
+    pub debit_cnt: i64,
+    pub debit_amt: i64,
+    pub debit_rev_cnt: i64,
+    pub debit_rev_amt: i64,
+    pub credit_cnt: i64,
+    pub credit_amt: i64,
+    pub credit_rev_cnt: i64,
+    pub credit_rev_amt: i64,
+    pub inquiry_cnt: i64,
+    pub auth_cnt: i64,
+    pub upload_cnt: i32, // Transactions uploaded (0320) after an out-of-balance response
+    pub attempts: i32,
+    pub resp_cd: Option<String>, // DE39 of the last 0510
+    pub open_dtm: String,        // YYYYMMDDhhmmss
+    pub close_dtm: Option<String>,
+    pub updt_dtm: Option<String>,
+    pub busi_dt: Option<String>, // Business date of the last settlement attempt (YYYYMMDD)
+}
+
+impl SettlementBatch {
+    /// Batch number as sent in DE60 (6 digits)
+    pub fn batch_no_de60(&self) -> String {
+        format!("{:06}", self.batch_no)
+    }
+}
+
+/// How a batch transaction counts in the reconciliation totals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TotalsCategory {
+    /// Sales, cashback purchases, cash and completions
+    Debit,
+    /// Refunds
+    Credit,
+    Inquiry,
+    Authorization,
+}
+
+impl TotalsCategory {
+    /// Category of a stored transaction; None for messages that never settle on
+    /// their own (voids, reversals, tip adjustments)
+    pub fn of(tx: &Iso8583Transaction) -> Option<Self> {
+        let mti = tx.msg_typ.as_deref().unwrap_or_default();
+        let processing_code = tx.field_003.as_deref().unwrap_or_default();
+        if mti.starts_with("04") || tx.is_adjustment() {
+            return None;
+        }
+        if processing_code.starts_with("31") {
+            return Some(TotalsCategory::Inquiry);
+        }
+        if mti.starts_with("01") {
+            return Some(TotalsCategory::Authorization);
+        }
+        if processing_code.starts_with("20") {
+            return Some(TotalsCategory::Credit);
+        }
+        Some(TotalsCategory::Debit)
+    }
+}
+
+/// Reconciliation totals of a batch (minor units of the settlement currency)
+/// Voided transactions count as reversals and not in the debit/credit totals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchTotals {
+    pub debit_count: i64,
+    pub debit_amount: i64,
+    pub debit_reversal_count: i64,
+    pub debit_reversal_amount: i64,
+    pub credit_count: i64,
+    pub credit_amount: i64,
+    pub credit_reversal_count: i64,
+    pub credit_reversal_amount: i64,
+    pub inquiry_count: i64,
+    pub authorization_count: i64,
+}
+
+impl BatchTotals {
+    /// Totals of the approved and voided transactions in the settlement currency
+    pub fn from_transactions(transactions: &[Iso8583Transaction], currency: &Currency) -> Self {
+        let mut totals = Self::default();
+        for tx in transactions {
+            if tx
+                .field_049
+                .as_deref()
+                .is_some_and(|c| c != currency.numeric)
+            {
+                continue;
+            }
+            let (Some(category), Some(state)) = (TotalsCategory::of(tx), tx.state()) else {
+                continue;
+            };
+            let amount = tx.settlement_amount().unwrap_or(0);
+            match (category, state) {
+                (TotalsCategory::Debit, TransactionState::Approved) => {
+                    totals.debit_count += 1;
+                    totals.debit_amount += amount;
+                }
+                (TotalsCategory::Debit, TransactionState::Voided) => {
+                    totals.debit_reversal_count += 1;
+                    totals.debit_reversal_amount += amount;
+                }
+                (TotalsCategory::Credit, TransactionState::Approved) => {
+                    totals.credit_count += 1;
+                    totals.credit_amount += amount;
+                }
+                (TotalsCategory::Credit, TransactionState::Voided) => {
+                    totals.credit_reversal_count += 1;
+                    totals.credit_reversal_amount += amount;
+                }
+                (TotalsCategory::Inquiry, TransactionState::Approved) => totals.inquiry_count += 1,
+                (TotalsCategory::Authorization, TransactionState::Approved) => {
+                    totals.authorization_count += 1
+                }
+                _ => {}
+            }
+        }
+        totals
+    }
+
+    /// Net settlement amount: debits less credits (positive is owed to the merchant)
+    pub fn net_amount(&self) -> i64 {
+        self.debit_amount - self.credit_amount
+    }
+
+    /// DE97 Amount, net settlement: C (credit to the merchant) or D, then n16
+    pub fn to_de97(self) -> String {
+        let net = self.net_amount();
+        let sign = if net < 0 { 'D' } else { 'C' };
+        format!("{}{:016}", sign, net.unsigned_abs())
+    }
+
+    /// Totals reported to the terminal, amounts in major units
+    pub fn to_json(self, currency: &'static Currency) -> serde_json::Value {
+        let amount = |minor: i64| Amount::from_minor(minor, currency).to_json();
+        serde_json::json!({
+            "debitCount": self.debit_count,
+            "debitAmount": amount(self.debit_amount),
+            "debitReversalCount": self.debit_reversal_count,
+            "debitReversalAmount": amount(self.debit_reversal_amount),
+            "creditCount": self.credit_count,
+            "creditAmount": amount(self.credit_amount),
+            "creditReversalCount": self.credit_reversal_count,
+            "creditReversalAmount": amount(self.credit_reversal_amount),
+            "inquiryCount": self.inquiry_count,
+            "authorizationCount": self.authorization_count,
+            "netAmount": amount(self.net_amount()),
+            "currency": currency.alpha,
+        })
+    }
+
+    /// Set the reconciliation totals of an 0500: counts in DE74-DE81, amounts in
+    /// DE86-DE89 and the net amount in DE97. No fees are charged at the terminal,
+    /// so DE82-DE85 are not sent
+    pub fn apply(&self, msg: &mut Iso8583Message) {
+        let counts = [
+            (74, self.credit_count),
+            (75, self.credit_reversal_count),
+            (76, self.debit_count),
+            (77, self.debit_reversal_count),
+            (78, 0), // Transfers
+            (79, 0), // Transfer reversals
+            (80, self.inquiry_count),
+            (81, self.authorization_count),
+        ];
+        for (de, count) in counts {
+            msg.set_field(de, format!("{:010}", count));
+        }
+
+        let amounts = [
+            (86, self.credit_amount),
+            (87, self.credit_reversal_amount),
+            (88, self.debit_amount),
+            (89, self.debit_reversal_amount),
+        ];
+        for (de, amount) in amounts {
+            msg.set_field(de, format!("{:016}", amount));
+        }
+
+        msg.set_field(97, self.to_de97());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(
+        mti: &str,
+        processing_code: &str,
+        amount: i64,
+        state: TransactionState,
+    ) -> Iso8583Transaction {
+        let mut tx = Iso8583Transaction::new("000001", mti);
+        tx.field_003 = Some(processing_code.to_string());
+        tx.field_004 = Some(format!("{:012}", amount));
+        tx.field_049 = Some("704".to_string());
+        tx.tr_type = Some(state.as_str().to_string());
+        tx
+    }
+
+    #[test]
+    fn test_batch_totals() {
+        let mut tipped = tx("0200", "000000", 100000, TransactionState::Approved);
+        tipped.tip_amt = Some(15000);
+        let mut tip_adjustment = tx("0220", "020000", 115000, TransactionState::Approved);
+        tip_adjustment.tip_amt = Some(15000);
+        let mut usd = tx("0200", "000000", 2500, TransactionState::Approved);
+        usd.field_049 = Some("840".to_string());
+
+        let transactions = vec![
+            tipped,
+            tip_adjustment,
+            usd,
+            tx("0200", "000000", 50000, TransactionState::Approved),
+            tx("0200", "000000", 30000, TransactionState::Voided),
+            tx("0400", "000000", 30000, TransactionState::Approved),
+            tx("0200", "200000", 20000, TransactionState::Approved),
+            tx("0220", "000000", 70000, TransactionState::Approved),
+            tx("0100", "000000", 90000, TransactionState::Approved),
+            tx("0200", "310000", 0, TransactionState::Approved),
+            tx("0200", "000000", 10000, TransactionState::Declined),
+        ];
+        let totals = BatchTotals::from_transactions(&transactions, Currency::vnd());
+
+        assert_eq!(totals.debit_count, 3);
+        assert_eq!(totals.debit_amount, 235000);
+        assert_eq!(totals.debit_reversal_count, 1);
+        assert_eq!(totals.debit_reversal_amount, 30000);
+        assert_eq!(totals.credit_count, 1);
+        assert_eq!(totals.credit_amount, 20000);
+        assert_eq!(totals.inquiry_count, 1);
+        assert_eq!(totals.authorization_count, 1);
+        assert_eq!(totals.net_amount(), 215000);
+    }
+
+    #[test]
+    fn test_totals_in_settlement_request() {
+        let totals = BatchTotals {
+            debit_count: 3,
+            debit_amount: 235000,
+            credit_count: 1,
+            credit_amount: 300000,
+            ..Default::default()
+        };
+        let mut msg = Iso8583Message::new("0500");
+        totals.apply(&mut msg);
+
+        assert_eq!(msg.get_field(74).unwrap(), "0000000001");
+        assert_eq!(msg.get_field(76).unwrap(), "0000000003");
+        assert_eq!(msg.get_field(88).unwrap(), "0000000000235000");
+        assert_eq!(msg.get_field(97).unwrap(), "D0000000000065000");
+        assert!(msg.get_field(82).is_none());
+    }
+}
@@ -0,0 +1,217 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::FromRow;
+use std::collections::HashMap;
+
+/// Longest receipt header line a terminal printer takes
+const MAX_RECEIPT_LINE: usize = 48;
+
+/// EMV application the terminal supports
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EmvAid {
+    /// Application identifier (hex, 5-16 bytes)
+    pub aid: String,
+    pub label: Option<String>,
+    /// Application version number (9F09)
+    pub app_version: Option<String>,
+    /// Terminal floor limit in major units of the terminal currency
+    pub floor_limit: Option<String>,
+    /// Terminal action codes (hex, 5 bytes)
+    pub tac_default: Option<String>,
+    pub tac_denial: Option<String>,
+    pub tac_online: Option<String>,
+    /// AID may be selected by partial name match
+    #[serde(default)]
+    pub partial_selection: bool,
+}
+
+/// Certification authority public key for offline data authentication
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Capk {
+    /// Registered application provider identifier (hex, 5 bytes)
+    pub rid: String,
+    /// Key index (hex, 1 byte)
+    pub index: String,
+    /// Modulus (hex)
+    pub modulus: String,
+    /// Exponent (hex): 03 or 010001
+    pub exponent: String,
+    /// Expiry date (YYMMDD)
+    pub expiry: Option<String>,
+}
+
+/// Host the terminal connects to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HostEndpoint {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub tls: bool,
+}
+
+/// Parameters downloaded to the terminal
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalParameters {
+    #[serde(default)]
+    pub aids: Vec<EmvAid>,
+    #[serde(default)]
+    pub capks: Vec<Capk>,
+    /// Floor limits by ISO 4217 alpha code, in major units
+    #[serde(default)]
+    pub floor_limits: HashMap<String, String>,
+    /// Hosts in order of preference
+    #[serde(default)]
+    pub hosts: Vec<HostEndpoint>,
+    /// Lines printed at the top of every receipt
+    #[serde(default)]
+    pub receipt_header: Vec<String>,
+}
+
+impl TerminalParameters {
+    /// Check the parameters before they are published or downloaded
+    pub fn validate(&self) -> Result<(), String> {
+        for aid in &self.aids {
+            if !is_hex(&aid.aid, 5, 16) {
+                return Err(format!("invalid AID {}", aid.aid));
+            }
+            for tac in [&aid.tac_default, &aid.tac_denial, &aid.tac_online]
+                .into_iter()
+                .flatten()
+            {
+                if !is_hex(tac, 5, 5) {
+                    return Err(format!("invalid TAC {} of AID {}", tac, aid.aid));
+                }
+            }
+        }
+        for capk in &self.capks {
+            if !is_hex(&capk.rid, 5, 5) || !is_hex(&capk.index, 1, 1) {
+                return Err(format!("invalid CAPK {}/{}", capk.rid, capk.index));
+            }
+            if !is_hex(&capk.modulus, 64, 248) || !matches!(capk.exponent.as_str(), "03" | "010001")
+            {
+                return Err(format!("invalid CAPK key {}/{}", capk.rid, capk.index));
+            }
+        }
+        if self.hosts.iter().any(|h| h.host.is_empty() || h.port == 0) {
+            return Err("invalid host endpoint".to_string());
+        }
+        if let Some(line) = self
+            .receipt_header
+            .iter()
+            .find(|line| line.chars().count() > MAX_RECEIPT_LINE)
+        {
+            return Err(format!("receipt header line too long: {}", line));
+        }
+        Ok(())
+    }
+}
+
+/// Hex string of `min..=max` bytes
+fn is_hex(value: &str, min: usize, max: usize) -> bool {
+    value.len().is_multiple_of(2)
+        && (min * 2..=max * 2).contains(&value.len())
+        && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Published version of a parameter set; versions are never changed once published
+#[derive(Debug, Clone, FromRow)]
+pub struct ParamSet {
+    pub param_set_id: String,
+    pub version: i32,
+    pub parameters: String, // TerminalParameters JSON
+}
+
+impl ParamSet {
+    pub fn parameters(&self) -> Result<TerminalParameters, String> {
+        let parameters: TerminalParameters =
+            serde_json::from_str(&self.parameters).map_err(|e| e.to_string())?;
+        parameters.validate()?;
+        Ok(parameters)
+    }
+}
+
+/// Parameter content split into download blocks of `block_size` characters
+#[derive(Debug, Clone)]
+pub struct ParamDownload {
+    blocks: Vec<String>,
+    /// SHA-256 (hex) of the whole content, checked by the terminal after reassembly
+    pub checksum: String,
+}
+
+impl ParamDownload {
+    pub fn new(content: &str, block_size: usize) -> Self {
+        let chars: Vec<char> = content.chars().collect();
+        Self {
+            blocks: chars
+                .chunks(block_size.max(1))
+                .map(|block| block.iter().collect())
+                .collect(),
+            checksum: hex::encode_upper(Sha256::digest(content.as_bytes())),
+        }
+    }
+
+    pub fn block(&self, index: usize) -> Option<&str> {
+        self.blocks.get(index).map(String::as_str)
+    }
+
+    pub fn total_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+}
+
+/// Terminal logon and parameter state
+#[derive(Debug, Clone, FromRow)]
+pub struct ManagedTerminal {
+    pub trm_id: String,
+    pub status: String,
+    pub logon_key: Option<String>, // Hex HMAC key the terminal signs its logon challenge with
+    pub param_set_id: String,
+    pub param_version: Option<i32>, // Version the terminal confirmed it applied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_parameters() {
+        let parameters: TerminalParameters = serde_json::from_value(serde_json::json!({
+            "aids": [{"aid": "A0000000031010", "label": "VISA", "tacDenial": "0010000000"}],
+            "capks": [{"rid": "A000000003", "index": "92", "exponent": "03",
+                       "modulus": "996AF56F".repeat(44)}],
+            "floorLimits": {"VND": "1000000"},
+            "hosts": [{"host": "10.0.0.1", "port": 8583, "tls": true}],
+            "receiptHeader": ["PHO HANOI", "DISTRICT 1, HCMC"]
+        }))
+        .unwrap();
+        assert_eq!(parameters.validate(), Ok(()));
+
+        let mut bad_aid = parameters.clone();
+        bad_aid.aids[0].aid = "A0000".to_string();
+        assert!(bad_aid.validate().is_err());
+
+        let mut bad_capk = parameters.clone();
+        bad_capk.capks[0].exponent = "05".to_string();
+        assert!(bad_capk.validate().is_err());
+
+        let mut long_header = parameters;
+        long_header.receipt_header.push("X".repeat(49));
+        assert!(long_header.validate().is_err());
+    }
+
+    #[test]
+    fn test_download_blocks() {
+        let download = ParamDownload::new("{\"receiptHeader\":[\"Phở\"]}", 10);
+        assert_eq!(download.total_blocks(), 3);
+        let reassembled: String = (0..download.total_blocks())
+            .map(|i| download.block(i).unwrap())
+            .collect();
+        assert_eq!(reassembled, "{\"receiptHeader\":[\"Phở\"]}");
+        assert!(download.block(3).is_none());
+        assert_eq!(download.checksum.len(), 64);
+    }
+}
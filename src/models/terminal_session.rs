@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+/// Logon challenge waiting for the terminal's answer
+#[derive(Debug, Clone)]
+struct PendingChallenge {
+    trm_id: String,
+    challenge: String,
+    issued_at: Instant,
+}
+
+/// Logon state of one terminal connection
+/// A connection is bound to the first terminal that logs on over it; requests for
+/// any other terminal ID are refused until the connection closes
+#[derive(Debug, Default)]
+pub struct TerminalSession {
+    trm_id: Option<String>,
+    pending: Option<PendingChallenge>,
+}
+
+impl TerminalSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Terminal the connection is logged on as
+    pub fn trm_id(&self) -> Option<&str> {
+        self.trm_id.as_deref()
+    }
+
+    pub fn is_logged_on(&self) -> bool {
+        self.trm_id.is_some()
+    }
+
+    /// Connection is logged on as the given terminal
+    pub fn authorizes(&self, trm_id: &str) -> bool {
+        self.trm_id.as_deref() == Some(trm_id)
+    }
+
+    /// Terminal may log on over this connection
+    pub fn can_bind(&self, trm_id: &str) -> bool {
+        self.trm_id.as_deref().is_none_or(|bound| bound == trm_id)
+    }
+
+    /// Remember the challenge sent to a terminal, replacing any earlier one
+    pub fn set_challenge(&mut self, trm_id: &str, challenge: String) {
+        self.pending = Some(PendingChallenge {
+            trm_id: trm_id.to_string(),
+            challenge,
+            issued_at: Instant::now(),
+        });
+    }
+
+    /// Challenge issued to the terminal within `ttl`; each challenge is answered once
+    pub fn take_challenge(&mut self, trm_id: &str, ttl: Duration) -> Option<String> {
+        self.pending
+            .take()
+            .filter(|p| p.trm_id == trm_id && p.issued_at.elapsed() < ttl)
+            .map(|p| p.challenge)
+    }
+
+    pub fn bind(&mut self, trm_id: &str) {
+        self.trm_id = Some(trm_id.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_binding() {
+        let ttl = Duration::from_secs(60);
+        let mut session = TerminalSession::new();
+        assert!(!session.is_logged_on());
+        assert!(session.can_bind("TERM0001"));
+
+        session.set_challenge("TERM0001", "ABCD".to_string());
+        assert_eq!(session.take_challenge("TERM0002", ttl), None);
+        // An answer for another terminal burns the challenge
+        session.set_challenge("TERM0001", "ABCD".to_string());
+        assert_eq!(
+            session.take_challenge("TERM0001", ttl),
+            Some("ABCD".to_string())
+        );
+        assert_eq!(session.take_challenge("TERM0001", ttl), None);
+
+        session.bind("TERM0001");
+        assert!(session.authorizes("TERM0001"));
+        assert!(!session.authorizes("TERM0002"));
+        assert!(!session.can_bind("TERM0002"));
+    }
+}
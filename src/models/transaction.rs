@@ -1,3 +1,4 @@
+use crate::models::amount::{Amount, Currency};
 use chrono::Local;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
@@ -10,8 +11,11 @@ pub enum TransactionState {
     Approved,
     Declined,
     Timeout,
+    /// Queued for store-and-forward after a host timeout, waiting for the host
+    Stored,
     Reversed,
     Voided,
+    Settled,
     Failed,
 }
 
@@ -23,8 +27,10 @@ impl TransactionState {
             TransactionState::Approved => "APPROVED",
             TransactionState::Declined => "DECLINED",
             TransactionState::Timeout => "TIMEOUT",
+            TransactionState::Stored => "STORED",
             TransactionState::Reversed => "REVERSED",
             TransactionState::Voided => "VOIDED",
+            TransactionState::Settled => "SETTLED",
             TransactionState::Failed => "FAILED",
         }
     }
@@ -36,12 +42,120 @@ impl TransactionState {
             "APPROVED" => Some(TransactionState::Approved),
             "DECLINED" => Some(TransactionState::Declined),
             "TIMEOUT" => Some(TransactionState::Timeout),
+            "STORED" => Some(TransactionState::Stored),
             "REVERSED" => Some(TransactionState::Reversed),
             "VOIDED" => Some(TransactionState::Voided),
+            "SETTLED" => Some(TransactionState::Settled),
             "FAILED" => Some(TransactionState::Failed),
             _ => None,
         }
     }
+
+    /// State machine: the state reached when `event` happens in this state
+    /// Returns None for illegal transitions
+    pub fn on(&self, event: TransactionEvent) -> Option<TransactionState> {
+        use TransactionEvent as E;
+        use TransactionState as S;
+
+        match (self, event) {
+            (S::Created, E::Send) => Some(S::Sent),
+            (S::Created | S::Sent, E::Fail) => Some(S::Failed),
+            (S::Sent | S::Stored, E::Approve) => Some(S::Approved),
+            (S::Created, E::ApproveOffline) => Some(S::Approved),
+            (S::Sent, E::StandIn) => Some(S::Approved),
+            (S::Sent | S::Stored, E::Decline) => Some(S::Declined),
+            (S::Sent, E::TimeOut) => Some(S::Timeout),
+            (S::Sent, E::Store) => Some(S::Stored),
+            (S::Approved | S::Timeout, E::Reverse) => Some(S::Reversed),
+            (S::Approved, E::Void) => Some(S::Voided),
+            (S::Approved, E::Settle) => Some(S::Settled),
+            _ => None,
+        }
+    }
+}
+
+/// Events driving the transaction state machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionEvent {
+    /// Request sent to the host
+    Send,
+    /// Host approved
+    Approve,
+    /// Card approved offline (EMV TC); the host is advised later
+    ApproveOffline,
+    /// Approved in stand-in after the host did not answer; the host is advised later
+    StandIn,
+    /// Host declined
+    Decline,
+    /// No host response in time
+    TimeOut,
+    /// Queued for store-and-forward; approved once the host acknowledges it
+    Store,
+    /// Reversal confirmed by the host
+    Reverse,
+    /// Void approved
+    Void,
+    /// Included in a closed settlement batch
+    Settle,
+    /// Processing error
+    Fail,
+}
+
+impl TransactionEvent {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TransactionEvent::Send => "SEND",
+            TransactionEvent::Approve => "APPROVE",
+            TransactionEvent::ApproveOffline => "APPROVE_OFFLINE",
+            TransactionEvent::StandIn => "STAND_IN",
+            TransactionEvent::Decline => "DECLINE",
+            TransactionEvent::TimeOut => "TIME_OUT",
+            TransactionEvent::Store => "STORE",
+            TransactionEvent::Reverse => "REVERSE",
+            TransactionEvent::Void => "VOID",
+            TransactionEvent::Settle => "SETTLE",
+            TransactionEvent::Fail => "FAIL",
+        }
+    }
+
+    /// Event corresponding to the outcome of a host exchange
+    pub fn from_outcome(state: &TransactionState) -> TransactionEvent {
+        match state {
+            TransactionState::Approved => TransactionEvent::Approve,
+            TransactionState::Declined => TransactionEvent::Decline,
+            TransactionState::Timeout => TransactionEvent::TimeOut,
+            _ => TransactionEvent::Fail,
+        }
+    }
+
+    /// Guard evaluated against the current record before the transition
+    pub fn guard(&self, tx: &Iso8583Transaction) -> Result<(), String> {
+        match self {
+            TransactionEvent::Void if tx.refund_amt.unwrap_or(0) > 0 => {
+                Err("transaction has refunds and cannot be voided".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// State change requested by a component, recorded in the state history
+#[derive(Debug, Clone)]
+pub struct StateTransition<'a> {
+    pub event: TransactionEvent,
+    /// Component requesting the change
+    pub actor: &'a str,
+    pub reason: &'a str,
+}
+
+impl<'a> StateTransition<'a> {
+    pub fn new(event: TransactionEvent, actor: &'a str, reason: &'a str) -> Self {
+        Self {
+            event,
+            actor,
+            reason,
+        }
+    }
 }
 
 /// ISO8583 Transaction Record
@@ -61,11 +175,14 @@ pub struct Iso8583Transaction {
     pub field_002: Option<String>, // PAN
     pub field_003: Option<String>, // Processing Code
     pub field_004: Option<String>, // Amount
+    pub field_006: Option<String>, // Cardholder Billing Amount (DCC)
     pub field_007: Option<String>, // Transmission Date/Time
+    pub field_010: Option<String>, // Conversion Rate, Cardholder Billing (DCC)
     pub field_011: Option<String>, // STAN
     pub field_012: Option<String>, // Time
     pub field_013: Option<String>, // Date
     pub field_014: Option<String>, // Expiration Date
+    pub field_015: Option<String>, // Settlement Date (MMDD of the business date)
     pub field_022: Option<String>, // POS Entry Mode
     pub field_023: Option<String>, // Card Sequence Number
     pub field_025: Option<String>, // POS Condition Code
@@ -78,6 +195,7 @@ pub struct Iso8583Transaction {
     pub field_042: Option<String>, // Merchant ID
     pub field_043: Option<String>, // Merchant Name/Location
     pub field_049: Option<String>, // Currency Code
+    pub field_051: Option<String>, // Currency Code, Cardholder Billing (DCC)
     pub field_052: Option<String>, // PIN Data
     pub field_054: Option<String>, // Additional Amounts
     pub field_055: Option<String>, // EMV Data
@@ -98,6 +216,22 @@ pub struct Iso8583Transaction {
     pub inst_dtm: Option<String>, // Insert datetime
     pub updt_dtm: Option<String>, // Update datetime
     pub tr_type: Option<String>,  // Transaction type/state
+
+    // Link to the original transaction (void, refund, completion, reversal)
+    pub orig_tr_dt: Option<String>,
+    pub orig_tr_tm: Option<String>,
+    pub orig_tr_uniq_no: Option<String>,
+    pub refund_amt: Option<i64>, // Cumulative refunded amount (minor units), on the original
+    pub resp_json: Option<String>, // Final terminal response, replayed to retransmissions
+    pub apprv_amt: Option<i64>,  // Approved amount (minor units), below DE4 on partial approval
+    pub tip_amt: Option<i64>,    // Tip (minor units); on an adjustment, the tip it sets
+    pub cashback_amt: Option<i64>, // Cash handed out on a purchase with cashback (minor units)
+    pub offline_flags: Option<String>, // Review flags of an offline approval, comma separated
+    pub card_scheme: Option<String>, // Scheme from the BIN table
+    pub host_group: Option<String>, // Host the transaction was routed to; follow-ups use the same
+    pub stand_in: bool,             // Approved in stand-in, counts towards STIP exposure
+    pub risk_flags: Option<String>, // Risk rules that flagged the transaction, comma separated
+    pub busi_dt: Option<String>,    // Business date (YYYYMMDD), set by the business calendar
 }
 
 impl Iso8583Transaction {
@@ -117,11 +251,14 @@ impl Iso8583Transaction {
             field_002: None,
             field_003: None,
             field_004: None,
+            field_006: None,
             field_007: None,
+            field_010: None,
             field_011: Some(stan.to_string()),
             field_012: Some(now.format("%H%M%S").to_string()),
             field_013: Some(now.format("%m%d").to_string()),
             field_014: None,
+            field_015: None,
             field_022: None,
             field_023: None,
             field_025: None,
@@ -134,6 +271,7 @@ impl Iso8583Transaction {
             field_042: None,
             field_043: None,
             field_049: None,
+            field_051: None,
             field_052: None,
             field_054: None,
             field_055: None,
@@ -153,7 +291,89 @@ impl Iso8583Transaction {
             inst_dtm: Some(now.format("%Y%m%d%H%M%S").to_string()),
             updt_dtm: None,
             tr_type: Some(TransactionState::Created.as_str().to_string()),
+            orig_tr_dt: None,
+            orig_tr_tm: None,
+            orig_tr_uniq_no: None,
+            refund_amt: None,
+            resp_json: None,
+            apprv_amt: None,
+            tip_amt: None,
+            cashback_amt: None,
+            offline_flags: None,
+            card_scheme: None,
+            host_group: None,
+            stand_in: false,
+            risk_flags: None,
+            busi_dt: None,
+        }
+    }
+
+    /// Link this follow-up record to its original transaction
+    pub fn link_original(&mut self, original: &Iso8583Transaction) {
+        self.orig_tr_dt = Some(original.tr_dt.clone());
+        self.orig_tr_tm = Some(original.tr_tm.clone());
+        self.orig_tr_uniq_no = original.tr_uniq_no.clone();
+    }
+
+    /// Current transaction state (from tr_type)
+    pub fn state(&self) -> Option<TransactionState> {
+        self.tr_type.as_deref().and_then(TransactionState::from_str)
+    }
+
+    /// Transaction amount (DE4) in its currency (DE49, VND when absent)
+    pub fn amount(&self) -> Option<Amount> {
+        let currency = self
+            .field_049
+            .as_deref()
+            .and_then(Currency::from_code)
+            .unwrap_or(Currency::vnd());
+        self.field_004
+            .as_deref()
+            .and_then(|a| Amount::from_iso(a, currency).ok())
+    }
+
+    /// Transaction amount in minor units (DE4)
+    pub fn amount_minor(&self) -> Option<i64> {
+        self.field_004.as_deref().and_then(|a| a.parse().ok())
+    }
+
+    /// Amount actually approved (minor units): the partial amount, otherwise DE4
+    /// Cashback is not part of it, DE4 of a purchase with cashback carries both
+    pub fn approved_amount(&self) -> Option<i64> {
+        self.apprv_amt.or_else(|| {
+            self.amount_minor()
+                .map(|amount| amount - self.cashback_amt.unwrap_or(0))
+        })
+    }
+
+    /// Adjustment advice (DE3 02xxxx), e.g. a tip adjustment of an earlier sale
+    pub fn is_adjustment(&self) -> bool {
+        self.field_003
+            .as_deref()
+            .is_some_and(|pc| pc.starts_with("02"))
+    }
+
+    /// Refund (DE3 20xxxx); its approved amount is counted in the sale's `refund_amt`
+    pub fn is_refund(&self) -> bool {
+        self.field_003
+            .as_deref()
+            .is_some_and(|pc| pc.starts_with("20"))
+    }
+
+    /// Amount counted in settlement totals (minor units): approved amount plus
+    /// cashback and tip. Adjustments count through the sale they adjust
+    pub fn settlement_amount(&self) -> Option<i64> {
+        if self.is_adjustment() {
+            return Some(0);
         }
+        self.approved_amount()
+            .map(|amount| amount + self.cashback_amt.unwrap_or(0) + self.tip_amt.unwrap_or(0))
+    }
+
+    /// Amount still available for refund (minor units)
+    pub fn refundable_amount(&self) -> Option<i64> {
+        self.approved_amount()
+            .map(|amount| amount - self.refund_amt.unwrap_or(0))
     }
 
     /// Set field value by data element number
@@ -164,11 +384,14 @@ impl Iso8583Transaction {
             2 => self.field_002 = value,
             3 => self.field_003 = value,
             4 => self.field_004 = value,
+            6 => self.field_006 = value,
             7 => self.field_007 = value,
+            10 => self.field_010 = value,
             11 => self.field_011 = value,
             12 => self.field_012 = value,
             13 => self.field_013 = value,
             14 => self.field_014 = value,
+            15 => self.field_015 = value,
             22 => self.field_022 = value,
             23 => self.field_023 = value,
             25 => self.field_025 = value,
@@ -184,6 +407,7 @@ impl Iso8583Transaction {
             42 => self.field_042 = value,
             43 => self.field_043 = value,
             49 => self.field_049 = value,
+            51 => self.field_051 = value,
             52 => self.field_052 = value,
             54 => self.field_054 = value,
             55 => self.field_055 = value,
@@ -212,11 +436,14 @@ impl Iso8583Transaction {
             2 => self.field_002.as_ref(),
             3 => self.field_003.as_ref(),
             4 => self.field_004.as_ref(),
+            6 => self.field_006.as_ref(),
             7 => self.field_007.as_ref(),
+            10 => self.field_010.as_ref(),
             11 => self.field_011.as_ref(),
             12 => self.field_012.as_ref(),
             13 => self.field_013.as_ref(),
             14 => self.field_014.as_ref(),
+            15 => self.field_015.as_ref(),
             22 => self.field_022.as_ref(),
             23 => self.field_023.as_ref(),
             25 => self.field_025.as_ref(),
@@ -229,6 +456,7 @@ impl Iso8583Transaction {
             42 => self.field_042.as_ref(),
             43 => self.field_043.as_ref(),
             49 => self.field_049.as_ref(),
+            51 => self.field_051.as_ref(),
             52 => self.field_052.as_ref(),
             54 => self.field_054.as_ref(),
             55 => self.field_055.as_ref(),
@@ -249,3 +477,112 @@ impl Iso8583Transaction {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_machine_transitions() {
+        use TransactionEvent as E;
+        use TransactionState as S;
+
+        assert_eq!(S::Created.on(E::Send), Some(S::Sent));
+        assert_eq!(S::Sent.on(E::Approve), Some(S::Approved));
+        assert_eq!(S::Sent.on(E::TimeOut), Some(S::Timeout));
+        assert_eq!(S::Timeout.on(E::Reverse), Some(S::Reversed));
+        assert_eq!(S::Approved.on(E::Void), Some(S::Voided));
+        assert_eq!(S::Approved.on(E::Settle), Some(S::Settled));
+
+        // Illegal transitions
+        assert_eq!(S::Approved.on(E::Send), None);
+        assert_eq!(S::Voided.on(E::Void), None);
+        assert_eq!(S::Settled.on(E::Void), None);
+        assert_eq!(S::Declined.on(E::Approve), None);
+        assert_eq!(S::Sent.on(E::Void), None);
+    }
+
+    #[test]
+    fn test_stored_reversal_completes_on_delivery() {
+        use TransactionEvent as E;
+        use TransactionState as S;
+
+        // Host timeout: the 0400 is stored and the original stays untouched
+        let reversal = S::Created.on(E::Send).and_then(|s| s.on(E::Store));
+        assert_eq!(reversal, Some(S::Stored));
+        assert_eq!(S::Stored.on(E::Reverse), None);
+        assert_eq!(S::Stored.on(E::Void), None);
+
+        // SAF delivery: the 0400 is approved and the original reversed, once
+        assert_eq!(S::Stored.on(E::Approve), Some(S::Approved));
+        assert_eq!(S::Approved.on(E::Reverse), Some(S::Reversed));
+        assert_eq!(S::Reversed.on(E::Reverse), None);
+    }
+
+    #[test]
+    fn test_stored_void_declined_on_delivery() {
+        use TransactionEvent as E;
+        use TransactionState as S;
+
+        // SAF delivery answered with a decline: the stored void ends declined and the
+        // sale stays approved
+        assert_eq!(S::Stored.on(E::Decline), Some(S::Declined));
+        assert_eq!(S::Declined.on(E::Void), None);
+        assert_eq!(S::Approved.on(E::Void), Some(S::Voided));
+    }
+
+    #[test]
+    fn test_final_states_have_no_transitions() {
+        use TransactionEvent as E;
+
+        let events = [
+            E::Send,
+            E::Approve,
+            E::Decline,
+            E::TimeOut,
+            E::Store,
+            E::Reverse,
+            E::Void,
+            E::Settle,
+            E::Fail,
+        ];
+        for state in [
+            TransactionState::Declined,
+            TransactionState::Reversed,
+            TransactionState::Voided,
+            TransactionState::Settled,
+            TransactionState::Failed,
+        ] {
+            assert!(events.iter().all(|e| state.on(*e).is_none()));
+        }
+    }
+
+    #[test]
+    fn test_settlement_amount_includes_tip_and_cashback() {
+        let mut sale = Iso8583Transaction::new("000001", "0200");
+        sale.field_003 = Some("090000".to_string());
+        sale.field_004 = Some("000000150000".to_string());
+        sale.cashback_amt = Some(50000);
+        assert_eq!(sale.approved_amount(), Some(100000));
+        assert_eq!(sale.settlement_amount(), Some(150000));
+
+        sale.tip_amt = Some(15000);
+        assert_eq!(sale.settlement_amount(), Some(165000));
+
+        let mut adjustment = Iso8583Transaction::new("000002", "0220");
+        adjustment.field_003 = Some("020000".to_string());
+        adjustment.field_004 = Some("000000115000".to_string());
+        adjustment.tip_amt = Some(15000);
+        assert_eq!(adjustment.settlement_amount(), Some(0));
+    }
+
+    #[test]
+    fn test_void_guard_rejects_refunded_sale() {
+        let mut tx = Iso8583Transaction::new("000001", "0200");
+        assert!(TransactionEvent::Void.guard(&tx).is_ok());
+
+        tx.refund_amt = Some(100);
+        assert!(TransactionEvent::Void.guard(&tx).is_err());
+        assert!(TransactionEvent::Reverse.guard(&tx).is_ok());
+    }
+}
@@ -0,0 +1,27 @@
+use crate::models::bin_range::BinRange;
+use sqlx::PgPool;
+
+/// BIN table repository
+pub struct BinRepository {
+    pub pool: PgPool,
+}
+
+impl BinRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Active BIN ranges
+    pub async fn find_active(&self) -> Result<Vec<BinRange>, sqlx::Error> {
+        sqlx::query_as::<_, BinRange>(
+            r#"
+            SELECT low_bin, high_bin, scheme, issuer, country, card_type, host_group
+            FROM bin_range
+            WHERE active = TRUE
+            ORDER BY low_bin
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+}
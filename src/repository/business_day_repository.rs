@@ -0,0 +1,45 @@
+use chrono::Local;
+use sqlx::PgPool;
+
+/// Business days opened by cutovers
+pub struct BusinessDayRepository {
+    pub pool: PgPool,
+}
+
+impl BusinessDayRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Latest business date opened (YYYYMMDD)
+    pub async fn current(&self) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT busi_dt FROM business_day
+            ORDER BY busi_dt DESC
+            LIMIT 1
+            "#,
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Record the opening of a business day; opening it again is a no-op
+    pub async fn open(&self, busi_dt: &str, source: &str) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+        sqlx::query(
+            r#"
+            INSERT INTO business_day (busi_dt, source, open_dtm)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (busi_dt) DO NOTHING
+            "#,
+        )
+        .bind(busi_dt)
+        .bind(source)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -1,6 +1,28 @@
-use crate::models::transaction::{Iso8583Transaction, TransactionState};
+use crate::models::transaction::{
+    Iso8583Transaction, StateTransition, TransactionEvent, TransactionState,
+};
 use chrono::Local;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres};
+use thiserror::Error;
+
+/// Errors of state-changing repository operations
+#[derive(Debug, Error)]
+pub enum TransitionError {
+    #[error("Transaction not found")]
+    NotFound,
+
+    #[error("Illegal state transition: {event:?} in state {from:?}")]
+    Illegal {
+        from: TransactionState,
+        event: TransactionEvent,
+    },
+
+    #[error("Transition rejected: {0}")]
+    Guard(String),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
 
 /// Transaction Repository for database operations
 pub struct CardTransactionRepository {
@@ -12,8 +34,10 @@ impl CardTransactionRepository {
         Self { pool }
     }
 
-    /// Insert a new transaction
-    pub async fn insert(&self, tx: &Iso8583Transaction) -> Result<(), sqlx::Error> {
+    /// Insert a new transaction and record its initial state
+    pub async fn insert(&self, tx: &Iso8583Transaction, actor: &str) -> Result<(), sqlx::Error> {
+        let mut db_tx = self.pool.begin().await?;
+
         sqlx::query(
             r#"
             INSERT INTO iso8583_payment (
@@ -26,7 +50,12 @@ impl CardTransactionRepository {
                 field_060, field_061, field_062, field_063, field_064,
                 field_070, field_090, field_095, field_102, field_103,
                 field_123, field_127, field_128,
-                inst_dtm, tr_type
+                inst_dtm, tr_type,
+                orig_tr_dt, orig_tr_tm, orig_tr_uniq_no,
+                field_006, field_010, field_051,
+                tip_amt, cashback_amt, offline_flags,
+                card_scheme, host_group, risk_flags,
+                field_015, busi_dt
             )
             VALUES (
                 $1, $2, $3, $4, $5,
@@ -38,7 +67,12 @@ impl CardTransactionRepository {
                 $31, $32, $33, $34, $35,
                 $36, $37, $38, $39, $40,
                 $41, $42, $43,
-                $44, $45
+                $44, $45,
+                $46, $47, $48,
+                $49, $50, $51,
+                $52, $53, $54,
+                $55, $56, $57,
+                $58, $59
             )
             "#,
         )
@@ -87,13 +121,43 @@ impl CardTransactionRepository {
         .bind(&tx.field_128)
         .bind(&tx.inst_dtm)
         .bind(&tx.tr_type)
-        .execute(&self.pool)
+        .bind(&tx.orig_tr_dt)
+        .bind(&tx.orig_tr_tm)
+        .bind(&tx.orig_tr_uniq_no)
+        .bind(&tx.field_006)
+        .bind(&tx.field_010)
+        .bind(&tx.field_051)
+        .bind(tx.tip_amt)
+        .bind(tx.cashback_amt)
+        .bind(&tx.offline_flags)
+        .bind(&tx.card_scheme)
+        .bind(&tx.host_group)
+        .bind(&tx.risk_flags)
+        .bind(&tx.field_015)
+        .bind(&tx.busi_dt)
+        .execute(&mut *db_tx)
         .await?;
 
+        Self::record_history(
+            &mut db_tx,
+            &tx.tr_dt,
+            &tx.tr_tm,
+            tx.tr_uniq_no.as_deref().unwrap_or_default(),
+            None,
+            tx.tr_type.as_deref().unwrap_or(TransactionState::Created.as_str()),
+            None,
+            actor,
+            "created",
+        )
+        .await?;
+
+        db_tx.commit().await?;
         Ok(())
     }
 
-    /// Update transaction with response data
+    /// Update transaction with response data and apply a state transition
+    /// The current row is locked, the transition checked against the state machine
+    /// and recorded in the state history; illegal transitions are rejected
     pub async fn update_response(
         &self,
         tr_dt: &str,
@@ -102,9 +166,36 @@ impl CardTransactionRepository {
         response_code: Option<&str>,
         auth_code: Option<&str>,
         rrn: Option<&str>,
-        state: &TransactionState,
-    ) -> Result<(), sqlx::Error> {
+        transition: &StateTransition<'_>,
+    ) -> Result<TransactionState, TransitionError> {
         let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+        let mut db_tx = self.pool.begin().await?;
+
+        let current = sqlx::query_as::<_, Iso8583Transaction>(
+            r#"
+            SELECT * FROM iso8583_payment
+            WHERE tr_dt = $1 AND tr_tm = $2 AND tr_uniq_no = $3
+            FOR UPDATE
+            "#,
+        )
+        .bind(tr_dt)
+        .bind(tr_tm)
+        .bind(tr_uniq_no)
+        .fetch_optional(&mut *db_tx)
+        .await?
+        .ok_or(TransitionError::NotFound)?;
+
+        let from = current.state().unwrap_or(TransactionState::Created);
+        let to = from
+            .on(transition.event)
+            .ok_or_else(|| TransitionError::Illegal {
+                from: from.clone(),
+                event: transition.event,
+            })?;
+        transition
+            .event
+            .guard(&current)
+            .map_err(TransitionError::Guard)?;
 
         sqlx::query(
             r#"
@@ -123,9 +214,73 @@ impl CardTransactionRepository {
         .bind(rrn)
         .bind(auth_code)
         .bind(response_code)
-        .bind(state.as_str())
+        .bind(to.as_str())
         .bind(now)
-        .execute(&self.pool)
+        .execute(&mut *db_tx)
+        .await?;
+
+        Self::record_history(
+            &mut db_tx,
+            tr_dt,
+            tr_tm,
+            tr_uniq_no,
+            Some(from.as_str()),
+            to.as_str(),
+            Some(transition.event.as_str()),
+            transition.actor,
+            transition.reason,
+        )
+        .await?;
+
+        db_tx.commit().await?;
+        Ok(to)
+    }
+
+    /// Apply a state transition without response data (e.g. original marked VOIDED)
+    pub async fn update_state(
+        &self,
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+        transition: &StateTransition<'_>,
+    ) -> Result<TransactionState, TransitionError> {
+        self.update_response(tr_dt, tr_tm, tr_uniq_no, None, None, None, transition)
+            .await
+    }
+
+    /// Append a row to the state history
+    #[allow(clippy::too_many_arguments)]
+    async fn record_history(
+        db_tx: &mut sqlx::Transaction<'_, Postgres>,
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+        from_state: Option<&str>,
+        to_state: &str,
+        event: Option<&str>,
+        actor: &str,
+        reason: &str,
+    ) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S%3f").to_string();
+
+        sqlx::query(
+            r#"
+            INSERT INTO iso8583_payment_state_history (
+                tr_dt, tr_tm, tr_uniq_no, from_state, to_state, event, actor, reason, chg_dtm
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(tr_dt)
+        .bind(tr_tm)
+        .bind(tr_uniq_no)
+        .bind(from_state)
+        .bind(to_state)
+        .bind(event)
+        .bind(actor)
+        .bind(reason)
+        .bind(now)
+        .execute(&mut **db_tx)
         .await?;
 
         Ok(())
@@ -176,6 +331,267 @@ impl CardTransactionRepository {
         Ok(result)
     }
     
+    /// Find the original transaction referenced by a follow-up (void, refund, completion)
+    /// Matches terminal + RRN (and STAN when both are given), or terminal + STAN when
+    /// no RRN is given; STANs wrap, so a STAN only matches transactions of the
+    /// terminal's open batch. Reversals, voids, tip adjustments and inquiries can never
+    /// be an original. Follow-up records carry the original RRN too, so refunds and
+    /// incremental auths are only eligible as the original of a `void`; completions
+    /// stay eligible so they can be voided or refunded. The newest match wins
+    pub async fn find_original(
+        &self,
+        trm_id: &str,
+        rrn: Option<&str>,
+        stan: Option<&str>,
+        void: bool,
+    ) -> Result<Option<Iso8583Transaction>, sqlx::Error> {
+        if rrn.is_none() && stan.is_none() {
+            return Ok(None);
+        }
+
+        let result = sqlx::query_as::<_, Iso8583Transaction>(
+            r#"
+            SELECT * FROM iso8583_payment
+            WHERE trm_id = $1
+              AND COALESCE(msg_typ, '') NOT LIKE '04%'
+              AND COALESCE(field_003, '') NOT LIKE '02%'
+              AND COALESCE(field_003, '') NOT LIKE '31%'
+              AND ($4 OR NOT (orig_tr_uniq_no IS NOT NULL
+                              AND (msg_typ LIKE '01%' OR field_003 LIKE '20%')))
+              AND (($2::TEXT IS NOT NULL AND field_037 = $2
+                    AND ($3::TEXT IS NULL OR field_011 = $3))
+                OR ($2::TEXT IS NULL AND field_011 = $3
+                    AND (batch_id IS NULL
+                      OR batch_id IN (SELECT batch_id FROM settlement_batch
+                                      WHERE trm_id = $1 AND status = 'OPEN'))))
+            ORDER BY inst_dtm DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(trm_id)
+        .bind(rrn)
+        .bind(stan)
+        .bind(void)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Reserve `amount` (minor units) against the original's refundable balance
+    /// Returns false when cumulative refunds would exceed the refundable amount: the
+    /// approved amount, or DE4 without the cashback (see `Iso8583Transaction::refundable_amount`)
+    pub async fn reserve_refund_amount(
+        &self,
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+        amount: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE iso8583_payment
+            SET refund_amt = COALESCE(refund_amt, 0) + $4,
+                updt_dtm = $5
+            WHERE tr_dt = $1 AND tr_tm = $2 AND tr_uniq_no = $3
+              AND COALESCE(refund_amt, 0) + $4
+                  <= COALESCE(apprv_amt, CAST(field_004 AS BIGINT) - COALESCE(cashback_amt, 0))
+            "#,
+        )
+        .bind(tr_dt)
+        .bind(tr_tm)
+        .bind(tr_uniq_no)
+        .bind(amount)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Set the tip of an approved sale after its tip adjustment was approved
+    /// Returns false when the sale is no longer open (voided, settled) in the meantime
+    pub async fn update_tip_amount(
+        &self,
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+        tip_amt: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE iso8583_payment
+            SET tip_amt = $4,
+                updt_dtm = $5
+            WHERE tr_dt = $1 AND tr_tm = $2 AND tr_uniq_no = $3
+              AND tr_type = $6
+            "#,
+        )
+        .bind(tr_dt)
+        .bind(tr_tm)
+        .bind(tr_uniq_no)
+        .bind(tip_amt)
+        .bind(now)
+        .bind(TransactionState::Approved.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Flag a transaction approved in stand-in
+    pub async fn mark_stand_in(
+        &self,
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+    ) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        sqlx::query(
+            r#"
+            UPDATE iso8583_payment
+            SET stand_in = TRUE,
+                updt_dtm = $4
+            WHERE tr_dt = $1 AND tr_tm = $2 AND tr_uniq_no = $3
+            "#,
+        )
+        .bind(tr_dt)
+        .bind(tr_tm)
+        .bind(tr_uniq_no)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record the amount the host approved (below DE4 on a partial approval)
+    pub async fn update_approved_amount(
+        &self,
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+        amount: i64,
+    ) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        sqlx::query(
+            r#"
+            UPDATE iso8583_payment
+            SET apprv_amt = $4,
+                updt_dtm = $5
+            WHERE tr_dt = $1 AND tr_tm = $2 AND tr_uniq_no = $3
+            "#,
+        )
+        .bind(tr_dt)
+        .bind(tr_tm)
+        .bind(tr_uniq_no)
+        .bind(amount)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Give a voided refund's amount back to the refundable balance of its sale
+    pub async fn release_voided_refund(
+        &self,
+        refund: &Iso8583Transaction,
+    ) -> Result<(), sqlx::Error> {
+        let (Some(tr_dt), Some(tr_tm), Some(tr_uniq_no), Some(amount)) = (
+            refund.orig_tr_dt.as_deref(),
+            refund.orig_tr_tm.as_deref(),
+            refund.orig_tr_uniq_no.as_deref(),
+            refund.approved_amount(),
+        ) else {
+            return Ok(());
+        };
+        self.release_refund_amount(tr_dt, tr_tm, tr_uniq_no, amount)
+            .await
+    }
+
+    /// Give back a reservation made by `reserve_refund_amount` (refund not approved)
+    pub async fn release_refund_amount(
+        &self,
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+        amount: i64,
+    ) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        sqlx::query(
+            r#"
+            UPDATE iso8583_payment
+            SET refund_amt = GREATEST(COALESCE(refund_amt, 0) - $4, 0),
+                updt_dtm = $5
+            WHERE tr_dt = $1 AND tr_tm = $2 AND tr_uniq_no = $3
+            "#,
+        )
+        .bind(tr_dt)
+        .bind(tr_tm)
+        .bind(tr_uniq_no)
+        .bind(amount)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Find follow-up transactions linked to an original
+    pub async fn find_follow_ups(
+        &self,
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+    ) -> Result<Vec<Iso8583Transaction>, sqlx::Error> {
+        let result = sqlx::query_as::<_, Iso8583Transaction>(
+            r#"
+            SELECT * FROM iso8583_payment
+            WHERE orig_tr_dt = $1 AND orig_tr_tm = $2 AND orig_tr_uniq_no = $3
+            ORDER BY inst_dtm ASC
+            "#,
+        )
+        .bind(tr_dt)
+        .bind(tr_tm)
+        .bind(tr_uniq_no)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Store the final terminal response for replay to retransmissions
+    pub async fn save_response_json(
+        &self,
+        trm_id: &str,
+        tr_uniq_no: &str,
+        resp_json: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE iso8583_payment
+            SET resp_json = $3
+            WHERE trm_id = $1 AND tr_uniq_no = $2
+            "#,
+        )
+        .bind(trm_id)
+        .bind(tr_uniq_no)
+        .bind(resp_json)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Find a terminal's transaction by its transactionId (duplicate detection)
     pub async fn find_by_transaction_id_and_trm_id(&self,transaction_id: String, trm_id: String) 
     -> Result<Option<Iso8583Transaction>, sqlx::Error> {
         let result = sqlx::query_as::<_, Iso8583Transaction>(
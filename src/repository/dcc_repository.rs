@@ -0,0 +1,89 @@
+use chrono::Local;
+use sqlx::{FromRow, PgPool};
+
+/// Open DCC offer as stored; currencies are ISO 4217 numeric, the rate is the DE10 value
+#[derive(Debug, Clone, FromRow)]
+pub struct DccQuoteRow {
+    pub local_amt: i64,
+    pub local_ccy: String,
+    pub billing_amt: i64,
+    pub billing_ccy: String,
+    pub conv_rate: String,
+    pub markup_bps: i32,
+    pub expr_dtm: String,
+}
+
+/// Open DCC offers
+pub struct DccRepository {
+    pub pool: PgPool,
+}
+
+impl DccRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Store the offer made for a terminal transaction, replacing an earlier one,
+    /// and drop offers that expired
+    pub async fn save_quote(
+        &self,
+        trm_id: &str,
+        tr_uniq_no: &str,
+        quote: &DccQuoteRow,
+    ) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM dcc_quote WHERE expr_dtm < $1")
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO dcc_quote (
+                trm_id, tr_uniq_no, local_amt, local_ccy, billing_amt, billing_ccy,
+                conv_rate, markup_bps, expr_dtm, inst_dtm
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (trm_id, tr_uniq_no) DO UPDATE
+                SET local_amt = $3, local_ccy = $4, billing_amt = $5, billing_ccy = $6,
+                    conv_rate = $7, markup_bps = $8, expr_dtm = $9, inst_dtm = $10
+            "#,
+        )
+        .bind(trm_id)
+        .bind(tr_uniq_no)
+        .bind(quote.local_amt)
+        .bind(&quote.local_ccy)
+        .bind(quote.billing_amt)
+        .bind(&quote.billing_ccy)
+        .bind(&quote.conv_rate)
+        .bind(quote.markup_bps)
+        .bind(&quote.expr_dtm)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+
+    /// Remove and return the offer of a terminal transaction; an offer is answered once
+    pub async fn take_quote(
+        &self,
+        trm_id: &str,
+        tr_uniq_no: &str,
+    ) -> Result<Option<DccQuoteRow>, sqlx::Error> {
+        sqlx::query_as::<_, DccQuoteRow>(
+            r#"
+            DELETE FROM dcc_quote
+            WHERE trm_id = $1 AND tr_uniq_no = $2
+            RETURNING local_amt, local_ccy, billing_amt, billing_ccy,
+                      conv_rate, markup_bps, expr_dtm
+            "#,
+        )
+        .bind(trm_id)
+        .bind(tr_uniq_no)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
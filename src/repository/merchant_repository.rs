@@ -0,0 +1,30 @@
+use crate::models::merchant::TerminalMaster;
+use sqlx::PgPool;
+
+/// Merchant and terminal master data repository
+pub struct MerchantRepository {
+    pub pool: PgPool,
+}
+
+impl MerchantRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Terminal with the master data of its merchant
+    pub async fn find_terminal(&self, trm_id: &str) -> Result<Option<TerminalMaster>, sqlx::Error> {
+        sqlx::query_as::<_, TerminalMaster>(
+            r#"
+            SELECT t.trm_id, t.status AS trm_status, m.merchant_id, m.status AS merchant_status,
+                   m.name AS merchant_name, m.city, m.country, m.mcc, m.currency, m.acquirer_id,
+                   COALESCE(t.allowed_tx_types, m.allowed_tx_types) AS allowed_tx_types
+            FROM terminal t
+            JOIN merchant m ON m.merchant_id = t.merchant_id
+            WHERE t.trm_id = $1
+            "#,
+        )
+        .bind(trm_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
@@ -1,3 +1,12 @@
-
 pub mod qr_transaction_repository;
+pub mod bin_repository;
+pub mod business_day_repository;
 pub mod card_transaction_repository;
+pub mod dcc_repository;
+pub mod merchant_repository;
+pub mod preauth_repository;
+pub mod risk_repository;
+pub mod saf_repository;
+pub mod settlement_repository;
+pub mod terminal_repository;
+pub mod trace_repository;
\ No newline at end of file
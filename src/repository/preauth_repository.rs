@@ -0,0 +1,151 @@
+use crate::models::preauth_hold::{HoldStatus, PreAuthHold};
+use chrono::Local;
+use sqlx::PgPool;
+
+/// Pre-authorization hold repository
+pub struct PreAuthRepository {
+    pub pool: PgPool,
+}
+
+impl PreAuthRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Open a hold for an approved pre-authorization
+    pub async fn insert(&self, hold: &PreAuthHold) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO iso8583_preauth_hold (
+                tr_dt, tr_tm, tr_uniq_no, trm_id, authorized_amt,
+                completed_amt, status, expire_dtm, inst_dtm
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(&hold.tr_dt)
+        .bind(&hold.tr_tm)
+        .bind(&hold.tr_uniq_no)
+        .bind(&hold.trm_id)
+        .bind(hold.authorized_amt)
+        .bind(hold.completed_amt)
+        .bind(&hold.status)
+        .bind(&hold.expire_dtm)
+        .bind(&hold.inst_dtm)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Find the hold of a pre-auth transaction
+    pub async fn find_by_key(
+        &self,
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+    ) -> Result<Option<PreAuthHold>, sqlx::Error> {
+        let result = sqlx::query_as::<_, PreAuthHold>(
+            r#"
+            SELECT * FROM iso8583_preauth_hold
+            WHERE tr_dt = $1 AND tr_tm = $2 AND tr_uniq_no = $3
+            "#,
+        )
+        .bind(tr_dt)
+        .bind(tr_tm)
+        .bind(tr_uniq_no)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Add an approved incremental authorization to an open hold
+    /// Returns false when the hold is no longer open
+    pub async fn add_incremental(
+        &self,
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+        amount: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE iso8583_preauth_hold
+            SET authorized_amt = authorized_amt + $4,
+                updt_dtm = $5
+            WHERE tr_dt = $1 AND tr_tm = $2 AND tr_uniq_no = $3
+              AND status = $6
+            "#,
+        )
+        .bind(tr_dt)
+        .bind(tr_tm)
+        .bind(tr_uniq_no)
+        .bind(amount)
+        .bind(now)
+        .bind(HoldStatus::Open.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Close an open hold (completed, released or expired)
+    /// Returns false when the hold was not open anymore
+    pub async fn close(
+        &self,
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+        status: HoldStatus,
+        completed_amt: Option<i64>,
+    ) -> Result<bool, sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE iso8583_preauth_hold
+            SET status = $4,
+                completed_amt = COALESCE($5, completed_amt),
+                updt_dtm = $6
+            WHERE tr_dt = $1 AND tr_tm = $2 AND tr_uniq_no = $3
+              AND status = $7
+            "#,
+        )
+        .bind(tr_dt)
+        .bind(tr_tm)
+        .bind(tr_uniq_no)
+        .bind(status.as_str())
+        .bind(completed_amt)
+        .bind(now)
+        .bind(HoldStatus::Open.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Expire every open hold past its expiry, returning the expired holds
+    pub async fn expire_due(&self) -> Result<Vec<PreAuthHold>, sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        let result = sqlx::query_as::<_, PreAuthHold>(
+            r#"
+            UPDATE iso8583_preauth_hold
+            SET status = $1,
+                updt_dtm = $2
+            WHERE status = $3 AND expire_dtm <= $2
+            RETURNING *
+            "#,
+        )
+        .bind(HoldStatus::Expired.as_str())
+        .bind(now)
+        .bind(HoldStatus::Open.as_str())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+}
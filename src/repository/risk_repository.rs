@@ -0,0 +1,107 @@
+use chrono::{Duration, Local};
+use sqlx::{FromRow, PgPool};
+
+/// Risk rule as stored in the database, the definition is the rule JSON without its ID
+#[derive(Debug, Clone, FromRow)]
+pub struct RiskRuleRow {
+    pub rule_id: String,
+    pub definition: String,
+}
+
+/// Risk rules and velocity counters
+pub struct RiskRepository {
+    pub pool: PgPool,
+}
+
+impl RiskRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Active rules in evaluation order
+    pub async fn find_active_rules(&self) -> Result<Vec<RiskRuleRow>, sqlx::Error> {
+        sqlx::query_as::<_, RiskRuleRow>(
+            r#"
+            SELECT rule_id, definition
+            FROM risk_rule
+            WHERE active = TRUE
+            ORDER BY priority, rule_id
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Record a counter event, drop events older than the window and return the
+    /// count and amount of the window
+    pub async fn add_counter_event(
+        &self,
+        counter_key: &str,
+        amount: i64,
+        window_secs: i64,
+    ) -> Result<(i64, i64), sqlx::Error> {
+        let now = Local::now();
+        let since = (now - Duration::seconds(window_secs))
+            .format("%Y%m%d%H%M%S")
+            .to_string();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO risk_counter_event (counter_key, amount, event_dtm)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(counter_key)
+        .bind(amount)
+        .bind(now.format("%Y%m%d%H%M%S").to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM risk_counter_event
+            WHERE counter_key = $1 AND event_dtm < $2
+            "#,
+        )
+        .bind(counter_key)
+        .bind(&since)
+        .execute(&mut *tx)
+        .await?;
+
+        let totals = Self::totals(&mut tx, counter_key, &since).await?;
+        tx.commit().await?;
+        Ok(totals)
+    }
+
+    /// Count and amount of the counter events within the window
+    pub async fn counter_totals(
+        &self,
+        counter_key: &str,
+        window_secs: i64,
+    ) -> Result<(i64, i64), sqlx::Error> {
+        let since = (Local::now() - Duration::seconds(window_secs))
+            .format("%Y%m%d%H%M%S")
+            .to_string();
+        let mut conn = self.pool.acquire().await?;
+        Self::totals(&mut conn, counter_key, &since).await
+    }
+
+    async fn totals(
+        conn: &mut sqlx::PgConnection,
+        counter_key: &str,
+        since: &str,
+    ) -> Result<(i64, i64), sqlx::Error> {
+        sqlx::query_as::<_, (i64, i64)>(
+            r#"
+            SELECT COUNT(*), COALESCE(SUM(amount), 0)::BIGINT
+            FROM risk_counter_event
+            WHERE counter_key = $1 AND event_dtm >= $2
+            "#,
+        )
+        .bind(counter_key)
+        .bind(since)
+        .fetch_one(conn)
+        .await
+    }
+}
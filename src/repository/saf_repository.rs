@@ -0,0 +1,205 @@
+use crate::models::saf_entry::{SafEntry, SafStatus, SafType};
+use chrono::Local;
+use sqlx::PgPool;
+
+/// Store-and-forward queue repository
+pub struct SafRepository {
+    pub pool: PgPool,
+}
+
+impl SafRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Queue a message for the given transaction
+    /// Returns false when the same kind of message is already queued for it
+    pub async fn enqueue(
+        &self,
+        saf_type: SafType,
+        tr_dt: &str,
+        tr_tm: &str,
+        tr_uniq_no: &str,
+        trm_id: Option<&str>,
+        message: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO iso8583_saf_queue (
+                saf_type, tr_dt, tr_tm, tr_uniq_no, trm_id,
+                message, attempts, status, next_attempt_dtm, inst_dtm
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, 0, $7, $8, $8)
+            ON CONFLICT (saf_type, tr_dt, tr_tm, tr_uniq_no) DO NOTHING
+            "#,
+        )
+        .bind(saf_type.as_str())
+        .bind(tr_dt)
+        .bind(tr_tm)
+        .bind(tr_uniq_no)
+        .bind(trm_id)
+        .bind(message)
+        .bind(SafStatus::Pending.as_str())
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Oldest pending entry of each terminal, if its next attempt is due
+    /// A terminal's later entries wait until its head is delivered or dead
+    pub async fn find_due_heads(&self) -> Result<Vec<SafEntry>, sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        let result = sqlx::query_as::<_, SafEntry>(
+            r#"
+            SELECT * FROM (
+                SELECT DISTINCT ON (COALESCE(trm_id, '')) *
+                FROM iso8583_saf_queue
+                WHERE status = $1
+                ORDER BY COALESCE(trm_id, ''), saf_id ASC
+            ) heads
+            WHERE next_attempt_dtm <= $2
+            ORDER BY saf_id ASC
+            "#,
+        )
+        .bind(SafStatus::Pending.as_str())
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Record a failed attempt and schedule the next one
+    pub async fn record_attempt(
+        &self,
+        saf_id: i64,
+        next_attempt_dtm: &str,
+        error: &str,
+    ) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        sqlx::query(
+            r#"
+            UPDATE iso8583_saf_queue
+            SET attempts = attempts + 1,
+                next_attempt_dtm = $2,
+                last_error = $3,
+                updt_dtm = $4
+            WHERE saf_id = $1
+            "#,
+        )
+        .bind(saf_id)
+        .bind(next_attempt_dtm)
+        .bind(error)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Close an entry as delivered with the DE39 the host answered
+    pub async fn mark_delivered(
+        &self,
+        saf_id: i64,
+        response_code: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        sqlx::query(
+            r#"
+            UPDATE iso8583_saf_queue
+            SET attempts = attempts + 1,
+                status = $2,
+                response_code = $3,
+                last_error = NULL,
+                updt_dtm = $4
+            WHERE saf_id = $1
+            "#,
+        )
+        .bind(saf_id)
+        .bind(SafStatus::Delivered.as_str())
+        .bind(response_code)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Close an entry as delivered or dead
+    pub async fn update_status(
+        &self,
+        saf_id: i64,
+        status: SafStatus,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        sqlx::query(
+            r#"
+            UPDATE iso8583_saf_queue
+            SET attempts = attempts + 1,
+                status = $2,
+                last_error = $3,
+                updt_dtm = $4
+            WHERE saf_id = $1
+            "#,
+        )
+        .bind(saf_id)
+        .bind(status.as_str())
+        .bind(error)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Dead entries waiting for operator action
+    pub async fn find_dead(&self) -> Result<Vec<SafEntry>, sqlx::Error> {
+        let result = sqlx::query_as::<_, SafEntry>(
+            r#"
+            SELECT * FROM iso8583_saf_queue
+            WHERE status = $1
+            ORDER BY saf_id ASC
+            "#,
+        )
+        .bind(SafStatus::Dead.as_str())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Put a dead entry back in the queue with a fresh attempt budget
+    /// The counter restarts at 1 so it is still sent with the repeat MTI
+    /// Returns false when the entry does not exist or is not dead
+    pub async fn requeue_dead(&self, saf_id: i64) -> Result<bool, sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE iso8583_saf_queue
+            SET status = $2,
+                attempts = 1,
+                next_attempt_dtm = $4,
+                updt_dtm = $4
+            WHERE saf_id = $1 AND status = $3
+            "#,
+        )
+        .bind(saf_id)
+        .bind(SafStatus::Pending.as_str())
+        .bind(SafStatus::Dead.as_str())
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
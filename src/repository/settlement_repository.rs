@@ -0,0 +1,162 @@
+use crate::models::settlement_batch::{BatchStatus, BatchTotals, SettlementBatch};
+use crate::models::transaction::{Iso8583Transaction, TransactionState};
+use chrono::Local;
+use sqlx::PgPool;
+
+/// Terminal settlement batch repository
+pub struct SettlementRepository {
+    pub pool: PgPool,
+}
+
+impl SettlementRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Open batch of a terminal, opening the next batch number when there is none
+    pub async fn open_batch(
+        &self,
+        trm_id: &str,
+        currency: &str,
+    ) -> Result<SettlementBatch, sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+        sqlx::query(
+            r#"
+            INSERT INTO settlement_batch (trm_id, batch_no, status, currency, open_dtm)
+            VALUES (
+                $1,
+                COALESCE(
+                    (SELECT batch_no FROM settlement_batch
+                     WHERE trm_id = $1
+                     ORDER BY batch_id DESC
+                     LIMIT 1),
+                    0
+                ) % 999999 + 1,
+                $2, $3, $4
+            )
+            ON CONFLICT (trm_id) WHERE status = 'OPEN' DO NOTHING
+            "#,
+        )
+        .bind(trm_id)
+        .bind(BatchStatus::Open.as_str())
+        .bind(currency)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query_as::<_, SettlementBatch>(
+            r#"
+            SELECT * FROM settlement_batch
+            WHERE trm_id = $1 AND status = $2
+            "#,
+        )
+        .bind(trm_id)
+        .bind(BatchStatus::Open.as_str())
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Move the terminal's completed transactions not yet in a batch into the batch
+    /// Transactions still in flight join the next batch
+    pub async fn assign_transactions(
+        &self,
+        batch_id: i64,
+        trm_id: &str,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE iso8583_payment
+            SET batch_id = $1
+            WHERE trm_id = $2
+              AND batch_id IS NULL
+              AND tr_type IN ($3, $4)
+            "#,
+        )
+        .bind(batch_id)
+        .bind(trm_id)
+        .bind(TransactionState::Approved.as_str())
+        .bind(TransactionState::Voided.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Transactions of a batch in the order they were made
+    pub async fn find_transactions(
+        &self,
+        batch_id: i64,
+    ) -> Result<Vec<Iso8583Transaction>, sqlx::Error> {
+        sqlx::query_as::<_, Iso8583Transaction>(
+            r#"
+            SELECT * FROM iso8583_payment
+            WHERE batch_id = $1
+            ORDER BY tr_dt, tr_tm, tr_uniq_no
+            "#,
+        )
+        .bind(batch_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Record the outcome of a settlement attempt with the totals that were sent
+    /// The batch is closed when the host settled it in balance, on business date `busi_dt`
+    pub async fn save_attempt(
+        &self,
+        batch_id: i64,
+        totals: &BatchTotals,
+        status: BatchStatus,
+        resp_cd: Option<&str>,
+        upload_cnt: i32,
+        busi_dt: &str,
+    ) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+        let close_dtm = (status == BatchStatus::Closed).then_some(now.as_str());
+
+        sqlx::query(
+            r#"
+            UPDATE settlement_batch
+            SET debit_cnt = $2,
+                debit_amt = $3,
+                debit_rev_cnt = $4,
+                debit_rev_amt = $5,
+                credit_cnt = $6,
+                credit_amt = $7,
+                credit_rev_cnt = $8,
+                credit_rev_amt = $9,
+                inquiry_cnt = $10,
+                auth_cnt = $11,
+                status = $12,
+                resp_cd = $13,
+                upload_cnt = upload_cnt + $14,
+                attempts = attempts + 1,
+                close_dtm = $15,
+                updt_dtm = $16,
+                busi_dt = $17
+            WHERE batch_id = $1
+            "#,
+        )
+        .bind(batch_id)
+        .bind(totals.debit_count)
+        .bind(totals.debit_amount)
+        .bind(totals.debit_reversal_count)
+        .bind(totals.debit_reversal_amount)
+        .bind(totals.credit_count)
+        .bind(totals.credit_amount)
+        .bind(totals.credit_reversal_count)
+        .bind(totals.credit_reversal_amount)
+        .bind(totals.inquiry_count)
+        .bind(totals.authorization_count)
+        .bind(status.as_str())
+        .bind(resp_cd)
+        .bind(upload_cnt)
+        .bind(close_dtm)
+        .bind(&now)
+        .bind(busi_dt)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use crate::models::terminal_params::{ManagedTerminal, ParamSet};
+use chrono::Local;
+use sqlx::PgPool;
+
+/// Terminal logon and parameter download repository
+pub struct TerminalRepository {
+    pub pool: PgPool,
+}
+
+impl TerminalRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn find_terminal(
+        &self,
+        trm_id: &str,
+    ) -> Result<Option<ManagedTerminal>, sqlx::Error> {
+        sqlx::query_as::<_, ManagedTerminal>(
+            r#"
+            SELECT trm_id, status, logon_key, param_set_id, param_version
+            FROM terminal
+            WHERE trm_id = $1
+            "#,
+        )
+        .bind(trm_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn record_logon(&self, trm_id: &str) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+        sqlx::query("UPDATE terminal SET last_logon_dtm = $2 WHERE trm_id = $1")
+            .bind(trm_id)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Latest published version of a parameter set
+    pub async fn latest_param_set(
+        &self,
+        param_set_id: &str,
+    ) -> Result<Option<ParamSet>, sqlx::Error> {
+        sqlx::query_as::<_, ParamSet>(
+            r#"
+            SELECT param_set_id, version, parameters
+            FROM terminal_param_set
+            WHERE param_set_id = $1
+            ORDER BY version DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(param_set_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn find_param_set(
+        &self,
+        param_set_id: &str,
+        version: i32,
+    ) -> Result<Option<ParamSet>, sqlx::Error> {
+        sqlx::query_as::<_, ParamSet>(
+            r#"
+            SELECT param_set_id, version, parameters
+            FROM terminal_param_set
+            WHERE param_set_id = $1 AND version = $2
+            "#,
+        )
+        .bind(param_set_id)
+        .bind(version)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Publish the next version of a parameter set; returns the new version
+    pub async fn publish_param_set(
+        &self,
+        param_set_id: &str,
+        parameters: &str,
+    ) -> Result<i32, sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+        sqlx::query_scalar::<_, i32>(
+            r#"
+            INSERT INTO terminal_param_set (param_set_id, version, parameters, inst_dtm)
+            SELECT $1, COALESCE(MAX(version), 0) + 1, $2, $3
+            FROM terminal_param_set
+            WHERE param_set_id = $1
+            RETURNING version
+            "#,
+        )
+        .bind(param_set_id)
+        .bind(parameters)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Terminal started downloading a version
+    pub async fn record_download(&self, trm_id: &str, version: i32) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+        sqlx::query(
+            "UPDATE terminal SET param_download_version = $2, param_updt_dtm = $3 WHERE trm_id = $1",
+        )
+        .bind(trm_id)
+        .bind(version)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Terminal applied a version
+    pub async fn confirm_param_version(
+        &self,
+        trm_id: &str,
+        version: i32,
+    ) -> Result<(), sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+        sqlx::query(
+            "UPDATE terminal SET param_version = $2, param_updt_dtm = $3 WHERE trm_id = $1",
+        )
+        .bind(trm_id)
+        .bind(version)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
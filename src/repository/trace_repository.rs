@@ -0,0 +1,33 @@
+use chrono::Local;
+use sqlx::PgPool;
+
+/// Trace number (STAN/RRN) block leases
+pub struct TraceRepository {
+    pub pool: PgPool,
+}
+
+impl TraceRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Lease `size` consecutive values of a space, creating it at 1; returns the first
+    /// The row lock serialises concurrent leases, so blocks never overlap
+    pub async fn lease_block(&self, space: &str, size: i64) -> Result<i64, sqlx::Error> {
+        let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+        sqlx::query_scalar::<_, i64>(
+            r#"
+            INSERT INTO trace_number_space (space, next_value, updt_dtm)
+            VALUES ($1, 1 + $2, $3)
+            ON CONFLICT (space) DO UPDATE
+                SET next_value = trace_number_space.next_value + $2, updt_dtm = $3
+            RETURNING next_value - $2
+            "#,
+        )
+        .bind(space)
+        .bind(size)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await
+    }
+}